@@ -0,0 +1,189 @@
+use std::{env, process, time::SystemTime};
+
+use vulkan_test::{
+    compare::{ImageComparer, Threshold},
+    context::Context,
+    format::save_float_image,
+    output::{Output, OutputOptions},
+};
+
+const USAGE: &str = "usage: compare <image> <reference> [options]
+
+options:
+    --heat-map <path>       where to save the heat map of the differences (default heat-map.png)
+    --difference <path>     also save the absolute differences, unamplified, as a 16-bit PNG,
+                            Radiance HDR, OpenEXR or 32-bit float TIFF picked by the extension
+    --amplify <factor>      how much differences are scaled up in the heat map, so with 10 a
+                            difference of a tenth is white (default 10)
+    --threshold <metric>:<value>
+                            fail when max-error or rmse is above the value, or psnr (in decibels)
+                            or ssim is below it. can be given more than once
+    --json                  print the metrics as JSON
+    --out-dir <path>        directory to save everything in (default the working directory)
+    --overwrite             replace files that are already there instead of refusing to start
+
+both images can be in any format the image crate reads, and are compared channel by channel as
+they're stored, with values from 0 to 1. RMSE and PSNR cover red, green and blue, SSIM the luma.
+exits with 1 when the images can't be compared, and 2 when they're past a threshold.
+
+every run also writes a JSON sidecar next to the heat map, with .json in place of its extension,
+recording the images, command line, device, how long comparing took and the files written.";
+
+struct Args {
+    image: String,
+    reference: String,
+    heat_map: String,
+    difference: Option<String>,
+    amplify: f32,
+    thresholds: Vec<Threshold>,
+    json: bool,
+    out: OutputOptions,
+}
+
+fn parse_args() -> Args {
+    let mut paths = Vec::new();
+    let mut heat_map = "heat-map.png".to_string();
+    let mut difference = None;
+    let mut amplify = 10.0;
+    let mut thresholds = Vec::new();
+    let mut json = false;
+    let mut out = OutputOptions::default();
+    let mut argv = env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        if !arg.starts_with("--") {
+            paths.push(arg);
+            continue;
+        }
+        if arg == "--json" {
+            json = true;
+            continue;
+        }
+        if arg == "--overwrite" {
+            out.overwrite = true;
+            continue;
+        }
+        let value = argv
+            .next()
+            .unwrap_or_else(|| exit_with_usage(&format!("'{}' needs a value", arg)));
+        match arg.as_str() {
+            "--heat-map" => heat_map = value,
+            "--difference" => difference = Some(value),
+            "--amplify" => {
+                amplify = value
+                    .parse()
+                    .ok()
+                    .filter(|&amplify: &f32| amplify > 0.0)
+                    .unwrap_or_else(|| {
+                        exit_with_usage(&format!("amplify '{}' must be a positive number", value))
+                    })
+            }
+            "--threshold" => thresholds.push(
+                value
+                    .parse()
+                    .unwrap_or_else(|e: String| exit_with_usage(&e)),
+            ),
+            "--out-dir" => out.dir = value.into(),
+            _ => exit_with_usage(&format!("unknown option '{}'", arg)),
+        }
+    }
+    let [image, reference] = paths
+        .try_into()
+        .unwrap_or_else(|_| exit_with_usage("an image and a reference are needed"));
+    Args {
+        image,
+        reference,
+        heat_map,
+        difference,
+        amplify,
+        thresholds,
+        json,
+        out,
+    }
+}
+
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(1);
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn main() {
+    let args = parse_args();
+
+    // claim the files first, so nothing already there is lost
+    let mut output = Output::new("compare", args.out.clone());
+    output.set_parameter("image", args.image.as_str());
+    output.set_parameter("reference", args.reference.as_str());
+    output.set_parameter("amplify", args.amplify);
+    let heat_map_path = output
+        .claim(&args.heat_map)
+        .unwrap_or_else(|e| exit_with_error(&e));
+    let difference_path = args
+        .difference
+        .as_ref()
+        .map(|path| output.claim(path).unwrap_or_else(|e| exit_with_error(&e)));
+
+    // load images
+    let [image, reference] = [&args.image, &args.reference].map(|path| {
+        image::open(path)
+            .unwrap_or_else(|e| exit_with_usage(&format!("could not open '{}': {}", path, e)))
+    });
+
+    // setup vulkan
+    let context = Context::new().expect("failed to setup vulkan");
+    let comparer = ImageComparer::new(&context);
+
+    // compare
+    let start = SystemTime::now();
+    let comparison = comparer
+        .compare_images(&context, &image, &reference, args.amplify)
+        .unwrap_or_else(|e| exit_with_error(&e));
+    let elapsed = start.elapsed().expect("could not elapse time");
+    let metrics = &comparison.metrics;
+    if args.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&metrics.to_json()).expect("could not serialize metrics")
+        );
+    } else {
+        println!(
+            "Compared {}x{} images on {} in {:?}",
+            metrics.width,
+            metrics.height,
+            context.device_name(),
+            elapsed
+        );
+        println!("max error: {:.6}", metrics.largest_error());
+        println!("RMSE:      {:.6}", metrics.color_rmse());
+        println!("PSNR:      {:.2} dB", metrics.psnr());
+        println!("SSIM:      {:.6}", metrics.ssim);
+    }
+
+    // save differences
+    comparison
+        .heat_map
+        .save(&heat_map_path)
+        .unwrap_or_else(|e| {
+            exit_with_error(&format!(
+                "could not save '{}': {}",
+                heat_map_path.display(),
+                e
+            ))
+        });
+    if let Some(path) = &difference_path {
+        save_float_image(&comparison.difference, &path.to_string_lossy())
+            .unwrap_or_else(|e| exit_with_error(&e));
+    }
+    output
+        .write_sidecar(context.device_name(), elapsed)
+        .unwrap_or_else(|e| exit_with_error(&e));
+
+    if let Err(e) = metrics.check(&args.thresholds) {
+        eprintln!("{}", e);
+        process::exit(2);
+    }
+}
@@ -0,0 +1,35 @@
+#version 460
+
+// the work group size is WORK_GROUP_SIZE in main.rs, set through specialization
+layout(local_size_x_id = 0, local_size_y = 1, local_size_z = 1) in;
+
+layout(set = 0, binding = 0) readonly buffer Input {
+    uint values[];
+} input_buf;
+
+layout(set = 0, binding = 1) writeonly buffer Survivors {
+    uint values[];
+} survivors;
+
+layout(set = 0, binding = 2) buffer Count {
+    uint count;
+} counter;
+
+layout(push_constant) uniform CompactParams {
+    uint len;
+    uint threshold;
+} params;
+
+void main() {
+    uint idx = gl_GlobalInvocationID.x;
+    if (idx >= params.len) {
+        return;
+    }
+
+    // keep values under the threshold, packed at the front of the output in no particular order
+    uint value = input_buf.values[idx];
+    if (value < params.threshold) {
+        uint slot = atomicAdd(counter.count, 1);
+        survivors.values[slot] = value;
+    }
+}
@@ -0,0 +1,319 @@
+use std::sync::Arc;
+
+use vulkano::{
+    buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage},
+    command_buffer::{
+        allocator::{StandardCommandBufferAllocator, StandardCommandBufferAllocatorCreateInfo},
+        AutoCommandBufferBuilder, CommandBufferUsage, DispatchIndirectCommand,
+    },
+    descriptor_set::{
+        allocator::StandardDescriptorSetAllocator, PersistentDescriptorSet, WriteDescriptorSet,
+    },
+    device::{Device, DeviceCreateInfo, QueueCreateInfo, QueueFlags},
+    instance::{Instance, InstanceCreateInfo},
+    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
+    pipeline::{
+        compute::ComputePipelineCreateInfo, layout::PipelineDescriptorSetLayoutCreateInfo,
+        ComputePipeline, Pipeline, PipelineBindPoint, PipelineLayout,
+        PipelineShaderStageCreateInfo,
+    },
+    shader::ShaderModule,
+    sync::{self, GpuFuture},
+    VulkanLibrary,
+};
+
+/// Number of values fed into the compaction pass.
+const INPUT_LEN: u32 = 1 << 20;
+
+/// Values below this survive compaction, roughly a tenth of the input.
+const THRESHOLD: u32 = u32::MAX / 10;
+
+/// `local_size_x` of the compaction and processing shaders, which the preparation shader sizes
+/// the processing pass by. Every shader gets it as specialization constant 0.
+const WORK_GROUP_SIZE: u32 = 64;
+
+#[derive(BufferContents)]
+#[repr(C)]
+struct CompactParams {
+    len: u32,
+    threshold: u32,
+}
+
+fn get_compute_pipeline(device: Arc<Device>, shader: Arc<ShaderModule>) -> Arc<ComputePipeline> {
+    let entry_point = shader
+        .specialize([(0, WORK_GROUP_SIZE.into())].into_iter().collect())
+        .expect("failed to specialize shader")
+        .entry_point("main")
+        .expect("failed to create entry point");
+    let stage = PipelineShaderStageCreateInfo::new(entry_point);
+    let layout = PipelineLayout::new(
+        device.clone(),
+        PipelineDescriptorSetLayoutCreateInfo::from_stages([&stage])
+            .into_pipeline_layout_create_info(device.clone())
+            .expect("could not create pipeline layout info"),
+    )
+    .expect("could not create pipeline layout");
+    ComputePipeline::new(
+        device,
+        None,
+        ComputePipelineCreateInfo::stage_layout(stage, layout),
+    )
+    .expect("failed to create compute pipeline")
+}
+
+fn main() {
+    // setup vulkan
+    let library = VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance =
+        Instance::new(library, InstanceCreateInfo::default()).expect("failed to create instance");
+
+    // setup device
+    let physical_device = instance
+        .enumerate_physical_devices()
+        .expect("could not enumerate devices")
+        .next()
+        .expect("no devices available");
+    let queue_family_index = physical_device
+        .queue_family_properties()
+        .iter()
+        .position(|queue_family_properties| {
+            queue_family_properties
+                .queue_flags
+                .contains(QueueFlags::COMPUTE)
+        })
+        .expect("couldn't find a compute queue family") as u32;
+    let (device, mut queues) = Device::new(
+        physical_device,
+        DeviceCreateInfo {
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+    )
+    .expect("failed to create device");
+    let queue = queues.next().unwrap();
+    let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
+
+    // setup input, pseudo-random so how much survives depends on the data
+    let mut state = 0x2545_f491_u32;
+    let input_data: Vec<u32> = (0..INPUT_LEN)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        })
+        .collect();
+
+    // setup buffers
+    let host_writable = AllocationCreateInfo {
+        memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+            | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+        ..Default::default()
+    };
+    let host_readable = AllocationCreateInfo {
+        memory_type_filter: MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+        ..Default::default()
+    };
+    let input_buffer = Buffer::from_iter(
+        memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        host_writable.clone(),
+        input_data.iter().copied(),
+    )
+    .expect("failed to create input buffer");
+    let survivors_buffer = Buffer::new_slice::<u32>(
+        memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        host_readable.clone(),
+        INPUT_LEN as u64,
+    )
+    .expect("failed to create survivors buffer");
+    let count_buffer = Buffer::from_data(
+        memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        host_readable,
+        0u32,
+    )
+    .expect("failed to create count buffer");
+    let indirect_buffer = Buffer::from_iter(
+        memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER | BufferUsage::INDIRECT_BUFFER,
+            ..Default::default()
+        },
+        host_writable,
+        [DispatchIndirectCommand { x: 0, y: 1, z: 1 }],
+    )
+    .expect("failed to create indirect buffer");
+
+    // setup compute pipelines
+    let compact_pipeline = get_compute_pipeline(
+        device.clone(),
+        shaders::load_compact(device.clone()).expect("failed to create compact shader module"),
+    );
+    let prepare_pipeline = get_compute_pipeline(
+        device.clone(),
+        shaders::load_prepare(device.clone()).expect("failed to create prepare shader module"),
+    );
+    let process_pipeline = get_compute_pipeline(
+        device.clone(),
+        shaders::load_process(device.clone()).expect("failed to create process shader module"),
+    );
+
+    // setup descriptors
+    let descriptor_set_allocator =
+        StandardDescriptorSetAllocator::new(device.clone(), Default::default());
+    let compact_set = PersistentDescriptorSet::new(
+        &descriptor_set_allocator,
+        compact_pipeline.layout().set_layouts()[0].clone(),
+        [
+            WriteDescriptorSet::buffer(0, input_buffer.clone()),
+            WriteDescriptorSet::buffer(1, survivors_buffer.clone()),
+            WriteDescriptorSet::buffer(2, count_buffer.clone()),
+        ],
+        [],
+    )
+    .expect("failed to create compact descriptor set");
+    let prepare_set = PersistentDescriptorSet::new(
+        &descriptor_set_allocator,
+        prepare_pipeline.layout().set_layouts()[0].clone(),
+        [
+            WriteDescriptorSet::buffer(0, count_buffer.clone()),
+            WriteDescriptorSet::buffer(1, indirect_buffer.clone()),
+        ],
+        [],
+    )
+    .expect("failed to create prepare descriptor set");
+    let process_set = PersistentDescriptorSet::new(
+        &descriptor_set_allocator,
+        process_pipeline.layout().set_layouts()[0].clone(),
+        [
+            WriteDescriptorSet::buffer(0, survivors_buffer.clone()),
+            WriteDescriptorSet::buffer(1, count_buffer.clone()),
+        ],
+        [],
+    )
+    .expect("failed to create process descriptor set");
+
+    // build command buffer, the processing pass's size is only ever known on the GPU
+    let command_buffer_allocator = StandardCommandBufferAllocator::new(
+        device.clone(),
+        StandardCommandBufferAllocatorCreateInfo::default(),
+    );
+    let mut command_buffer_builder = AutoCommandBufferBuilder::primary(
+        &command_buffer_allocator,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+    .expect("failed to create command buffer builder");
+    command_buffer_builder
+        .bind_pipeline_compute(compact_pipeline.clone())
+        .expect("failed to bind compact pipeline")
+        .bind_descriptor_sets(
+            PipelineBindPoint::Compute,
+            compact_pipeline.layout().clone(),
+            0,
+            compact_set,
+        )
+        .expect("failed to bind compact descriptor set")
+        .push_constants(
+            compact_pipeline.layout().clone(),
+            0,
+            CompactParams {
+                len: INPUT_LEN,
+                threshold: THRESHOLD,
+            },
+        )
+        .expect("failed to push compact parameters")
+        .dispatch([INPUT_LEN.div_ceil(WORK_GROUP_SIZE), 1, 1])
+        .expect("failed to dispatch compaction")
+        .bind_pipeline_compute(prepare_pipeline.clone())
+        .expect("failed to bind prepare pipeline")
+        .bind_descriptor_sets(
+            PipelineBindPoint::Compute,
+            prepare_pipeline.layout().clone(),
+            0,
+            prepare_set,
+        )
+        .expect("failed to bind prepare descriptor set")
+        .dispatch([1, 1, 1])
+        .expect("failed to dispatch work count preparation")
+        .bind_pipeline_compute(process_pipeline.clone())
+        .expect("failed to bind process pipeline")
+        .bind_descriptor_sets(
+            PipelineBindPoint::Compute,
+            process_pipeline.layout().clone(),
+            0,
+            process_set,
+        )
+        .expect("failed to bind process descriptor set")
+        .dispatch_indirect(indirect_buffer.clone())
+        .expect("failed to dispatch processing indirectly");
+    let command_buffer = command_buffer_builder
+        .build()
+        .expect("failed to build command buffer");
+
+    // submit command buffer
+    let future = sync::now(device.clone())
+        .then_execute(queue.clone(), command_buffer)
+        .expect("failed to execute command buffer")
+        .then_signal_fence_and_flush()
+        .expect("failed to signal fence and flush");
+    future.wait(None).unwrap();
+
+    // compute the same thing on the CPU
+    let mut cpu_survivors: Vec<u32> = input_data
+        .iter()
+        .filter(|&&value| value < THRESHOLD)
+        .map(|value| value * 3 + 1)
+        .collect();
+
+    // check that execution was correct, survivors come out of the GPU in any order
+    let count = *count_buffer.read().expect("failed to read count buffer") as usize;
+    println!(
+        "{} of {} values survived compaction, processed by {} work groups",
+        count,
+        INPUT_LEN,
+        count.div_ceil(WORK_GROUP_SIZE as usize)
+    );
+    println!("Checking that values match...");
+    let mut gpu_survivors = survivors_buffer
+        .read()
+        .expect("failed to read survivors buffer")[..count]
+        .to_vec();
+    gpu_survivors.sort_unstable();
+    cpu_survivors.sort_unstable();
+    assert_eq!(gpu_survivors, cpu_survivors);
+    println!("Values were equivalent");
+}
+
+mod shaders {
+    vulkano_shaders::shader! {
+        shaders: {
+            compact: {
+                ty: "compute",
+                path: "examples/compute-indirect/compact.glsl"
+            },
+            prepare: {
+                ty: "compute",
+                path: "examples/compute-indirect/prepare.glsl"
+            },
+            process: {
+                ty: "compute",
+                path: "examples/compute-indirect/process.glsl"
+            },
+        }
+    }
+}
@@ -0,0 +1,24 @@
+#version 460
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+// WORK_GROUP_SIZE in main.rs, the size of the processing pass's work groups
+layout(constant_id = 0) const uint WORK_GROUP_SIZE = 64;
+
+layout(set = 0, binding = 0) readonly buffer Count {
+    uint count;
+} counter;
+
+// laid out like `VkDispatchIndirectCommand`
+layout(set = 0, binding = 1) writeonly buffer Indirect {
+    uint x;
+    uint y;
+    uint z;
+} dispatch;
+
+void main() {
+    // enough work groups of the processing pass to cover every survivor
+    dispatch.x = (counter.count + WORK_GROUP_SIZE - 1) / WORK_GROUP_SIZE;
+    dispatch.y = 1;
+    dispatch.z = 1;
+}
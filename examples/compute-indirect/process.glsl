@@ -0,0 +1,21 @@
+#version 460
+
+// the work group size is WORK_GROUP_SIZE in main.rs, set through specialization
+layout(local_size_x_id = 0, local_size_y = 1, local_size_z = 1) in;
+
+layout(set = 0, binding = 0) buffer Survivors {
+    uint values[];
+} survivors;
+
+layout(set = 0, binding = 1) readonly buffer Count {
+    uint count;
+} counter;
+
+void main() {
+    uint idx = gl_GlobalInvocationID.x;
+    if (idx >= counter.count) {
+        return;
+    }
+
+    survivors.values[idx] = survivors.values[idx] * 3 + 1;
+}
@@ -0,0 +1,218 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, Frame, RgbaImage,
+};
+
+/// Speed of the GIF color quantizer, from 1 (best) to 30 (fastest).
+const GIF_SPEED: i32 = 10;
+
+/// Where the frames of an animation go, picked by the output's extension.
+pub enum FrameWriter {
+    /// `name.png` becomes `name-0000.png`, `name-0001.png` and so on.
+    Png {
+        stem: String,
+        digits: usize,
+        next: u32,
+    },
+    Gif {
+        encoder: GifEncoder<BufWriter<File>>,
+        delay: Delay,
+    },
+    /// Uncompressed 4:4:4 video, which most video tools can read.
+    Y4m(BufWriter<File>),
+}
+
+impl FrameWriter {
+    pub fn create(
+        path: &str,
+        width: u32,
+        height: u32,
+        fps: u32,
+        frames: u32,
+    ) -> Result<Self, String> {
+        let create = || {
+            File::create(path)
+                .map(BufWriter::new)
+                .map_err(|e| format!("could not create '{}': {}", path, e))
+        };
+        if let Some(stem) = path.strip_suffix(".png") {
+            Ok(FrameWriter::Png {
+                stem: stem.to_string(),
+                digits: frame_digits(frames),
+                next: 0,
+            })
+        } else if path.ends_with(".gif") {
+            let mut encoder = GifEncoder::new_with_speed(create()?, GIF_SPEED);
+            encoder
+                .set_repeat(Repeat::Infinite)
+                .map_err(|e| format!("could not write '{}': {}", path, e))?;
+            Ok(FrameWriter::Gif {
+                encoder,
+                delay: Delay::from_numer_denom_ms(1000, fps),
+            })
+        } else if path.ends_with(".y4m") {
+            let mut file = create()?;
+            writeln!(
+                file,
+                "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                width, height, fps
+            )
+            .map_err(|e| format!("could not write '{}': {}", path, e))?;
+            Ok(FrameWriter::Y4m(file))
+        } else {
+            Err(format!(
+                "don't know how to write an animation to '{}', use .png, .gif or .y4m",
+                path
+            ))
+        }
+    }
+
+    pub fn write(&mut self, frame: RgbaImage) -> Result<(), String> {
+        match self {
+            FrameWriter::Png { stem, digits, next } => {
+                let path = frame_path(stem, *digits, *next);
+                *next += 1;
+                frame
+                    .save(&path)
+                    .map_err(|e| format!("could not save '{}': {}", path, e))
+            }
+            FrameWriter::Gif { encoder, delay } => encoder
+                .encode_frame(Frame::from_parts(frame, 0, 0, *delay))
+                .map_err(|e| format!("could not encode frame: {}", e)),
+            FrameWriter::Y4m(file) => {
+                let mut planes = vec![Vec::new(); 3];
+                for pixel in frame.pixels() {
+                    for (plane, value) in planes.iter_mut().zip(rgb_to_ycbcr(pixel.0)) {
+                        plane.push(value);
+                    }
+                }
+                file.write_all(b"FRAME\n")
+                    .and_then(|_| planes.iter().try_for_each(|plane| file.write_all(plane)))
+                    .map_err(|e| format!("could not write frame: {}", e))
+            }
+        }
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        match self {
+            FrameWriter::Png { .. } => Ok(()),
+            // the trailer is written when the encoder is dropped
+            FrameWriter::Gif { encoder, .. } => {
+                drop(encoder);
+                Ok(())
+            }
+            FrameWriter::Y4m(mut file) => file
+                .flush()
+                .map_err(|e| format!("could not write frame: {}", e)),
+        }
+    }
+}
+
+/// The files an animation of `frames` frames written to `path` ends up in.
+pub fn frame_paths(path: &str, frames: u32) -> Vec<String> {
+    match path.strip_suffix(".png") {
+        Some(stem) => (0..frames)
+            .map(|frame| frame_path(stem, frame_digits(frames), frame))
+            .collect(),
+        None => vec![path.to_string()],
+    }
+}
+
+fn frame_digits(frames: u32) -> usize {
+    frames.saturating_sub(1).to_string().len().max(4)
+}
+
+fn frame_path(stem: &str, digits: usize, frame: u32) -> String {
+    format!("{}-{:0width$}.png", stem, frame, width = digits)
+}
+
+/// Writes an image a band of rows at a time, for images too big to hold in memory at once.
+pub enum RowWriter {
+    Png(png::StreamWriter<'static, BufWriter<File>>),
+    /// Iteration counts from raw renders as a 16-bit grayscale PNG.
+    CountsPng(png::StreamWriter<'static, BufWriter<File>>),
+    /// Iteration counts from raw renders as little-endian u32s.
+    Counts(BufWriter<File>),
+}
+
+impl RowWriter {
+    pub fn create(path: &str, width: u32, height: u32, raw: bool) -> Result<Self, String> {
+        let png = path.ends_with(".png");
+        if !png && !raw {
+            return Err(format!(
+                "don't know how to write a tiled image to '{}', use .png",
+                path
+            ));
+        }
+        let file = File::create(path)
+            .map(BufWriter::new)
+            .map_err(|e| format!("could not create '{}': {}", path, e))?;
+        if !png {
+            return Ok(RowWriter::Counts(file));
+        }
+
+        let mut encoder = png::Encoder::new(file, width, height);
+        if raw {
+            encoder.set_color(png::ColorType::Grayscale);
+            encoder.set_depth(png::BitDepth::Sixteen);
+        } else {
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+        }
+        let stream = encoder
+            .write_header()
+            .and_then(|writer| writer.into_stream_writer())
+            .map_err(|e| format!("could not write '{}': {}", path, e))?;
+        Ok(if raw {
+            RowWriter::CountsPng(stream)
+        } else {
+            RowWriter::Png(stream)
+        })
+    }
+
+    /// Writes whole rows of RGBA8 pixels, or in raw mode of counts packed into them.
+    pub fn write(&mut self, rows: &[u8]) -> Result<(), String> {
+        let counts = rows
+            .chunks_exact(4)
+            .map(|pixel| u32::from_le_bytes(pixel.try_into().unwrap()));
+        match self {
+            RowWriter::Png(stream) => stream.write_all(rows),
+            RowWriter::CountsPng(stream) => {
+                let bytes: Vec<u8> = counts
+                    .flat_map(|c| (c.min(u16::MAX as u32) as u16).to_be_bytes())
+                    .collect();
+                stream.write_all(&bytes)
+            }
+            RowWriter::Counts(file) => {
+                let bytes: Vec<u8> = counts.flat_map(u32::to_le_bytes).collect();
+                file.write_all(&bytes)
+            }
+        }
+        .map_err(|e| format!("could not write rows: {}", e))
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        match self {
+            RowWriter::Png(stream) | RowWriter::CountsPng(stream) => stream
+                .finish()
+                .map_err(|e| format!("could not finish image: {}", e)),
+            RowWriter::Counts(mut file) => file
+                .flush()
+                .map_err(|e| format!("could not write rows: {}", e)),
+        }
+    }
+}
+
+/// BT.601 limited range, the default for Y4M.
+fn rgb_to_ycbcr([r, g, b, _]: [u8; 4]) -> [u8; 3] {
+    let [r, g, b] = [r, g, b].map(|c| c as f32);
+    let y = 16.0 + (65.738 * r + 129.057 * g + 25.064 * b) / 256.0;
+    let cb = 128.0 + (-37.945 * r - 74.494 * g + 112.439 * b) / 256.0;
+    let cr = 128.0 + (112.439 * r - 94.154 * g - 18.285 * b) / 256.0;
+    [y, cb, cr].map(|v| v.round().clamp(0.0, 255.0) as u8)
+}
@@ -1,192 +1,847 @@
-use std::{sync::Arc, time::SystemTime};
-
-use image::{ImageBuffer, Rgba};
-use vulkano::{
-    buffer::{Buffer, BufferCreateInfo, BufferUsage},
-    command_buffer::{
-        allocator::{StandardCommandBufferAllocator, StandardCommandBufferAllocatorCreateInfo},
-        AutoCommandBufferBuilder, CommandBufferUsage, CopyImageToBufferInfo,
-    },
-    descriptor_set::{
-        allocator::StandardDescriptorSetAllocator, PersistentDescriptorSet, WriteDescriptorSet,
-    },
-    device::{Device, DeviceCreateInfo, QueueCreateInfo, QueueFlags},
-    format::Format,
-    image::{view::ImageView, Image, ImageCreateInfo, ImageType, ImageUsage},
-    instance::{Instance, InstanceCreateInfo},
-    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
-    pipeline::{
-        compute::ComputePipelineCreateInfo, layout::PipelineDescriptorSetLayoutCreateInfo,
-        ComputePipeline, Pipeline, PipelineBindPoint, PipelineLayout,
-        PipelineShaderStageCreateInfo,
+use std::{
+    env, fs, process,
+    sync::mpsc,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use encode::{frame_paths, FrameWriter, RowWriter};
+use image::{ImageBuffer, Luma, RgbaImage};
+use vulkan_test::{
+    context::Context,
+    format::{save_float_image, ColorFormat},
+    fractal::{
+        animation::{Easing, Keyframe, ZoomAnimation},
+        buddhabrot::{BuddhabrotRenderer, BuddhabrotSettings},
+        iteration_counts,
+        palette::{Palette, BUILTIN_NAMES},
+        progressive::ProgressiveRender,
+        tiled::TiledRenderer,
+        ColorMode, Fractal, FractalRenderer, Precision, RenderSettings,
     },
-    sync::{self, GpuFuture},
-    VulkanLibrary,
+    output::{Output, OutputOptions},
 };
+use vulkano::device::Features;
+
+mod encode;
+
+/// Images with more samples than this are rendered in tiles even without `--tile`.
+const MAX_UNTILED_PIXELS: u64 = 8192 * 8192;
+
+/// Tile size when tiling wasn't asked for but the image needs it.
+const DEFAULT_TILE_SIZE: u32 = 2048;
+
+const USAGE: &str = "usage: compute-mandelbrot [options]
+
+options:
+    --fractal <fractal>     mandelbrot, julia:<x>,<y>, burning-ship, tricorn or multibrot:<power>
+                            (default mandelbrot)
+    --width <pixels>        width of the image (default 1024)
+    --height <pixels>       height of the image (default 1024)
+    --center <x>,<y>        point in the complex plane at the middle of the image (default -1,0)
+    --zoom <factor>         magnification, where 1 shows 2 units of the plane vertically (default 1)
+    --iterations <count>    iterations before a point is considered inside the set (default 200)
+    --output <path>         where to save the image, in --out-dir (default a name made of the
+                            fractal, size, view, iterations and time, like
+                            mandelbrot-1024x1024-c-1_0-z1-i200-20261018-183500.png)
+    --out-dir <path>        directory to save everything in (default the working directory)
+    --overwrite             replace files that are already there instead of refusing to start
+    --color <mode>          grayscale, palette or raw (default grayscale)
+    --palette <name|path>   classic, fire, ice, grayscale or a palette file (default classic)
+    --palette-offset <f>    shifts the palette by this fraction of its length (default 0)
+    --palette-period <n>    iterations per cycle through the palette (default 64)
+    --precision <mode>      auto, single, double or perturbation (default auto)
+    --supersample <pattern> none, <n>x<n>, rotated-grid or jittered:<n> (default none)
+    --tile <pixels>         render in tiles of at most this size, streaming them to the output
+    --format <format>       rgba8, rgba16, rgba16f or rgba32f (default rgba8)
+
+progressive options:
+    --step <iterations>     render at most this many iterations per submission, so long renders
+                            don't trip the driver's timeout
+    --preview-every <n>     save the render so far every n steps, next to the output with
+                            -preview added to its name
+    --stop-below <fraction> stop once less than this fraction of pixels is still iterating, and
+                            treat those as inside the set (default 0)
+
+animation options:
+    --animate <frames>      render a zoom from --center and --zoom to the end view
+    --end-center <x>,<y>    center of the last frame (default --center)
+    --end-zoom <factor>     zoom of the last frame (default 1000 times --zoom)
+    --easing <easing>       linear, ease-in, ease-out or ease-in-out (default ease-in-out)
+    --fps <rate>            frame rate of GIF and Y4M output (default 30)
+
+buddhabrot options:
+    --buddhabrot <limits>   render a Buddhabrot of the orbits that escape within <n> iterations
+                            instead, or a Nebulabrot with <r>,<g>,<b> limits for each channel
+    --samples <count>       random points to iterate (default 16777216)
+    --seed <n>              picks the random points, the same seed gives the same image
+                            (default 0)
+    --gamma <g>             higher values brighten faint orbits (default 2)
+
+palette files have one color stop per line, either '#rrggbb' or '<position> #rrggbb' with
+positions in [0, 1). raw mode saves the iteration count of every pixel, as a 16-bit grayscale
+image when the output ends in .png and as little-endian u32s otherwise.
+
+auto precision moves from single to double precision to perturbation as the zoom deepens.
+perturbation only renders the mandelbrot set. it reads the center at full precision, so give it
+as many decimal digits as the zoom needs. deep zooms also need many more iterations.
+
+images larger than the device can render at once, or than comfortably fit in memory, are
+rendered in tiles of 2048 pixels unless --tile says otherwise. tiled images are written a row
+of tiles at a time, so color output must be a .png.
+
+rendering in steps keeps every pixel's orbit on the device between submissions. previews show
+pixels that haven't escaped yet as inside the set. it can't be combined with tiles or animations.
+
+supersampling renders several samples in every pixel and averages them, which smooths the
+jagged edges of the set. <n>x<n> is an even grid, rotated-grid takes four samples that never
+share a row or column and jittered:<n> moves each sample of an n by n grid to a random spot
+within its cell. raw output can't be supersampled.
+
+formats other than rgba8 render into a storage image with more bits per channel, which the
+device has to support. they're saved without rounding to 8 bits, as a 16-bit PNG, Radiance HDR,
+OpenEXR or 32-bit float TIFF when the output ends in .png, .hdr, .exr or .tiff. they can't be
+used for raw output, tiles or animations.
+
+a Buddhabrot iterates random points and counts how often their orbits pass through each pixel
+before escaping. its view defaults to -0.4,0 at zoom 0.75. --fractal, --iterations, the color
+and palette options, --precision and --supersample don't apply to it, and it can't be tiled,
+animated or rendered in other formats.
+
+animations are written as numbered images when the output ends in .png, and as an animated
+GIF or uncompressed Y4M video when it ends in .gif or .y4m.
+
+every run also writes a JSON sidecar next to the output, with .json in place of its extension,
+recording the settings, command line, device and how long rendering took.";
+
+struct Args {
+    settings: RenderSettings,
+    /// A name is made up from the settings when there's none.
+    output: Option<String>,
+    out: OutputOptions,
+    tile_size: Option<u32>,
+    animation: Option<Animation>,
+    buddhabrot: Option<BuddhabrotSettings>,
+    progressive: Option<Progressive>,
+}
+
+struct Progressive {
+    iterations_per_step: u32,
+    preview_every: Option<u32>,
+    stop_below: f64,
+}
+
+struct Animation {
+    zoom: ZoomAnimation,
+    fps: u32,
+}
+
+impl Args {
+    fn parse() -> Self {
+        let mut settings = RenderSettings::default();
+        let mut output = None;
+        let mut out = OutputOptions::default();
+        let mut tile_size = None;
+        let mut frames = None;
+        let mut end_center = None;
+        let mut end_zoom = None;
+        let mut easing = Easing::EaseInOut;
+        let mut fps = 30;
+        let mut view_given = [false, false];
+        let mut limits = None;
+        let mut buddhabrot = BuddhabrotSettings::default();
+        let mut iterations_per_step = None;
+        let mut preview_every = None;
+        let mut stop_below = None;
+        let mut argv = env::args().skip(1);
+        while let Some(flag) = argv.next() {
+            if flag == "--overwrite" {
+                out.overwrite = true;
+                continue;
+            }
+            let value = argv
+                .next()
+                .unwrap_or_else(|| exit_with_usage(&format!("'{}' needs a value", flag)));
+            match flag.as_str() {
+                "--fractal" => {
+                    settings.fractal = value
+                        .parse()
+                        .unwrap_or_else(|e: String| exit_with_usage(&e))
+                }
+                "--width" => settings.width = parse_positive(&flag, &value),
+                "--height" => settings.height = parse_positive(&flag, &value),
+                "--center" => {
+                    (settings.center, settings.exact_center) = parse_center(&flag, &value);
+                    view_given[0] = true;
+                }
+                "--zoom" => {
+                    settings.zoom = parse_zoom(&flag, &value);
+                    view_given[1] = true;
+                }
+                "--iterations" => settings.iterations = parse_positive(&flag, &value),
+                "--output" => output = Some(value),
+                "--out-dir" => out.dir = value.into(),
+                "--color" => {
+                    settings.color = match value.as_str() {
+                        "grayscale" => ColorMode::Grayscale,
+                        "palette" => ColorMode::Palette,
+                        "raw" => ColorMode::Raw,
+                        _ => exit_with_usage(&format!("unknown color mode '{}'", value)),
+                    }
+                }
+                "--palette" => {
+                    settings.palette = if BUILTIN_NAMES.contains(&value.as_str()) {
+                        Palette::builtin(&value).unwrap()
+                    } else {
+                        Palette::load(&value).unwrap_or_else(|e| exit_with_usage(&e))
+                    }
+                }
+                "--palette-offset" => {
+                    settings.palette_offset = value
+                        .parse()
+                        .ok()
+                        .filter(|o: &f32| o.is_finite())
+                        .unwrap_or_else(|| exit_with_usage("--palette-offset must be a number"));
+                }
+                "--palette-period" => {
+                    settings.palette_period = value
+                        .parse()
+                        .ok()
+                        .filter(|p: &f32| p.is_finite() && *p > 0.0)
+                        .unwrap_or_else(|| {
+                            exit_with_usage("--palette-period must be a positive number")
+                        });
+                }
+                "--precision" => {
+                    settings.precision = match value.as_str() {
+                        "auto" => Precision::Auto,
+                        "single" => Precision::Single,
+                        "double" => Precision::Double,
+                        "perturbation" => Precision::Perturbation,
+                        _ => exit_with_usage(&format!("unknown precision '{}'", value)),
+                    }
+                }
+                "--supersample" => {
+                    settings.supersampling = value
+                        .parse()
+                        .unwrap_or_else(|e: String| exit_with_usage(&e))
+                }
+                "--tile" => tile_size = Some(parse_positive(&flag, &value)),
+                "--format" => {
+                    settings.format = value
+                        .parse()
+                        .unwrap_or_else(|e: String| exit_with_usage(&e))
+                }
+                "--animate" => frames = Some(parse_positive(&flag, &value)),
+                "--end-center" => end_center = Some(parse_center(&flag, &value)),
+                "--end-zoom" => end_zoom = Some(parse_zoom(&flag, &value)),
+                "--easing" => {
+                    easing = value
+                        .parse()
+                        .unwrap_or_else(|e: String| exit_with_usage(&e))
+                }
+                "--fps" => fps = parse_positive(&flag, &value),
+                "--buddhabrot" => {
+                    limits = Some(
+                        value
+                            .parse()
+                            .unwrap_or_else(|e: String| exit_with_usage(&e)),
+                    )
+                }
+                "--samples" => buddhabrot.samples = parse_positive(&flag, &value),
+                "--seed" => {
+                    buddhabrot.seed = value
+                        .parse()
+                        .unwrap_or_else(|_| exit_with_usage("--seed must be a whole number"))
+                }
+                "--gamma" => {
+                    buddhabrot.gamma = value
+                        .parse()
+                        .ok()
+                        .filter(|g: &f32| g.is_finite() && *g > 0.0)
+                        .unwrap_or_else(|| exit_with_usage("--gamma must be a positive number"))
+                }
+                "--step" => iterations_per_step = Some(parse_positive(&flag, &value)),
+                "--preview-every" => preview_every = Some(parse_positive(&flag, &value)),
+                "--stop-below" => {
+                    stop_below = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|f: &f64| (0.0..=1.0).contains(f))
+                            .unwrap_or_else(|| {
+                                exit_with_usage("--stop-below must be a number from 0 to 1")
+                            }),
+                    )
+                }
+                _ => exit_with_usage(&format!("unknown option '{}'", flag)),
+            }
+        }
+
+        if settings.format != ColorFormat::Rgba8 {
+            if settings.color == ColorMode::Raw {
+                exit_with_usage("raw output can only be rgba8");
+            }
+            if tile_size.is_some() || frames.is_some() {
+                exit_with_usage("tiles and animations can only be rgba8");
+            }
+        }
+        if iterations_per_step.is_none() && (preview_every.is_some() || stop_below.is_some()) {
+            exit_with_usage("--preview-every and --stop-below need --step");
+        }
+        let progressive = iterations_per_step.map(|iterations_per_step| {
+            if tile_size.is_some() || frames.is_some() || limits.is_some() {
+                exit_with_usage("tiles, animations and Buddhabrots can't be rendered in steps");
+            }
+            Progressive {
+                iterations_per_step,
+                preview_every,
+                stop_below: stop_below.unwrap_or(0.0),
+            }
+        });
+        let buddhabrot = limits.map(|limits| {
+            if tile_size.is_some() || frames.is_some() || settings.format != ColorFormat::Rgba8 {
+                exit_with_usage(
+                    "a Buddhabrot can't be tiled, animated or rendered in other formats",
+                );
+            }
+            BuddhabrotSettings {
+                width: settings.width,
+                height: settings.height,
+                center: if view_given[0] {
+                    settings.center
+                } else {
+                    buddhabrot.center
+                },
+                zoom: if view_given[1] {
+                    settings.zoom
+                } else {
+                    buddhabrot.zoom
+                },
+                limits,
+                ..buddhabrot
+            }
+        });
+        let animation = frames.map(|frames| {
+            if settings.color == ColorMode::Raw {
+                exit_with_usage("raw output can't be animated");
+            }
+            if tile_size.is_some() {
+                exit_with_usage("animations can't be tiled");
+            }
+            let start = Keyframe::from_settings(&settings);
+            let (center, exact_center) =
+                end_center.unwrap_or((start.center, start.exact_center.clone()));
+            let end = Keyframe {
+                center,
+                exact_center,
+                zoom: end_zoom.unwrap_or(start.zoom * 1000.0),
+            };
+            Animation {
+                zoom: ZoomAnimation {
+                    start,
+                    end,
+                    frames,
+                    easing,
+                },
+                fps,
+            }
+        });
+        Args {
+            settings,
+            output,
+            out,
+            tile_size,
+            animation,
+            buddhabrot,
+            progressive,
+        }
+    }
+}
+
+/// Parses `<x>,<y>`, keeping the text for full precision rendering.
+fn parse_center(flag: &str, value: &str) -> ([f64; 2], Option<[String; 2]>) {
+    let coords: Vec<String> = value.split(',').map(|c| c.trim().to_string()).collect();
+    let coords: [String; 2] = coords
+        .try_into()
+        .unwrap_or_else(|_| exit_with_usage(&format!("{} takes exactly <x>,<y>", flag)));
+    let center = [0, 1].map(|i| {
+        coords[i]
+            .parse::<f64>()
+            .unwrap_or_else(|e| exit_with_usage(&format!("bad value for {}: {}", flag, e)))
+    });
+    (center, Some(coords))
+}
+
+fn parse_zoom(flag: &str, value: &str) -> f64 {
+    value
+        .parse()
+        .ok()
+        .filter(|z: &f64| z.is_finite() && *z > 0.0)
+        .unwrap_or_else(|| exit_with_usage(&format!("{} must be a positive number", flag)))
+}
+
+fn parse_positive(flag: &str, value: &str) -> u32 {
+    value
+        .parse()
+        .ok()
+        .filter(|&v| v > 0)
+        .unwrap_or_else(|| exit_with_usage(&format!("{} must be a positive integer", flag)))
+}
+
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(1);
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
 
 fn main() {
-    // setup vulkan
-    let library = VulkanLibrary::new().expect("no local Vulkan library/DLL");
-    let instance =
-        Instance::new(library, InstanceCreateInfo::default()).expect("failed to create instance");
-
-    // setup device
-    let physical_device = instance
-        .enumerate_physical_devices()
-        .expect("could not enumerate devices")
-        .next()
-        .expect("no devices available");
-    let queue_family_index = physical_device
-        .queue_family_properties()
-        .iter()
-        .enumerate()
-        .position(|(_i, queue_family_properties)| {
-            queue_family_properties
-                .queue_flags
-                .contains(QueueFlags::GRAPHICS)
-        })
-        .expect("couldn't find a graphical queue fmaily") as u32;
-    let (device, mut queues) = Device::new(
-        physical_device,
-        DeviceCreateInfo {
-            queue_create_infos: vec![QueueCreateInfo {
-                queue_family_index,
-                ..Default::default()
-            }],
-            ..Default::default()
-        },
-    )
-    .expect("failed to create device");
-    let queue = queues.next().unwrap();
-    let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
-
-    // setup compute pipeline
-    let shader = cs::load(device.clone()).expect("failed to create shader module");
-    let entry_point = shader
-        .entry_point("main")
-        .expect("failed to create entry point");
-    let stage = PipelineShaderStageCreateInfo::new(entry_point);
-    let layout = PipelineLayout::new(
-        device.clone(),
-        PipelineDescriptorSetLayoutCreateInfo::from_stages([&stage])
-            .into_pipeline_layout_create_info(device.clone())
-            .expect("could not create pipeline layout info"),
-    )
-    .expect("could not create pipeline layout");
-    let compute_pipeline = ComputePipeline::new(
-        device.clone(),
-        None,
-        ComputePipelineCreateInfo::stage_layout(stage, layout),
-    )
-    .expect("failed to create compute pipeline");
-
-    // setup image input
-    let image = Image::new(
-        memory_allocator.clone(),
-        ImageCreateInfo {
-            image_type: ImageType::Dim2d,
-            format: Format::R8G8B8A8_UNORM,
-            extent: [1024, 1024, 1],
-            usage: ImageUsage::STORAGE | ImageUsage::TRANSFER_SRC,
-            ..Default::default()
-        },
-        AllocationCreateInfo {
-            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
-            ..Default::default()
+    let args = Args::parse();
+
+    // claim every file first, so a long render doesn't end with nowhere to save it
+    let (output, path) = claim_files(&args).unwrap_or_else(|e| exit_with_error(&e));
+
+    // setup vulkan, with doubles for deep zooms and storage images of any format where the
+    // device has them
+    let context = Context::with_optional_features(
+        Features::empty(),
+        Features {
+            shader_float64: true,
+            shader_storage_image_read_without_format: true,
+            shader_storage_image_write_without_format: true,
+            ..Features::empty()
         },
     )
-    .expect("failed to create image");
-    let image_view = ImageView::new_default(image.clone()).expect("could not create image view");
-
-    // setup descriptor
-    let descriptor_set_allocator =
-        StandardDescriptorSetAllocator::new(device.clone(), Default::default());
-    let descriptor_set_layout_index = 0;
-    let descriptor_set_layout = compute_pipeline
-        .layout()
-        .set_layouts()
-        .get(descriptor_set_layout_index)
-        .expect("could not get correct descriptor set");
-    let descriptor_set = PersistentDescriptorSet::new(
-        &descriptor_set_allocator,
-        descriptor_set_layout.clone(),
-        [WriteDescriptorSet::image_view(
-            descriptor_set_layout_index as u32,
-            image_view.clone(),
-        )],
-        [],
-    )
-    .expect("failed to create descriptor set");
-
-    // create buffer for image output
-    let buf = Buffer::from_iter(
-        memory_allocator.clone(),
-        BufferCreateInfo {
-            usage: BufferUsage::TRANSFER_DST,
-            ..Default::default()
-        },
-        AllocationCreateInfo {
-            memory_type_filter: MemoryTypeFilter::PREFER_HOST
-                | MemoryTypeFilter::HOST_RANDOM_ACCESS,
-            ..Default::default()
+    .expect("failed to setup vulkan");
+
+    let elapsed = match &args.buddhabrot {
+        Some(buddhabrot) => render_buddhabrot(&context, buddhabrot, &path),
+        None => render(&context, &args, &path),
+    };
+    output
+        .write_sidecar(context.device_name(), elapsed)
+        .unwrap_or_else(|e| exit_with_error(&e));
+}
+
+/// Starts the run's output and claims the files it writes: the image, or the frames of an
+/// animation, and any previews. Returns where the image goes.
+fn claim_files(args: &Args) -> Result<(Output, String), String> {
+    let settings = &args.settings;
+    let (mut output, name_parts) = match &args.buddhabrot {
+        Some(buddhabrot) => {
+            let mut output = Output::new("buddhabrot", args.out.clone());
+            output.set_parameter("width", buddhabrot.width);
+            output.set_parameter("height", buddhabrot.height);
+            output.set_parameter("center", buddhabrot.center.to_vec());
+            output.set_parameter("zoom", buddhabrot.zoom);
+            output.set_parameter("limits", buddhabrot.limits.0.to_vec());
+            output.set_parameter("samples", buddhabrot.samples);
+            output.set_parameter("seed", buddhabrot.seed);
+            output.set_parameter("gamma", buddhabrot.gamma);
+            let name_parts = vec![
+                format!("{}x{}", buddhabrot.width, buddhabrot.height),
+                format!("c{},{}", buddhabrot.center[0], buddhabrot.center[1]),
+                format!("z{}", buddhabrot.zoom),
+                format!("s{}", buddhabrot.samples),
+            ];
+            (output, name_parts)
+        }
+        None => {
+            let mut output = Output::new(fractal_name(settings.fractal), args.out.clone());
+            describe(&mut output, args);
+            let name_parts = vec![
+                format!("{}x{}", settings.width, settings.height),
+                format!("c{},{}", settings.center[0], settings.center[1]),
+                format!("z{}", settings.zoom),
+                format!("i{}", settings.iterations),
+            ];
+            (output, name_parts)
+        }
+    };
+
+    let name = match &args.output {
+        Some(name) => name.clone(),
+        None => output.file_name(&name_parts, "png"),
+    };
+    let path = match &args.animation {
+        Some(animation) => {
+            output.claim_sequence(&name, &frame_paths(&name, animation.zoom.frames))?
+        }
+        None => output.claim(&name)?,
+    };
+    if args
+        .progressive
+        .as_ref()
+        .is_some_and(|progressive| progressive.preview_every.is_some())
+    {
+        output.claim(&preview_path(&name))?;
+    }
+    Ok((output, path.to_string_lossy().into_owned()))
+}
+
+/// Records the settings of a fractal render in the sidecar.
+fn describe(output: &mut Output, args: &Args) {
+    let settings = &args.settings;
+    output.set_parameter("fractal", format!("{:?}", settings.fractal));
+    output.set_parameter("width", settings.width);
+    output.set_parameter("height", settings.height);
+    match &settings.exact_center {
+        Some(center) => output.set_parameter("center", center.to_vec()),
+        None => output.set_parameter("center", settings.center.to_vec()),
+    }
+    output.set_parameter("zoom", settings.zoom);
+    output.set_parameter("iterations", settings.iterations);
+    output.set_parameter("color", format!("{:?}", settings.color));
+    output.set_parameter("palette_offset", settings.palette_offset);
+    output.set_parameter("palette_period", settings.palette_period);
+    output.set_parameter("precision", format!("{:?}", settings.precision));
+    output.set_parameter("supersampling", format!("{:?}", settings.supersampling));
+    output.set_parameter("format", settings.format.name());
+    if let Some(tile_size) = args.tile_size {
+        output.set_parameter("tile_size", tile_size);
+    }
+    if let Some(animation) = &args.animation {
+        let end = &animation.zoom.end;
+        output.set_parameter("frames", animation.zoom.frames);
+        match &end.exact_center {
+            Some(center) => output.set_parameter("end_center", center.to_vec()),
+            None => output.set_parameter("end_center", end.center.to_vec()),
+        }
+        output.set_parameter("end_zoom", end.zoom);
+        output.set_parameter("easing", format!("{:?}", animation.zoom.easing));
+        output.set_parameter("fps", animation.fps);
+    }
+    if let Some(progressive) = &args.progressive {
+        output.set_parameter("step", progressive.iterations_per_step);
+        output.set_parameter("stop_below", progressive.stop_below);
+    }
+}
+
+fn fractal_name(fractal: Fractal) -> &'static str {
+    match fractal {
+        Fractal::Mandelbrot => "mandelbrot",
+        Fractal::Julia { .. } => "julia",
+        Fractal::BurningShip => "burning-ship",
+        Fractal::Tricorn => "tricorn",
+        Fractal::Multibrot { .. } => "multibrot",
+    }
+}
+
+/// `name.png` becomes `name-preview.png`.
+fn preview_path(path: &str) -> String {
+    let extension = path
+        .rsplit_once('.')
+        .filter(|(_, extension)| !extension.contains(['/', '\\']));
+    match extension {
+        Some((stem, extension)) => format!("{}-preview.{}", stem, extension),
+        None => format!("{}-preview", path),
+    }
+}
+
+/// Renders a fractal to `path` however `args` asks for, returning how long it took.
+fn render(context: &Context, args: &Args, path: &str) -> Duration {
+    let settings = &args.settings;
+
+    // big images don't fit in a single device image, or in memory
+    let max_size = context
+        .device
+        .physical_device()
+        .properties()
+        .max_image_dimension2_d;
+    let tile_size = args.tile_size.or_else(|| {
+        let samples_per_side = settings.supersampling.samples_per_side() as u64;
+        let [width, height] =
+            [settings.width, settings.height].map(|s| s as u64 * samples_per_side);
+        let too_big = width.max(height) > max_size as u64 || width * height > MAX_UNTILED_PIXELS;
+        let tileable = args.animation.is_none()
+            && args.progressive.is_none()
+            && settings.format == ColorFormat::Rgba8;
+        (too_big && tileable).then_some(DEFAULT_TILE_SIZE)
+    });
+    if let Some(tile_size) = tile_size {
+        return render_tiled(context, args, path, tile_size);
+    }
+
+    // animations need the precision of their deepest frame throughout
+    let renderer_settings = match &args.animation {
+        Some(animation) => animation.zoom.deepest(settings),
+        None => settings.clone(),
+    };
+    let renderer =
+        FractalRenderer::new(context, &renderer_settings).unwrap_or_else(|e| exit_with_usage(&e));
+    println!(
+        "Rendering {:?} on {} with {:?} precision{}",
+        settings.fractal,
+        context.device_name(),
+        renderer.precision(),
+        if renderer.uses_doubles() {
+            " (doubles)"
+        } else {
+            ""
+        }
+    );
+
+    if let Some(animation) = &args.animation {
+        return animate(context, &renderer, args, path, animation);
+    }
+
+    if let Some(progressive) = &args.progressive {
+        return render_progressive(context, &renderer, args, path, progressive);
+    }
+
+    if settings.format != ColorFormat::Rgba8 {
+        return render_float(context, &renderer, args, path);
+    }
+
+    // render
+    let render_start = SystemTime::now();
+    let image = renderer
+        .render(context, settings)
+        .unwrap_or_else(|e| exit_with_usage(&e));
+    let render_elapsed = render_start
+        .elapsed()
+        .expect("could not elapse render time");
+    println!(
+        "Rendered {}x{} in {:?}",
+        settings.width, settings.height, render_elapsed
+    );
+
+    // save
+    if settings.color == ColorMode::Raw {
+        save_iteration_counts(path, settings.iterations, &image);
+    } else {
+        image.save(path).expect("failed to save image");
+    }
+    render_elapsed
+}
+
+/// Renders in a format with more than 8 bits per channel and saves it at full precision.
+fn render_float(
+    context: &Context,
+    renderer: &FractalRenderer,
+    args: &Args,
+    path: &str,
+) -> Duration {
+    let settings = &args.settings;
+    let render_start = SystemTime::now();
+    let image = renderer
+        .render_float(context, settings)
+        .unwrap_or_else(|e| exit_with_usage(&e));
+    let render_elapsed = render_start
+        .elapsed()
+        .expect("could not elapse render time");
+    println!(
+        "Rendered {}x{} as {:?} in {:?}",
+        settings.width,
+        settings.height,
+        settings.format.format(),
+        render_elapsed
+    );
+
+    save_float_image(&image, path).unwrap_or_else(|e| exit_with_error(&e));
+    render_elapsed
+}
+
+/// Renders a Buddhabrot, which has its own renderer and settings.
+fn render_buddhabrot(context: &Context, settings: &BuddhabrotSettings, path: &str) -> Duration {
+    let renderer =
+        BuddhabrotRenderer::new(context, settings).unwrap_or_else(|e| exit_with_usage(&e));
+    println!(
+        "Rendering a Buddhabrot of {} samples with limits {:?} on {}",
+        settings.samples,
+        settings.limits.0,
+        context.device_name()
+    );
+
+    let render_start = SystemTime::now();
+    let image = renderer.render(context, settings);
+    let render_elapsed = render_start
+        .elapsed()
+        .expect("could not elapse render time");
+    println!(
+        "Rendered {}x{} in {:?}",
+        settings.width, settings.height, render_elapsed
+    );
+
+    image.save(path).expect("failed to save image");
+    render_elapsed
+}
+
+/// Renders a limited number of iterations at a time, saving previews along the way, until every
+/// pixel is done or few enough are still iterating.
+fn render_progressive(
+    context: &Context,
+    renderer: &FractalRenderer,
+    args: &Args,
+    path: &str,
+    progressive: &Progressive,
+) -> Duration {
+    let settings = &args.settings;
+    let mut render =
+        ProgressiveRender::new(context, renderer, settings).unwrap_or_else(|e| exit_with_usage(&e));
+    let preview_path = preview_path(path);
+
+    let render_start = SystemTime::now();
+    let mut steps = 0;
+    while !render.is_finished() && render.active_fraction() >= progressive.stop_below {
+        render.step(context, progressive.iterations_per_step);
+        steps += 1;
+        println!(
+            "Ran {}/{} iterations, {:.2}% of pixels still iterating",
+            render.iterations_done(),
+            settings.iterations,
+            render.active_fraction() * 100.0
+        );
+        let preview_due = progressive
+            .preview_every
+            .is_some_and(|every| steps % every == 0);
+        if preview_due && !render.is_finished() {
+            save_render(context, renderer, args, &preview_path);
+        }
+    }
+    let render_elapsed = render_start
+        .elapsed()
+        .expect("could not elapse render time");
+    println!(
+        "Rendered {}x{} in {} steps in {:?}",
+        settings.width, settings.height, steps, render_elapsed
+    );
+
+    save_render(context, renderer, args, path);
+    render_elapsed
+}
+
+/// Saves whatever `renderer`'s image holds to `path`, in the format and color mode asked for.
+fn save_render(context: &Context, renderer: &FractalRenderer, args: &Args, path: &str) {
+    let settings = &args.settings;
+    if settings.format != ColorFormat::Rgba8 {
+        save_float_image(&renderer.download_float(context), path)
+            .unwrap_or_else(|e| exit_with_error(&e));
+    } else if settings.color == ColorMode::Raw {
+        save_iteration_counts(path, settings.iterations, &renderer.download(context));
+    } else {
+        renderer
+            .download(context)
+            .save(path)
+            .expect("failed to save image");
+    }
+}
+
+/// Renders every frame of `animation`, handing each one to an encoder thread so the next frame
+/// renders while the last one is encoded.
+fn animate(
+    context: &Context,
+    renderer: &FractalRenderer,
+    args: &Args,
+    path: &str,
+    animation: &Animation,
+) -> Duration {
+    let settings = &args.settings;
+    let frames = animation.zoom.frames;
+    let mut writer =
+        FrameWriter::create(path, settings.width, settings.height, animation.fps, frames)
+            .unwrap_or_else(|e| exit_with_usage(&e));
+
+    // a single slot, so rendering never runs more than a frame ahead of encoding
+    let (sender, receiver) = mpsc::sync_channel::<RgbaImage>(1);
+    let encoder = thread::spawn(move || {
+        for frame in receiver {
+            writer.write(frame)?;
+        }
+        writer.finish()
+    });
+
+    let animation_start = SystemTime::now();
+    for frame in 0..frames {
+        let image = renderer
+            .render(context, &animation.zoom.frame(settings, frame))
+            .unwrap_or_else(|e| exit_with_usage(&e));
+        // only fails when the encoder has given up, its error is reported below
+        if sender.send(image).is_err() {
+            break;
+        }
+        println!("Rendered frame {}/{}", frame + 1, frames);
+    }
+    drop(sender);
+
+    if let Err(e) = encoder.join().expect("encoder thread panicked") {
+        exit_with_error(&e);
+    }
+    let animation_elapsed = animation_start
+        .elapsed()
+        .expect("could not elapse animation time");
+    println!("Wrote {} frames in {:?}", frames, animation_elapsed);
+    animation_elapsed
+}
+
+/// Renders a tile at a time, writing each row of tiles to the output as soon as it's done.
+fn render_tiled(context: &Context, args: &Args, path: &str, tile_size: u32) -> Duration {
+    let settings = &args.settings;
+    let renderer =
+        TiledRenderer::new(context, settings, tile_size).unwrap_or_else(|e| exit_with_usage(&e));
+    let [tile_width, tile_height] = renderer.tile_size();
+    println!(
+        "Rendering {:?} on {} with {:?} precision{} in {}x{} tiles",
+        settings.fractal,
+        context.device_name(),
+        renderer.precision(),
+        if renderer.uses_doubles() {
+            " (doubles)"
+        } else {
+            ""
         },
-        (0..1024 * 1024 * 4).map(|_| 0u8),
-    )
-    .expect("could not create buffer");
+        tile_width,
+        tile_height
+    );
 
-    // create buffer builder
-    let command_buffer_allocator = StandardCommandBufferAllocator::new(
-        device.clone(),
-        StandardCommandBufferAllocatorCreateInfo::default(),
+    let raw = settings.color == ColorMode::Raw;
+    if raw && path.ends_with(".png") && settings.iterations > u16::MAX as u32 {
+        println!("Counts above {} are clamped in 16-bit output", u16::MAX);
+    }
+    let mut writer = RowWriter::create(path, settings.width, settings.height, raw)
+        .unwrap_or_else(|e| exit_with_usage(&e));
+    let render_start = SystemTime::now();
+    let mut rows_done = 0;
+    renderer
+        .render(context, settings, |rows| {
+            writer.write(rows)?;
+            rows_done += (rows.len() / (settings.width as usize * 4)) as u32;
+            println!("Rendered {}/{} rows", rows_done, settings.height);
+            Ok(())
+        })
+        .and_then(|_| writer.finish())
+        .unwrap_or_else(|e| exit_with_error(&e));
+    let render_elapsed = render_start
+        .elapsed()
+        .expect("could not elapse render time");
+    println!(
+        "Rendered {}x{} in {:?}",
+        settings.width, settings.height, render_elapsed
     );
-    let mut command_buffer_builder = AutoCommandBufferBuilder::primary(
-        &command_buffer_allocator,
-        queue.queue_family_index(),
-        CommandBufferUsage::OneTimeSubmit,
-    )
-    .expect("failed to create command buffer builder");
-
-    // build buffer
-    let work_group_counts = [1024 / 8, 1024 / 8, 1];
-    command_buffer_builder
-        .bind_pipeline_compute(compute_pipeline.clone())
-        .expect("failed to bind pipeline command buffer builder")
-        .bind_descriptor_sets(
-            PipelineBindPoint::Compute,
-            compute_pipeline.layout().clone(),
-            descriptor_set_layout_index as u32,
-            descriptor_set,
-        )
-        .expect("failed to bind command buffer to descriptor sets")
-        .dispatch(work_group_counts)
-        .expect("failed to dispatch work groups")
-        .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
-            image.clone(),
-            buf.clone(),
-        ))
-        .unwrap();
-    let command_buffer = command_buffer_builder
-        .build()
-        .expect("failed to build command buffer");
-
-    // submit command buffer
-    let future = sync::now(device.clone())
-        .then_execute(queue.clone(), command_buffer)
-        .expect("failed to execute")
-        .then_signal_fence_and_flush()
-        .unwrap();
-
-    future.wait(None).unwrap();
-    
-    // read buffer
-    let buf_content = buf.read().expect("could not read buffer");
-    let image_buf = ImageBuffer::<Rgba<u8>, _>::from_raw(1024, 1024, &buf_content[..]).expect("failed to create image from buffer");
-    image_buf.save("mandelbrot.png").expect("failed to save image");
-}
-
-mod cs {
-    vulkano_shaders::shader! {
-        ty: "compute",
-        path: "examples/compute-mandelbrot/shader.glsl"
+    render_elapsed
+}
+
+/// Saves the counts the shader packed into each pixel's channels in raw mode.
+fn save_iteration_counts(path: &str, iterations: u32, image: &RgbaImage) {
+    let counts = iteration_counts(image);
+    if path.ends_with(".png") {
+        if iterations > u16::MAX as u32 {
+            println!("Counts above {} are clamped in 16-bit output", u16::MAX);
+        }
+        let counts = counts
+            .into_iter()
+            .map(|c| c.min(u16::MAX as u32) as u16)
+            .collect();
+        let image_buf =
+            ImageBuffer::<Luma<u16>, Vec<u16>>::from_raw(image.width(), image.height(), counts)
+                .expect("failed to create image from buffer");
+        image_buf.save(path).expect("failed to save image");
+    } else {
+        let bytes: Vec<u8> = counts.into_iter().flat_map(u32::to_le_bytes).collect();
+        fs::write(path, bytes).expect("failed to save iteration counts");
     }
 }
@@ -1,165 +1,171 @@
-use std::{sync::Arc, time::SystemTime};
+use std::{env, process, time::SystemTime};
 
+use vulkan_test::split::{split_work_groups, worker::WORK_GROUP_SIZE, Element, Worker};
 use vulkano::{
-    buffer::{Buffer, BufferCreateInfo, BufferUsage},
-    command_buffer::{
-        allocator::{StandardCommandBufferAllocator, StandardCommandBufferAllocatorCreateInfo},
-        AutoCommandBufferBuilder, CommandBufferUsage,
-    },
-    descriptor_set::{
-        allocator::StandardDescriptorSetAllocator, PersistentDescriptorSet, WriteDescriptorSet,
-    },
-    device::{Device, DeviceCreateInfo, QueueCreateInfo, QueueFlags},
+    half::f16,
     instance::{Instance, InstanceCreateInfo},
-    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
-    pipeline::{
-        compute::ComputePipelineCreateInfo, layout::PipelineDescriptorSetLayoutCreateInfo,
-        ComputePipeline, Pipeline, PipelineBindPoint, PipelineLayout,
-        PipelineShaderStageCreateInfo,
-    },
-    sync::{self, GpuFuture},
     VulkanLibrary,
 };
 
+const USAGE: &str = "usage: compute [u32|i32|u64|f32|f64|f16] [--devices all|<index>,<index>,...]";
+
+/// Which physical devices to split the job between.
+enum DeviceSelection {
+    All,
+    /// Indices into the instance's physical devices. Repeating an index creates several logical
+    /// devices on the same hardware, which lets the split be tested with a single device.
+    Indices(Vec<usize>),
+}
+
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(1);
+}
+
 fn main() {
+    let mut element_type = u32::NAME.to_string();
+    let mut device_selection = DeviceSelection::Indices(vec![0]);
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--devices" => {
+                let value = args
+                    .next()
+                    .unwrap_or_else(|| exit_with_usage("--devices needs a value"));
+                device_selection = if value == "all" {
+                    DeviceSelection::All
+                } else {
+                    DeviceSelection::Indices(
+                        value
+                            .split(',')
+                            .map(|i| {
+                                i.trim().parse().unwrap_or_else(|_| {
+                                    exit_with_usage(&format!("invalid device index '{}'", i))
+                                })
+                            })
+                            .collect(),
+                    )
+                };
+            }
+            _ => element_type = arg,
+        }
+    }
+
+    // pick which element type to run the kernel with
+    match element_type.as_str() {
+        "u32" => run::<u32>(&device_selection),
+        "i32" => run::<i32>(&device_selection),
+        "u64" => run::<u64>(&device_selection),
+        "f32" => run::<f32>(&device_selection),
+        "f64" => run::<f64>(&device_selection),
+        "f16" => run::<f16>(&device_selection),
+        other => exit_with_usage(&format!("unknown element type '{}'", other)),
+    }
+}
+
+fn run<T: Element>(device_selection: &DeviceSelection) {
+    println!("Running kernel with {} elements\n", T::NAME);
+
     // setup vulkan
     let library = VulkanLibrary::new().expect("no local Vulkan library/DLL");
     let instance =
         Instance::new(library, InstanceCreateInfo::default()).expect("failed to create instance");
 
-    // setup device
-    let physical_device = instance
+    // setup a worker per selected device
+    let physical_devices: Vec<_> = instance
         .enumerate_physical_devices()
         .expect("could not enumerate devices")
-        .next()
-        .expect("no devices available");
-    let queue_family_index = physical_device
-        .queue_family_properties()
+        .collect();
+    if physical_devices.is_empty() {
+        panic!("no devices available");
+    }
+    let device_indices = match device_selection {
+        DeviceSelection::All => (0..physical_devices.len()).collect(),
+        DeviceSelection::Indices(indices) => indices.clone(),
+    };
+    let workers: Vec<Worker<T>> = device_indices
         .iter()
-        .enumerate()
-        .position(|(_i, queue_family_properties)| {
-            queue_family_properties
-                .queue_flags
-                .contains(QueueFlags::GRAPHICS)
+        .map(|&i| {
+            let physical_device = physical_devices.get(i).unwrap_or_else(|| {
+                exit_with_usage(&format!(
+                    "there is no device {}, only {} available",
+                    i,
+                    physical_devices.len()
+                ))
+            });
+            Worker::new(physical_device.clone()).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                process::exit(1);
+            })
         })
-        .expect("couldn't find a graphical queue fmaily") as u32;
-    let (device, mut queues) = Device::new(
-        physical_device,
-        DeviceCreateInfo {
-            queue_create_infos: vec![QueueCreateInfo {
-                queue_family_index,
-                ..Default::default()
-            }],
-            ..Default::default()
-        },
-    )
-    .expect("failed to create device");
-    let queue = queues.next().unwrap();
-    let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
-
-    // setup original buffer
-    let data_iter = 0..65536_u32;
-    let data_buffer = Buffer::from_iter(
-        memory_allocator.clone(),
-        BufferCreateInfo {
-            usage: BufferUsage::STORAGE_BUFFER,
-            ..Default::default()
-        },
-        AllocationCreateInfo {
-            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
-                | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-            ..Default::default()
-        },
-        data_iter.clone(),
-    )
-    .expect("failed to create data buffer");
-
-    // setup compute pipeline
-    let shader = cs::load(device.clone()).expect("failed to create shader module");
-    let entry_point = shader
-        .entry_point("main")
-        .expect("failed to create entry point");
-    let stage = PipelineShaderStageCreateInfo::new(entry_point);
-    let layout = PipelineLayout::new(
-        device.clone(),
-        PipelineDescriptorSetLayoutCreateInfo::from_stages([&stage])
-            .into_pipeline_layout_create_info(device.clone())
-            .expect("could not create pipeline layout info"),
-    )
-    .expect("could not create pipeline layout");
-    let compute_pipeline = ComputePipeline::new(
-        device.clone(),
-        None,
-        ComputePipelineCreateInfo::stage_layout(stage, layout),
-    )
-    .expect("failed to create compute pipeline");
-
-    // setup descriptor
-    let descriptor_set_allocator =
-        StandardDescriptorSetAllocator::new(device.clone(), Default::default());
-    let pipeline_layout = compute_pipeline.layout();
-    let descriptor_set_layouts = pipeline_layout.set_layouts();
-    let descriptor_set_layout_index = 0;
-    let descriptor_set_layout = descriptor_set_layouts
-        .get(descriptor_set_layout_index)
-        .expect("could not get correct descriptor set");
-    let descriptor_set = PersistentDescriptorSet::new(
-        &descriptor_set_allocator,
-        descriptor_set_layout.clone(),
-        [WriteDescriptorSet::buffer(0, data_buffer.clone())],
-        [],
-    )
-    .expect("failed to create descriptor set");
-
-    // dispatch command buffer
-    let command_buffer_allocator = StandardCommandBufferAllocator::new(
-        device.clone(),
-        StandardCommandBufferAllocatorCreateInfo::default(),
-    );
-    let mut command_buffer_builder = AutoCommandBufferBuilder::primary(
-        &command_buffer_allocator,
-        queue.queue_family_index(),
-        CommandBufferUsage::OneTimeSubmit,
-    )
-    .expect("failed to create command buffer builder");
-    let work_group_counts = [1024, 1, 1];
-    command_buffer_builder
-        .bind_pipeline_compute(compute_pipeline.clone())
-        .expect("failed to bind pipeline command buffer builder")
-        .bind_descriptor_sets(
-            PipelineBindPoint::Compute,
-            compute_pipeline.layout().clone(),
-            descriptor_set_layout_index as u32,
-            descriptor_set,
-        )
-        .expect("failed to bind command buffer to descriptor sets")
-        .dispatch(work_group_counts)
-        .expect("failed to dispatch work groups");
-    let command_buffer = command_buffer_builder
-        .build()
-        .expect("failed to build command buffer");
-
-    // submit command buffer
-    let now_future = sync::now(device.clone());
+        .collect();
+
+    // setup original data
+    let data: Vec<T> = (0..65536_u32).map(T::from_index).collect();
+    let total_work_groups = data.len() as u32 / WORK_GROUP_SIZE;
+
+    // split the job between devices according to how fast each one is
+    let throughputs: Vec<f64> = if workers.len() == 1 {
+        vec![1.0]
+    } else {
+        println!("Measuring device throughput...");
+        let throughputs = workers
+            .iter()
+            .zip(&device_indices)
+            .map(|(worker, i)| {
+                let throughput = worker.measure_throughput();
+                println!(
+                    "\tdevice {} ({}): {:.0} elements/s",
+                    i,
+                    worker.device_name(),
+                    throughput
+                );
+                throughput
+            })
+            .collect();
+        println!();
+        throughputs
+    };
+
+    // upload each device's share of the data
+    let chunks: Vec<_> = split_work_groups(total_work_groups, &throughputs)
+        .into_iter()
+        .map(|(device, groups)| {
+            let worker = &workers[device];
+            let elements =
+                (groups.start * WORK_GROUP_SIZE) as usize..(groups.end * WORK_GROUP_SIZE) as usize;
+            println!(
+                "device {} ({}) gets elements {}..{}",
+                device_indices[device],
+                worker.device_name(),
+                elements.start,
+                elements.end
+            );
+            let buffer = worker.upload(&data[elements]);
+            (worker, buffer)
+        })
+        .collect();
+    println!();
 
     // time GPU's execution, for fun
     println!("Starting timer for GPU to compute...");
     let gpu_start = SystemTime::now();
-    let future = now_future
-        .then_execute(queue.clone(), command_buffer)
-        .expect("failed to execute command buffer")
-        .then_signal_fence_and_flush()
-        .expect("failed to signal fence and flush");
-    future.wait(None).unwrap();
+    let futures: Vec<_> = chunks
+        .iter()
+        .map(|(worker, buffer)| worker.dispatch(buffer.clone()))
+        .collect();
+    for future in futures {
+        future.wait(None).unwrap();
+    }
     let gpu_elapsed = gpu_start.elapsed().expect("could not elapse gpu time");
     println!("Done\n");
 
     // time CPU's execution, for fun
-    let mut cpu_buffer: Vec<_> = data_iter.collect();
+    let mut cpu_buffer = data;
     println!("Starting timer for CPU to compute...");
     let cpu_start = SystemTime::now();
     for n in cpu_buffer.iter_mut() {
-        *n *= 12;
+        *n = n.cpu_kernel();
     }
     let cpu_elapsed = cpu_start.elapsed().expect("could not elapse cpu time");
     println!("Done\n");
@@ -167,18 +173,23 @@ fn main() {
     // check differences
     println!("GPU took this long: {:?}\nCPU took this long: {:?}\n", gpu_elapsed, cpu_elapsed);
 
+    // merge every device's results back into one
+    let mut gpu_buffer = Vec::with_capacity(cpu_buffer.len());
+    for (_, buffer) in &chunks {
+        gpu_buffer.extend_from_slice(&buffer.read().expect("failed to read data buffer"));
+    }
+
     // check that exectution was correct
     println!("Checking that values match...");
-    let content = data_buffer.read().expect("failed to read data buffer");
-    for (gpu_val, cpu_val) in content.iter().zip(cpu_buffer.iter()) {
-        assert_eq!(*gpu_val, *cpu_val);
+    assert_eq!(gpu_buffer.len(), cpu_buffer.len());
+    for (i, (gpu_val, cpu_val)) in gpu_buffer.iter().zip(cpu_buffer.iter()).enumerate() {
+        assert!(
+            T::matches(*gpu_val, *cpu_val),
+            "value {} differs: GPU gave {:?}, CPU gave {:?}",
+            i,
+            gpu_val,
+            cpu_val
+        );
     }
     println!("Values were equivelent");
 }
-
-mod cs {
-    vulkano_shaders::shader!{
-        ty: "compute",
-        path: "examples/compute/shader.glsl"
-    }
-}
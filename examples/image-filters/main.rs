@@ -0,0 +1,142 @@
+use std::{env, process, time::SystemTime};
+
+use image::ImageFormat;
+use vulkan_test::{
+    context::Context,
+    filters::{apply_cpu_chain, max_channel_difference, Filter, FilterPipelines},
+    output::{Output, OutputOptions},
+};
+
+const USAGE: &str = "usage: image-filters <input> <output> [options] <filter>...
+
+options:
+    --out-dir <path>        directory to save the output in (default the working directory)
+    --overwrite             replace files that are already there instead of refusing to start
+    --verify                check the result against the CPU reference
+
+filters are applied in the order given:
+    blur:<sigma>
+    sobel
+    grayscale
+    brightness-contrast:<brightness>,<contrast>
+    gamma:<gamma>
+    unsharp:<sigma>,<amount>
+
+every run also writes a JSON sidecar next to the output, with .json in place of its extension,
+recording the filters, command line, device, how long filtering took and the file written.";
+
+/// How far each filter in the chain may drift from the CPU reference, in 8-bit levels.
+const TOLERANCE_PER_FILTER: u8 = 2;
+
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(1);
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn main() {
+    let mut verify = false;
+    let mut out = OutputOptions::default();
+    let mut paths = Vec::new();
+    let mut filters = Vec::new();
+    let mut filter_names = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--verify" => verify = true,
+            "--overwrite" => out.overwrite = true,
+            "--out-dir" => {
+                out.dir = args
+                    .next()
+                    .unwrap_or_else(|| exit_with_usage("--out-dir needs a value"))
+                    .into()
+            }
+            _ if paths.len() < 2 => paths.push(arg),
+            _ => {
+                filters.push(
+                    arg.parse::<Filter>()
+                        .unwrap_or_else(|e| exit_with_usage(&e)),
+                );
+                filter_names.push(arg);
+            }
+        }
+    }
+    let [input_path, output_path]: [String; 2] = paths
+        .try_into()
+        .unwrap_or_else(|_| exit_with_usage("an input and output path are needed"));
+    if !ImageFormat::from_path(&output_path).is_ok_and(|format| format.can_write()) {
+        exit_with_usage(&format!(
+            "don't know how to save an image to '{}'",
+            output_path
+        ));
+    }
+
+    // claim the output first, so nothing already there is lost
+    let mut output = Output::new("image-filters", out);
+    output.set_parameter("input", input_path.as_str());
+    output.set_parameter("filters", filter_names);
+    let output_path = output
+        .claim(&output_path)
+        .unwrap_or_else(|e| exit_with_error(&e));
+
+    // load image
+    let input = image::open(&input_path)
+        .unwrap_or_else(|e| exit_with_usage(&format!("could not open '{}': {}", input_path, e)))
+        .to_rgba8();
+
+    // setup vulkan
+    let context = Context::new().expect("failed to setup vulkan");
+    let max_size = context
+        .device
+        .physical_device()
+        .properties()
+        .max_image_dimension2_d;
+    if input.width().max(input.height()) > max_size {
+        exit_with_usage(&format!(
+            "'{}' is {}x{}, but the device's largest image is {}x{}",
+            input_path,
+            input.width(),
+            input.height(),
+            max_size,
+            max_size
+        ));
+    }
+    let pipelines = FilterPipelines::new(&context);
+    println!("Filtering on {}...", context.device_name());
+
+    // run filters
+    let gpu_start = SystemTime::now();
+    let filtered = pipelines.apply(&context, &input, &filters);
+    let gpu_elapsed = gpu_start.elapsed().expect("could not elapse gpu time");
+    println!("Done in {:?}", gpu_elapsed);
+    filtered.save(&output_path).unwrap_or_else(|e| {
+        exit_with_error(&format!(
+            "could not save '{}': {}",
+            output_path.display(),
+            e
+        ))
+    });
+    output
+        .write_sidecar(context.device_name(), gpu_elapsed)
+        .unwrap_or_else(|e| exit_with_error(&e));
+
+    // check against the CPU
+    if verify {
+        println!("Checking against the CPU reference...");
+        let expected = apply_cpu_chain(&input, &filters);
+        let difference = max_channel_difference(&filtered, &expected);
+        let tolerance = TOLERANCE_PER_FILTER.saturating_mul(filters.len().min(255) as u8);
+        println!(
+            "Largest difference was {} (tolerance {})",
+            difference, tolerance
+        );
+        if difference > tolerance {
+            eprintln!("GPU output does not match the CPU reference");
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::{env, fs, process, time::SystemTime};
+
+use vulkan_test::{
+    context::Context,
+    output::{Output, OutputOptions},
+    stats::{StatsPipeline, DEFAULT_PERCENTILES},
+    transfer::upload_rgba8,
+};
+use vulkano::image::ImageUsage;
+
+const USAGE: &str = "usage: image-stats <input> [options]
+
+options:
+    --json <path>           where to write the statistics (default printed)
+    --histogram <path>      also save a picture of the histograms, as a PNG or anything else the
+                            image crate writes
+    --percentiles <p>,...   percentiles to report, from 0 to 100
+    --out-dir <path>        directory to save everything in (default the working directory)
+    --overwrite             replace files that are already there instead of refusing to start
+
+prints per-channel statistics as JSON, or writes them to --json. runs that write a file also
+write a JSON sidecar next to the first of them, with .json in place of its extension, or
+.sidecar.json in place of the statistics' own .json, recording the command line, device, how
+long gathering the statistics took and the files written.";
+
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(1);
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn main() {
+    let mut input_path = None;
+    let mut json_path = None;
+    let mut histogram_path = None;
+    let mut percentiles = DEFAULT_PERCENTILES.to_vec();
+    let mut out = OutputOptions::default();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .unwrap_or_else(|| exit_with_usage(&format!("{} needs a value", name)))
+        };
+        match arg.as_str() {
+            "--json" => json_path = Some(value("--json")),
+            "--histogram" => histogram_path = Some(value("--histogram")),
+            "--out-dir" => out.dir = value("--out-dir").into(),
+            "--overwrite" => out.overwrite = true,
+            "--percentiles" => {
+                percentiles = value("--percentiles")
+                    .split(',')
+                    .map(|p| match p.trim().parse::<f64>() {
+                        Ok(p) if (0.0..=100.0).contains(&p) => p,
+                        _ => exit_with_usage(&format!("invalid percentile '{}'", p)),
+                    })
+                    .collect();
+            }
+            _ if input_path.is_none() => input_path = Some(arg),
+            _ => exit_with_usage(&format!("unexpected argument '{}'", arg)),
+        }
+    }
+    let input_path = input_path.unwrap_or_else(|| exit_with_usage("an input path is needed"));
+
+    // claim the files first, so nothing already there is lost
+    let mut output = Output::new("image-stats", out);
+    output.set_parameter("input", input_path.as_str());
+    output.set_parameter("percentiles", percentiles.clone());
+    let [json_path, histogram_path] = [json_path, histogram_path]
+        .map(|path| path.map(|path| output.claim(&path).unwrap_or_else(|e| exit_with_error(&e))));
+
+    // load image
+    let input = image::open(&input_path)
+        .unwrap_or_else(|e| exit_with_usage(&format!("could not open '{}': {}", input_path, e)))
+        .to_rgba8();
+
+    // gather stats on the GPU
+    let context = Context::new().expect("failed to setup vulkan");
+    let image = upload_rgba8(&context, &input, ImageUsage::STORAGE);
+    let start = SystemTime::now();
+    let stats = StatsPipeline::new(&context).compute(&context, image);
+    let elapsed = start.elapsed().expect("could not elapse time");
+
+    // write results
+    let json = serde_json::to_string_pretty(&stats.to_json(&percentiles))
+        .expect("failed to serialize stats");
+    match json_path {
+        Some(path) => fs::write(&path, json).unwrap_or_else(|e| {
+            exit_with_error(&format!("could not write '{}': {}", path.display(), e))
+        }),
+        None => println!("{}", json),
+    }
+    if let Some(path) = histogram_path {
+        stats.histogram_image().save(&path).unwrap_or_else(|e| {
+            exit_with_error(&format!("could not save '{}': {}", path.display(), e))
+        });
+    }
+    output
+        .write_sidecar(context.device_name(), elapsed)
+        .unwrap_or_else(|e| exit_with_error(&e));
+}
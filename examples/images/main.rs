@@ -1,132 +1,394 @@
-use std::sync::Arc;
-
-use image::{ImageBuffer, Rgba};
-use vulkano::{
-    buffer::{Buffer, BufferCreateInfo, BufferUsage},
-    command_buffer::{
-        allocator::{StandardCommandBufferAllocator, StandardCommandBufferAllocatorCreateInfo},
-        AutoCommandBufferBuilder, ClearColorImageInfo, CommandBufferUsage, CopyBufferToImageInfo,
-    },
-    device::{Device, DeviceCreateInfo, QueueCreateInfo, QueueFlags},
-    format::{ClearColorValue, Format},
-    image::{Image, ImageCreateInfo, ImageType, ImageUsage},
-    instance::{Instance, InstanceCreateInfo},
-    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
-    VulkanLibrary, sync::{self, GpuFuture},
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, SystemTime},
 };
 
-fn main() {
-    // setup vulkan
-    let library = VulkanLibrary::new().expect("no local Vulkan library/DLL");
-    let instance =
-        Instance::new(library, InstanceCreateInfo::default()).expect("failed to create instance");
-
-    // setup device
-    let physical_device = instance
-        .enumerate_physical_devices()
-        .expect("could not enumerate devices")
-        .next()
-        .expect("no devices available");
-    let queue_family_index = physical_device
-        .queue_family_properties()
+use image::{DynamicImage, Rgba, Rgba32FImage};
+use vulkan_test::{
+    context::Context,
+    convert::{convert_cpu, max_difference, Conversion, ConvertMethod, Converter},
+    format::{save_float_image, ColorFormat},
+    output::{Output, OutputOptions},
+    texture::Texture,
+};
+use vulkano::device::Features;
+
+const USAGE: &str = "usage: images [options]
+
+options:
+    --input <path>          image to convert, in any format the image crate reads such as PNG,
+                            JPEG, BMP, TGA or WebP, the first image of a KTX2 or DDS texture, or
+                            a directory of them (default a solid red 1024x1024 image)
+    --output <path>         where to save the result in --out-dir, or a directory to save the
+                            results in when the input is one (default a name made of the input,
+                            format, size and time, like
+                            images-photo-rgba8-20261018-183500.png)
+    --out-dir <path>        directory to save everything in (default the working directory)
+    --overwrite             replace files that are already there instead of refusing to start
+    --extension <ext>       file type of results --output doesn't name: the images of a directory,
+                            or the result when there's no --output (default png)
+    --format <format>       format the input is uploaded as: rgba8, rgba8-srgb, rgba16, rgba16f or
+                            rgba32f (default rgba8). rgba8-srgb takes the input as sRGB encoded
+    --to <format>           format to convert to (default the same as --format)
+    --crop <x>,<y>,<w>,<h>  only convert this part of the input
+    --rotate <degrees>      90, 180 or 270 clockwise, after cropping
+    --flip <axis>           horizontal, vertical or both, after rotating
+    --resize <w>x<h>        size of the result (default the size after cropping and rotating)
+    --filter <filter>       nearest, bilinear, bicubic or lanczos (default bilinear)
+    --swizzle <channels>    four of r, g, b, a, 0 and 1 picking what goes in each channel, like bgra
+                            to swap red and blue (default rgba)
+    --method <method>       blit or compute (default blit when it can do the conversion and the
+                            device can blit the formats, otherwise compute)
+    --verify                check every result against the CPU reference
+
+filtering and swizzling happen on linear colors, so sRGB colors are decoded first and encoded
+again when converting to rgba8-srgb. results other than rgba8 and rgba8-srgb are saved without
+rounding to 8 bits, as a 16-bit PNG, Radiance HDR, OpenEXR or 32-bit float TIFF when the output
+ends in .png, .hdr, .exr or .tiff. outputs ending in .ktx2 or .dds keep the converted pixels
+exactly as they are on the device.
+
+every run also writes a JSON sidecar next to the output, with .json in place of its extension,
+recording the conversion, command line, device, how long converting took and the files written.";
+
+/// How far results may drift from the CPU reference, in 8-bit levels of the stored colors.
+const TOLERANCE: f32 = 2.0 / 255.0;
+
+struct Args {
+    input: Option<String>,
+    /// A name is made up from the conversion when there's none.
+    output: Option<String>,
+    out: OutputOptions,
+    extension: String,
+    conversion: Conversion,
+    /// Whether `--to` was given, otherwise it follows `--format`.
+    to_given: bool,
+    method: Option<ConvertMethod>,
+    verify: bool,
+}
+
+fn parse_args() -> Args {
+    let mut args = Args {
+        input: None,
+        output: None,
+        out: OutputOptions::default(),
+        extension: "png".to_string(),
+        conversion: Conversion::default(),
+        to_given: false,
+        method: None,
+        verify: false,
+    };
+    let mut argv = env::args().skip(1);
+    while let Some(flag) = argv.next() {
+        if flag == "--verify" {
+            args.verify = true;
+            continue;
+        }
+        if flag == "--overwrite" {
+            args.out.overwrite = true;
+            continue;
+        }
+        let value = argv
+            .next()
+            .unwrap_or_else(|| exit_with_usage(&format!("'{}' needs a value", flag)));
+        let conversion = &mut args.conversion;
+        match flag.as_str() {
+            "--input" => args.input = Some(value),
+            "--output" => args.output = Some(value),
+            "--out-dir" => args.out.dir = value.into(),
+            "--extension" => args.extension = value.trim_start_matches('.').to_string(),
+            "--format" => conversion.from = parse(&value),
+            "--to" => {
+                conversion.to = parse(&value);
+                args.to_given = true;
+            }
+            "--crop" => conversion.crop = Some(parse(&value)),
+            "--rotate" => {
+                conversion.quarter_turns = match value.as_str() {
+                    "0" => 0,
+                    "90" => 1,
+                    "180" => 2,
+                    "270" => 3,
+                    _ => exit_with_usage(&format!("can't rotate by '{}' degrees", value)),
+                }
+            }
+            "--flip" => {
+                let (horizontal, vertical) = match value.as_str() {
+                    "horizontal" => (true, false),
+                    "vertical" => (false, true),
+                    "both" => (true, true),
+                    _ => exit_with_usage(&format!("unknown axis '{}'", value)),
+                };
+                conversion.flip_horizontal = horizontal;
+                conversion.flip_vertical = vertical;
+            }
+            "--resize" => {
+                let size = value
+                    .split_once('x')
+                    .and_then(|(w, h)| Some([w.parse().ok()?, h.parse().ok()?]))
+                    .filter(|size: &[u32; 2]| size.iter().all(|&side| side > 0))
+                    .unwrap_or_else(|| {
+                        exit_with_usage(&format!("size '{}' must be <width>x<height>", value))
+                    });
+                conversion.size = Some(size);
+            }
+            "--filter" => conversion.filter = parse(&value),
+            "--swizzle" => conversion.swizzle = parse(&value),
+            "--method" => args.method = Some(parse(&value)),
+            _ => exit_with_usage(&format!("unknown option '{}'", flag)),
+        }
+    }
+    if !args.to_given {
+        args.conversion.to = args.conversion.from;
+    }
+    args
+}
+
+fn parse<T: std::str::FromStr<Err = String>>(value: &str) -> T {
+    value
+        .parse()
+        .unwrap_or_else(|e: String| exit_with_usage(&e))
+}
+
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(1);
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+/// Records the conversion in the sidecar.
+fn describe(output: &mut Output, args: &Args) {
+    let conversion = &args.conversion;
+    if let Some(input) = &args.input {
+        output.set_parameter("input", input.as_str());
+    }
+    if let Some(crop) = conversion.crop {
+        output.set_parameter("crop", vec![crop.x, crop.y, crop.width, crop.height]);
+    }
+    output.set_parameter("rotate", conversion.quarter_turns * 90);
+    output.set_parameter("flip_horizontal", conversion.flip_horizontal);
+    output.set_parameter("flip_vertical", conversion.flip_vertical);
+    if let Some(size) = conversion.size {
+        output.set_parameter("resize", size.to_vec());
+    }
+    output.set_parameter("filter", format!("{:?}", conversion.filter));
+    let swizzle: String = conversion
+        .swizzle
+        .0
         .iter()
-        .enumerate()
-        .position(|(_i, queue_family_properties)| {
-            queue_family_properties
-                .queue_flags
-                .contains(QueueFlags::GRAPHICS)
+        .map(|&channel| b"rgba01"[channel as usize] as char)
+        .collect();
+    output.set_parameter("swizzle", swizzle);
+    output.set_parameter("format", conversion.from.name());
+    output.set_parameter("to", conversion.to.name());
+    if let Some(method) = args.method {
+        output.set_parameter("method", format!("{:?}", method));
+    }
+}
+
+/// A name for results of `input` when `--output` doesn't give one.
+fn output_name(output: &Output, args: &Args, input: &str, extension: &str) -> String {
+    let mut parameters = vec![input.to_string(), args.conversion.to.name().to_string()];
+    if let Some([width, height]) = args.conversion.size {
+        parameters.push(format!("{}x{}", width, height));
+    }
+    output.file_name(&parameters, extension)
+}
+
+/// Pairs of input and output paths for every image to convert, with the outputs claimed so
+/// nothing gets overwritten. There's no input path for the default red image.
+fn claim_jobs(output: &mut Output, args: &Args) -> Result<Vec<(Option<PathBuf>, PathBuf)>, String> {
+    let Some(input) = &args.input else {
+        let name = match &args.output {
+            Some(name) => name.clone(),
+            None => output_name(output, args, "red", &args.extension),
+        };
+        return Ok(vec![(None, output.claim(&name)?)]);
+    };
+    let input = Path::new(input);
+    let stem = input
+        .file_stem()
+        .map_or("input".into(), |stem| stem.to_string_lossy());
+    if !input.is_dir() {
+        let name = match &args.output {
+            Some(name) => name.clone(),
+            None => output_name(output, args, &stem, &args.extension),
+        };
+        return Ok(vec![(Some(input.to_path_buf()), output.claim(&name)?)]);
+    }
+
+    let dir = match &args.output {
+        Some(dir) => dir.clone(),
+        None => output_name(output, args, &stem, ""),
+    };
+    output.claim_dir(&dir)?;
+    let mut inputs: Vec<PathBuf> = fs::read_dir(input)
+        .map_err(|e| format!("could not read '{}': {}", input.display(), e))?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| {
+            path.is_file() && (is_texture(path) || image::ImageFormat::from_path(path).is_ok())
         })
-        .expect("couldn't find a graphical queue fmaily") as u32;
-    let (device, mut queues) = Device::new(
-        physical_device,
-        DeviceCreateInfo {
-            queue_create_infos: vec![QueueCreateInfo {
-                queue_family_index,
-                ..Default::default()
-            }],
-            ..Default::default()
-        },
-    )
-    .expect("failed to create device");
-    let queue = queues.next().unwrap();
-    let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
-
-    // create image
-    let image = Image::new(
-        memory_allocator.clone(),
-        ImageCreateInfo {
-            image_type: ImageType::Dim2d,
-            format: Format::R8G8B8A8_UNORM,
-            extent: [1024, 1024, 1],
-            usage: ImageUsage::TRANSFER_DST | ImageUsage::TRANSFER_SRC,
-            ..Default::default()
-        },
-        AllocationCreateInfo {
-            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
-            ..Default::default()
-        },
-    )
-    .expect("could not create image");
-    let pixel_data_iter = (0..1024 * 1024 * 4).enumerate().map(|(i, _)| {
-        match i % 4 {
-            0 => 255, // red
-            1 => 0,   // green
-            2 => 0,   // blue
-            3 => 255, // alpha
-            _ => unreachable!("`i % 4` should only contain numbers 0-3 (inclusive)")
+        .collect();
+    inputs.sort();
+    let mut jobs: Vec<(Option<PathBuf>, PathBuf)> = Vec::new();
+    for path in inputs {
+        // the extension is added on, so stems with dots in them are kept whole
+        let mut name = path.file_stem().unwrap().to_os_string();
+        name.push(".");
+        name.push(&args.extension);
+        let name = Path::new(&dir).join(name);
+        let claimed = output.claim(&name.to_string_lossy()).map_err(|e| {
+            match jobs.iter().find(|(_, claimed)| claimed.ends_with(&name)) {
+                Some((Some(other), _)) => format!(
+                    "'{}' and '{}' would both be saved as '{}'",
+                    other.display(),
+                    path.display(),
+                    name.display()
+                ),
+                _ => e,
+            }
+        })?;
+        jobs.push((Some(path), claimed));
+    }
+    Ok(jobs)
+}
+
+fn is_texture(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("ktx2") || e.eq_ignore_ascii_case("dds"))
+}
+
+/// Opens an image, or the first image of a texture.
+fn open(path: &Path) -> Result<DynamicImage, String> {
+    if is_texture(path) {
+        return Texture::open(&path.to_string_lossy())?.to_dynamic_image(0, 0, 0);
+    }
+    image::open(path).map_err(|e| format!("could not open '{}': {}", path.display(), e))
+}
+
+/// Converts `input` and saves it to `output`, checking it against the CPU when `verify` is set.
+/// Returns how long converting took.
+fn convert(
+    context: &Context,
+    converter: &Converter,
+    args: &Args,
+    input: &DynamicImage,
+    output: &str,
+) -> Result<Duration, String> {
+    let conversion = &args.conversion;
+    let method = match args.method {
+        Some(method) => method,
+        None => converter.pick_method(context, conversion)?,
+    };
+    let start = SystemTime::now();
+    let readback = converter.convert(context, input, conversion, method)?;
+    let elapsed = start.elapsed().expect("could not elapse time");
+    println!(
+        "Converted {}x{} {:?} to {}x{} {:?} with {:?} in {:?}",
+        input.width(),
+        input.height(),
+        conversion.from.format(),
+        readback.width,
+        readback.height,
+        conversion.to.format(),
+        method,
+        elapsed
+    );
+
+    if is_texture(Path::new(output)) {
+        Texture::from_readback(readback.clone()).save(output)?;
+    } else {
+        let pixels = readback.to_dynamic_image()?;
+        if matches!(conversion.to, ColorFormat::Rgba8 | ColorFormat::Rgba8Srgb) {
+            pixels
+                .save(output)
+                .map_err(|e| format!("could not save '{}': {}", output, e))?;
+        } else {
+            save_float_image(&pixels.to_rgba32f(), output)?;
         }
-    });
-
-    // create buffer from image
-    let buf = Buffer::from_iter(
-        memory_allocator.clone(),
-        BufferCreateInfo {
-            usage: BufferUsage::TRANSFER_DST | BufferUsage::TRANSFER_SRC,
-            ..Default::default()
-        },
-        AllocationCreateInfo {
-            memory_type_filter: MemoryTypeFilter::PREFER_HOST
-                | MemoryTypeFilter::HOST_RANDOM_ACCESS,
-            ..Default::default()
+    }
+
+    if args.verify {
+        let expected = convert_cpu(input, conversion)?;
+        let actual = conversion
+            .to
+            .decode(readback.width, readback.height, &readback.into_packed());
+        let difference = max_difference(conversion.to, &actual, &expected);
+        println!(
+            "Largest difference from the CPU reference was {:.2} levels (tolerance {:.2})",
+            difference * 255.0,
+            TOLERANCE * 255.0
+        );
+        if difference > TOLERANCE {
+            return Err("result does not match the CPU reference".to_string());
+        }
+    }
+    Ok(elapsed)
+}
+
+fn main() {
+    let args = parse_args();
+
+    // claim every file first, so nothing already there is lost
+    let mut output = Output::new("images", args.out.clone());
+    describe(&mut output, &args);
+    let jobs = claim_jobs(&mut output, &args).unwrap_or_else(|e| exit_with_error(&e));
+
+    // setup vulkan
+    let context = Context::with_optional_features(
+        Features::empty(),
+        Features {
+            shader_storage_image_write_without_format: true,
+            ..Features::empty()
         },
-        pixel_data_iter,
     )
-    .expect("failed to create buffer");
+    .expect("failed to setup vulkan");
+    let converter = Converter::new(&context);
+    println!("Converting on {}", context.device_name());
 
-    // dispatch command buffer
-    let command_buffer_allocator = StandardCommandBufferAllocator::new(
-        device.clone(),
-        StandardCommandBufferAllocatorCreateInfo::default(),
-    );
-    let mut builder = AutoCommandBufferBuilder::primary(
-        &command_buffer_allocator,
-        queue_family_index,
-        CommandBufferUsage::OneTimeSubmit,
-    )
-    .expect("failed to create command buffer builder");
-    builder
-        .clear_color_image(ClearColorImageInfo {
-            clear_value: ClearColorValue::Float([0.0, 0.0, 1.0, 1.0]),
-            ..ClearColorImageInfo::image(image.clone())
-        })
-        .expect("could not clear image")
-        .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(
-            buf.clone(),
-            image.clone(),
-        ))
-        .expect("failed to copy buffer to image");
-    let command_buffer = builder.build().expect("failed to build command buffer");
-
-    // execute
-    let future = sync::now(device.clone())
-        .then_execute(queue.clone(), command_buffer)
-        .expect("failed to execute command buffer")
-        .then_signal_fence_and_flush()
-        .unwrap();
-    future.wait(None).unwrap();
-
-    // extract image
-    let buffer_content = buf.read().expect("failed to read buffer");
-    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(1024, 1024, buffer_content).expect("failed to extract image");
-    image.save("image.png").expect("could not save image");
+    let mut elapsed = Duration::ZERO;
+    let mut failures = 0;
+    for (input, path) in &jobs {
+        let pixels = match input {
+            Some(input) => {
+                println!("{} -> {}", input.display(), path.display());
+                open(input)
+            }
+            None => Ok(DynamicImage::ImageRgba32F(Rgba32FImage::from_pixel(
+                1024,
+                1024,
+                Rgba([1.0, 0.0, 0.0, 1.0]),
+            ))),
+        };
+        let result = pixels.and_then(|pixels| {
+            convert(
+                &context,
+                &converter,
+                &args,
+                &pixels,
+                &path.to_string_lossy(),
+            )
+        });
+        match result {
+            Ok(time) => elapsed += time,
+            Err(e) => {
+                eprintln!("{}", e);
+                failures += 1;
+            }
+        }
+    }
+    output
+        .write_sidecar(context.device_name(), elapsed)
+        .unwrap_or_else(|e| exit_with_error(&e));
+    if failures > 0 {
+        if args.input.is_some() {
+            eprintln!("{} of {} images failed", failures, jobs.len());
+        }
+        process::exit(1);
+    }
 }
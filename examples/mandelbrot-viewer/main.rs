@@ -0,0 +1,319 @@
+use std::{env, process, sync::Arc};
+
+use vulkan_test::{
+    context::Context,
+    fractal::{
+        explorer::Explorer,
+        palette::{Palette, BUILTIN_NAMES},
+        ColorMode, RenderSettings,
+    },
+};
+use vulkano::{
+    command_buffer::BlitImageInfo,
+    device::Features,
+    format::{Format, FormatFeatures},
+    image::{sampler::Filter, ImageUsage},
+    swapchain::{self, Swapchain, SwapchainCreateInfo, SwapchainPresentInfo},
+    sync::GpuFuture,
+    Validated, VulkanError,
+};
+use winit::{
+    dpi::LogicalSize,
+    event::{
+        ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode,
+        WindowEvent,
+    },
+    event_loop::EventLoop,
+    window::WindowBuilder,
+};
+
+/// How much one notch of the scroll wheel zooms in.
+const ZOOM_PER_NOTCH: f64 = 1.25;
+
+/// Touchpads scroll in pixels, this many of which count as one notch.
+const PIXELS_PER_NOTCH: f64 = 50.0;
+
+const USAGE: &str = "usage: mandelbrot-viewer [options]
+
+options:
+    --fractal <fractal>     mandelbrot, julia:<x>,<y>, burning-ship, tricorn or multibrot:<power>
+                            (default mandelbrot)
+    --width <pixels>        initial width of the window (default 1024)
+    --height <pixels>       initial height of the window (default 768)
+    --iterations <count>    iterations before a point is considered inside the set (default 200)
+    --palette <name|path>   classic, fire, ice, grayscale or a palette file (default classic)
+
+controls:
+    drag                    pan
+    scroll                  zoom in or out around the cursor
+    up / down               double or halve the iterations
+    p                       switch to the next built-in palette
+    r                       go back to the starting view
+    escape                  quit";
+
+fn parse_args() -> RenderSettings {
+    let mut settings = RenderSettings {
+        width: 1024,
+        height: 768,
+        color: ColorMode::Palette,
+        ..Default::default()
+    };
+    let mut argv = env::args().skip(1);
+    while let Some(flag) = argv.next() {
+        let value = argv
+            .next()
+            .unwrap_or_else(|| exit_with_usage(&format!("'{}' needs a value", flag)));
+        match flag.as_str() {
+            "--fractal" => {
+                settings.fractal = value
+                    .parse()
+                    .unwrap_or_else(|e: String| exit_with_usage(&e))
+            }
+            "--width" => settings.width = parse_positive(&flag, &value),
+            "--height" => settings.height = parse_positive(&flag, &value),
+            "--iterations" => settings.iterations = parse_positive(&flag, &value),
+            "--palette" => {
+                settings.palette = if BUILTIN_NAMES.contains(&value.as_str()) {
+                    Palette::builtin(&value).unwrap()
+                } else {
+                    Palette::load(&value).unwrap_or_else(|e| exit_with_usage(&e))
+                }
+            }
+            _ => exit_with_usage(&format!("unknown option '{}'", flag)),
+        }
+    }
+    settings
+}
+
+fn parse_positive(flag: &str, value: &str) -> u32 {
+    value
+        .parse()
+        .ok()
+        .filter(|&v| v > 0)
+        .unwrap_or_else(|| exit_with_usage(&format!("{} must be a positive integer", flag)))
+}
+
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(1);
+}
+
+fn main() {
+    let settings = parse_args();
+
+    // setup window and vulkan, with doubles for deep zooms where the device has them
+    let event_loop = EventLoop::new();
+    let window = Arc::new(
+        WindowBuilder::new()
+            .with_title("mandelbrot viewer")
+            .with_inner_size(LogicalSize::new(settings.width, settings.height))
+            .build(&event_loop)
+            .expect("failed to create window"),
+    );
+    let (context, surface) = Context::for_window(
+        &event_loop,
+        window.clone(),
+        Features {
+            shader_float64: true,
+            ..Features::empty()
+        },
+    )
+    .expect("failed to setup vulkan");
+    println!("Exploring on {}", context.device_name());
+
+    // create swapchain, which renders are copied into
+    let physical_device = context.device.physical_device();
+    let capabilities = physical_device
+        .surface_capabilities(&surface, Default::default())
+        .expect("failed to get surface capabilities");
+    if !capabilities
+        .supported_usage_flags
+        .contains(ImageUsage::TRANSFER_DST)
+    {
+        eprintln!("the window's images can't be copied into");
+        process::exit(1);
+    }
+    let formats = physical_device
+        .surface_formats(&surface, Default::default())
+        .expect("failed to get surface formats");
+    // renders already hold sRGB encoded colors, so prefer a format that doesn't encode them again
+    let blit_dst = |format: Format| {
+        physical_device
+            .format_properties(format)
+            .map(|p| p.optimal_tiling_features.contains(FormatFeatures::BLIT_DST))
+            .unwrap_or(false)
+    };
+    let (image_format, _) = formats
+        .iter()
+        .copied()
+        .filter(|(format, _)| blit_dst(*format))
+        .min_by_key(|(format, _)| match format {
+            Format::B8G8R8A8_UNORM | Format::R8G8B8A8_UNORM => 0,
+            _ => 1,
+        })
+        .unwrap_or_else(|| {
+            eprintln!("the window has no format renders can be copied into");
+            process::exit(1);
+        });
+    let composite_alpha = capabilities
+        .supported_composite_alpha
+        .into_iter()
+        .next()
+        .unwrap();
+    let (mut swapchain, mut images) = Swapchain::new(
+        context.device.clone(),
+        surface,
+        SwapchainCreateInfo {
+            min_image_count: capabilities.min_image_count + 1,
+            image_format,
+            image_extent: window.inner_size().into(),
+            image_usage: ImageUsage::TRANSFER_DST,
+            composite_alpha,
+            ..Default::default()
+        },
+    )
+    .expect("failed to create swapchain");
+
+    let size = window.inner_size();
+    let mut explorer = Explorer::new(RenderSettings {
+        width: size.width,
+        height: size.height,
+        ..settings
+    });
+    let mut cursor = [0.0, 0.0];
+    let mut dragging = false;
+    let mut recreate_swapchain = false;
+
+    // setup event loop, which sleeps until there's input
+    event_loop.run(move |event, _, control_flow| {
+        control_flow.set_wait();
+
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => control_flow.set_exit(),
+                WindowEvent::Resized(size) => {
+                    recreate_swapchain = true;
+                    if size.width > 0 && size.height > 0 {
+                        explorer.resize(size.width, size.height);
+                    }
+                }
+                WindowEvent::CursorMoved { position, .. } => {
+                    if dragging {
+                        explorer.pan(position.x - cursor[0], position.y - cursor[1]);
+                    }
+                    cursor = [position.x, position.y];
+                }
+                WindowEvent::MouseInput {
+                    state,
+                    button: MouseButton::Left,
+                    ..
+                } => dragging = state == ElementState::Pressed,
+                WindowEvent::MouseWheel { delta, .. } => {
+                    let notches = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => y as f64,
+                        MouseScrollDelta::PixelDelta(position) => position.y / PIXELS_PER_NOTCH,
+                    };
+                    explorer.zoom_at(cursor[0], cursor[1], ZOOM_PER_NOTCH.powf(notches));
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(key),
+                            ..
+                        },
+                    ..
+                } => match key {
+                    VirtualKeyCode::Up => explorer.scale_iterations(2.0),
+                    VirtualKeyCode::Down => explorer.scale_iterations(0.5),
+                    VirtualKeyCode::P => explorer.next_palette(),
+                    VirtualKeyCode::R => explorer.reset(),
+                    VirtualKeyCode::Escape => control_flow.set_exit(),
+                    _ => (),
+                },
+                _ => (),
+            },
+            Event::MainEventsCleared if explorer.needs_render() => window.request_redraw(),
+            Event::RedrawRequested(_) => {
+                if recreate_swapchain {
+                    let size = window.inner_size();
+                    if size.width == 0 || size.height == 0 {
+                        return;
+                    }
+                    (swapchain, images) = swapchain
+                        .recreate(SwapchainCreateInfo {
+                            image_extent: size.into(),
+                            ..swapchain.create_info()
+                        })
+                        .expect("failed to recreate swapchain");
+                    recreate_swapchain = false;
+                }
+
+                let (image_i, suboptimal, acquire_future) =
+                    match swapchain::acquire_next_image(swapchain.clone(), None)
+                        .map_err(Validated::unwrap)
+                    {
+                        Ok(r) => r,
+                        Err(VulkanError::OutOfDate) => {
+                            recreate_swapchain = true;
+                            window.request_redraw();
+                            return;
+                        }
+                        Err(e) => panic!("failed to acquire next image: {}", e),
+                    };
+                if suboptimal {
+                    recreate_swapchain = true;
+                }
+
+                // render only when the view changed, then copy the latest render to the window
+                let mut builder = context.command_buffer_builder();
+                let rendering = explorer.needs_render();
+                if let Err(e) = explorer.record(&context, &mut builder) {
+                    eprintln!("{}", e);
+                }
+                let Some(render) = explorer.image() else {
+                    eprintln!("nothing to show");
+                    control_flow.set_exit();
+                    return;
+                };
+                builder
+                    .blit_image(BlitImageInfo {
+                        filter: Filter::Nearest,
+                        ..BlitImageInfo::images(render, images[image_i as usize].clone())
+                    })
+                    .expect("failed to blit render to window");
+                let command_buffer = builder.build().expect("failed to build command buffer");
+
+                let future = acquire_future
+                    .then_execute(context.queue.clone(), command_buffer)
+                    .expect("failed to execute command buffer")
+                    .then_swapchain_present(
+                        context.queue.clone(),
+                        SwapchainPresentInfo::swapchain_image_index(swapchain.clone(), image_i),
+                    )
+                    .then_signal_fence_and_flush();
+                // frames are only drawn on input, so waiting for each one costs little
+                match future.map_err(Validated::unwrap) {
+                    Ok(future) => future.wait(None).expect("failed to wait for frame"),
+                    Err(VulkanError::OutOfDate) => {
+                        recreate_swapchain = true;
+                        window.request_redraw();
+                    }
+                    Err(e) => eprintln!("failed to present frame: {}", e),
+                }
+
+                if rendering {
+                    let settings = explorer.settings();
+                    window.set_title(&format!(
+                        "{:?} - zoom {:.3e}, {} iterations, {:?} precision",
+                        settings.fractal,
+                        settings.zoom,
+                        settings.iterations,
+                        explorer.precision().unwrap()
+                    ));
+                }
+            }
+            _ => (),
+        }
+    });
+}
@@ -0,0 +1,201 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process,
+    time::SystemTime,
+};
+
+use vulkan_test::{
+    context::Context,
+    format::{save_float_image, ColorFormat},
+    mipmap::{mip_levels, read_back_levels, MipmapGenerator, MipmapMethod},
+    output::{Output, OutputOptions},
+    texture::Texture,
+};
+use vulkano::device::Features;
+
+const USAGE: &str = "usage: mipmaps <input> <output> [options]
+
+options:
+    --format <format>       rgba8, rgba16, rgba16f or rgba32f (default rgba8)
+    --method <method>       blit, or compute for formats that can't be blitted (default blit when
+                            the format supports it, otherwise compute)
+    --out-dir <path>        directory to save everything in (default the working directory)
+    --overwrite             replace files that are already there instead of refusing to start
+
+an output ending in .ktx2 or .dds gets every level in one KTX2 or DDS file. any other output gets one image per
+level, with -<level> added to its name, so mips.png is written as mips-0.png, mips-1.png and so
+on. formats other than rgba8 are saved like the images example saves them.
+
+every run also writes a JSON sidecar next to the output, with .json in place of its extension,
+recording the format, method, command line, device, how long generating took and the files
+written.";
+
+struct Args {
+    input: String,
+    output: String,
+    format: ColorFormat,
+    method: Option<MipmapMethod>,
+    out: OutputOptions,
+}
+
+fn parse_args() -> Args {
+    let mut paths = Vec::new();
+    let mut format = ColorFormat::Rgba8;
+    let mut method = None;
+    let mut out = OutputOptions::default();
+    let mut argv = env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        if !arg.starts_with("--") {
+            paths.push(arg);
+            continue;
+        }
+        if arg == "--overwrite" {
+            out.overwrite = true;
+            continue;
+        }
+        let value = argv
+            .next()
+            .unwrap_or_else(|| exit_with_usage(&format!("'{}' needs a value", arg)));
+        match arg.as_str() {
+            "--format" => {
+                format = value
+                    .parse()
+                    .unwrap_or_else(|e: String| exit_with_usage(&e))
+            }
+            "--method" => {
+                method = Some(
+                    value
+                        .parse()
+                        .unwrap_or_else(|e: String| exit_with_usage(&e)),
+                )
+            }
+            "--out-dir" => out.dir = value.into(),
+            _ => exit_with_usage(&format!("unknown option '{}'", arg)),
+        }
+    }
+    let [input, output] = paths
+        .try_into()
+        .unwrap_or_else(|_| exit_with_usage("an input and output path are needed"));
+    Args {
+        input,
+        output,
+        format,
+        method,
+        out,
+    }
+}
+
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(1);
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+/// Whether `path` names a KTX2 or DDS file, which gets every level.
+fn is_texture_file(path: &str) -> bool {
+    let path = path.to_lowercase();
+    path.ends_with(".ktx2") || path.ends_with(".dds")
+}
+
+/// `path` with `-<index>` added to its file name, before the extension.
+fn numbered_path(path: &Path, index: u32) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(format!("-{}", index));
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+fn main() {
+    let args = parse_args();
+
+    // load image
+    let input = image::open(&args.input)
+        .unwrap_or_else(|e| exit_with_usage(&format!("could not open '{}': {}", args.input, e)));
+
+    // claim every file first, so nothing already there is lost
+    let mut output = Output::new("mipmaps", args.out.clone());
+    output.set_parameter("input", args.input.as_str());
+    output.set_parameter("format", args.format.name());
+    if let Some(method) = args.method {
+        output.set_parameter("method", format!("{:?}", method));
+    }
+    let levels = mip_levels(input.width(), input.height());
+    let path = if is_texture_file(&args.output) {
+        output.claim(&args.output)
+    } else {
+        let files: Vec<String> = (0..levels)
+            .map(|level| {
+                numbered_path(Path::new(&args.output), level)
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        output.claim_sequence(&args.output, &files)
+    }
+    .unwrap_or_else(|e| exit_with_error(&e));
+
+    // setup vulkan
+    let context = Context::with_optional_features(
+        Features::empty(),
+        Features {
+            shader_storage_image_read_without_format: true,
+            shader_storage_image_write_without_format: true,
+            ..Features::empty()
+        },
+    )
+    .expect("failed to setup vulkan");
+    let generator = MipmapGenerator::new(&context);
+    let method = args
+        .method
+        .map_or_else(|| generator.pick_method(&context, args.format), Ok)
+        .unwrap_or_else(|e| exit_with_error(&e));
+
+    // generate mip chain
+    let start = SystemTime::now();
+    let image = generator
+        .generate(&context, &input, args.format, method)
+        .unwrap_or_else(|e| exit_with_error(&e));
+    let elapsed = start.elapsed().expect("could not elapse time");
+    println!(
+        "Generated {} mip levels of {:?} with {:?} on {} in {:?}",
+        image.mip_levels(),
+        args.format.format(),
+        method,
+        context.device_name(),
+        elapsed
+    );
+
+    // save levels
+    if is_texture_file(&args.output) {
+        Texture::download(&context, image)
+            .and_then(|texture| texture.save(&path.to_string_lossy()))
+            .unwrap_or_else(|e| exit_with_error(&e));
+    } else {
+        let readbacks = read_back_levels(&context, image).unwrap_or_else(|e| exit_with_error(&e));
+        for (level, readback) in (0..).zip(&readbacks) {
+            let path = numbered_path(&path, level);
+            let pixels = readback
+                .to_dynamic_image()
+                .unwrap_or_else(|e| exit_with_error(&e));
+            if args.format == ColorFormat::Rgba8 {
+                pixels.save(&path).unwrap_or_else(|e| {
+                    exit_with_error(&format!("could not save '{}': {}", path.display(), e))
+                });
+            } else {
+                save_float_image(&pixels.to_rgba32f(), &path.to_string_lossy())
+                    .unwrap_or_else(|e| exit_with_error(&e));
+            }
+        }
+    }
+    output
+        .write_sidecar(context.device_name(), elapsed)
+        .unwrap_or_else(|e| exit_with_error(&e));
+}
@@ -0,0 +1,263 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process,
+    time::SystemTime,
+};
+
+use vulkan_test::{
+    context::Context,
+    convert::max_difference,
+    format::{save_float_image, ColorFormat},
+    layered::{blur_cpu, fill_cpu, LayeredProcessor, Pattern, Shape},
+    output::{Output, OutputOptions},
+    texture::Texture,
+};
+use vulkano::device::Features;
+
+const USAGE: &str = "usage: volumes <output> [options]
+
+options:
+    --shape <shape>         array:<layers>, volume:<depth>, cube or cube:<cubes> (default
+                            volume:64)
+    --size <w>x<h>          size of each layer, face or slice (default 64x64)
+    --format <format>       rgba8, rgba16, rgba16f or rgba32f (default rgba8)
+    --pattern <pattern>     gradient or sphere (default sphere)
+    --blur <radius>         box blur each layer and face, or the whole volume, this many pixels
+                            each way after filling it
+    --verify                check the result against the CPU reference
+    --out-dir <path>        directory to save everything in (default the working directory)
+    --overwrite             replace files that are already there instead of refusing to start
+
+an output ending in .ktx2 or .dds gets the whole image in one file. any other output gets one
+image per layer, face or slice, with -<index> added to its name, so slices.png is written as
+slices-0.png, slices-1.png and so on. cubemap faces go +x, -x, +y, -y, +z, -z for each cube.
+formats other than rgba8 are saved like the images example saves them.
+
+every run also writes a JSON sidecar next to the output, with .json in place of its extension,
+recording the shape, size, format, pattern, blur, command line, device, how long filling and
+blurring took and the files written.";
+
+/// How far results may drift from the CPU reference, in 8-bit levels.
+const TOLERANCE: f32 = 1.0 / 255.0;
+
+struct Args {
+    output: String,
+    shape: Shape,
+    size: [u32; 2],
+    format: ColorFormat,
+    pattern: Pattern,
+    blur: Option<u32>,
+    verify: bool,
+    out: OutputOptions,
+}
+
+fn parse_args() -> Args {
+    let mut output = None;
+    let mut args = Args {
+        output: String::new(),
+        shape: Shape::Volume { depth: 64 },
+        size: [64, 64],
+        format: ColorFormat::Rgba8,
+        pattern: Pattern::Sphere,
+        blur: None,
+        verify: false,
+        out: OutputOptions::default(),
+    };
+    let mut argv = env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        if !arg.starts_with("--") {
+            if output.replace(arg).is_some() {
+                exit_with_usage("only one output path is needed");
+            }
+            continue;
+        }
+        if arg == "--verify" {
+            args.verify = true;
+            continue;
+        }
+        if arg == "--overwrite" {
+            args.out.overwrite = true;
+            continue;
+        }
+        let value = argv
+            .next()
+            .unwrap_or_else(|| exit_with_usage(&format!("'{}' needs a value", arg)));
+        match arg.as_str() {
+            "--shape" => args.shape = parse(&value),
+            "--size" => {
+                args.size = value
+                    .split_once('x')
+                    .and_then(|(w, h)| Some([w.parse().ok()?, h.parse().ok()?]))
+                    .filter(|size: &[u32; 2]| size.iter().all(|&side| side > 0))
+                    .unwrap_or_else(|| {
+                        exit_with_usage(&format!("size '{}' must be <width>x<height>", value))
+                    })
+            }
+            "--format" => args.format = parse(&value),
+            "--pattern" => args.pattern = parse(&value),
+            "--blur" => {
+                args.blur = Some(value.parse().unwrap_or_else(|_| {
+                    exit_with_usage(&format!("blur radius '{}' must be a whole number", value))
+                }))
+            }
+            "--out-dir" => args.out.dir = value.into(),
+            _ => exit_with_usage(&format!("unknown option '{}'", arg)),
+        }
+    }
+    args.output = output.unwrap_or_else(|| exit_with_usage("an output path is needed"));
+    args
+}
+
+fn parse<T: std::str::FromStr<Err = String>>(value: &str) -> T {
+    value
+        .parse()
+        .unwrap_or_else(|e: String| exit_with_usage(&e))
+}
+
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(1);
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+/// Whether `path` names a KTX2 or DDS file, which gets the whole image.
+fn is_texture_file(path: &str) -> bool {
+    let path = path.to_lowercase();
+    path.ends_with(".ktx2") || path.ends_with(".dds")
+}
+
+/// `path` with `-<index>` added to its file name, before the extension.
+fn numbered_path(path: &Path, index: u32) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(format!("-{}", index));
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+/// Starts the run's output and claims the files it writes: the texture, or one image per slice
+/// of `shape`.
+fn claim_files(args: &Args) -> Result<(Output, PathBuf), String> {
+    let mut output = Output::new("volumes", args.out.clone());
+    output.set_parameter("shape", format!("{:?}", args.shape));
+    output.set_parameter("size", args.size.to_vec());
+    output.set_parameter("format", args.format.name());
+    output.set_parameter("pattern", format!("{:?}", args.pattern));
+    if let Some(radius) = args.blur {
+        output.set_parameter("blur", radius);
+    }
+    if is_texture_file(&args.output) {
+        let path = output.claim(&args.output)?;
+        return Ok((output, path));
+    }
+    let slices = args
+        .shape
+        .slices()
+        .ok_or_else(|| format!("{:?} has too many slices", args.shape))?;
+    let files: Vec<String> = (0..slices)
+        .map(|index| {
+            numbered_path(Path::new(&args.output), index)
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+    let path = output.claim_sequence(&args.output, &files)?;
+    Ok((output, path))
+}
+
+fn main() {
+    let args = parse_args();
+    let [width, height] = args.size;
+
+    // setup vulkan
+    let context = Context::with_optional_features(
+        Features::empty(),
+        Features {
+            shader_storage_image_read_without_format: true,
+            shader_storage_image_write_without_format: true,
+            ..Features::empty()
+        },
+    )
+    .expect("failed to setup vulkan");
+    let processor = LayeredProcessor::new(&context);
+
+    // fill and blur
+    let start = SystemTime::now();
+    let mut image = processor
+        .create(&context, args.format, width, height, args.shape)
+        .unwrap_or_else(|e| exit_with_error(&e));
+    // claim every file before any work, now the device has taken the shape, so nothing already
+    // there is lost
+    let (output, path) = claim_files(&args).unwrap_or_else(|e| exit_with_error(&e));
+    processor.fill(&context, image.clone(), args.pattern);
+    if let Some(radius) = args.blur {
+        image = processor.blur(&context, image, radius);
+    }
+    let elapsed = start.elapsed().expect("could not elapse time");
+    println!(
+        "Made a {}x{} {:?} of {:?} on {} in {:?}",
+        width,
+        height,
+        args.shape,
+        args.format.format(),
+        context.device_name(),
+        elapsed
+    );
+    let texture = Texture::download(&context, image).unwrap_or_else(|e| exit_with_error(&e));
+
+    if args.verify {
+        let mut expected = fill_cpu(width, height, args.shape, args.pattern);
+        if let Some(radius) = args.blur {
+            expected = blur_cpu(&expected, args.shape, radius);
+        }
+        let difference = texture
+            .slices(0)
+            .into_iter()
+            .zip(&expected)
+            .map(|(slice, expected)| {
+                let actual = args.format.decode(width, height, &slice.into_packed());
+                max_difference(args.format, &actual, expected)
+            })
+            .fold(0.0, f32::max);
+        println!(
+            "Largest difference from the CPU reference was {:.2} levels (tolerance {:.2})",
+            difference * 255.0,
+            TOLERANCE * 255.0
+        );
+        if difference > TOLERANCE {
+            exit_with_error("result does not match the CPU reference");
+        }
+    }
+
+    // save
+    if is_texture_file(&args.output) {
+        texture
+            .save(&path.to_string_lossy())
+            .unwrap_or_else(|e| exit_with_error(&e));
+    } else {
+        for (index, slice) in (0..).zip(texture.slices(0)) {
+            let path = numbered_path(&path, index);
+            let pixels = slice
+                .to_dynamic_image()
+                .unwrap_or_else(|e| exit_with_error(&e));
+            if args.format == ColorFormat::Rgba8 {
+                pixels.save(&path).unwrap_or_else(|e| {
+                    exit_with_error(&format!("could not save '{}': {}", path.display(), e))
+                });
+            } else {
+                save_float_image(&pixels.to_rgba32f(), &path.to_string_lossy())
+                    .unwrap_or_else(|e| exit_with_error(&e));
+            }
+        }
+    }
+    output
+        .write_sidecar(context.device_name(), elapsed)
+        .unwrap_or_else(|e| exit_with_error(&e));
+}
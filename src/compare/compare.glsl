@@ -0,0 +1,115 @@
+#version 460
+
+// one pixel per invocation, reduced to one partial result per work group
+layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+
+// only read with texelFetch, so the values are compared as they're stored
+layout(set = 0, binding = 0) uniform sampler2D a;
+layout(set = 0, binding = 1) uniform sampler2D b;
+layout(set = 0, binding = 2, rgba32f) uniform writeonly image2D difference;
+layout(set = 0, binding = 3, rgba8) uniform writeonly image2D heat_map;
+
+struct Partial {
+    vec4 sum_squared;
+    vec4 max_error;
+    // only x is used, the rest keeps the struct the same size on every device
+    vec4 ssim_sum;
+};
+
+// one per work group, in row-major order, summed on the host
+layout(set = 0, binding = 4) writeonly buffer Partials {
+    Partial partials[];
+};
+
+layout(push_constant) uniform CompareParams {
+    // how much the largest channel difference is scaled up before mapping it to a color
+    float amplify;
+} params;
+
+// the usual SSIM window, an 11x11 gaussian with a sigma of 1.5, and constants for values in
+// [0, 1]
+const int SSIM_RADIUS = 5;
+const float SSIM_SIGMA = 1.5;
+const float C1 = 0.0001;
+const float C2 = 0.0009;
+
+const vec3 LUMA = vec3(0.2126, 0.7152, 0.0722);
+
+shared vec4 shared_squared[256];
+shared vec4 shared_error[256];
+shared float shared_ssim[256];
+
+// SSIM of the luma around pos, with the window cut off at the edges of the image
+float ssim(ivec2 pos, ivec2 size) {
+    float weight_sum = 0.0;
+    float mean_a = 0.0;
+    float mean_b = 0.0;
+    float sum_aa = 0.0;
+    float sum_bb = 0.0;
+    float sum_ab = 0.0;
+    for (int dy = -SSIM_RADIUS; dy <= SSIM_RADIUS; dy++) {
+        for (int dx = -SSIM_RADIUS; dx <= SSIM_RADIUS; dx++) {
+            ivec2 p = pos + ivec2(dx, dy);
+            if (any(lessThan(p, ivec2(0))) || any(greaterThanEqual(p, size))) {
+                continue;
+            }
+            float weight = exp(-float(dx * dx + dy * dy) / (2.0 * SSIM_SIGMA * SSIM_SIGMA));
+            float la = dot(texelFetch(a, p, 0).rgb, LUMA);
+            float lb = dot(texelFetch(b, p, 0).rgb, LUMA);
+            weight_sum += weight;
+            mean_a += weight * la;
+            mean_b += weight * lb;
+            sum_aa += weight * la * la;
+            sum_bb += weight * lb * lb;
+            sum_ab += weight * la * lb;
+        }
+    }
+    mean_a /= weight_sum;
+    mean_b /= weight_sum;
+    float variance_a = sum_aa / weight_sum - mean_a * mean_a;
+    float variance_b = sum_bb / weight_sum - mean_b * mean_b;
+    float covariance = sum_ab / weight_sum - mean_a * mean_b;
+    return ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+        / ((mean_a * mean_a + mean_b * mean_b + C1) * (variance_a + variance_b + C2));
+}
+
+void main() {
+    ivec2 pos = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = textureSize(a, 0);
+    uint index = gl_LocalInvocationIndex;
+
+    // pixels past the edges add nothing
+    vec4 error = vec4(0.0);
+    float pixel_ssim = 0.0;
+    if (all(lessThan(pos, size))) {
+        error = abs(texelFetch(a, pos, 0) - texelFetch(b, pos, 0));
+        imageStore(difference, pos, error);
+        float largest = max(max(error.r, error.g), max(error.b, error.a));
+        float t = clamp(largest * params.amplify, 0.0, 1.0);
+        // black through red and yellow to white
+        imageStore(heat_map, pos, vec4(clamp(3.0 * t - vec3(0.0, 1.0, 2.0), 0.0, 1.0), 1.0));
+        pixel_ssim = ssim(pos, size);
+    }
+    shared_squared[index] = error * error;
+    shared_error[index] = error;
+    shared_ssim[index] = pixel_ssim;
+    barrier();
+
+    for (uint stride = 128; stride > 0; stride >>= 1) {
+        if (index < stride) {
+            shared_squared[index] += shared_squared[index + stride];
+            shared_error[index] = max(shared_error[index], shared_error[index + stride]);
+            shared_ssim[index] += shared_ssim[index + stride];
+        }
+        barrier();
+    }
+
+    if (index == 0) {
+        uint group = gl_WorkGroupID.y * gl_NumWorkGroups.x + gl_WorkGroupID.x;
+        partials[group] = Partial(
+            shared_squared[0],
+            shared_error[0],
+            vec4(shared_ssim[0], 0.0, 0.0, 0.0)
+        );
+    }
+}
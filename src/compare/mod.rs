@@ -0,0 +1,412 @@
+//! Comparing rendered images against references: how far apart they are, and where.
+
+use std::{str::FromStr, sync::Arc};
+
+use image::{DynamicImage, Rgba, Rgba32FImage, RgbaImage};
+use serde_json::{json, Value};
+use vulkano::{
+    buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage},
+    descriptor_set::WriteDescriptorSet,
+    format::Format,
+    image::{
+        sampler::{Sampler, SamplerCreateInfo},
+        view::ImageView,
+        Image, ImageUsage,
+    },
+    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter},
+    pipeline::{ComputePipeline, Pipeline, PipelineBindPoint},
+};
+
+use crate::{
+    context::Context,
+    format::ColorFormat,
+    stats::CHANNEL_NAMES,
+    transfer::{create_image, download, download_rgba8, upload_converted},
+};
+
+/// Rec. 709 luma weights, which SSIM is measured on.
+const LUMA: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+/// Radius and sigma of the gaussian SSIM window, and its constants for values in [0, 1], the
+/// same as in compare.glsl.
+const SSIM_RADIUS: i64 = 5;
+const SSIM_SIGMA: f64 = 1.5;
+const C1: f64 = 0.0001;
+const C2: f64 = 0.0009;
+
+/// Matches `Partial` in compare.glsl.
+#[derive(BufferContents, Clone, Copy)]
+#[repr(C)]
+struct Partial {
+    sum_squared: [f32; 4],
+    max_error: [f32; 4],
+    ssim_sum: [f32; 4],
+}
+
+#[derive(BufferContents, Clone, Copy)]
+#[repr(C)]
+struct CompareParams {
+    amplify: f32,
+}
+
+/// How far apart two images of the same size are.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Metrics {
+    pub width: u32,
+    pub height: u32,
+    /// Largest absolute difference in each channel.
+    pub max_error: [f32; 4],
+    /// Root mean squared error of each channel.
+    pub rmse: [f64; 4],
+    /// Mean structural similarity of the luma, 1 for identical images.
+    pub ssim: f64,
+}
+
+impl Metrics {
+    /// Largest absolute difference in any channel.
+    pub fn largest_error(&self) -> f32 {
+        self.max_error.into_iter().fold(0.0, f32::max)
+    }
+
+    /// Root mean squared error of red, green and blue together.
+    pub fn color_rmse(&self) -> f64 {
+        (self.rmse[..3].iter().map(|e| e * e).sum::<f64>() / 3.0).sqrt()
+    }
+
+    /// Peak signal to noise ratio of red, green and blue in decibels, for a peak of 1. Infinite
+    /// when they're identical.
+    pub fn psnr(&self) -> f64 {
+        -20.0 * self.color_rmse().log10()
+    }
+
+    /// Fails with every threshold the images are past.
+    pub fn check(&self, thresholds: &[Threshold]) -> Result<(), String> {
+        let failures: Vec<String> = thresholds
+            .iter()
+            .filter_map(|threshold| {
+                let (name, value, limit, passed) = match *threshold {
+                    Threshold::MaxError(limit) => {
+                        let value = self.largest_error() as f64;
+                        ("max error", value, limit, value <= limit)
+                    }
+                    Threshold::Rmse(limit) => {
+                        let value = self.color_rmse();
+                        ("RMSE", value, limit, value <= limit)
+                    }
+                    Threshold::Psnr(limit) => ("PSNR", self.psnr(), limit, self.psnr() >= limit),
+                    Threshold::Ssim(limit) => ("SSIM", self.ssim, limit, self.ssim >= limit),
+                };
+                (!passed).then(|| format!("{} of {:.6} is past {}", name, value, limit))
+            })
+            .collect();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures.join(", "))
+        }
+    }
+
+    /// Infinite PSNRs become `null`.
+    pub fn to_json(&self) -> Value {
+        let channels: serde_json::Map<String, Value> = CHANNEL_NAMES
+            .iter()
+            .enumerate()
+            .map(|(c, name)| {
+                let metrics = json!({
+                    "max_error": self.max_error[c],
+                    "rmse": self.rmse[c],
+                });
+                (name.to_string(), metrics)
+            })
+            .collect();
+        json!({
+            "width": self.width,
+            "height": self.height,
+            "max_error": self.largest_error(),
+            "rmse": self.color_rmse(),
+            "psnr": self.psnr(),
+            "ssim": self.ssim,
+            "channels": channels,
+        })
+    }
+}
+
+/// A limit the images have to stay within, parsed from `<metric>:<value>` on the command line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Threshold {
+    /// `max-error:<value>`, the largest difference in any channel can't be more
+    MaxError(f64),
+    /// `rmse:<value>`, the color RMSE can't be more
+    Rmse(f64),
+    /// `psnr:<decibels>`, the PSNR can't be less
+    Psnr(f64),
+    /// `ssim:<value>`, the SSIM can't be less
+    Ssim(f64),
+}
+
+impl FromStr for Threshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (metric, value) = s
+            .split_once(':')
+            .ok_or_else(|| format!("threshold '{}' must be <metric>:<value>", s))?;
+        let value: f64 = value
+            .parse()
+            .map_err(|_| format!("invalid value '{}' for threshold '{}'", value, metric))?;
+        match metric {
+            "max-error" => Ok(Threshold::MaxError(value)),
+            "rmse" => Ok(Threshold::Rmse(value)),
+            "psnr" => Ok(Threshold::Psnr(value)),
+            "ssim" => Ok(Threshold::Ssim(value)),
+            _ => Err(format!("unknown metric '{}'", metric)),
+        }
+    }
+}
+
+/// Everything a comparison finds.
+pub struct Comparison {
+    pub metrics: Metrics,
+    /// Absolute difference of every channel of every pixel.
+    pub difference: Rgba32FImage,
+    /// The largest channel difference of every pixel, amplified and colored from black through
+    /// red and yellow to white.
+    pub heat_map: RgbaImage,
+}
+
+/// Compares images on the GPU.
+pub struct ImageComparer {
+    sampler: Arc<Sampler>,
+    pipeline: Arc<ComputePipeline>,
+}
+
+impl ImageComparer {
+    pub fn new(context: &Context) -> Self {
+        let shader = shader::load(context.device.clone()).expect("failed to create shader module");
+        Self {
+            sampler: Sampler::new(context.device.clone(), SamplerCreateInfo::default())
+                .expect("failed to create sampler"),
+            pipeline: context.compute_pipeline(shader),
+        }
+    }
+
+    /// Uploads `a` and `b` as 32-bit floats, which holds every value they can have exactly, and
+    /// compares them.
+    pub fn compare_images(
+        &self,
+        context: &Context,
+        a: &DynamicImage,
+        b: &DynamicImage,
+        amplify: f32,
+    ) -> Result<Comparison, String> {
+        let [a, b] = [a, b].map(|pixels| {
+            upload_converted(
+                context,
+                pixels,
+                ColorFormat::Rgba32Float,
+                ImageUsage::SAMPLED,
+            )
+        });
+        self.compare(context, a?, b?, amplify)
+    }
+
+    /// Compares `a` and `b`, images of the same size created with `SAMPLED` usage, channel by
+    /// channel as they're stored. `amplify` scales the differences up in the heat map.
+    pub fn compare(
+        &self,
+        context: &Context,
+        a: Arc<Image>,
+        b: Arc<Image>,
+        amplify: f32,
+    ) -> Result<Comparison, String> {
+        let [width, height, _] = a.extent();
+        if b.extent() != a.extent() {
+            let [b_width, b_height, _] = b.extent();
+            return Err(format!(
+                "can't compare a {}x{} image with a {}x{} one",
+                width, height, b_width, b_height
+            ));
+        }
+        let difference = create_image(
+            context,
+            Format::R32G32B32A32_SFLOAT,
+            width,
+            height,
+            ImageUsage::STORAGE | ImageUsage::TRANSFER_SRC,
+        );
+        let heat_map = create_image(
+            context,
+            Format::R8G8B8A8_UNORM,
+            width,
+            height,
+            ImageUsage::STORAGE | ImageUsage::TRANSFER_SRC,
+        );
+        let groups = [width.div_ceil(16), height.div_ceil(16)];
+        let partials = Buffer::new_slice::<Partial>(
+            context.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            (groups[0] * groups[1]) as u64,
+        )
+        .expect("failed to create partials buffer");
+
+        // setup descriptor
+        let view = |image| ImageView::new_default(image).expect("could not create image view");
+        let descriptor_set = context.descriptor_set(
+            &self.pipeline,
+            [
+                WriteDescriptorSet::image_view_sampler(0, view(a), self.sampler.clone()),
+                WriteDescriptorSet::image_view_sampler(1, view(b), self.sampler.clone()),
+                WriteDescriptorSet::image_view(2, view(difference.clone())),
+                WriteDescriptorSet::image_view(3, view(heat_map.clone())),
+                WriteDescriptorSet::buffer(4, partials.clone()),
+            ],
+        );
+
+        // dispatch
+        let mut builder = context.command_buffer_builder();
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())
+            .expect("failed to bind pipeline")
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.pipeline.layout().clone(),
+                0,
+                descriptor_set,
+            )
+            .expect("failed to bind descriptor set")
+            .push_constants(self.pipeline.layout().clone(), 0, CompareParams { amplify })
+            .expect("failed to push compare parameters")
+            .dispatch([groups[0], groups[1], 1])
+            .expect("failed to dispatch work groups");
+        context.execute(builder);
+
+        // sum the work groups' partial results on the host
+        let partials = partials.read().expect("failed to read partials buffer");
+        let mut sum_squared = [0.0f64; 4];
+        let mut max_error = [0.0f32; 4];
+        let mut ssim_sum = 0.0;
+        for partial in partials.iter() {
+            for c in 0..4 {
+                sum_squared[c] += partial.sum_squared[c] as f64;
+                max_error[c] = max_error[c].max(partial.max_error[c]);
+            }
+            ssim_sum += partial.ssim_sum[0] as f64;
+        }
+        let pixel_count = width as f64 * height as f64;
+        let metrics = Metrics {
+            width,
+            height,
+            max_error,
+            rmse: sum_squared.map(|sum| (sum / pixel_count).sqrt()),
+            ssim: ssim_sum / pixel_count,
+        };
+
+        Ok(Comparison {
+            metrics,
+            difference: ColorFormat::Rgba32Float.decode(
+                width,
+                height,
+                &download(context, difference),
+            ),
+            heat_map: download_rgba8(context, heat_map),
+        })
+    }
+}
+
+/// The metrics `ImageComparer` finds, worked out on the CPU.
+pub fn compare_cpu(a: &Rgba32FImage, b: &Rgba32FImage) -> Result<Metrics, String> {
+    let (width, height) = a.dimensions();
+    if b.dimensions() != a.dimensions() {
+        return Err(format!(
+            "can't compare a {}x{} image with a {}x{} one",
+            width,
+            height,
+            b.width(),
+            b.height()
+        ));
+    }
+    let mut sum_squared = [0.0f64; 4];
+    let mut max_error = [0.0f32; 4];
+    for (pa, pb) in a.pixels().zip(b.pixels()) {
+        for c in 0..4 {
+            let error = (pa[c] - pb[c]).abs();
+            sum_squared[c] += (error * error) as f64;
+            max_error[c] = max_error[c].max(error);
+        }
+    }
+
+    let luma = |image: &Rgba32FImage| -> Vec<f64> {
+        image
+            .pixels()
+            .map(|p| (0..3).map(|c| (p[c] * LUMA[c]) as f64).sum())
+            .collect()
+    };
+    let (luma_a, luma_b) = (luma(a), luma(b));
+    let (w, h) = (width as i64, height as i64);
+    let mut ssim_sum = 0.0;
+    for y in 0..h {
+        for x in 0..w {
+            let mut sums = [0.0; 6];
+            for sy in (y - SSIM_RADIUS).max(0)..=(y + SSIM_RADIUS).min(h - 1) {
+                for sx in (x - SSIM_RADIUS).max(0)..=(x + SSIM_RADIUS).min(w - 1) {
+                    let (dx, dy) = ((sx - x) as f64, (sy - y) as f64);
+                    let weight = (-(dx * dx + dy * dy) / (2.0 * SSIM_SIGMA * SSIM_SIGMA)).exp();
+                    let (la, lb) = (
+                        luma_a[(sy * w + sx) as usize],
+                        luma_b[(sy * w + sx) as usize],
+                    );
+                    for (sum, value) in
+                        sums.iter_mut()
+                            .zip([1.0, la, lb, la * la, lb * lb, la * lb])
+                    {
+                        *sum += weight * value;
+                    }
+                }
+            }
+            let [weight_sum, mean_a, mean_b, sum_aa, sum_bb, sum_ab] = sums;
+            let (mean_a, mean_b) = (mean_a / weight_sum, mean_b / weight_sum);
+            let variance_a = sum_aa / weight_sum - mean_a * mean_a;
+            let variance_b = sum_bb / weight_sum - mean_b * mean_b;
+            let covariance = sum_ab / weight_sum - mean_a * mean_b;
+            ssim_sum += ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (variance_a + variance_b + C2));
+        }
+    }
+
+    let pixel_count = width as f64 * height as f64;
+    Ok(Metrics {
+        width,
+        height,
+        max_error,
+        rmse: sum_squared.map(|sum| (sum / pixel_count).sqrt()),
+        ssim: ssim_sum / pixel_count,
+    })
+}
+
+/// The heat map `ImageComparer` draws for `difference`, on the CPU.
+pub fn heat_map_cpu(difference: &Rgba32FImage, amplify: f32) -> RgbaImage {
+    RgbaImage::from_fn(difference.width(), difference.height(), |x, y| {
+        let largest = difference.get_pixel(x, y).0.into_iter().fold(0.0, f32::max);
+        let t = (largest * amplify).clamp(0.0, 1.0);
+        let [r, g, b] = [0.0, 1.0, 2.0].map(|offset| {
+            let c = (3.0 * t - offset).clamp(0.0, 1.0);
+            (c * 255.0).round() as u8
+        });
+        Rgba([r, g, b, 255])
+    })
+}
+
+mod shader {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/compare/compare.glsl"
+    }
+}
@@ -0,0 +1,232 @@
+use std::sync::Arc;
+
+use vulkano::{
+    command_buffer::{
+        allocator::{StandardCommandBufferAllocator, StandardCommandBufferAllocatorCreateInfo},
+        AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer,
+    },
+    descriptor_set::{
+        allocator::StandardDescriptorSetAllocator, PersistentDescriptorSet, WriteDescriptorSet,
+    },
+    device::{
+        physical::{PhysicalDevice, PhysicalDeviceType},
+        Device, DeviceCreateInfo, DeviceExtensions, Features, Queue, QueueCreateInfo,
+        QueueFamilyProperties, QueueFlags,
+    },
+    instance::{Instance, InstanceCreateInfo},
+    memory::allocator::StandardMemoryAllocator,
+    pipeline::{
+        compute::ComputePipelineCreateInfo, layout::PipelineDescriptorSetLayoutCreateInfo,
+        ComputePipeline, Pipeline, PipelineLayout, PipelineShaderStageCreateInfo,
+    },
+    shader::ShaderModule,
+    swapchain::Surface,
+    sync::{self, GpuFuture},
+    VulkanLibrary,
+};
+use winit::{event_loop::EventLoop, window::Window};
+
+/// A device and queue with the allocators needed to run headless workloads on them.
+pub struct Context {
+    pub device: Arc<Device>,
+    pub queue: Arc<Queue>,
+    pub memory_allocator: Arc<StandardMemoryAllocator>,
+    pub command_buffer_allocator: StandardCommandBufferAllocator,
+    pub descriptor_set_allocator: StandardDescriptorSetAllocator,
+}
+
+impl Context {
+    /// Sets up the most capable device with a compute queue, preferring real GPUs over software
+    /// ones.
+    pub fn new() -> Result<Self, String> {
+        Self::with_features(Features::empty())
+    }
+
+    /// Like `new`, but only considers devices supporting `features`, and enables them.
+    pub fn with_features(features: Features) -> Result<Self, String> {
+        Self::with_optional_features(features, Features::empty())
+    }
+
+    /// Like `with_features`, but also enables whichever of `optional` the chosen device
+    /// supports. Check `device.enabled_features()` to see which ones it got.
+    pub fn with_optional_features(features: Features, optional: Features) -> Result<Self, String> {
+        // setup vulkan
+        let library =
+            VulkanLibrary::new().map_err(|e| format!("no local Vulkan library: {}", e))?;
+        let instance = Instance::new(library, InstanceCreateInfo::default())
+            .map_err(|e| format!("failed to create instance: {}", e))?;
+
+        Self::with_instance(
+            instance,
+            features,
+            optional,
+            DeviceExtensions::empty(),
+            |_, (_, q)| q.queue_flags.contains(QueueFlags::COMPUTE),
+        )
+    }
+
+    /// Like `with_optional_features`, but for drawing to `window` too. The device gets
+    /// `khr_swapchain` and the queue can run graphics and compute work and present to the
+    /// returned surface.
+    pub fn for_window(
+        event_loop: &EventLoop<()>,
+        window: Arc<Window>,
+        optional: Features,
+    ) -> Result<(Self, Arc<Surface>), String> {
+        // setup vulkan and surface
+        let library =
+            VulkanLibrary::new().map_err(|e| format!("no local Vulkan library: {}", e))?;
+        let instance = Instance::new(
+            library,
+            InstanceCreateInfo {
+                enabled_extensions: Surface::required_extensions(event_loop),
+                ..Default::default()
+            },
+        )
+        .map_err(|e| format!("failed to create instance: {}", e))?;
+        let surface = Surface::from_window(instance.clone(), window)
+            .map_err(|e| format!("failed to create surface from window: {}", e))?;
+
+        let context = Self::with_instance(
+            instance,
+            Features::empty(),
+            optional,
+            DeviceExtensions {
+                khr_swapchain: true,
+                ..DeviceExtensions::empty()
+            },
+            |p, (i, q)| {
+                q.queue_flags
+                    .contains(QueueFlags::GRAPHICS | QueueFlags::COMPUTE)
+                    && p.surface_support(i, &surface).unwrap_or(false)
+            },
+        )?;
+        Ok((context, surface))
+    }
+
+    /// Sets up a device of `instance` with `features` and `extensions`, and a queue from the
+    /// first family `queue_filter` accepts.
+    fn with_instance(
+        instance: Arc<Instance>,
+        features: Features,
+        optional: Features,
+        extensions: DeviceExtensions,
+        queue_filter: impl Fn(&PhysicalDevice, (u32, &QueueFamilyProperties)) -> bool,
+    ) -> Result<Self, String> {
+        // setup device
+        let (physical_device, queue_family_index) = instance
+            .enumerate_physical_devices()
+            .map_err(|e| format!("could not enumerate devices: {}", e))?
+            .filter(|p| p.supported_features().contains(&features))
+            .filter(|p| p.supported_extensions().contains(&extensions))
+            .filter_map(|p| {
+                p.queue_family_properties()
+                    .iter()
+                    .enumerate()
+                    .position(|(i, q)| queue_filter(&p, (i as u32, q)))
+                    .map(|q| (p, q as u32))
+            })
+            .min_by_key(|(p, _)| match p.properties().device_type {
+                PhysicalDeviceType::DiscreteGpu => 0,
+                PhysicalDeviceType::IntegratedGpu => 1,
+                PhysicalDeviceType::VirtualGpu => 2,
+                PhysicalDeviceType::Cpu => 3,
+                _ => 4,
+            })
+            .ok_or_else(|| format!("no device available with features {:?}", features))?;
+        let enabled_features =
+            features.union(&physical_device.supported_features().intersection(&optional));
+        let (device, mut queues) = Device::new(
+            physical_device,
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                enabled_extensions: extensions,
+                enabled_features,
+                ..Default::default()
+            },
+        )
+        .map_err(|e| format!("failed to create device: {}", e))?;
+        let queue = queues.next().unwrap();
+
+        // setup allocators
+        let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
+        let command_buffer_allocator = StandardCommandBufferAllocator::new(
+            device.clone(),
+            StandardCommandBufferAllocatorCreateInfo::default(),
+        );
+        let descriptor_set_allocator =
+            StandardDescriptorSetAllocator::new(device.clone(), Default::default());
+
+        Ok(Self {
+            device,
+            queue,
+            memory_allocator,
+            command_buffer_allocator,
+            descriptor_set_allocator,
+        })
+    }
+
+    pub fn device_name(&self) -> &str {
+        &self.device.physical_device().properties().device_name
+    }
+
+    /// Creates a compute pipeline running `shader`'s `main` with a layout inferred from it.
+    pub fn compute_pipeline(&self, shader: Arc<ShaderModule>) -> Arc<ComputePipeline> {
+        let entry_point = shader
+            .entry_point("main")
+            .expect("failed to create entry point");
+        let stage = PipelineShaderStageCreateInfo::new(entry_point);
+        let layout = PipelineLayout::new(
+            self.device.clone(),
+            PipelineDescriptorSetLayoutCreateInfo::from_stages([&stage])
+                .into_pipeline_layout_create_info(self.device.clone())
+                .expect("could not create pipeline layout info"),
+        )
+        .expect("could not create pipeline layout");
+        ComputePipeline::new(
+            self.device.clone(),
+            None,
+            ComputePipelineCreateInfo::stage_layout(stage, layout),
+        )
+        .expect("failed to create compute pipeline")
+    }
+
+    /// Creates a descriptor set for set 0 of `pipeline`.
+    pub fn descriptor_set(
+        &self,
+        pipeline: &Arc<ComputePipeline>,
+        writes: impl IntoIterator<Item = WriteDescriptorSet>,
+    ) -> Arc<PersistentDescriptorSet> {
+        PersistentDescriptorSet::new(
+            &self.descriptor_set_allocator,
+            pipeline.layout().set_layouts()[0].clone(),
+            writes,
+            [],
+        )
+        .expect("failed to create descriptor set")
+    }
+
+    pub fn command_buffer_builder(&self) -> AutoCommandBufferBuilder<PrimaryAutoCommandBuffer> {
+        AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .expect("failed to create command buffer builder")
+    }
+
+    /// Submits everything recorded in `builder` and blocks until the GPU has finished it.
+    pub fn execute(&self, builder: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+        let command_buffer = builder.build().expect("failed to build command buffer");
+        sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), command_buffer)
+            .expect("failed to execute command buffer")
+            .then_signal_fence_and_flush()
+            .expect("failed to signal fence and flush")
+            .wait(None)
+            .expect("failed to wait for command buffer");
+    }
+}
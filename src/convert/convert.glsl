@@ -0,0 +1,164 @@
+#version 460
+
+layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+// only read with texelFetch, so the sampler doesn't matter, but sRGB images are decoded to linear
+layout(set = 0, binding = 0) uniform sampler2D src;
+
+#ifdef UNFORMATTED
+// the output's format is only known at runtime, which needs shaderStorageImageWriteWithoutFormat
+layout(set = 0, binding = 1) uniform writeonly image2D dst;
+#else
+layout(set = 0, binding = 1, rgba8) uniform writeonly image2D dst;
+#endif
+
+#define NEAREST 0u
+#define BILINEAR 1u
+#define BICUBIC 2u
+#define LANCZOS 3u
+
+const float PI = 3.14159265358979;
+
+layout(push_constant) uniform ConvertParams {
+    // x, y, width and height of the part of src to convert
+    ivec4 crop;
+    // which channel of the filtered color each output channel takes, 4 for 0 and 5 for 1
+    uvec4 swizzle;
+    // quarter turns clockwise, applied after cropping
+    uint rotation;
+    // bit 0 flips horizontally and bit 1 vertically, applied after rotating
+    uint flip;
+    uint resize_filter;
+    // storage images can't be sRGB, so the shader encodes colors itself
+    uint encode_srgb;
+} params;
+
+vec4 fetch(int x, int y) {
+    // clamped to the edges of the whole image, like blit_image
+    ivec2 pos = clamp(ivec2(x, y), ivec2(0), textureSize(src, 0) - 1);
+    return texelFetch(src, pos, 0);
+}
+
+// Catmull-Rom
+float cubic(float x) {
+    x = abs(x);
+    if (x < 1.0) {
+        return (1.5 * x - 2.5) * x * x + 1.0;
+    } else if (x < 2.0) {
+        return ((-0.5 * x + 2.5) * x - 4.0) * x + 2.0;
+    }
+    return 0.0;
+}
+
+float sinc(float x) {
+    if (x == 0.0) {
+        return 1.0;
+    }
+    return sin(PI * x) / (PI * x);
+}
+
+float lanczos3(float x) {
+    return abs(x) < 3.0 ? sinc(x) * sinc(x / 3.0) : 0.0;
+}
+
+float kernel(float x) {
+    return params.resize_filter == BICUBIC ? cubic(x) : lanczos3(x);
+}
+
+// filters src around pos, in pixels, where each output pixel covers scale pixels of src along
+// each axis
+vec4 sample_src(vec2 pos, vec2 scale) {
+    if (params.resize_filter == NEAREST) {
+        ivec2 i = ivec2(floor(pos));
+        return fetch(i.x, i.y);
+    }
+    if (params.resize_filter == BILINEAR) {
+        vec2 p = pos - 0.5;
+        ivec2 i = ivec2(floor(p));
+        vec2 f = p - floor(p);
+        vec4 top = mix(fetch(i.x, i.y), fetch(i.x + 1, i.y), f.x);
+        vec4 bottom = mix(fetch(i.x, i.y + 1), fetch(i.x + 1, i.y + 1), f.x);
+        return mix(top, bottom, f.y);
+    }
+
+    // the kernel is stretched when shrinking, so every pixel of src counts
+    float radius = params.resize_filter == BICUBIC ? 2.0 : 3.0;
+    vec2 stretch = max(scale, 1.0);
+    ivec2 lo = ivec2(floor(pos - radius * stretch));
+    ivec2 hi = ivec2(ceil(pos + radius * stretch));
+    vec4 sum = vec4(0.0);
+    float total = 0.0;
+    for (int y = lo.y; y < hi.y; y++) {
+        float weight_y = kernel((float(y) + 0.5 - pos.y) / stretch.y);
+        if (weight_y == 0.0) {
+            continue;
+        }
+        for (int x = lo.x; x < hi.x; x++) {
+            float weight = kernel((float(x) + 0.5 - pos.x) / stretch.x) * weight_y;
+            sum += weight * fetch(x, y);
+            total += weight;
+        }
+    }
+    return sum / total;
+}
+
+float linear_to_srgb(float c) {
+    c = clamp(c, 0.0, 1.0);
+    return c <= 0.0031308 ? c * 12.92 : 1.055 * pow(c, 1.0 / 2.4) - 0.055;
+}
+
+void main() {
+    ivec2 pos = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = imageSize(dst);
+    if (any(greaterThanEqual(pos, size))) {
+        return;
+    }
+
+    // where the middle of this pixel is in the cropped, rotated and flipped image
+    vec2 cropped = vec2(params.crop.zw);
+    bool turned = (params.rotation & 1u) == 1u;
+    vec2 oriented = turned ? cropped.yx : cropped;
+    vec2 o = (vec2(pos) + 0.5) * oriented / vec2(size);
+    if ((params.flip & 1u) != 0u) {
+        o.x = oriented.x - o.x;
+    }
+    if ((params.flip & 2u) != 0u) {
+        o.y = oriented.y - o.y;
+    }
+
+    // and where that is before rotating
+    vec2 uv;
+    switch (params.rotation) {
+    case 0u:
+        uv = o;
+        break;
+    case 1u:
+        uv = vec2(o.y, cropped.y - o.x);
+        break;
+    case 2u:
+        uv = cropped - o;
+        break;
+    default:
+        uv = vec2(cropped.x - o.y, o.x);
+        break;
+    }
+    vec2 scale = oriented / vec2(size);
+    if (turned) {
+        scale = scale.yx;
+    }
+    vec4 color = sample_src(vec2(params.crop.xy) + uv, scale);
+
+    vec4 swizzled;
+    for (int c = 0; c < 4; c++) {
+        uint from = params.swizzle[c];
+        swizzled[c] = from < 4u ? color[from] : float(from - 4u);
+    }
+    if (params.encode_srgb != 0u) {
+        swizzled.rgb = vec3(
+            linear_to_srgb(swizzled.r),
+            linear_to_srgb(swizzled.g),
+            linear_to_srgb(swizzled.b)
+        );
+    }
+    imageStore(dst, pos, swizzled);
+}
@@ -0,0 +1,609 @@
+use std::{f32::consts::PI, str::FromStr, sync::Arc};
+
+use image::{DynamicImage, Rgba, Rgba32FImage};
+use vulkano::{
+    buffer::BufferContents,
+    command_buffer::{BlitImageInfo, ImageBlit},
+    descriptor_set::WriteDescriptorSet,
+    format::{Format, FormatFeatures},
+    image::{
+        sampler::{Filter, Sampler, SamplerCreateInfo},
+        view::ImageView,
+        ImageUsage,
+    },
+    pipeline::{ComputePipeline, Pipeline, PipelineBindPoint},
+};
+
+use crate::{
+    context::Context,
+    format::{linear_to_srgb, ColorFormat},
+    transfer::{create_image, read_back, upload_converted, Readback, Subresource},
+};
+
+/// How pixels are worked out when resizing, parsed from the command line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResizeFilter {
+    /// `nearest`
+    Nearest,
+    /// `bilinear`, between the four nearest pixels, like `blit_image` with linear filtering
+    Bilinear,
+    /// `bicubic`, Catmull-Rom
+    Bicubic,
+    /// `lanczos`, Lanczos with three lobes
+    Lanczos,
+}
+
+impl FromStr for ResizeFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(ResizeFilter::Nearest),
+            "bilinear" => Ok(ResizeFilter::Bilinear),
+            "bicubic" => Ok(ResizeFilter::Bicubic),
+            "lanczos" => Ok(ResizeFilter::Lanczos),
+            _ => Err(format!("unknown filter '{}'", s)),
+        }
+    }
+}
+
+/// A rectangle of pixels, parsed from `<x>,<y>,<width>,<height>` on the command line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Crop {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Crop {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s
+            .split(',')
+            .map(|part| part.trim().parse::<u32>())
+            .collect::<Result<Vec<_>, _>>();
+        match parts.as_deref() {
+            Ok(&[x, y, width, height]) if width > 0 && height > 0 => Ok(Crop {
+                x,
+                y,
+                width,
+                height,
+            }),
+            _ => Err(format!(
+                "crop '{}' must be <x>,<y>,<width>,<height> with a positive width and height",
+                s
+            )),
+        }
+    }
+}
+
+/// Which channel of the color each output channel takes, parsed from four of `r`, `g`, `b`, `a`,
+/// `0` and `1` on the command line, so `bgra` swaps red and blue.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Swizzle(pub [u32; 4]);
+
+impl Swizzle {
+    /// Every channel stays where it is.
+    pub const IDENTITY: Swizzle = Swizzle([0, 1, 2, 3]);
+
+    pub fn apply(self, color: [f32; 4]) -> [f32; 4] {
+        self.0.map(|from| match from {
+            0..=3 => color[from as usize],
+            _ => (from - 4) as f32,
+        })
+    }
+}
+
+impl Default for Swizzle {
+    fn default() -> Self {
+        Swizzle::IDENTITY
+    }
+}
+
+impl FromStr for Swizzle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let channels = s
+            .chars()
+            .map(|c| "rgba01".find(c).map(|i| i as u32))
+            .collect::<Option<Vec<_>>>();
+        channels
+            .and_then(|channels| channels.try_into().ok())
+            .map(Swizzle)
+            .ok_or_else(|| format!("swizzle '{}' must be four of r, g, b, a, 0 and 1", s))
+    }
+}
+
+/// What to do to an image, in order: crop, rotate, flip, resize, swizzle, then convert to
+/// another format.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conversion {
+    /// The whole image when there's none.
+    pub crop: Option<Crop>,
+    /// Quarter turns clockwise.
+    pub quarter_turns: u32,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+    /// The size of the cropped and rotated image when there's none.
+    pub size: Option<[u32; 2]>,
+    pub filter: ResizeFilter,
+    pub swizzle: Swizzle,
+    /// The format the input is uploaded as. sRGB formats take it as sRGB encoded.
+    pub from: ColorFormat,
+    pub to: ColorFormat,
+}
+
+impl Default for Conversion {
+    fn default() -> Self {
+        Self {
+            crop: None,
+            quarter_turns: 0,
+            flip_horizontal: false,
+            flip_vertical: false,
+            size: None,
+            filter: ResizeFilter::Bilinear,
+            swizzle: Swizzle::IDENTITY,
+            from: ColorFormat::Rgba8,
+            to: ColorFormat::Rgba8,
+        }
+    }
+}
+
+impl Conversion {
+    /// The part of an image of the given size to convert. Fails when the crop doesn't fit.
+    pub fn crop_region(&self, width: u32, height: u32) -> Result<Crop, String> {
+        let Some(crop) = self.crop else {
+            return Ok(Crop {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            });
+        };
+        if crop.x as u64 + crop.width as u64 > width as u64
+            || crop.y as u64 + crop.height as u64 > height as u64
+        {
+            return Err(format!(
+                "crop of {}x{} at {},{} doesn't fit in a {}x{} image",
+                crop.width, crop.height, crop.x, crop.y, width, height
+            ));
+        }
+        Ok(crop)
+    }
+
+    /// The size of the converted image, for an input of the given size.
+    pub fn output_size(&self, width: u32, height: u32) -> Result<[u32; 2], String> {
+        let crop = self.crop_region(width, height)?;
+        Ok(self.size.unwrap_or(if self.turned() {
+            [crop.height, crop.width]
+        } else {
+            [crop.width, crop.height]
+        }))
+    }
+
+    /// Whether `blit_image` can do all of it, leaving formats aside: it can flip, which turns
+    /// half way too, but not turn a quarter, swizzle or filter with more than four pixels.
+    pub fn can_blit(&self) -> bool {
+        !self.turned()
+            && self.swizzle == Swizzle::IDENTITY
+            && matches!(self.filter, ResizeFilter::Nearest | ResizeFilter::Bilinear)
+    }
+
+    /// Whether the image ends up on its side, swapping its width and height.
+    fn turned(&self) -> bool {
+        self.quarter_turns % 2 == 1
+    }
+
+    fn params(&self, crop: Crop) -> ConvertParams {
+        ConvertParams {
+            crop: [crop.x, crop.y, crop.width, crop.height].map(|c| c as i32),
+            swizzle: self.swizzle.0,
+            rotation: self.quarter_turns % 4,
+            flip: self.flip_horizontal as u32 | (self.flip_vertical as u32) << 1,
+            resize_filter: self.filter as u32,
+            encode_srgb: (self.to == ColorFormat::Rgba8Srgb) as u32,
+        }
+    }
+}
+
+/// How a conversion is run, parsed from the command line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConvertMethod {
+    /// `blit`, `blit_image`, for conversions and formats it can do
+    Blit,
+    /// `compute`, a compute shader, for anything
+    Compute,
+}
+
+impl FromStr for ConvertMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blit" => Ok(ConvertMethod::Blit),
+            "compute" => Ok(ConvertMethod::Compute),
+            _ => Err(format!("unknown conversion method '{}'", s)),
+        }
+    }
+}
+
+#[derive(BufferContents, Clone, Copy)]
+#[repr(C)]
+struct ConvertParams {
+    crop: [i32; 4],
+    swizzle: [u32; 4],
+    rotation: u32,
+    flip: u32,
+    resize_filter: u32,
+    encode_srgb: u32,
+}
+
+/// Crops, turns, flips, resizes, swizzles and converts images on the GPU.
+pub struct Converter {
+    sampler: Arc<Sampler>,
+    rgba8: Arc<ComputePipeline>,
+    /// Only there when the device has the feature to write storage images without a format.
+    unformatted: Option<Arc<ComputePipeline>>,
+}
+
+impl Converter {
+    pub fn new(context: &Context) -> Self {
+        let rgba8 =
+            shaders::rgba8::load(context.device.clone()).expect("failed to create shader module");
+        let unformatted = context
+            .device
+            .enabled_features()
+            .shader_storage_image_write_without_format
+            .then(|| {
+                let shader = shaders::unformatted::load(context.device.clone())
+                    .expect("failed to create shader module");
+                context.compute_pipeline(shader)
+            });
+        Self {
+            sampler: Sampler::new(context.device.clone(), SamplerCreateInfo::default())
+                .expect("failed to create sampler"),
+            rgba8: context.compute_pipeline(rgba8),
+            unformatted,
+        }
+    }
+
+    /// Whether `method` can run `conversion` on this device, and why not if it can't.
+    pub fn check_support(
+        &self,
+        context: &Context,
+        conversion: &Conversion,
+        method: ConvertMethod,
+    ) -> Result<(), String> {
+        let physical_device = context.device.physical_device();
+        match method {
+            ConvertMethod::Blit => {
+                if !conversion.can_blit() {
+                    return Err(
+                        "blit_image can't turn a quarter, swizzle or filter bicubic or \
+                                lanczos"
+                            .to_string(),
+                    );
+                }
+                let mut src_features = FormatFeatures::BLIT_SRC;
+                if conversion.filter == ResizeFilter::Bilinear {
+                    src_features |= FormatFeatures::SAMPLED_IMAGE_FILTER_LINEAR;
+                }
+                conversion
+                    .from
+                    .check_support(physical_device, src_features)?;
+                conversion
+                    .to
+                    .check_support(physical_device, FormatFeatures::BLIT_DST)
+            }
+            ConvertMethod::Compute => {
+                conversion
+                    .from
+                    .check_support(physical_device, FormatFeatures::SAMPLED_IMAGE)?;
+                let to = storage_format(conversion.to);
+                to.check_support(physical_device, FormatFeatures::STORAGE_IMAGE)?;
+                if to != ColorFormat::Rgba8 && self.unformatted.is_none() {
+                    return Err(format!(
+                        "converting to {:?} in a compute shader needs the \
+                         shader_storage_image_write_without_format feature",
+                        to.format()
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Blits when the conversion and formats allow, otherwise falls back to the compute shader.
+    pub fn pick_method(
+        &self,
+        context: &Context,
+        conversion: &Conversion,
+    ) -> Result<ConvertMethod, String> {
+        self.check_support(context, conversion, ConvertMethod::Blit)
+            .map(|_| ConvertMethod::Blit)
+            .or_else(|blit_error| {
+                self.check_support(context, conversion, ConvertMethod::Compute)
+                    .map(|_| ConvertMethod::Compute)
+                    .map_err(|e| format!("{}, and {}", blit_error, e))
+            })
+    }
+
+    /// Uploads `input`, converts it with `method`, waits for it to finish and reads the result
+    /// back.
+    pub fn convert(
+        &self,
+        context: &Context,
+        input: &DynamicImage,
+        conversion: &Conversion,
+        method: ConvertMethod,
+    ) -> Result<Readback, String> {
+        self.check_support(context, conversion, method)?;
+        let crop = conversion.crop_region(input.width(), input.height())?;
+        let [width, height] = conversion.output_size(input.width(), input.height())?;
+        let max_size = context
+            .device
+            .physical_device()
+            .properties()
+            .max_image_dimension2_d;
+        if width == 0 || height == 0 || width.max(height) > max_size {
+            return Err(format!(
+                "can't convert to {}x{}, images have to be from 1x1 to {}x{}",
+                width, height, max_size, max_size
+            ));
+        }
+
+        let src_usage = match method {
+            ConvertMethod::Blit => ImageUsage::TRANSFER_SRC,
+            ConvertMethod::Compute => ImageUsage::SAMPLED,
+        };
+        let src = upload_converted(context, input, conversion.from, src_usage)?;
+        let mut builder = context.command_buffer_builder();
+        let dst = match method {
+            ConvertMethod::Blit => {
+                let dst = create_image(
+                    context,
+                    conversion.to.format(),
+                    width,
+                    height,
+                    ImageUsage::TRANSFER_DST | ImageUsage::TRANSFER_SRC,
+                );
+                // a half turn is the same as flipping both ways
+                let half_turn = conversion.quarter_turns % 4 == 2;
+                let [mut x0, mut x1] = [crop.x, crop.x + crop.width];
+                let [mut y0, mut y1] = [crop.y, crop.y + crop.height];
+                if conversion.flip_horizontal != half_turn {
+                    (x0, x1) = (x1, x0);
+                }
+                if conversion.flip_vertical != half_turn {
+                    (y0, y1) = (y1, y0);
+                }
+                let filter = match conversion.filter {
+                    ResizeFilter::Nearest => Filter::Nearest,
+                    _ => Filter::Linear,
+                };
+                builder
+                    .blit_image(BlitImageInfo {
+                        regions: [ImageBlit {
+                            src_subresource: src.subresource_layers(),
+                            src_offsets: [[x0, y0, 0], [x1, y1, 1]],
+                            dst_subresource: dst.subresource_layers(),
+                            dst_offsets: [[0, 0, 0], [width, height, 1]],
+                            ..Default::default()
+                        }]
+                        .into(),
+                        filter,
+                        ..BlitImageInfo::images(src, dst.clone())
+                    })
+                    .expect("failed to blit image");
+                dst
+            }
+            ConvertMethod::Compute => {
+                let dst = create_image(
+                    context,
+                    storage_format(conversion.to).format(),
+                    width,
+                    height,
+                    ImageUsage::STORAGE | ImageUsage::TRANSFER_SRC,
+                );
+                let pipeline = match dst.format() {
+                    Format::R8G8B8A8_UNORM => &self.rgba8,
+                    _ => self.unformatted.as_ref().unwrap(),
+                };
+                let src_view = ImageView::new_default(src).expect("could not create image view");
+                let dst_view =
+                    ImageView::new_default(dst.clone()).expect("could not create image view");
+                let descriptor_set = context.descriptor_set(
+                    pipeline,
+                    [
+                        WriteDescriptorSet::image_view_sampler(0, src_view, self.sampler.clone()),
+                        WriteDescriptorSet::image_view(1, dst_view),
+                    ],
+                );
+                builder
+                    .bind_pipeline_compute(pipeline.clone())
+                    .expect("failed to bind pipeline")
+                    .bind_descriptor_sets(
+                        PipelineBindPoint::Compute,
+                        pipeline.layout().clone(),
+                        0,
+                        descriptor_set,
+                    )
+                    .expect("failed to bind descriptor set")
+                    .push_constants(pipeline.layout().clone(), 0, conversion.params(crop))
+                    .expect("failed to push conversion parameters")
+                    .dispatch([width.div_ceil(8), height.div_ceil(8), 1])
+                    .expect("failed to dispatch work groups");
+                dst
+            }
+        };
+        context.execute(builder);
+
+        let mut readback = read_back(context, dst, Subresource::default())?;
+        // the shader wrote sRGB encoded colors into a UNORM image
+        readback.format = conversion.to.format();
+        Ok(readback)
+    }
+}
+
+/// The format the compute shader writes `format` as, since storage images can't be sRGB.
+fn storage_format(format: ColorFormat) -> ColorFormat {
+    match format {
+        ColorFormat::Rgba8Srgb => ColorFormat::Rgba8,
+        format => format,
+    }
+}
+
+/// Runs `conversion` on the CPU the same way the compute shader does, returning linear colors
+/// before they're encoded in `conversion.to`.
+pub fn convert_cpu(input: &DynamicImage, conversion: &Conversion) -> Result<Rgba32FImage, String> {
+    let crop = conversion.crop_region(input.width(), input.height())?;
+    let [width, height] = conversion.output_size(input.width(), input.height())?;
+    // what the GPU sees after uploading
+    let from = conversion.from;
+    let src = from.decode(input.width(), input.height(), &from.encode_file(input));
+    let fetch = |x: i64, y: i64| {
+        let x = x.clamp(0, src.width() as i64 - 1) as u32;
+        let y = y.clamp(0, src.height() as i64 - 1) as u32;
+        src.get_pixel(x, y).0
+    };
+
+    let cropped = [crop.width as f32, crop.height as f32];
+    let turns = conversion.quarter_turns % 4;
+    let oriented = if conversion.turned() {
+        [cropped[1], cropped[0]]
+    } else {
+        cropped
+    };
+    let mut scale = [oriented[0] / width as f32, oriented[1] / height as f32];
+    if conversion.turned() {
+        scale.swap(0, 1);
+    }
+    let sample = |pos: [f32; 2]| -> [f32; 4] {
+        match conversion.filter {
+            ResizeFilter::Nearest => fetch(pos[0].floor() as i64, pos[1].floor() as i64),
+            ResizeFilter::Bilinear => {
+                let p = [pos[0] - 0.5, pos[1] - 0.5];
+                let [i, j] = p.map(|c| c.floor() as i64);
+                let [fx, fy] = p.map(|c| c - c.floor());
+                let lerp = |a: [f32; 4], b: [f32; 4], t: f32| {
+                    [0, 1, 2, 3].map(|c| a[c] + (b[c] - a[c]) * t)
+                };
+                let top = lerp(fetch(i, j), fetch(i + 1, j), fx);
+                let bottom = lerp(fetch(i, j + 1), fetch(i + 1, j + 1), fx);
+                lerp(top, bottom, fy)
+            }
+            ResizeFilter::Bicubic | ResizeFilter::Lanczos => {
+                let (kernel, radius): (fn(f32) -> f32, f32) =
+                    if conversion.filter == ResizeFilter::Bicubic {
+                        (cubic, 2.0)
+                    } else {
+                        (lanczos3, 3.0)
+                    };
+                let stretch = scale.map(|s| s.max(1.0));
+                let lo = [0, 1].map(|k| (pos[k] - radius * stretch[k]).floor() as i64);
+                let hi = [0, 1].map(|k| (pos[k] + radius * stretch[k]).ceil() as i64);
+                let mut sum = [0.0; 4];
+                let mut total = 0.0;
+                for y in lo[1]..hi[1] {
+                    let weight_y = kernel((y as f32 + 0.5 - pos[1]) / stretch[1]);
+                    if weight_y == 0.0 {
+                        continue;
+                    }
+                    for x in lo[0]..hi[0] {
+                        let weight = kernel((x as f32 + 0.5 - pos[0]) / stretch[0]) * weight_y;
+                        for (sum, c) in sum.iter_mut().zip(fetch(x, y)) {
+                            *sum += weight * c;
+                        }
+                        total += weight;
+                    }
+                }
+                sum.map(|c| c / total)
+            }
+        }
+    };
+
+    Ok(Rgba32FImage::from_fn(width, height, |x, y| {
+        let mut o = [
+            (x as f32 + 0.5) * oriented[0] / width as f32,
+            (y as f32 + 0.5) * oriented[1] / height as f32,
+        ];
+        if conversion.flip_horizontal {
+            o[0] = oriented[0] - o[0];
+        }
+        if conversion.flip_vertical {
+            o[1] = oriented[1] - o[1];
+        }
+        let [u, v] = match turns {
+            0 => o,
+            1 => [o[1], cropped[1] - o[0]],
+            2 => [cropped[0] - o[0], cropped[1] - o[1]],
+            _ => [cropped[0] - o[1], o[0]],
+        };
+        let color = sample([crop.x as f32 + u, crop.y as f32 + v]);
+        Rgba(conversion.swizzle.apply(color))
+    }))
+}
+
+/// Largest difference between any channel of `a` and `b`, linear colors of the same size, once
+/// they're encoded like `format` stores them, so sRGB colors compare in sRGB.
+pub fn max_difference(format: ColorFormat, a: &Rgba32FImage, b: &Rgba32FImage) -> f32 {
+    let encode = |i: usize, c: f32| match format {
+        ColorFormat::Rgba8Srgb if i % 4 != 3 => linear_to_srgb(c),
+        ColorFormat::Rgba8 | ColorFormat::Rgba8Srgb | ColorFormat::Rgba16 => c.clamp(0.0, 1.0),
+        _ => c,
+    };
+    a.as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .enumerate()
+        .map(|(i, (&a, &b))| (encode(i, a) - encode(i, b)).abs())
+        .fold(0.0, f32::max)
+}
+
+/// Catmull-Rom, the same as `cubic` in convert.glsl.
+fn cubic(x: f32) -> f32 {
+    let x = x.abs();
+    if x < 1.0 {
+        (1.5 * x - 2.5) * x * x + 1.0
+    } else if x < 2.0 {
+        ((-0.5 * x + 2.5) * x - 4.0) * x + 2.0
+    } else {
+        0.0
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+fn lanczos3(x: f32) -> f32 {
+    if x.abs() < 3.0 {
+        sinc(x) * sinc(x / 3.0)
+    } else {
+        0.0
+    }
+}
+
+mod shaders {
+    pub mod rgba8 {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/convert/convert.glsl"
+        }
+    }
+
+    pub mod unformatted {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/convert/convert.glsl",
+            define: [("UNFORMATTED", "")]
+        }
+    }
+}
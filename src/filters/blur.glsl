@@ -0,0 +1,32 @@
+#version 460
+
+layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+layout(set = 0, binding = 0, rgba8) uniform readonly image2D src;
+layout(set = 0, binding = 1, rgba8) uniform writeonly image2D dst;
+
+// one pass of a separable gaussian blur, along `direction`
+layout(push_constant) uniform BlurParams {
+    ivec2 direction;
+    int radius;
+    float sigma;
+} params;
+
+void main() {
+    ivec2 size = imageSize(src);
+    ivec2 pos = ivec2(gl_GlobalInvocationID.xy);
+    if (any(greaterThanEqual(pos, size))) {
+        return;
+    }
+
+    vec4 sum = vec4(0.0);
+    float weight_sum = 0.0;
+    for (int i = -params.radius; i <= params.radius; i++) {
+        float weight = exp(-float(i * i) / (2.0 * params.sigma * params.sigma));
+        ivec2 sample_pos = clamp(pos + params.direction * i, ivec2(0), size - 1);
+        sum += imageLoad(src, sample_pos) * weight;
+        weight_sum += weight;
+    }
+
+    imageStore(dst, pos, sum / weight_sum);
+}
@@ -0,0 +1,430 @@
+use std::{str::FromStr, sync::Arc};
+
+use image::{Rgba, RgbaImage};
+use vulkano::{
+    buffer::BufferContents,
+    command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer},
+    descriptor_set::WriteDescriptorSet,
+    image::{view::ImageView, Image, ImageUsage},
+    pipeline::{ComputePipeline, Pipeline, PipelineBindPoint},
+};
+
+use crate::{
+    context::Context,
+    transfer::{create_rgba8_image, download_rgba8, upload_rgba8},
+};
+
+/// Blurs never look further than this many pixels away, however large sigma gets.
+const MAX_BLUR_RADIUS: i32 = 64;
+
+/// Rec. 709 luma weights, as used by the shaders.
+const LUMA: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+/// An image filter, parsed from `name` or `name:arg,arg` on the command line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    /// `blur:<sigma>`
+    GaussianBlur { sigma: f32 },
+    /// `sobel`, edge magnitude of the luminance
+    Sobel,
+    /// `grayscale`
+    Grayscale,
+    /// `brightness-contrast:<brightness>,<contrast>`, brightness is added after scaling the
+    /// distance from mid gray by contrast
+    BrightnessContrast { brightness: f32, contrast: f32 },
+    /// `gamma:<gamma>`, values above 1 brighten
+    Gamma { gamma: f32 },
+    /// `unsharp:<sigma>,<amount>`
+    UnsharpMask { sigma: f32, amount: f32 },
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, args) = s.split_once(':').unwrap_or((s, ""));
+        let args = args
+            .split(',')
+            .filter(|a| !a.is_empty())
+            .map(|a| {
+                a.trim()
+                    .parse::<f32>()
+                    .map_err(|_| format!("invalid argument '{}' for filter '{}'", a, name))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let positive = |value: f32, what: &str| {
+            if value > 0.0 {
+                Ok(value)
+            } else {
+                Err(format!("{} of filter '{}' must be positive", what, name))
+            }
+        };
+
+        let filter = match (name, args.as_slice()) {
+            ("blur", &[sigma]) => Filter::GaussianBlur {
+                sigma: positive(sigma, "sigma")?,
+            },
+            ("sobel", &[]) => Filter::Sobel,
+            ("grayscale", &[]) => Filter::Grayscale,
+            ("brightness-contrast", &[brightness, contrast]) => Filter::BrightnessContrast {
+                brightness,
+                contrast,
+            },
+            ("gamma", &[gamma]) => Filter::Gamma {
+                gamma: positive(gamma, "gamma")?,
+            },
+            ("unsharp", &[sigma, amount]) => Filter::UnsharpMask {
+                sigma: positive(sigma, "sigma")?,
+                amount,
+            },
+            ("blur" | "sobel" | "grayscale" | "brightness-contrast" | "gamma" | "unsharp", _) => {
+                return Err(format!(
+                    "wrong number of arguments for filter '{}' ({} given)",
+                    name,
+                    args.len()
+                ))
+            }
+            _ => return Err(format!("unknown filter '{}'", name)),
+        };
+        Ok(filter)
+    }
+}
+
+impl Filter {
+    /// Applies the filter on the CPU, doing the same math as the shaders so the GPU's output
+    /// can be checked against it.
+    pub fn apply_cpu(&self, image: &RgbaImage) -> RgbaImage {
+        match *self {
+            Filter::GaussianBlur { sigma } => blur_cpu(image, sigma),
+            Filter::Sobel => sobel_cpu(image),
+            Filter::Grayscale => map_rgb(image, |rgb| [luminance(rgb); 3]),
+            Filter::BrightnessContrast {
+                brightness,
+                contrast,
+            } => map_rgb(image, |rgb| {
+                rgb.map(|c| (c - 0.5) * contrast + 0.5 + brightness)
+            }),
+            Filter::Gamma { gamma } => map_rgb(image, |rgb| rgb.map(|c| c.powf(1.0 / gamma))),
+            Filter::UnsharpMask { sigma, amount } => {
+                let blurred = blur_cpu(image, sigma);
+                RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+                    let original = to_float(image.get_pixel(x, y));
+                    let blurred = to_float(blurred.get_pixel(x, y));
+                    let mut out = original;
+                    for (o, b) in out.iter_mut().zip(blurred).take(3) {
+                        *o += amount * (*o - b);
+                    }
+                    to_unorm(out)
+                })
+            }
+        }
+    }
+}
+
+/// Applies a chain of filters on the CPU.
+pub fn apply_cpu_chain(image: &RgbaImage, filters: &[Filter]) -> RgbaImage {
+    filters
+        .iter()
+        .fold(image.clone(), |image, filter| filter.apply_cpu(&image))
+}
+
+/// Largest difference between any channel of two equally sized images.
+pub fn max_channel_difference(a: &RgbaImage, b: &RgbaImage) -> u8 {
+    assert_eq!(a.dimensions(), b.dimensions(), "images differ in size");
+    a.as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(a, b)| a.abs_diff(*b))
+        .max()
+        .unwrap_or(0)
+}
+
+fn blur_radius(sigma: f32) -> i32 {
+    ((3.0 * sigma).ceil() as i32).min(MAX_BLUR_RADIUS)
+}
+
+fn to_float(pixel: &Rgba<u8>) -> [f32; 4] {
+    pixel.0.map(|c| c as f32 / 255.0)
+}
+
+/// Converts like storing to an `R8G8B8A8_UNORM` image does.
+fn to_unorm(color: [f32; 4]) -> Rgba<u8> {
+    Rgba(color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+}
+
+fn luminance(rgb: [f32; 3]) -> f32 {
+    rgb.iter().zip(LUMA).map(|(c, w)| c * w).sum()
+}
+
+fn map_rgb(image: &RgbaImage, f: impl Fn([f32; 3]) -> [f32; 3]) -> RgbaImage {
+    RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b, a] = to_float(image.get_pixel(x, y));
+        let [r, g, b] = f([r, g, b]);
+        to_unorm([r, g, b, a])
+    })
+}
+
+fn clamped_pixel(image: &RgbaImage, x: i32, y: i32) -> [f32; 4] {
+    let x = x.clamp(0, image.width() as i32 - 1) as u32;
+    let y = y.clamp(0, image.height() as i32 - 1) as u32;
+    to_float(image.get_pixel(x, y))
+}
+
+fn blur_pass_cpu(image: &RgbaImage, direction: [i32; 2], sigma: f32) -> RgbaImage {
+    let radius = blur_radius(sigma);
+    RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+        let mut sum = [0.0; 4];
+        let mut weight_sum = 0.0;
+        for i in -radius..=radius {
+            let weight = (-((i * i) as f32) / (2.0 * sigma * sigma)).exp();
+            let sample = clamped_pixel(
+                image,
+                x as i32 + direction[0] * i,
+                y as i32 + direction[1] * i,
+            );
+            for (s, c) in sum.iter_mut().zip(sample) {
+                *s += c * weight;
+            }
+            weight_sum += weight;
+        }
+        to_unorm(sum.map(|s| s / weight_sum))
+    })
+}
+
+fn blur_cpu(image: &RgbaImage, sigma: f32) -> RgbaImage {
+    // each pass goes through an 8-bit image on the GPU, so round in between here too
+    let horizontal = blur_pass_cpu(image, [1, 0], sigma);
+    blur_pass_cpu(&horizontal, [0, 1], sigma)
+}
+
+fn sobel_cpu(image: &RgbaImage) -> RgbaImage {
+    RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+        let l = |dx: i32, dy: i32| {
+            let [r, g, b, _] = clamped_pixel(image, x as i32 + dx, y as i32 + dy);
+            luminance([r, g, b])
+        };
+        let gx = (l(1, -1) + 2.0 * l(1, 0) + l(1, 1)) - (l(-1, -1) + 2.0 * l(-1, 0) + l(-1, 1));
+        let gy = (l(-1, 1) + 2.0 * l(0, 1) + l(1, 1)) - (l(-1, -1) + 2.0 * l(0, -1) + l(1, -1));
+        let magnitude = (gx * gx + gy * gy).sqrt();
+        let alpha = to_float(image.get_pixel(x, y))[3];
+        to_unorm([magnitude, magnitude, magnitude, alpha])
+    })
+}
+
+#[derive(BufferContents)]
+#[repr(C)]
+struct BlurParams {
+    direction: [i32; 2],
+    radius: i32,
+    sigma: f32,
+}
+
+#[derive(BufferContents)]
+#[repr(C)]
+struct PointwiseParams {
+    op: u32,
+    a: f32,
+    b: f32,
+}
+
+#[derive(BufferContents)]
+#[repr(C)]
+struct UnsharpParams {
+    amount: f32,
+}
+
+// keep in sync with the `OP_*` constants in pointwise.glsl
+const OP_GRAYSCALE: u32 = 0;
+const OP_BRIGHTNESS_CONTRAST: u32 = 1;
+const OP_GAMMA: u32 = 2;
+
+/// Compute pipelines for every filter, created once and reused for any number of images.
+pub struct FilterPipelines {
+    blur: Arc<ComputePipeline>,
+    sobel: Arc<ComputePipeline>,
+    pointwise: Arc<ComputePipeline>,
+    unsharp: Arc<ComputePipeline>,
+}
+
+impl FilterPipelines {
+    pub fn new(context: &Context) -> Self {
+        let load = |shader: Result<_, _>| {
+            context.compute_pipeline(shader.expect("failed to create shader module"))
+        };
+        Self {
+            blur: load(shaders::load_blur(context.device.clone())),
+            sobel: load(shaders::load_sobel(context.device.clone())),
+            pointwise: load(shaders::load_pointwise(context.device.clone())),
+            unsharp: load(shaders::load_unsharp(context.device.clone())),
+        }
+    }
+
+    /// Uploads `input`, runs `filters` over it in order on the GPU and reads the result back.
+    pub fn apply(&self, context: &Context, input: &RgbaImage, filters: &[Filter]) -> RgbaImage {
+        let usage = ImageUsage::STORAGE | ImageUsage::TRANSFER_SRC;
+        let (width, height) = input.dimensions();
+
+        // ping-pong between three images, the first always holding the latest result
+        let mut images = [
+            upload_rgba8(context, input, usage),
+            create_rgba8_image(context, width, height, usage),
+            create_rgba8_image(context, width, height, usage),
+        ];
+
+        let mut builder = context.command_buffer_builder();
+        for filter in filters {
+            match *filter {
+                Filter::GaussianBlur { sigma } => {
+                    self.record_blur(context, &mut builder, &images, sigma);
+                    images.rotate_left(2);
+                }
+                Filter::Sobel => {
+                    self.bind(context, &mut builder, &self.sobel, &images[..2]);
+                    dispatch_over(&mut builder, &images[0]);
+                    images.swap(0, 1);
+                }
+                Filter::Grayscale => {
+                    self.record_pointwise(context, &mut builder, &images, OP_GRAYSCALE, 0.0, 0.0);
+                    images.swap(0, 1);
+                }
+                Filter::BrightnessContrast {
+                    brightness,
+                    contrast,
+                } => {
+                    self.record_pointwise(
+                        context,
+                        &mut builder,
+                        &images,
+                        OP_BRIGHTNESS_CONTRAST,
+                        brightness,
+                        contrast,
+                    );
+                    images.swap(0, 1);
+                }
+                Filter::Gamma { gamma } => {
+                    self.record_pointwise(context, &mut builder, &images, OP_GAMMA, gamma, 0.0);
+                    images.swap(0, 1);
+                }
+                Filter::UnsharpMask { sigma, amount } => {
+                    // blurred copy ends up in the third image, sharpened result in the second
+                    self.record_blur(context, &mut builder, &images, sigma);
+                    let unsharp_images = [&images[0], &images[2], &images[1]];
+                    self.bind(context, &mut builder, &self.unsharp, unsharp_images);
+                    builder
+                        .push_constants(self.unsharp.layout().clone(), 0, UnsharpParams { amount })
+                        .expect("failed to push unsharp parameters");
+                    dispatch_over(&mut builder, &images[0]);
+                    images.swap(0, 1);
+                }
+            }
+        }
+        context.execute(builder);
+
+        download_rgba8(context, images[0].clone())
+    }
+
+    /// Binds `pipeline` with `images` at bindings 0, 1, 2... of set 0.
+    fn bind<'a>(
+        &self,
+        context: &Context,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        pipeline: &Arc<ComputePipeline>,
+        images: impl IntoIterator<Item = &'a Arc<Image>>,
+    ) {
+        let writes = images.into_iter().enumerate().map(|(binding, image)| {
+            let view = ImageView::new_default(image.clone()).expect("could not create image view");
+            WriteDescriptorSet::image_view(binding as u32, view)
+        });
+        let descriptor_set = context.descriptor_set(pipeline, writes);
+        builder
+            .bind_pipeline_compute(pipeline.clone())
+            .expect("failed to bind pipeline")
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                descriptor_set,
+            )
+            .expect("failed to bind descriptor set");
+    }
+
+    /// Blurs `images[0]` into `images[2]`, using `images[1]` for the horizontal pass.
+    fn record_blur(
+        &self,
+        context: &Context,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        images: &[Arc<Image>; 3],
+        sigma: f32,
+    ) {
+        for (direction, pass) in [([1, 0], &images[..2]), ([0, 1], &images[1..])] {
+            self.bind(context, builder, &self.blur, pass);
+            builder
+                .push_constants(
+                    self.blur.layout().clone(),
+                    0,
+                    BlurParams {
+                        direction,
+                        radius: blur_radius(sigma),
+                        sigma,
+                    },
+                )
+                .expect("failed to push blur parameters");
+            dispatch_over(builder, &images[0]);
+        }
+    }
+
+    /// Runs the pointwise shader from `images[0]` into `images[1]`.
+    fn record_pointwise(
+        &self,
+        context: &Context,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        images: &[Arc<Image>; 3],
+        op: u32,
+        a: f32,
+        b: f32,
+    ) {
+        self.bind(context, builder, &self.pointwise, &images[..2]);
+        builder
+            .push_constants(
+                self.pointwise.layout().clone(),
+                0,
+                PointwiseParams { op, a, b },
+            )
+            .expect("failed to push pointwise parameters");
+        dispatch_over(builder, &images[0]);
+    }
+}
+
+/// Dispatches enough 8x8 work groups to cover every pixel of `image`.
+fn dispatch_over(
+    builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    image: &Arc<Image>,
+) {
+    let [width, height, _] = image.extent();
+    builder
+        .dispatch([width.div_ceil(8), height.div_ceil(8), 1])
+        .expect("failed to dispatch work groups");
+}
+
+mod shaders {
+    vulkano_shaders::shader! {
+        shaders: {
+            blur: {
+                ty: "compute",
+                path: "src/filters/blur.glsl"
+            },
+            sobel: {
+                ty: "compute",
+                path: "src/filters/sobel.glsl"
+            },
+            pointwise: {
+                ty: "compute",
+                path: "src/filters/pointwise.glsl"
+            },
+            unsharp: {
+                ty: "compute",
+                path: "src/filters/unsharp.glsl"
+            },
+        }
+    }
+}
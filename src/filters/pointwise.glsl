@@ -0,0 +1,36 @@
+#version 460
+
+layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+layout(set = 0, binding = 0, rgba8) uniform readonly image2D src;
+layout(set = 0, binding = 1, rgba8) uniform writeonly image2D dst;
+
+const uint OP_GRAYSCALE = 0u;
+const uint OP_BRIGHTNESS_CONTRAST = 1u;
+const uint OP_GAMMA = 2u;
+
+// `a` and `b` mean different things depending on `op`, see `Filter` on the Rust side
+layout(push_constant) uniform PointwiseParams {
+    uint op;
+    float a;
+    float b;
+} params;
+
+void main() {
+    ivec2 pos = ivec2(gl_GlobalInvocationID.xy);
+    if (any(greaterThanEqual(pos, imageSize(src)))) {
+        return;
+    }
+
+    vec4 color = imageLoad(src, pos);
+    vec3 rgb = color.rgb;
+    if (params.op == OP_GRAYSCALE) {
+        rgb = vec3(dot(rgb, vec3(0.2126, 0.7152, 0.0722)));
+    } else if (params.op == OP_BRIGHTNESS_CONTRAST) {
+        rgb = (rgb - 0.5) * params.b + 0.5 + params.a;
+    } else if (params.op == OP_GAMMA) {
+        rgb = pow(rgb, vec3(1.0 / params.a));
+    }
+
+    imageStore(dst, pos, vec4(clamp(rgb, 0.0, 1.0), color.a));
+}
@@ -0,0 +1,34 @@
+#version 460
+
+layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+layout(set = 0, binding = 0, rgba8) uniform readonly image2D src;
+layout(set = 0, binding = 1, rgba8) uniform writeonly image2D dst;
+
+float luminance(ivec2 pos) {
+    vec3 rgb = imageLoad(src, clamp(pos, ivec2(0), imageSize(src) - 1)).rgb;
+    return dot(rgb, vec3(0.2126, 0.7152, 0.0722));
+}
+
+void main() {
+    ivec2 size = imageSize(src);
+    ivec2 pos = ivec2(gl_GlobalInvocationID.xy);
+    if (any(greaterThanEqual(pos, size))) {
+        return;
+    }
+
+    float top_left = luminance(pos + ivec2(-1, -1));
+    float top = luminance(pos + ivec2(0, -1));
+    float top_right = luminance(pos + ivec2(1, -1));
+    float left = luminance(pos + ivec2(-1, 0));
+    float right = luminance(pos + ivec2(1, 0));
+    float bottom_left = luminance(pos + ivec2(-1, 1));
+    float bottom = luminance(pos + ivec2(0, 1));
+    float bottom_right = luminance(pos + ivec2(1, 1));
+
+    float gx = (top_right + 2.0 * right + bottom_right) - (top_left + 2.0 * left + bottom_left);
+    float gy = (bottom_left + 2.0 * bottom + bottom_right) - (top_left + 2.0 * top + top_right);
+    float magnitude = clamp(sqrt(gx * gx + gy * gy), 0.0, 1.0);
+
+    imageStore(dst, pos, vec4(vec3(magnitude), imageLoad(src, pos).a));
+}
@@ -0,0 +1,22 @@
+#version 460
+
+layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+layout(set = 0, binding = 0, rgba8) uniform readonly image2D original;
+layout(set = 0, binding = 1, rgba8) uniform readonly image2D blurred;
+layout(set = 0, binding = 2, rgba8) uniform writeonly image2D dst;
+
+layout(push_constant) uniform UnsharpParams {
+    float amount;
+} params;
+
+void main() {
+    ivec2 pos = ivec2(gl_GlobalInvocationID.xy);
+    if (any(greaterThanEqual(pos, imageSize(original)))) {
+        return;
+    }
+
+    vec4 color = imageLoad(original, pos);
+    vec3 detail = color.rgb - imageLoad(blurred, pos).rgb;
+    imageStore(dst, pos, vec4(clamp(color.rgb + params.amount * detail, 0.0, 1.0), color.a));
+}
@@ -0,0 +1,241 @@
+use std::{fs::File, io::BufWriter, str::FromStr};
+
+use image::{
+    codecs::hdr::HdrEncoder, DynamicImage, ImageBuffer, Rgb, Rgba, Rgba32FImage, RgbaImage,
+};
+use vulkano::{
+    device::physical::PhysicalDevice,
+    format::{Format, FormatFeatures},
+    half::f16,
+};
+
+/// A four channel color format images can be rendered into, parsed from the command line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorFormat {
+    /// `rgba8`, `R8G8B8A8_UNORM`
+    Rgba8,
+    /// `rgba8-srgb`, `R8G8B8A8_SRGB`, which can't be a storage image
+    Rgba8Srgb,
+    /// `rgba16`, `R16G16B16A16_UNORM`
+    Rgba16,
+    /// `rgba16f`, `R16G16B16A16_SFLOAT`
+    Rgba16Float,
+    /// `rgba32f`, `R32G32B32A32_SFLOAT`
+    Rgba32Float,
+}
+
+impl FromStr for ColorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rgba8" => Ok(ColorFormat::Rgba8),
+            "rgba8-srgb" => Ok(ColorFormat::Rgba8Srgb),
+            "rgba16" => Ok(ColorFormat::Rgba16),
+            "rgba16f" => Ok(ColorFormat::Rgba16Float),
+            "rgba32f" => Ok(ColorFormat::Rgba32Float),
+            _ => Err(format!("unknown format '{}'", s)),
+        }
+    }
+}
+
+impl ColorFormat {
+    /// The name it's parsed from.
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorFormat::Rgba8 => "rgba8",
+            ColorFormat::Rgba8Srgb => "rgba8-srgb",
+            ColorFormat::Rgba16 => "rgba16",
+            ColorFormat::Rgba16Float => "rgba16f",
+            ColorFormat::Rgba32Float => "rgba32f",
+        }
+    }
+
+    pub fn format(self) -> Format {
+        match self {
+            ColorFormat::Rgba8 => Format::R8G8B8A8_UNORM,
+            ColorFormat::Rgba8Srgb => Format::R8G8B8A8_SRGB,
+            ColorFormat::Rgba16 => Format::R16G16B16A16_UNORM,
+            ColorFormat::Rgba16Float => Format::R16G16B16A16_SFLOAT,
+            ColorFormat::Rgba32Float => Format::R32G32B32A32_SFLOAT,
+        }
+    }
+
+    /// Bytes each pixel takes up.
+    pub fn pixel_size(self) -> usize {
+        match self {
+            ColorFormat::Rgba8 | ColorFormat::Rgba8Srgb => 4,
+            ColorFormat::Rgba16 | ColorFormat::Rgba16Float => 8,
+            ColorFormat::Rgba32Float => 16,
+        }
+    }
+
+    /// Checks that optimally tiled images of this format support all of `features` on
+    /// `physical_device`, for example `STORAGE_IMAGE` to render into them.
+    pub fn check_support(
+        self,
+        physical_device: &PhysicalDevice,
+        features: FormatFeatures,
+    ) -> Result<(), String> {
+        check_format_support(physical_device, self.format(), features)
+    }
+
+    /// Unpacks tightly packed pixels read back from an image of this format. sRGB colors are
+    /// decoded to linear, like sampling them does.
+    pub fn decode(self, width: u32, height: u32, bytes: &[u8]) -> Rgba32FImage {
+        let channels: Vec<f32> = match self {
+            ColorFormat::Rgba8 => bytes.iter().map(|&c| c as f32 / 255.0).collect(),
+            ColorFormat::Rgba8Srgb => bytes
+                .iter()
+                .enumerate()
+                .map(|(i, &c)| match i % 4 {
+                    3 => c as f32 / 255.0,
+                    _ => srgb_to_linear(c as f32 / 255.0),
+                })
+                .collect(),
+            ColorFormat::Rgba16 => bytes
+                .chunks_exact(2)
+                .map(|c| u16::from_ne_bytes([c[0], c[1]]) as f32 / 65535.0)
+                .collect(),
+            ColorFormat::Rgba16Float => bytes
+                .chunks_exact(2)
+                .map(|c| f16::from_ne_bytes([c[0], c[1]]).to_f32())
+                .collect(),
+            ColorFormat::Rgba32Float => bytes
+                .chunks_exact(4)
+                .map(|c| f32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+                .collect(),
+        };
+        Rgba32FImage::from_raw(width, height, channels).expect("wrong number of bytes for image")
+    }
+
+    /// Packs `image` for copying into an image of this format. Normalized formats clamp to
+    /// [0, 1], and sRGB formats take linear colors.
+    pub fn encode(self, image: &Rgba32FImage) -> Vec<u8> {
+        let channels = image.as_raw().iter().copied();
+        match self {
+            ColorFormat::Rgba8 => channels.map(to_unorm8).collect(),
+            ColorFormat::Rgba8Srgb => channels
+                .enumerate()
+                .map(|(i, c)| match i % 4 {
+                    3 => to_unorm8(c),
+                    _ => to_unorm8(linear_to_srgb(c)),
+                })
+                .collect(),
+            ColorFormat::Rgba16 => channels.flat_map(|c| to_unorm16(c).to_ne_bytes()).collect(),
+            ColorFormat::Rgba16Float => channels
+                .flat_map(|c| f16::from_f32(c).to_ne_bytes())
+                .collect(),
+            ColorFormat::Rgba32Float => channels.flat_map(f32::to_ne_bytes).collect(),
+        }
+    }
+
+    /// Packs the pixels of an image file for copying into an image of this format, like `encode`,
+    /// except sRGB formats take the file as already sRGB encoded, as image files usually are.
+    pub fn encode_file(self, pixels: &DynamicImage) -> Vec<u8> {
+        match self {
+            ColorFormat::Rgba8Srgb => pixels.to_rgba8().into_raw(),
+            _ => self.encode(&pixels.to_rgba32f()),
+        }
+    }
+}
+
+/// Like `ColorFormat::check_support`, but for any format.
+pub fn check_format_support(
+    physical_device: &PhysicalDevice,
+    format: Format,
+    features: FormatFeatures,
+) -> Result<(), String> {
+    let properties = physical_device
+        .format_properties(format)
+        .map_err(|e| format!("could not get properties of {:?}: {}", format, e))?;
+    let missing = features - properties.optimal_tiling_features;
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "the device doesn't support {:?} for {:?} images",
+            missing, format
+        ))
+    }
+}
+
+/// The sRGB transfer function, from an encoded value in [0, 1] to linear.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// The inverse of `srgb_to_linear`, clamping to [0, 1].
+pub fn linear_to_srgb(c: f32) -> f32 {
+    let c = c.clamp(0.0, 1.0);
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn to_unorm8(c: f32) -> u8 {
+    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn to_unorm16(c: f32) -> u16 {
+    (c.clamp(0.0, 1.0) * 65535.0).round() as u16
+}
+
+/// Converts to 8 bits per channel, clamping to [0, 1].
+pub fn to_rgba8(image: &Rgba32FImage) -> RgbaImage {
+    RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+        Rgba(image.get_pixel(x, y).0.map(to_unorm8))
+    })
+}
+
+/// Saves `image` without losing more precision than the file type has to. The extension picks
+/// the file type: `.png` for 16-bit PNG, `.hdr` for Radiance HDR, which drops alpha, `.exr` for
+/// OpenEXR and `.tif` or `.tiff` for 32-bit float TIFF.
+pub fn save_float_image(image: &Rgba32FImage, path: &str) -> Result<(), String> {
+    let create = || {
+        File::create(path)
+            .map(BufWriter::new)
+            .map_err(|e| format!("could not create '{}': {}", path, e))
+    };
+    let extension = path.rsplit_once('.').map(|(_, e)| e.to_lowercase());
+    match extension.as_deref() {
+        Some("png") => {
+            let channels = image.as_raw().iter().map(|&c| to_unorm16(c)).collect();
+            ImageBuffer::<Rgba<u16>, Vec<u16>>::from_raw(image.width(), image.height(), channels)
+                .unwrap()
+                .save(path)
+                .map_err(|e| e.to_string())
+        }
+        Some("hdr") => {
+            let pixels: Vec<Rgb<f32>> = image.pixels().map(|p| Rgb([p[0], p[1], p[2]])).collect();
+            HdrEncoder::new(create()?)
+                .encode(&pixels, image.width() as usize, image.height() as usize)
+                .map_err(|e| e.to_string())
+        }
+        Some("exr") => DynamicImage::ImageRgba32F(image.clone())
+            .save(path)
+            .map_err(|e| e.to_string()),
+        Some("tif" | "tiff") => tiff::encoder::TiffEncoder::new(create()?)
+            .and_then(|mut encoder| {
+                encoder.write_image::<tiff::encoder::colortype::RGBA32Float>(
+                    image.width(),
+                    image.height(),
+                    image.as_raw(),
+                )
+            })
+            .map_err(|e| e.to_string()),
+        _ => {
+            return Err(format!(
+                "don't know how to save a float image to '{}', use .png, .hdr, .exr or .tiff",
+                path
+            ))
+        }
+    }
+    .map_err(|e| format!("could not save '{}': {}", path, e))
+}
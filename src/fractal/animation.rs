@@ -0,0 +1,128 @@
+use std::str::FromStr;
+
+use super::{fixed::Fixed, RenderSettings};
+
+/// How the zoom speeds up and slows down over an animation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl FromStr for Easing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(Easing::Linear),
+            "ease-in" => Ok(Easing::EaseIn),
+            "ease-out" => Ok(Easing::EaseOut),
+            "ease-in-out" => Ok(Easing::EaseInOut),
+            _ => Err(format!("unknown easing '{}'", s)),
+        }
+    }
+}
+
+impl Easing {
+    /// Maps linear progress through the animation, from 0 to 1, to eased progress.
+    pub fn apply(self, t: f64) -> f64 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Where the view is at one end of an animation.
+#[derive(Clone, Debug)]
+pub struct Keyframe {
+    pub center: [f64; 2],
+    /// `center` as decimal text, see `RenderSettings::exact_center`.
+    pub exact_center: Option<[String; 2]>,
+    pub zoom: f64,
+}
+
+impl Keyframe {
+    /// The view `settings` renders.
+    pub fn from_settings(settings: &RenderSettings) -> Self {
+        Self {
+            center: settings.center,
+            exact_center: settings.exact_center.clone(),
+            zoom: settings.zoom,
+        }
+    }
+}
+
+/// A zoom from one view to another over a number of frames.
+#[derive(Clone, Debug)]
+pub struct ZoomAnimation {
+    pub start: Keyframe,
+    pub end: Keyframe,
+    pub frames: u32,
+    pub easing: Easing,
+}
+
+impl ZoomAnimation {
+    /// `base` with the view of frame `frame`. The zoom changes geometrically, so every frame
+    /// magnifies by the same factor before easing, and the center moves so the end's center
+    /// drifts steadily across the screen instead of racing past at deep zooms.
+    pub fn frame(&self, base: &RenderSettings, frame: u32) -> RenderSettings {
+        let t = if self.frames > 1 {
+            frame as f64 / (self.frames - 1) as f64
+        } else {
+            0.0
+        };
+        let t = self.easing.apply(t);
+        let zoom = self.start.zoom * (self.end.zoom / self.start.zoom).powf(t);
+
+        // how much of the start's center is left, on screen it shrinks along with the view
+        let ratio = self.start.zoom / self.end.zoom;
+        let weight = if (ratio - 1.0).abs() < 1e-9 {
+            1.0 - t
+        } else {
+            (self.start.zoom / zoom - ratio) / (1.0 - ratio)
+        };
+        let offset = [0, 1].map(|i| (self.start.center[i] - self.end.center[i]) * weight);
+
+        let mut settings = RenderSettings {
+            center: [0, 1].map(|i| self.end.center[i] + offset[i]),
+            exact_center: None,
+            zoom,
+            ..base.clone()
+        };
+        if self.start.exact_center.is_some() || self.end.exact_center.is_some() {
+            // only the end needs full precision, the offset from it shrinks with the view
+            let limbs = settings.fraction_limbs();
+            let end_text = self
+                .end
+                .exact_center
+                .clone()
+                .unwrap_or_else(|| self.end.center.map(|c| c.to_string()));
+            settings.exact_center = Some([0, 1].map(|i| {
+                match (
+                    Fixed::parse(&end_text[i], limbs),
+                    Fixed::parse(&offset[i].to_string(), limbs),
+                ) {
+                    (Ok(end), Ok(offset)) => end.add(&offset).to_decimal(),
+                    // leave bad text for the renderer to report
+                    _ => end_text[i].clone(),
+                }
+            }));
+        }
+        settings
+    }
+
+    /// The settings of whichever end is zoomed in furthest, which decide the precision the
+    /// whole animation needs.
+    pub fn deepest(&self, base: &RenderSettings) -> RenderSettings {
+        if self.end.zoom >= self.start.zoom {
+            self.frame(base, self.frames.saturating_sub(1))
+        } else {
+            self.frame(base, 0)
+        }
+    }
+}
@@ -0,0 +1,107 @@
+#version 460
+
+// Every invocation picks a random point c and iterates it like the mandelbrot set. If its orbit
+// escapes, the orbit is iterated again and every point it visits before escaping counts as a
+// hit on the pixel it lands in, for each channel whose iteration limit it escaped within.
+
+layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+// width * height hits for red, then green, then blue
+layout(set = 0, binding = 0) buffer Hits {
+    uint hits[];
+};
+
+layout(push_constant) uniform AccumulateParams {
+    // point in the complex plane at the middle of the image
+    vec2 center;
+    // size of a pixel in the complex plane
+    float scale;
+    uint width;
+    uint height;
+    // iterations the orbits counted by red, green and blue have to escape within
+    uint limits[3];
+    uint seed;
+    // index of this dispatch's first sample among all of the render's samples
+    uint first_sample;
+    uint sample_count;
+} params;
+
+// orbits never come back once they leave this circle
+const float RADIUS_SQUARED = 4.0;
+
+// samples are taken from the square around that circle
+const vec2 SAMPLE_MIN = vec2(-2.0);
+const float SAMPLE_SIZE = 4.0;
+
+// lowbias32 by Chris Wellons, the same as `hash` in buddhabrot.rs
+uint hash(uint x) {
+    x ^= x >> 16;
+    x *= 0x7feb352du;
+    x ^= x >> 15;
+    x *= 0x846ca68bu;
+    x ^= x >> 16;
+    return x;
+}
+
+// whether c is in the main cardioid or the period 2 bulb, which never escape
+bool in_main_bulbs(vec2 c) {
+    vec2 d = c - vec2(0.25, 0.0);
+    float q = dot(d, d);
+    if (q * (q + d.x) <= 0.25 * c.y * c.y) {
+        return true;
+    }
+    vec2 e = c + vec2(1.0, 0.0);
+    return dot(e, e) <= 0.0625;
+}
+
+vec2 step_orbit(vec2 z, vec2 c) {
+    return vec2(z.x * z.x - z.y * z.y, 2.0 * z.x * z.y) + c;
+}
+
+void main() {
+    uint index = gl_GlobalInvocationID.x;
+    if (index >= params.sample_count) {
+        return;
+    }
+
+    uint h = hash((params.first_sample + index) ^ hash(params.seed));
+    vec2 c = SAMPLE_MIN + SAMPLE_SIZE * vec2(h >> 8, hash(h) >> 8) / 16777216.0;
+    if (in_main_bulbs(c)) {
+        return;
+    }
+
+    // find out if and when it escapes
+    uint max_limit = max(params.limits[0], max(params.limits[1], params.limits[2]));
+    vec2 z = vec2(0.0);
+    uint n = 0;
+    while (n < max_limit && dot(z, z) <= RADIUS_SQUARED) {
+        z = step_orbit(z, c);
+        n++;
+    }
+    if (dot(z, z) <= RADIUS_SQUARED) {
+        return;
+    }
+
+    // go over the orbit again, counting the points before it escaped
+    bool counted[3] = bool[3](
+        n <= params.limits[0],
+        n <= params.limits[1],
+        n <= params.limits[2]
+    );
+    uint pixels = params.width * params.height;
+    vec2 size = vec2(params.width, params.height);
+    z = vec2(0.0);
+    for (uint i = 1; i < n; i++) {
+        z = step_orbit(z, c);
+        vec2 pos = (z - params.center) / params.scale + size / 2.0;
+        if (any(lessThan(pos, vec2(0.0))) || any(greaterThanEqual(pos, size))) {
+            continue;
+        }
+        uint pixel = uint(pos.y) * params.width + uint(pos.x);
+        for (uint k = 0; k < 3; k++) {
+            if (counted[k]) {
+                atomicAdd(hits[k * pixels + pixel], 1u);
+            }
+        }
+    }
+}
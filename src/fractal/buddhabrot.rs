@@ -0,0 +1,448 @@
+use std::{str::FromStr, sync::Arc};
+
+use image::RgbaImage;
+use vulkano::{
+    buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer},
+    command_buffer::CopyBufferInfo,
+    descriptor_set::WriteDescriptorSet,
+    image::{view::ImageView, Image, ImageUsage},
+    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter},
+    pipeline::{ComputePipeline, Pipeline, PipelineBindPoint},
+};
+
+use crate::{
+    context::Context,
+    transfer::{create_rgba8_image, download_rgba8},
+};
+
+/// Samples each dispatch iterates. Renders are split into dispatches of this many, each
+/// submitted on its own, so a render with long orbits doesn't trip the driver's timeout.
+pub const SAMPLES_PER_DISPATCH: u32 = 1 << 20;
+
+/// Orbits never come back once they leave this circle, the same as in buddhabrot.glsl.
+const RADIUS_SQUARED: f32 = 4.0;
+
+/// Everything that decides what a Buddhabrot looks like. The view works the same as in
+/// `RenderSettings`.
+#[derive(Clone, Debug)]
+pub struct BuddhabrotSettings {
+    pub width: u32,
+    pub height: u32,
+    /// Point in the complex plane at the middle of the image.
+    pub center: [f64; 2],
+    /// Magnification, where 1 shows 2 units of the plane vertically.
+    pub zoom: f64,
+    pub limits: IterationLimits,
+    /// Random points iterated. More make a smoother image.
+    pub samples: u32,
+    /// Picks the random points, so renders with the same seed come out the same.
+    pub seed: u32,
+    /// Hits are scaled by the brightest pixel's and raised to `1 / gamma`, so higher values
+    /// bring out fainter orbits.
+    pub gamma: f32,
+}
+
+impl Default for BuddhabrotSettings {
+    fn default() -> Self {
+        Self {
+            width: 1024,
+            height: 1024,
+            center: [-0.4, 0.0],
+            zoom: 0.75,
+            limits: IterationLimits([2000, 200, 20]),
+            samples: 1 << 24,
+            seed: 0,
+            gamma: 2.0,
+        }
+    }
+}
+
+impl BuddhabrotSettings {
+    /// Size of a pixel in the complex plane.
+    pub fn scale(&self) -> f64 {
+        2.0 / (self.zoom * self.height as f64)
+    }
+
+    fn accumulate_params(&self, first_sample: u32, sample_count: u32) -> AccumulateParams {
+        AccumulateParams {
+            center: self.center.map(|c| c as f32),
+            scale: self.scale() as f32,
+            width: self.width,
+            height: self.height,
+            limits: self.limits.0,
+            seed: self.seed,
+            first_sample,
+            sample_count,
+        }
+    }
+}
+
+/// Iterations the orbits counted by the red, green and blue channels have to escape within,
+/// parsed from the command line as `<n>` for a grayscale Buddhabrot or `<r>,<g>,<b>` for a
+/// Nebulabrot.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IterationLimits(pub [u32; 3]);
+
+impl FromStr for IterationLimits {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let limits = s
+            .split(',')
+            .map(|l| l.trim().parse::<u32>().ok().filter(|&l| l > 0))
+            .collect::<Option<Vec<u32>>>()
+            .ok_or_else(|| format!("iteration limits '{}' must be positive integers", s))?;
+        match limits[..] {
+            [n] => Ok(IterationLimits([n; 3])),
+            [r, g, b] => Ok(IterationLimits([r, g, b])),
+            _ => Err(format!(
+                "give one iteration limit or one for each of red, green and blue, not '{}'",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(BufferContents, Clone, Copy)]
+#[repr(C)]
+struct AccumulateParams {
+    center: [f32; 2],
+    scale: f32,
+    width: u32,
+    height: u32,
+    limits: [u32; 3],
+    seed: u32,
+    first_sample: u32,
+    sample_count: u32,
+}
+
+#[derive(BufferContents, Clone, Copy)]
+#[repr(C)]
+struct PeakParams {
+    pixels: u32,
+}
+
+#[derive(BufferContents, Clone, Copy)]
+#[repr(C)]
+struct ToneMapParams {
+    exponent: f32,
+}
+
+/// The pipelines, hit counts and output image for rendering Buddhabrots of one size. Random
+/// points are iterated on the GPU with their orbits' hits added up atomically, then a second
+/// pass tone-maps the hits into an image.
+pub struct BuddhabrotRenderer {
+    width: u32,
+    height: u32,
+    accumulate_pipeline: Arc<ComputePipeline>,
+    peaks_pipeline: Arc<ComputePipeline>,
+    tone_map_pipeline: Arc<ComputePipeline>,
+    /// `width * height` hits for red, then green, then blue.
+    hits_buffer: Subbuffer<[u32]>,
+    /// Most hits of any pixel in each channel.
+    peaks_buffer: Subbuffer<[u32]>,
+    image: Arc<Image>,
+}
+
+impl BuddhabrotRenderer {
+    /// Sets up for renders of the size in `settings`.
+    pub fn new(context: &Context, settings: &BuddhabrotSettings) -> Result<Self, String> {
+        let max_size = context
+            .device
+            .physical_device()
+            .properties()
+            .max_image_dimension2_d;
+        if settings.width.max(settings.height) > max_size {
+            return Err(format!(
+                "{}x{} doesn't fit in the device's largest image of {}x{}",
+                settings.width, settings.height, max_size, max_size
+            ));
+        }
+        let pixels = settings.width as u64 * settings.height as u64;
+        if pixels * 3 > u32::MAX as u64 {
+            return Err(format!(
+                "{}x{} has too many pixels to count hits for",
+                settings.width, settings.height
+            ));
+        }
+
+        // setup compute pipelines
+        let accumulate_pipeline = context.compute_pipeline(
+            shaders::accumulate::load(context.device.clone())
+                .expect("failed to create shader module"),
+        );
+        let peaks_pipeline = context.compute_pipeline(
+            shaders::peaks::load(context.device.clone()).expect("failed to create shader module"),
+        );
+        let tone_map_pipeline = context.compute_pipeline(
+            shaders::tone_map::load(context.device.clone())
+                .expect("failed to create shader module"),
+        );
+
+        // setup hit counts, which start every render cleared, and image output
+        let device_buffer = |len: u64| {
+            Buffer::new_slice::<u32>(
+                context.memory_allocator.clone(),
+                BufferCreateInfo {
+                    usage: BufferUsage::STORAGE_BUFFER
+                        | BufferUsage::TRANSFER_DST
+                        | BufferUsage::TRANSFER_SRC,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                    ..Default::default()
+                },
+                len,
+            )
+            .expect("could not create buffer")
+        };
+        let image = create_rgba8_image(
+            context,
+            settings.width,
+            settings.height,
+            ImageUsage::STORAGE | ImageUsage::TRANSFER_SRC,
+        );
+
+        Ok(Self {
+            width: settings.width,
+            height: settings.height,
+            accumulate_pipeline,
+            peaks_pipeline,
+            tone_map_pipeline,
+            hits_buffer: device_buffer(pixels * 3),
+            peaks_buffer: device_buffer(3),
+            image,
+        })
+    }
+
+    /// Iterates the samples of `settings` and adds up their hits, replacing the last render's.
+    /// Its size is the one the renderer was created with, whatever `settings` says.
+    pub fn accumulate(&self, context: &Context, settings: &BuddhabrotSettings) {
+        let descriptor_set = context.descriptor_set(
+            &self.accumulate_pipeline,
+            [WriteDescriptorSet::buffer(0, self.hits_buffer.clone())],
+        );
+        let settings = BuddhabrotSettings {
+            width: self.width,
+            height: self.height,
+            ..settings.clone()
+        };
+
+        let mut builder = context.command_buffer_builder();
+        builder
+            .fill_buffer(self.hits_buffer.clone(), 0)
+            .expect("failed to clear hits");
+        for first_sample in (0..settings.samples).step_by(SAMPLES_PER_DISPATCH as usize) {
+            let sample_count = SAMPLES_PER_DISPATCH.min(settings.samples - first_sample);
+            builder
+                .bind_pipeline_compute(self.accumulate_pipeline.clone())
+                .expect("failed to bind pipeline")
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Compute,
+                    self.accumulate_pipeline.layout().clone(),
+                    0,
+                    descriptor_set.clone(),
+                )
+                .expect("failed to bind descriptor set")
+                .push_constants(
+                    self.accumulate_pipeline.layout().clone(),
+                    0,
+                    settings.accumulate_params(first_sample, sample_count),
+                )
+                .expect("failed to push accumulate parameters")
+                .dispatch([sample_count.div_ceil(64), 1, 1])
+                .expect("failed to dispatch work groups");
+            context.execute(builder);
+            builder = context.command_buffer_builder();
+        }
+        context.execute(builder);
+    }
+
+    /// The hits of the last `accumulate`, `width * height` for red, then green, then blue.
+    pub fn hits(&self, context: &Context) -> Vec<u32> {
+        let readback_buffer = Buffer::new_slice::<u32>(
+            context.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            self.hits_buffer.len(),
+        )
+        .expect("could not create buffer");
+
+        let mut builder = context.command_buffer_builder();
+        builder
+            .copy_buffer(CopyBufferInfo::buffers(
+                self.hits_buffer.clone(),
+                readback_buffer.clone(),
+            ))
+            .expect("failed to copy hits");
+        context.execute(builder);
+
+        let buf_content = readback_buffer.read().expect("could not read buffer");
+        buf_content.to_vec()
+    }
+
+    /// Renders `settings` and reads the image back.
+    pub fn render(&self, context: &Context, settings: &BuddhabrotSettings) -> RgbaImage {
+        self.accumulate(context, settings);
+
+        // find the brightest pixels, then scale everything by them
+        let pixels = self.width * self.height;
+        let peaks_set = context.descriptor_set(
+            &self.peaks_pipeline,
+            [
+                WriteDescriptorSet::buffer(0, self.hits_buffer.clone()),
+                WriteDescriptorSet::buffer(1, self.peaks_buffer.clone()),
+            ],
+        );
+        let image_view =
+            ImageView::new_default(self.image.clone()).expect("could not create image view");
+        let tone_map_set = context.descriptor_set(
+            &self.tone_map_pipeline,
+            [
+                WriteDescriptorSet::image_view(0, image_view),
+                WriteDescriptorSet::buffer(1, self.hits_buffer.clone()),
+                WriteDescriptorSet::buffer(2, self.peaks_buffer.clone()),
+            ],
+        );
+        let mut builder = context.command_buffer_builder();
+        builder
+            .fill_buffer(self.peaks_buffer.clone(), 0)
+            .expect("failed to clear peaks")
+            .bind_pipeline_compute(self.peaks_pipeline.clone())
+            .expect("failed to bind pipeline")
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.peaks_pipeline.layout().clone(),
+                0,
+                peaks_set,
+            )
+            .expect("failed to bind descriptor set")
+            .push_constants(
+                self.peaks_pipeline.layout().clone(),
+                0,
+                PeakParams { pixels },
+            )
+            .expect("failed to push peak parameters")
+            .dispatch([pixels.div_ceil(64), 1, 1])
+            .expect("failed to dispatch work groups")
+            .bind_pipeline_compute(self.tone_map_pipeline.clone())
+            .expect("failed to bind pipeline")
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.tone_map_pipeline.layout().clone(),
+                0,
+                tone_map_set,
+            )
+            .expect("failed to bind descriptor set")
+            .push_constants(
+                self.tone_map_pipeline.layout().clone(),
+                0,
+                ToneMapParams {
+                    exponent: 1.0 / settings.gamma,
+                },
+            )
+            .expect("failed to push tone map parameters")
+            .dispatch([self.width.div_ceil(8), self.height.div_ceil(8), 1])
+            .expect("failed to dispatch work groups");
+        context.execute(builder);
+
+        download_rgba8(context, self.image.clone())
+    }
+}
+
+/// Adds up the hits of `settings` on the CPU, the same way buddhabrot.glsl does. Meant for
+/// checking the GPU against with few samples, it's far too slow for real renders.
+pub fn accumulate_cpu(settings: &BuddhabrotSettings) -> Vec<u32> {
+    let params = settings.accumulate_params(0, settings.samples);
+    let [width, height] = [params.width, params.height];
+    let pixels = (width * height) as usize;
+    let size = [width as f32, height as f32];
+    let max_limit = *params.limits.iter().max().unwrap();
+    let step = |[x, y]: [f32; 2], [cx, cy]: [f32; 2]| [x * x - y * y + cx, 2.0 * x * y + cy];
+    let escaped = |[x, y]: [f32; 2]| x * x + y * y > RADIUS_SQUARED;
+
+    let mut hits = vec![0; pixels * 3];
+    for index in 0..params.sample_count {
+        let h = hash(index ^ hash(params.seed));
+        let c = [h, hash(h)].map(|h| -2.0 + 4.0 * (h >> 8) as f32 / 16777216.0);
+        if in_main_bulbs(c) {
+            continue;
+        }
+
+        let mut z = [0.0, 0.0];
+        let mut n = 0;
+        while n < max_limit && !escaped(z) {
+            z = step(z, c);
+            n += 1;
+        }
+        if !escaped(z) {
+            continue;
+        }
+
+        let counted = params.limits.map(|limit| n <= limit);
+        z = [0.0, 0.0];
+        for _ in 1..n {
+            z = step(z, c);
+            let pos = [0, 1].map(|i| (z[i] - params.center[i]) / params.scale + size[i] / 2.0);
+            if pos[0] < 0.0 || pos[1] < 0.0 || pos[0] >= size[0] || pos[1] >= size[1] {
+                continue;
+            }
+            let pixel = pos[1] as usize * width as usize + pos[0] as usize;
+            for k in 0..3 {
+                if counted[k] {
+                    hits[k * pixels + pixel] += 1;
+                }
+            }
+        }
+    }
+    hits
+}
+
+/// Whether `c` is in the main cardioid or the period 2 bulb, which never escape, the same as
+/// `in_main_bulbs` in buddhabrot.glsl.
+fn in_main_bulbs([x, y]: [f32; 2]) -> bool {
+    let dx = x - 0.25;
+    let q = dx * dx + y * y;
+    q * (q + dx) <= 0.25 * y * y || (x + 1.0) * (x + 1.0) + y * y <= 0.0625
+}
+
+/// lowbias32 by Chris Wellons, the same as `hash` in buddhabrot.glsl.
+fn hash(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846ca68b);
+    x ^= x >> 16;
+    x
+}
+
+mod shaders {
+    pub mod accumulate {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/fractal/buddhabrot.glsl"
+        }
+    }
+
+    pub mod peaks {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/fractal/peaks.glsl"
+        }
+    }
+
+    pub mod tone_map {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/fractal/tonemap.glsl"
+        }
+    }
+}
@@ -0,0 +1,35 @@
+#version 460
+
+layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+#ifdef UNFORMATTED
+// the images' format is only known at runtime, which needs shaderStorageImageReadWithoutFormat
+// and shaderStorageImageWriteWithoutFormat
+#extension GL_EXT_shader_image_load_formatted : require
+layout(set = 0, binding = 0) uniform readonly image2D src;
+layout(set = 0, binding = 1) uniform writeonly image2D dst;
+#else
+layout(set = 0, binding = 0, rgba8) uniform readonly image2D src;
+layout(set = 0, binding = 1, rgba8) uniform writeonly image2D dst;
+#endif
+
+layout(push_constant) uniform DownsampleParams {
+    // src holds this many pixels across and down for every pixel of dst
+    uint factor;
+} params;
+
+void main() {
+    ivec2 pos = ivec2(gl_GlobalInvocationID.xy);
+    if (any(greaterThanEqual(pos, imageSize(dst)))) {
+        return;
+    }
+
+    int factor = int(params.factor);
+    vec4 sum = vec4(0.0);
+    for (int y = 0; y < factor; y++) {
+        for (int x = 0; x < factor; x++) {
+            sum += imageLoad(src, pos * factor + ivec2(x, y));
+        }
+    }
+    imageStore(dst, pos, sum / float(factor * factor));
+}
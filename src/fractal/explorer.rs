@@ -0,0 +1,195 @@
+use std::sync::Arc;
+
+use image::RgbaImage;
+use vulkano::{
+    command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer},
+    image::Image,
+};
+
+use crate::context::Context;
+
+use super::{
+    palette::{Palette, BUILTIN_NAMES},
+    FractalRenderer, Precision, RenderSettings,
+};
+
+/// Fewest iterations `scale_iterations` goes down to.
+pub const MIN_ITERATIONS: u32 = 16;
+
+/// The view of an interactive viewer and the input that moves it around, kept apart from any
+/// window so it can be driven headlessly. Nothing is rendered until the view changes.
+pub struct Explorer {
+    settings: RenderSettings,
+    /// The view it started with, for `reset`.
+    initial: RenderSettings,
+    /// The view of the last render that worked, which a failed one falls back to.
+    rendered: Option<RenderSettings>,
+    renderer: Option<FractalRenderer>,
+    /// Index into `BUILTIN_NAMES` of the palette `next_palette` moved to last.
+    palette_index: usize,
+    changed: bool,
+}
+
+impl Explorer {
+    /// Starts at the view of `settings`. The center is tracked at full precision from then on,
+    /// so zooms can go as deep as perturbation allows.
+    pub fn new(mut settings: RenderSettings) -> Self {
+        if settings.exact_center.is_none() {
+            settings.exact_center = Some(settings.center.map(|c| c.to_string()));
+        }
+        Self {
+            initial: settings.clone(),
+            settings,
+            rendered: None,
+            renderer: None,
+            palette_index: 0,
+            changed: true,
+        }
+    }
+
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    /// Whether the view changed since the last render.
+    pub fn needs_render(&self) -> bool {
+        self.changed
+    }
+
+    /// The precision of the last render.
+    pub fn precision(&self) -> Option<Precision> {
+        self.renderer.as_ref().map(FractalRenderer::precision)
+    }
+
+    /// Moves the view along with a drag of `(dx, dy)` pixels.
+    pub fn pan(&mut self, dx: f64, dy: f64) {
+        if dx == 0.0 && dy == 0.0 {
+            return;
+        }
+        let scale = self.settings.scale();
+        self.settings.move_center([-dx * scale, -dy * scale]);
+        self.changed = true;
+    }
+
+    /// Magnifies the view by `factor`, keeping the point under pixel position `(x, y)` where it
+    /// is.
+    pub fn zoom_at(&mut self, x: f64, y: f64, factor: f64) {
+        if factor == 1.0 {
+            return;
+        }
+        let from_center = [
+            x - self.settings.width as f64 / 2.0,
+            y - self.settings.height as f64 / 2.0,
+        ];
+        let shift = self.settings.scale() * (1.0 - 1.0 / factor);
+        self.settings
+            .move_center(from_center.map(|offset| offset * shift));
+        self.settings.zoom *= factor;
+        self.changed = true;
+    }
+
+    /// Multiplies the iteration limit by `factor`, down to `MIN_ITERATIONS`.
+    pub fn scale_iterations(&mut self, factor: f64) {
+        let iterations =
+            ((self.settings.iterations as f64 * factor).round() as u32).max(MIN_ITERATIONS);
+        if iterations != self.settings.iterations {
+            self.settings.iterations = iterations;
+            self.changed = true;
+        }
+    }
+
+    /// Switches to the next built-in palette.
+    pub fn next_palette(&mut self) {
+        self.palette_index = (self.palette_index + 1) % BUILTIN_NAMES.len();
+        self.settings.palette = Palette::builtin(BUILTIN_NAMES[self.palette_index]).unwrap();
+        // the palette is fixed when the renderer is created
+        self.renderer = None;
+        self.changed = true;
+    }
+
+    /// Changes the size of the image, keeping the center and zoom.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if [width, height] != [self.settings.width, self.settings.height] {
+            self.settings.width = width;
+            self.settings.height = height;
+            self.renderer = None;
+            self.changed = true;
+        }
+    }
+
+    /// Goes back to the view it started with, at the current size.
+    pub fn reset(&mut self) {
+        self.settings = RenderSettings {
+            width: self.settings.width,
+            height: self.settings.height,
+            ..self.initial.clone()
+        };
+        self.palette_index = 0;
+        self.renderer = None;
+        self.changed = true;
+    }
+
+    /// Records rendering the view into `image()` if it changed since the last render. When
+    /// the view can't be rendered, for example because it's zoomed in too deep for the
+    /// fractal, it goes back to the last view that could. The size and palette are kept.
+    pub fn record(
+        &mut self,
+        context: &Context,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) -> Result<(), String> {
+        if !self.changed {
+            return Ok(());
+        }
+        match self.try_record(context, builder) {
+            Ok(()) => {
+                self.rendered = Some(self.settings.clone());
+                self.changed = false;
+                Ok(())
+            }
+            Err(e) => {
+                if let Some(rendered) = &self.rendered {
+                    self.settings.center = rendered.center;
+                    self.settings.exact_center = rendered.exact_center.clone();
+                    self.settings.zoom = rendered.zoom;
+                    self.settings.iterations = rendered.iterations;
+                    self.changed = self.renderer.is_none();
+                }
+                Err(e)
+            }
+        }
+    }
+
+    fn try_record(
+        &mut self,
+        context: &Context,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) -> Result<(), String> {
+        // auto precision changes with the zoom
+        let supports_double = context.device.enabled_features().shader_float64;
+        let precision = self
+            .settings
+            .precision
+            .resolve(self.settings.scale(), supports_double);
+        if self.precision() != Some(precision) {
+            self.renderer = Some(FractalRenderer::new(context, &self.settings)?);
+        }
+        self.renderer
+            .as_ref()
+            .unwrap()
+            .record(context, builder, &self.settings)
+    }
+
+    /// The image holding the last render, see `FractalRenderer::image`.
+    pub fn image(&self) -> Option<Arc<Image>> {
+        self.renderer.as_ref().map(FractalRenderer::image)
+    }
+
+    /// Renders the view if it changed and reads the latest render back, for running without a
+    /// window.
+    pub fn render(&mut self, context: &Context) -> Result<RgbaImage, String> {
+        let mut builder = context.command_buffer_builder();
+        self.record(context, &mut builder)?;
+        context.execute(builder);
+        Ok(self.renderer.as_ref().unwrap().download(context))
+    }
+}
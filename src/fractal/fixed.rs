@@ -0,0 +1,232 @@
+use std::cmp::Ordering;
+
+/// A signed fixed-point number with a 32-bit integer part and any number of 32-bit fractional
+/// limbs, for coordinates too precise for an `f64`.
+#[derive(Clone, Debug)]
+pub struct Fixed {
+    negative: bool,
+    /// Little-endian, with the integer part last.
+    limbs: Vec<u32>,
+}
+
+impl Fixed {
+    pub fn zero(fraction_limbs: usize) -> Self {
+        Self {
+            negative: false,
+            limbs: vec![0; fraction_limbs + 1],
+        }
+    }
+
+    /// Parses a decimal like `-0.7436438870371587047521915`, keeping as many fractional bits
+    /// as fit in `fraction_limbs` limbs.
+    pub fn parse(text: &str, fraction_limbs: usize) -> Result<Self, String> {
+        let bad_number = || format!("'{}' is not a decimal number", text);
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if integer.is_empty() && fraction.is_empty()
+            || !integer
+                .bytes()
+                .chain(fraction.bytes())
+                .all(|b| b.is_ascii_digit())
+        {
+            return Err(bad_number());
+        }
+
+        let mut number = Self::zero(fraction_limbs);
+        // build the fraction from its last digit up, so each step is one small division
+        for digit in fraction.bytes().rev() {
+            *number.limbs.last_mut().unwrap() = (digit - b'0') as u32;
+            number.divide_small(10);
+        }
+        let integer = if integer.is_empty() {
+            0
+        } else {
+            integer.parse::<u32>().map_err(|_| bad_number())?
+        };
+        *number.limbs.last_mut().unwrap() = integer;
+        number.negative = negative;
+        Ok(number)
+    }
+
+    /// Decimal text with enough digits to hold every fractional bit. The last digit is rounded
+    /// away from zero, so parsing the text gives back the same number.
+    pub fn to_decimal(&self) -> String {
+        let mut fraction = self.clone();
+        let mut integer = fraction.limbs.pop().unwrap();
+        fraction.limbs.push(0);
+        // each bit needs log10(2) decimal digits
+        let count = ((self.limbs.len() - 1) * 32 * 30103).div_ceil(100_000) + 1;
+        let mut digits = Vec::with_capacity(count);
+        for _ in 0..count {
+            fraction.multiply_small(10);
+            let digit = fraction.limbs.last_mut().unwrap();
+            digits.push(*digit as u8);
+            *digit = 0;
+        }
+        // parsing truncates, so whatever is left over rounds up
+        let mut carry = fraction.limbs.iter().any(|&limb| limb != 0);
+        for digit in digits.iter_mut().rev() {
+            if !carry {
+                break;
+            }
+            *digit = (*digit + 1) % 10;
+            carry = *digit == 0;
+        }
+        if carry {
+            integer = integer.wrapping_add(1);
+        }
+        let digits: String = digits
+            .iter()
+            .map(|&digit| char::from(b'0' + digit))
+            .collect();
+        format!(
+            "{}{}.{}",
+            if self.negative { "-" } else { "" },
+            integer,
+            digits
+        )
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = self
+            .limbs
+            .iter()
+            .rev()
+            .take(4)
+            .enumerate()
+            .map(|(i, &limb)| limb as f64 * 2f64.powi(-32 * i as i32))
+            .sum::<f64>();
+        if self.negative {
+            -magnitude
+        } else {
+            magnitude
+        }
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        if self.negative == other.negative {
+            return Self {
+                negative: self.negative,
+                limbs: add_magnitudes(&self.limbs, &other.limbs),
+            };
+        }
+        match compare_magnitudes(&self.limbs, &other.limbs) {
+            Ordering::Less => Self {
+                negative: other.negative,
+                limbs: subtract_magnitudes(&other.limbs, &self.limbs),
+            },
+            _ => Self {
+                negative: self.negative,
+                limbs: subtract_magnitudes(&self.limbs, &other.limbs),
+            },
+        }
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&other.neg())
+    }
+
+    pub fn neg(&self) -> Self {
+        Self {
+            negative: !self.negative,
+            limbs: self.limbs.clone(),
+        }
+    }
+
+    /// Product truncated to the precision of `self`. The integer part wraps if it overflows.
+    pub fn mul(&self, other: &Self) -> Self {
+        let len = self.limbs.len();
+        let mut product = vec![0u64; len * 2];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let total = product[i + j] + a as u64 * b as u64 + carry;
+                product[i + j] = total & 0xffff_ffff;
+                carry = total >> 32;
+            }
+            product[i + other.limbs.len()] += carry;
+        }
+        // both factors carry `len - 1` fractional limbs, so the product carries twice that
+        let limbs = product[len - 1..2 * len - 1]
+            .iter()
+            .map(|&limb| limb as u32)
+            .collect();
+        Self {
+            negative: self.negative != other.negative,
+            limbs,
+        }
+    }
+
+    fn multiply_small(&mut self, factor: u32) {
+        let mut carry = 0u64;
+        for limb in self.limbs.iter_mut() {
+            let value = *limb as u64 * factor as u64 + carry;
+            *limb = value as u32;
+            carry = value >> 32;
+        }
+    }
+
+    fn divide_small(&mut self, divisor: u32) {
+        let mut remainder = 0u64;
+        for limb in self.limbs.iter_mut().rev() {
+            let value = (remainder << 32) | *limb as u64;
+            *limb = (value / divisor as u64) as u32;
+            remainder = value % divisor as u64;
+        }
+    }
+}
+
+fn compare_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    a.iter().rev().cmp(b.iter().rev())
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut carry = 0u64;
+    a.iter()
+        .zip(b)
+        .map(|(&a, &b)| {
+            let total = a as u64 + b as u64 + carry;
+            carry = total >> 32;
+            total as u32
+        })
+        .collect()
+}
+
+/// `a - b`, where `a` is at least as large as `b`.
+fn subtract_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut borrow = 0i64;
+    a.iter()
+        .zip(b)
+        .map(|(&a, &b)| {
+            let mut difference = a as i64 - b as i64 - borrow;
+            borrow = (difference < 0) as i64;
+            if difference < 0 {
+                difference += 1 << 32;
+            }
+            difference as u32
+        })
+        .collect()
+}
+
+/// Iterates `z = z^2 + c` for the point `c` at full precision, returning every `z` from
+/// `z_0 = 0` up to the first one outside `bailout` or `max_iterations` is reached.
+pub fn reference_orbit(c: &[Fixed; 2], max_iterations: u32, bailout: f64) -> Vec<[f64; 2]> {
+    let fraction_limbs = c[0].limbs.len() - 1;
+    let mut z = [Fixed::zero(fraction_limbs), Fixed::zero(fraction_limbs)];
+    let mut orbit = vec![[0.0, 0.0]];
+    for _ in 0..max_iterations {
+        let x_squared = z[0].mul(&z[0]);
+        let y_squared = z[1].mul(&z[1]);
+        let xy = z[0].mul(&z[1]);
+        z = [x_squared.sub(&y_squared).add(&c[0]), xy.add(&xy).add(&c[1])];
+        let point = [z[0].to_f64(), z[1].to_f64()];
+        orbit.push(point);
+        if point[0] * point[0] + point[1] * point[1] > bailout * bailout {
+            break;
+        }
+    }
+    orbit
+}
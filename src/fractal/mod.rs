@@ -0,0 +1,1021 @@
+use std::{str::FromStr, sync::Arc};
+
+use image::{Rgba, Rgba32FImage, RgbaImage};
+use vulkano::{
+    buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer},
+    command_buffer::{AutoCommandBufferBuilder, CopyImageToBufferInfo, PrimaryAutoCommandBuffer},
+    descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
+    device::Device,
+    format::FormatFeatures,
+    image::{view::ImageView, Image, ImageUsage},
+    memory::allocator::{AllocationCreateInfo, MemoryAllocator, MemoryTypeFilter},
+    pipeline::{ComputePipeline, Pipeline, PipelineBindPoint},
+    shader::ShaderModule,
+    Validated, VulkanError,
+};
+
+use crate::{
+    context::Context,
+    format::{to_rgba8, ColorFormat},
+    transfer::create_image,
+};
+
+use fixed::{reference_orbit, Fixed};
+use supersample::{Downsampler, Supersampling};
+
+pub mod animation;
+pub mod buddhabrot;
+pub mod explorer;
+pub mod fixed;
+pub mod palette;
+pub mod progressive;
+pub mod supersample;
+pub mod tiled;
+
+pub use palette::Palette;
+
+/// Escape radius of the shader, which the reference orbit and the CPU reference stop at too.
+const BAILOUT: f64 = 256.0;
+
+/// Pixel sizes below which each precision runs out of bits to tell pixels apart.
+const SINGLE_PRECISION_MIN_SCALE: f64 = 1e-6;
+const DOUBLE_PRECISION_MIN_SCALE: f64 = 1e-14;
+
+/// Below this pixel size single-precision perturbation deltas underflow.
+const SINGLE_PRECISION_MIN_DELTA_SCALE: f64 = 1e-30;
+
+/// How close to zero, relative to the reference orbit, a pixel may get before the perturbation
+/// shader treats it as glitched and rebases it.
+const GLITCH_TOLERANCE: f32 = 1e-3;
+
+/// Highest exponent a multibrot may have.
+pub const MAX_POWER: u32 = 16;
+
+/// An escape-time fractal, parsed from `name` or `name:arg,arg` on the command line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fractal {
+    /// `mandelbrot`, `z^2 + c` starting from zero
+    Mandelbrot,
+    /// `julia:<x>,<y>`, `z^2 + c` for a fixed `c`, starting from the pixel's point
+    Julia { c: [f64; 2] },
+    /// `burning-ship`, `(|re z| + i |im z|)^2 + c`
+    BurningShip,
+    /// `tricorn`, `conj(z)^2 + c`
+    Tricorn,
+    /// `multibrot:<power>`, `z^power + c`
+    Multibrot { power: u32 },
+}
+
+impl FromStr for Fractal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, args) = s.split_once(':').unwrap_or((s, ""));
+        let args = args
+            .split(',')
+            .filter(|a| !a.is_empty())
+            .map(|a| {
+                a.trim()
+                    .parse::<f64>()
+                    .map_err(|_| format!("invalid argument '{}' for fractal '{}'", a, name))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let fractal = match (name, args.as_slice()) {
+            ("mandelbrot", &[]) => Fractal::Mandelbrot,
+            ("julia", &[x, y]) => Fractal::Julia { c: [x, y] },
+            ("burning-ship", &[]) => Fractal::BurningShip,
+            ("tricorn", &[]) => Fractal::Tricorn,
+            ("multibrot", &[power]) => {
+                if power.fract() != 0.0 || !(2.0..=MAX_POWER as f64).contains(&power) {
+                    return Err(format!(
+                        "power of fractal 'multibrot' must be a whole number from 2 to {}",
+                        MAX_POWER
+                    ));
+                }
+                Fractal::Multibrot {
+                    power: power as u32,
+                }
+            }
+            ("mandelbrot" | "julia" | "burning-ship" | "tricorn" | "multibrot", _) => {
+                return Err(format!(
+                    "wrong number of arguments for fractal '{}' ({} given)",
+                    name,
+                    args.len()
+                ))
+            }
+            _ => return Err(format!("unknown fractal '{}'", name)),
+        };
+        Ok(fractal)
+    }
+}
+
+impl Fractal {
+    /// Value of the shader's `fractal` push constant.
+    fn shader_id(self) -> u32 {
+        match self {
+            Fractal::Mandelbrot => 0,
+            Fractal::Julia { .. } => 1,
+            Fractal::BurningShip => 2,
+            Fractal::Tricorn => 3,
+            Fractal::Multibrot { .. } => 4,
+        }
+    }
+
+    fn power(self) -> u32 {
+        match self {
+            Fractal::Multibrot { power } => power,
+            _ => 2,
+        }
+    }
+
+    /// One step of the iteration, the same as `iterate` in shader.glsl.
+    pub fn iterate(self, z: [f64; 2], c: [f64; 2]) -> [f64; 2] {
+        let z = match self {
+            Fractal::BurningShip => [z[0].abs(), z[1].abs()],
+            Fractal::Tricorn => [z[0], -z[1]],
+            _ => z,
+        };
+        let mut product = z;
+        for _ in 1..self.power() {
+            product = complex_mul(product, z);
+        }
+        [product[0] + c[0], product[1] + c[1]]
+    }
+
+    /// Iterates the point of the complex plane a pixel covers until it escapes, returning how
+    /// many iterations that took, or `max_iterations` if it never did, and where it ended up.
+    pub fn escape_time(self, point: [f64; 2], max_iterations: u32) -> (u32, [f64; 2]) {
+        let (mut z, c) = match self {
+            Fractal::Julia { c } => (point, c),
+            _ => ([0.0, 0.0], point),
+        };
+        for i in 0..max_iterations {
+            z = self.iterate(z, c);
+            if z[0] * z[0] + z[1] * z[1] > BAILOUT * BAILOUT {
+                return (i, z);
+            }
+        }
+        (max_iterations, z)
+    }
+}
+
+fn complex_mul(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] * b[0] - a[1] * b[1], a[0] * b[1] + a[1] * b[0]]
+}
+
+/// How each pixel's iteration count is turned into the output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorMode {
+    /// The smooth iteration count as a fraction of the iteration limit.
+    Grayscale,
+    /// The smooth iteration count looked up in the palette, with the set itself black.
+    Palette,
+    /// The iteration count as a little-endian `u32` spread over the four channels, see
+    /// `iteration_counts`.
+    Raw,
+}
+
+impl ColorMode {
+    /// Value of the shader's `mode` push constant.
+    fn shader_mode(self) -> u32 {
+        match self {
+            ColorMode::Grayscale => 0,
+            ColorMode::Palette => 1,
+            ColorMode::Raw => 2,
+        }
+    }
+}
+
+/// How each pixel's point is iterated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Precision {
+    Auto,
+    Single,
+    /// Needs `shader_float64`.
+    Double,
+    /// Iterates offsets from a full precision reference orbit, in double precision if the device
+    /// supports it. Only renders the mandelbrot set.
+    Perturbation,
+}
+
+impl Precision {
+    /// Replaces `Auto` with the cheapest precision that can render pixels of size `scale`.
+    fn resolve(self, scale: f64, supports_double: bool) -> Self {
+        match self {
+            Precision::Auto if scale >= SINGLE_PRECISION_MIN_SCALE => Precision::Single,
+            Precision::Auto if scale >= DOUBLE_PRECISION_MIN_SCALE && supports_double => {
+                Precision::Double
+            }
+            Precision::Auto => Precision::Perturbation,
+            other => other,
+        }
+    }
+}
+
+/// Everything that decides what a render looks like.
+#[derive(Clone, Debug)]
+pub struct RenderSettings {
+    pub fractal: Fractal,
+    pub width: u32,
+    pub height: u32,
+    /// Point in the complex plane at the middle of the image.
+    pub center: [f64; 2],
+    /// `center` as decimal text, for when it has more digits than an `f64` holds. Perturbation
+    /// reads it at full precision.
+    pub exact_center: Option<[String; 2]>,
+    /// Magnification, where 1 shows 2 units of the plane vertically.
+    pub zoom: f64,
+    /// Iterations before a point is considered inside the set.
+    pub iterations: u32,
+    pub color: ColorMode,
+    pub palette: Palette,
+    /// Fraction of its length to shift the palette by.
+    pub palette_offset: f32,
+    /// Iterations it takes to go through the whole palette once.
+    pub palette_period: f32,
+    pub precision: Precision,
+    /// Where in each pixel the fractal is sampled. Raw output can't be supersampled.
+    pub supersampling: Supersampling,
+    /// Format of the image renders are stored in. Raw output needs `Rgba8`.
+    pub format: ColorFormat,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            fractal: Fractal::Mandelbrot,
+            width: 1024,
+            height: 1024,
+            center: [-1.0, 0.0],
+            exact_center: None,
+            zoom: 1.0,
+            iterations: 200,
+            color: ColorMode::Grayscale,
+            palette: Palette::builtin("classic").unwrap(),
+            palette_offset: 0.0,
+            palette_period: 64.0,
+            precision: Precision::Auto,
+            supersampling: Supersampling::None,
+            format: ColorFormat::Rgba8,
+        }
+    }
+}
+
+impl RenderSettings {
+    /// Size of a pixel in the complex plane.
+    pub fn scale(&self) -> f64 {
+        2.0 / (self.zoom * self.height as f64)
+    }
+
+    /// Point in the complex plane at the middle of pixel `(x, y)`.
+    pub fn pixel_point(&self, x: u32, y: u32) -> [f64; 2] {
+        self.sample_point(x, y, [0.5, 0.5])
+    }
+
+    /// Point in the complex plane at `offset` within pixel `(x, y)`, where `(0, 0)` is its top
+    /// left corner and `(1, 1)` its bottom right.
+    pub fn sample_point(&self, x: u32, y: u32, offset: [f32; 2]) -> [f64; 2] {
+        let offset = [
+            x as f64 + offset[0] as f64 - self.width as f64 / 2.0,
+            y as f64 + offset[1] as f64 - self.height as f64 / 2.0,
+        ];
+        [0, 1].map(|i| self.center[i] + offset[i] * self.scale())
+    }
+
+    /// The `width` by `height` part of the view whose top left pixel is pixel `(x, y)`, at the
+    /// same scale. The tile may hang off the right and bottom of the image.
+    pub fn tile(&self, x: u32, y: u32, width: u32, height: u32) -> RenderSettings {
+        // distance from the image's center to the tile's, in pixels
+        let offset = [
+            x as f64 + width as f64 / 2.0 - self.width as f64 / 2.0,
+            y as f64 + height as f64 / 2.0 - self.height as f64 / 2.0,
+        ]
+        .map(|o| o * self.scale());
+        let mut tile = RenderSettings {
+            width,
+            height,
+            zoom: self.zoom * self.height as f64 / height as f64,
+            ..self.clone()
+        };
+        tile.move_center(offset);
+        tile
+    }
+
+    /// Moves the center by `offset` in the complex plane, keeping `exact_center` at full
+    /// precision.
+    pub fn move_center(&mut self, offset: [f64; 2]) {
+        self.center = [0, 1].map(|i| self.center[i] + offset[i]);
+        if let Some(exact_center) = &self.exact_center {
+            let limbs = self.fraction_limbs();
+            self.exact_center = Some([0, 1].map(|i| {
+                match (
+                    Fixed::parse(&exact_center[i], limbs),
+                    Fixed::parse(&offset[i].to_string(), limbs),
+                ) {
+                    (Ok(center), Ok(offset)) => center.add(&offset).to_decimal(),
+                    // leave bad text for the renderer to report
+                    _ => exact_center[i].clone(),
+                }
+            }));
+        }
+    }
+
+    /// Push constants for running iterations `steps[0]` up to `steps[1]`.
+    fn params<R: Real>(
+        &self,
+        reference_len: u32,
+        supersampling: Supersampling,
+        steps: [u32; 2],
+    ) -> Params<R> {
+        let julia_c = match self.fractal {
+            Fractal::Julia { c } => c,
+            _ => [0.0, 0.0],
+        };
+        Params {
+            center: self.center.map(R::from_f64),
+            julia_c: julia_c.map(R::from_f64),
+            scale: R::from_f64(self.scale()),
+            max_iterations: self.iterations,
+            mode: self.color.shader_mode(),
+            palette_offset: self.palette_offset,
+            palette_period: self.palette_period,
+            reference_len,
+            glitch_tolerance: GLITCH_TOLERANCE,
+            fractal: self.fractal.shader_id(),
+            power: self.fractal.power(),
+            samples_per_side: supersampling.samples_per_side(),
+            jitter: supersampling.jitter(),
+            step_start: steps[0],
+            step_end: steps[1],
+        }
+    }
+
+    /// Fractional limbs a `Fixed` needs to resolve single pixels, with plenty to spare.
+    fn fraction_limbs(&self) -> usize {
+        let pixel_bits = (self.zoom * self.height as f64).log2().max(0.0) as usize;
+        (pixel_bits + 64) / 32 + 1
+    }
+
+    /// Full precision orbit of the center.
+    fn reference_orbit(&self) -> Result<Vec<[f64; 2]>, String> {
+        let fraction_limbs = self.fraction_limbs();
+        let text = self
+            .exact_center
+            .clone()
+            .unwrap_or_else(|| self.center.map(|c| c.to_string()));
+        let center = [
+            Fixed::parse(&text[0], fraction_limbs)?,
+            Fixed::parse(&text[1], fraction_limbs)?,
+        ];
+        Ok(reference_orbit(&center, self.iterations, BAILOUT))
+    }
+}
+
+/// Type the shader's `REAL` is defined as.
+trait Real: BufferContents + Copy {
+    fn from_f64(value: f64) -> Self;
+}
+
+impl Real for f32 {
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+}
+
+impl Real for f64 {
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+}
+
+/// Matches the push constant block in shader.glsl.
+#[derive(BufferContents, Clone, Copy)]
+#[repr(C)]
+struct Params<R> {
+    center: [R; 2],
+    julia_c: [R; 2],
+    scale: R,
+    max_iterations: u32,
+    mode: u32,
+    palette_offset: f32,
+    palette_period: f32,
+    reference_len: u32,
+    glitch_tolerance: f32,
+    fractal: u32,
+    power: u32,
+    samples_per_side: u32,
+    jitter: f32,
+    step_start: u32,
+    step_end: u32,
+}
+
+/// Where a render in steps keeps the state of its samples between them, see
+/// `ProgressiveRender`.
+#[derive(Clone)]
+struct ProgressBuffers {
+    states: Subbuffer<[u8]>,
+    /// Samples still iterating after the last step.
+    active: Subbuffer<[u32]>,
+}
+
+impl ProgressBuffers {
+    /// Bytes the shader's `State` takes up, with and without doubles.
+    fn state_size(doubles: bool) -> u64 {
+        if doubles {
+            48
+        } else {
+            32
+        }
+    }
+
+    fn new(context: &Context, samples: u64, doubles: bool) -> Self {
+        let states = Buffer::new_slice(
+            context.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..Default::default()
+            },
+            samples * Self::state_size(doubles),
+        )
+        .expect("could not create buffer");
+        let active = Buffer::new_slice(
+            context.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER | BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            1,
+        )
+        .expect("could not create buffer");
+        Self { states, active }
+    }
+}
+
+/// The pipeline, output image and buffers for rendering images of one size with one palette,
+/// reused across renders.
+pub struct FractalRenderer {
+    precision: Precision,
+    doubles: bool,
+    width: u32,
+    height: u32,
+    supersampling: Supersampling,
+    format: ColorFormat,
+    pipeline: Arc<ComputePipeline>,
+    /// Holds every sample, `samples_per_side` times the output's size each way.
+    image_view: Arc<ImageView>,
+    palette_buffer: Subbuffer<[[f32; 4]]>,
+    samples_buffer: Subbuffer<[[f32; 2]]>,
+    /// Averages the samples into `output_image` when there's more than one per pixel.
+    downsampler: Option<Downsampler>,
+    output_image: Arc<Image>,
+    /// Only used without perturbation, which needs a new set for every reference orbit.
+    descriptor_set: Arc<PersistentDescriptorSet>,
+    /// Bound when rendering in one go, which never touches them.
+    no_progress: ProgressBuffers,
+    readback_buffer: Subbuffer<[u8]>,
+}
+
+impl FractalRenderer {
+    /// Sets up for renders of the size, palette and supersampling in `settings`, picking the
+    /// precision from its zoom and whether `context` has `shader_float64` enabled.
+    pub fn new(context: &Context, settings: &RenderSettings) -> Result<Self, String> {
+        let supports_double = context.device.enabled_features().shader_float64;
+        let precision = settings
+            .precision
+            .resolve(settings.scale(), supports_double);
+        if precision == Precision::Double && !supports_double {
+            return Err("the device does not support double precision".to_string());
+        }
+        if precision == Precision::Perturbation && settings.fractal != Fractal::Mandelbrot {
+            return Err(format!(
+                "zoom is too deep for {:?}, only the mandelbrot set can use perturbation",
+                settings.fractal
+            ));
+        }
+        let doubles = precision == Precision::Double
+            || precision == Precision::Perturbation && supports_double;
+        if precision == Precision::Perturbation
+            && !doubles
+            && settings.scale() < SINGLE_PRECISION_MIN_DELTA_SCALE
+        {
+            return Err("zoom is too deep for a device without double precision".to_string());
+        }
+        let samples_per_side = settings.supersampling.samples_per_side();
+        if samples_per_side > 1 && settings.color == ColorMode::Raw {
+            return Err("raw output can't be supersampled".to_string());
+        }
+        // other formats are written without a format qualifier in the shader
+        let format = settings.format;
+        let unformatted = format != ColorFormat::Rgba8;
+        if unformatted {
+            if settings.color == ColorMode::Raw {
+                return Err("raw output can only be rendered as rgba8".to_string());
+            }
+            format.check_support(
+                context.device.physical_device(),
+                FormatFeatures::STORAGE_IMAGE | FormatFeatures::TRANSFER_SRC,
+            )?;
+            let features = context.device.enabled_features();
+            if !features.shader_storage_image_write_without_format
+                || samples_per_side > 1 && !features.shader_storage_image_read_without_format
+            {
+                return Err(format!(
+                    "rendering into {:?} needs the shader_storage_image_write_without_format \
+                     and, when supersampling, shader_storage_image_read_without_format features",
+                    format.format()
+                ));
+            }
+        }
+        let max_size = context
+            .device
+            .physical_device()
+            .properties()
+            .max_image_dimension2_d;
+        let [sample_width, sample_height] =
+            [settings.width, settings.height].map(|s| s as u64 * samples_per_side as u64);
+        if sample_width.max(sample_height) > max_size as u64 {
+            return Err(format!(
+                "{}x{} samples don't fit in the device's largest image of {}x{}, render in tiles",
+                sample_width, sample_height, max_size, max_size
+            ));
+        }
+
+        // setup compute pipeline
+        type Load = fn(Arc<Device>) -> Result<Arc<ShaderModule>, Validated<VulkanError>>;
+        let load: Load = match (precision, doubles, unformatted) {
+            (Precision::Perturbation, true, false) => shaders::perturbation_double::load,
+            (Precision::Perturbation, false, false) => shaders::perturbation_single::load,
+            (_, true, false) => shaders::double::load,
+            (_, false, false) => shaders::single::load,
+            (Precision::Perturbation, true, true) => shaders::perturbation_double_unformatted::load,
+            (Precision::Perturbation, false, true) => {
+                shaders::perturbation_single_unformatted::load
+            }
+            (_, true, true) => shaders::double_unformatted::load,
+            (_, false, true) => shaders::single_unformatted::load,
+        };
+        let shader = load(context.device.clone()).expect("failed to create shader module");
+        let pipeline = context.compute_pipeline(shader);
+
+        // setup image output, with a smaller image to downsample into when supersampling
+        let usage = ImageUsage::STORAGE | ImageUsage::TRANSFER_SRC;
+        let image = create_image(
+            context,
+            format.format(),
+            sample_width as u32,
+            sample_height as u32,
+            usage,
+        );
+        let (downsampler, output_image) = if samples_per_side > 1 {
+            let output_image = create_image(
+                context,
+                format.format(),
+                settings.width,
+                settings.height,
+                usage,
+            );
+            let downsampler = if unformatted {
+                Downsampler::unformatted(context)
+            } else {
+                Downsampler::new(context)
+            };
+            (Some(downsampler), output_image)
+        } else {
+            (None, image.clone())
+        };
+        let image_view = ImageView::new_default(image).expect("could not create image view");
+        let mut needed = FormatFeatures::empty();
+        if unformatted {
+            needed |= FormatFeatures::STORAGE_WRITE_WITHOUT_FORMAT;
+            if samples_per_side > 1 {
+                needed |= FormatFeatures::STORAGE_READ_WITHOUT_FORMAT;
+            }
+        }
+        if !image_view.format_features().contains(needed) {
+            return Err(format!(
+                "the device can't write {:?} images without a format in shaders",
+                format.format()
+            ));
+        }
+
+        // setup palette and sample pattern input
+        let palette_buffer = storage_buffer(
+            context.memory_allocator.clone(),
+            settings.palette.to_colors(),
+        );
+        let samples_buffer = storage_buffer(
+            context.memory_allocator.clone(),
+            settings.supersampling.offsets(),
+        );
+        let no_progress = ProgressBuffers::new(context, 1, doubles);
+        let descriptor_set = context.descriptor_set(
+            &pipeline,
+            [
+                WriteDescriptorSet::image_view(0, image_view.clone()),
+                WriteDescriptorSet::buffer(1, palette_buffer.clone()),
+                WriteDescriptorSet::buffer(3, samples_buffer.clone()),
+                WriteDescriptorSet::buffer(4, no_progress.states.clone()),
+                WriteDescriptorSet::buffer(5, no_progress.active.clone()),
+            ],
+        );
+
+        // create buffer for image output
+        let readback_buffer = Buffer::new_slice(
+            context.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            settings.width as u64 * settings.height as u64 * format.pixel_size() as u64,
+        )
+        .expect("could not create buffer");
+
+        Ok(Self {
+            precision,
+            doubles,
+            width: settings.width,
+            height: settings.height,
+            supersampling: settings.supersampling,
+            format,
+            pipeline,
+            image_view,
+            palette_buffer,
+            samples_buffer,
+            downsampler,
+            output_image,
+            descriptor_set,
+            no_progress,
+            readback_buffer,
+        })
+    }
+
+    /// The precision `Auto` resolved to.
+    pub fn precision(&self) -> Precision {
+        self.precision
+    }
+
+    /// Whether the shader does its math in doubles.
+    pub fn uses_doubles(&self) -> bool {
+        self.doubles
+    }
+
+    /// Renders the view, fractal and coloring of `settings`. Its size, palette and
+    /// supersampling are the ones the renderer was created with, whatever `settings` says.
+    pub fn render(
+        &self,
+        context: &Context,
+        settings: &RenderSettings,
+    ) -> Result<RgbaImage, String> {
+        let mut builder = context.command_buffer_builder();
+        self.record(context, &mut builder, settings)?;
+        Ok(self.to_rgba8(self.read_back(context, builder)))
+    }
+
+    /// Like `render`, but keeps all the precision of the renderer's format.
+    pub fn render_float(
+        &self,
+        context: &Context,
+        settings: &RenderSettings,
+    ) -> Result<Rgba32FImage, String> {
+        let mut builder = context.command_buffer_builder();
+        self.record(context, &mut builder, settings)?;
+        let bytes = self.read_back(context, builder);
+        Ok(self.format.decode(self.width, self.height, &bytes))
+    }
+
+    /// Reads back whatever `image()` holds, for example a preview of a render in steps.
+    pub fn download(&self, context: &Context) -> RgbaImage {
+        self.to_rgba8(self.read_back(context, context.command_buffer_builder()))
+    }
+
+    /// Like `download`, but keeps all the precision of the renderer's format.
+    pub fn download_float(&self, context: &Context) -> Rgba32FImage {
+        let bytes = self.read_back(context, context.command_buffer_builder());
+        self.format.decode(self.width, self.height, &bytes)
+    }
+
+    fn to_rgba8(&self, bytes: Vec<u8>) -> RgbaImage {
+        match self.format {
+            ColorFormat::Rgba8 => RgbaImage::from_raw(self.width, self.height, bytes)
+                .expect("failed to create image from buffer"),
+            format => to_rgba8(&format.decode(self.width, self.height, &bytes)),
+        }
+    }
+
+    /// Runs `builder` with a copy of `image()` to the host at the end, and returns the copy.
+    fn read_back(
+        &self,
+        context: &Context,
+        mut builder: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) -> Vec<u8> {
+        builder
+            .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+                self.output_image.clone(),
+                self.readback_buffer.clone(),
+            ))
+            .expect("failed to copy image to buffer");
+        context.execute(builder);
+
+        let buf_content = self.readback_buffer.read().expect("could not read buffer");
+        buf_content.to_vec()
+    }
+
+    /// The image renders end up in, of the settings' format and with `STORAGE` and
+    /// `TRANSFER_SRC` usage.
+    pub fn image(&self) -> Arc<Image> {
+        self.output_image.clone()
+    }
+
+    /// Records rendering `settings` into `image()`, like `render` but leaving the result on the
+    /// device, for example to show it in a window.
+    pub fn record(
+        &self,
+        context: &Context,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        settings: &RenderSettings,
+    ) -> Result<(), String> {
+        let (descriptor_set, reference_len) = self.descriptor_set(context, settings, None)?;
+        self.record_steps(
+            context,
+            builder,
+            settings,
+            descriptor_set,
+            reference_len,
+            [0, settings.iterations],
+        );
+        Ok(())
+    }
+
+    /// A descriptor set with the reference orbit of `settings`, when perturbing, and
+    /// `progress`, when rendering in steps. Also returns the length of the orbit.
+    fn descriptor_set(
+        &self,
+        context: &Context,
+        settings: &RenderSettings,
+        progress: Option<&ProgressBuffers>,
+    ) -> Result<(Arc<PersistentDescriptorSet>, u32), String> {
+        // setup reference orbit input
+        let mut reference_len = 0;
+        let buffers = progress.unwrap_or(&self.no_progress);
+        let progress_writes = [
+            WriteDescriptorSet::buffer(4, buffers.states.clone()),
+            WriteDescriptorSet::buffer(5, buffers.active.clone()),
+        ];
+        let descriptor_set = if self.precision == Precision::Perturbation {
+            let orbit = settings.reference_orbit()?;
+            reference_len = orbit.len() as u32;
+            let memory_allocator = context.memory_allocator.clone();
+            let orbit_write = if self.doubles {
+                WriteDescriptorSet::buffer(2, storage_buffer(memory_allocator, orbit))
+            } else {
+                let orbit = orbit.into_iter().map(|point| point.map(|v| v as f32));
+                WriteDescriptorSet::buffer(2, storage_buffer(memory_allocator, orbit))
+            };
+            context.descriptor_set(
+                &self.pipeline,
+                [
+                    WriteDescriptorSet::image_view(0, self.image_view.clone()),
+                    WriteDescriptorSet::buffer(1, self.palette_buffer.clone()),
+                    WriteDescriptorSet::buffer(3, self.samples_buffer.clone()),
+                    orbit_write,
+                ]
+                .into_iter()
+                .chain(progress_writes),
+            )
+        } else if progress.is_none() {
+            self.descriptor_set.clone()
+        } else {
+            context.descriptor_set(
+                &self.pipeline,
+                [
+                    WriteDescriptorSet::image_view(0, self.image_view.clone()),
+                    WriteDescriptorSet::buffer(1, self.palette_buffer.clone()),
+                    WriteDescriptorSet::buffer(3, self.samples_buffer.clone()),
+                ]
+                .into_iter()
+                .chain(progress_writes),
+            )
+        };
+        Ok((descriptor_set, reference_len))
+    }
+
+    /// Records running iterations `steps[0]` up to `steps[1]` of every sample and
+    /// downsampling the result into `image()`.
+    fn record_steps(
+        &self,
+        context: &Context,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        settings: &RenderSettings,
+        descriptor_set: Arc<PersistentDescriptorSet>,
+        reference_len: u32,
+        steps: [u32; 2],
+    ) {
+        // dispatch
+        let layout = self.pipeline.layout().clone();
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())
+            .expect("failed to bind pipeline")
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                layout.clone(),
+                0,
+                descriptor_set,
+            )
+            .expect("failed to bind descriptor set");
+        let supersampling = self.supersampling;
+        if self.doubles {
+            builder.push_constants(
+                layout,
+                0,
+                settings.params::<f64>(reference_len, supersampling, steps),
+            )
+        } else {
+            builder.push_constants(
+                layout,
+                0,
+                settings.params::<f32>(reference_len, supersampling, steps),
+            )
+        }
+        .expect("failed to push constants");
+        // the shader skips invocations past the edge, so partial work groups are fine
+        let [sample_width, sample_height, _] = self.image_view.image().extent();
+        builder
+            .dispatch([sample_width.div_ceil(8), sample_height.div_ceil(8), 1])
+            .expect("failed to dispatch work groups");
+        if let Some(downsampler) = &self.downsampler {
+            downsampler.record(
+                context,
+                builder,
+                self.image_view.image().clone(),
+                self.output_image.clone(),
+                supersampling.samples_per_side(),
+            );
+        }
+    }
+}
+
+fn storage_buffer<T: BufferContents>(
+    memory_allocator: Arc<dyn MemoryAllocator>,
+    data: impl IntoIterator<Item = T, IntoIter = impl ExactSizeIterator>,
+) -> Subbuffer<[T]> {
+    Buffer::from_iter(
+        memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        },
+        data,
+    )
+    .expect("failed to create storage buffer")
+}
+
+/// Unpacks the counts of a render in `ColorMode::Raw`, row by row.
+pub fn iteration_counts(image: &RgbaImage) -> Vec<u32> {
+    image.pixels().map(|p| u32::from_le_bytes(p.0)).collect()
+}
+
+/// Renders `settings` on the CPU in double precision, the same way the shader does.
+pub fn render_cpu(settings: &RenderSettings) -> RgbaImage {
+    let colors = settings.palette.to_colors();
+    let supersampling = settings.supersampling;
+    let samples = supersampling.samples_per_side().pow(2);
+    RgbaImage::from_fn(settings.width, settings.height, |x, y| {
+        if samples == 1 || settings.color == ColorMode::Raw {
+            return point_color(settings, &colors, settings.pixel_point(x, y));
+        }
+        // averaged the same as the downsample shader, after rounding each sample to 8 bits
+        let mut sum = [0.0; 4];
+        for index in 0..samples {
+            let offset = supersampling.sample_offset(x, y, index);
+            let color = point_color(settings, &colors, settings.sample_point(x, y, offset));
+            for (total, value) in sum.iter_mut().zip(color.0) {
+                *total += value as f32;
+            }
+        }
+        Rgba(sum.map(|total| (total / samples as f32).round() as u8))
+    })
+}
+
+/// The color `settings` gives `point`, the same as shader.glsl.
+fn point_color(settings: &RenderSettings, colors: &[[f32; 4]], point: [f64; 2]) -> Rgba<u8> {
+    let (i, z) = settings.fractal.escape_time(point, settings.iterations);
+    let inside = i == settings.iterations;
+    if settings.color == ColorMode::Raw {
+        return Rgba(i.to_le_bytes());
+    }
+
+    let z_squared = z[0] * z[0] + z[1] * z[1];
+    let smooth_i = if inside {
+        i as f32
+    } else {
+        i as f32 + 1.0
+            - ((z_squared.log2() / 2.0).log2() / (settings.fractal.power() as f64).log2()) as f32
+    };
+    let rgb = if settings.color == ColorMode::Palette {
+        if inside {
+            [0.0; 3]
+        } else {
+            sample_colors(
+                colors,
+                smooth_i / settings.palette_period + settings.palette_offset,
+            )
+        }
+    } else {
+        [(smooth_i / settings.iterations as f32).clamp(0.0, 1.0); 3]
+    };
+    let [r, g, b] = rgb.map(|c| (c * 255.0).round() as u8);
+    Rgba([r, g, b, 255])
+}
+
+/// Looks up `t` in the resampled palette, the same as `sample_palette` in shader.glsl.
+fn sample_colors(colors: &[[f32; 4]], t: f32) -> [f32; 3] {
+    let x = t.rem_euclid(1.0) * colors.len() as f32;
+    let i = x as usize % colors.len();
+    let (a, b) = (colors[i], colors[(i + 1) % colors.len()]);
+    [0, 1, 2].map(|c| a[c] + (b[c] - a[c]) * x.fract())
+}
+
+mod shaders {
+    pub mod single {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/fractal/shader.glsl"
+        }
+    }
+
+    pub mod double {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/fractal/shader.glsl",
+            define: [("REAL_DOUBLE", "")]
+        }
+    }
+
+    pub mod perturbation_single {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/fractal/shader.glsl",
+            define: [("PERTURBATION", "")]
+        }
+    }
+
+    pub mod perturbation_double {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/fractal/shader.glsl",
+            define: [("PERTURBATION", ""), ("REAL_DOUBLE", "")]
+        }
+    }
+
+    pub mod single_unformatted {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/fractal/shader.glsl",
+            define: [("UNFORMATTED_OUTPUT", "")]
+        }
+    }
+
+    pub mod double_unformatted {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/fractal/shader.glsl",
+            define: [("UNFORMATTED_OUTPUT", ""), ("REAL_DOUBLE", "")]
+        }
+    }
+
+    pub mod perturbation_single_unformatted {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/fractal/shader.glsl",
+            define: [("UNFORMATTED_OUTPUT", ""), ("PERTURBATION", "")]
+        }
+    }
+
+    pub mod perturbation_double_unformatted {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/fractal/shader.glsl",
+            define: [("UNFORMATTED_OUTPUT", ""), ("PERTURBATION", ""), ("REAL_DOUBLE", "")]
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::fs;
+
+/// How many colors a gradient is resampled to before it is uploaded for the shader.
+pub const PALETTE_SIZE: usize = 256;
+
+/// Names accepted by `Palette::builtin`.
+pub const BUILTIN_NAMES: [&str; 4] = ["classic", "fire", "ice", "grayscale"];
+
+/// A cyclic gradient defined by color stops at positions in `[0, 1)`. Past the last stop it
+/// blends back into the first, so the palette can be repeated without seams.
+#[derive(Clone, Debug)]
+pub struct Palette {
+    stops: Vec<(f32, [f32; 3])>,
+}
+
+impl Palette {
+    pub fn builtin(name: &str) -> Option<Self> {
+        let stops: &[(f32, u32)] = match name {
+            "classic" => &[
+                (0.0, 0x000764),
+                (0.16, 0x206bcb),
+                (0.42, 0xedffff),
+                (0.6425, 0xffaa00),
+                (0.8575, 0x000200),
+            ],
+            "fire" => &[
+                (0.0, 0x000000),
+                (0.25, 0x7f0000),
+                (0.5, 0xff4000),
+                (0.75, 0xffd000),
+                (0.9, 0xffffff),
+            ],
+            "ice" => &[
+                (0.0, 0x000010),
+                (0.3, 0x104080),
+                (0.6, 0x60c0ff),
+                (0.8, 0xf0ffff),
+            ],
+            "grayscale" => &[(0.0, 0x000000), (0.5, 0xffffff)],
+            _ => return None,
+        };
+        Some(Self {
+            stops: stops
+                .iter()
+                .map(|&(position, rgb)| (position, unpack_rgb(rgb)))
+                .collect(),
+        })
+    }
+
+    /// Reads a palette file, see `parse`.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("could not read '{}': {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("bad palette '{}': {}", path, e))
+    }
+
+    /// Parses one color stop per line, either `#rrggbb` or `<position> #rrggbb`. Positions are
+    /// in `[0, 1)`; when none are given the stops are spread evenly. Blank lines and lines
+    /// starting with `//` are skipped.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut positions = Vec::new();
+        let mut colors = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (position, color) = match fields[..] {
+                [color] => (None, color),
+                [position, color] => {
+                    let position = position
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|p| (0.0..1.0).contains(p))
+                        .ok_or_else(|| {
+                            format!("line {}: position must be in [0, 1)", number + 1)
+                        })?;
+                    (Some(position), color)
+                }
+                _ => return Err(format!("line {}: expected [position] #rrggbb", number + 1)),
+            };
+            let rgb = color
+                .strip_prefix('#')
+                .filter(|hex| hex.len() == 6)
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| {
+                    format!("line {}: '{}' is not a #rrggbb color", number + 1, color)
+                })?;
+            positions.push(position);
+            colors.push(unpack_rgb(rgb));
+        }
+
+        if colors.is_empty() {
+            return Err("no color stops".to_string());
+        }
+        let positions: Vec<f32> = if positions.iter().all(Option::is_some) {
+            positions.into_iter().flatten().collect()
+        } else if positions.iter().all(Option::is_none) {
+            (0..colors.len())
+                .map(|i| i as f32 / colors.len() as f32)
+                .collect()
+        } else {
+            return Err("either every stop or no stop must have a position".to_string());
+        };
+        if positions.windows(2).any(|w| w[0] >= w[1]) {
+            return Err("stop positions must be increasing".to_string());
+        }
+
+        Ok(Self {
+            stops: positions.into_iter().zip(colors).collect(),
+        })
+    }
+
+    /// Color at `t`, which wraps around every 1.0.
+    pub fn sample(&self, t: f32) -> [f32; 3] {
+        let t = t.rem_euclid(1.0);
+        let next = self
+            .stops
+            .iter()
+            .position(|&(position, _)| position > t)
+            .unwrap_or(self.stops.len());
+        let (start, start_color) = if next == 0 {
+            let (position, color) = self.stops[self.stops.len() - 1];
+            (position - 1.0, color)
+        } else {
+            self.stops[next - 1]
+        };
+        let (end, end_color) = if next == self.stops.len() {
+            let (position, color) = self.stops[0];
+            (position + 1.0, color)
+        } else {
+            self.stops[next]
+        };
+        let blend = if end > start {
+            (t - start) / (end - start)
+        } else {
+            0.0
+        };
+        [0, 1, 2].map(|c| start_color[c] + (end_color[c] - start_color[c]) * blend)
+    }
+
+    /// The gradient resampled to `PALETTE_SIZE` evenly spaced colors, laid out like the
+    /// `Palette` block in shader.glsl.
+    pub fn to_colors(&self) -> Vec<[f32; 4]> {
+        (0..PALETTE_SIZE)
+            .map(|i| {
+                let [r, g, b] = self.sample(i as f32 / PALETTE_SIZE as f32);
+                [r, g, b, 1.0]
+            })
+            .collect()
+    }
+}
+
+fn unpack_rgb(rgb: u32) -> [f32; 3] {
+    [16, 8, 0].map(|shift| ((rgb >> shift) & 0xff) as f32 / 255.0)
+}
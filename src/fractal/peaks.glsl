@@ -0,0 +1,32 @@
+#version 460
+
+// Finds the most hits any pixel got in each channel, for tonemap.glsl to scale by.
+
+layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+// width * height hits for red, then green, then blue
+layout(set = 0, binding = 0) readonly buffer Hits {
+    uint hits[];
+};
+
+// must start out zero
+layout(set = 0, binding = 1) buffer Peaks {
+    uint peaks[3];
+};
+
+layout(push_constant) uniform PeakParams {
+    uint pixels;
+} params;
+
+void main() {
+    uint pixel = gl_GlobalInvocationID.x;
+    if (pixel >= params.pixels) {
+        return;
+    }
+    for (uint k = 0; k < 3; k++) {
+        uint count = hits[k * params.pixels + pixel];
+        if (count > 0) {
+            atomicMax(peaks[k], count);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::sync::Arc;
+
+use vulkano::descriptor_set::PersistentDescriptorSet;
+
+use crate::context::Context;
+
+use super::{FractalRenderer, ProgressBuffers, RenderSettings};
+
+/// A render that runs a bounded number of iterations per submission, so high iteration counts
+/// don't trip the driver's timeout. Every sample's orbit is kept in a storage buffer between
+/// steps, and the renderer's image shows the render so far after each one, with samples that
+/// haven't escaped yet colored as if they're inside.
+pub struct ProgressiveRender<'a> {
+    renderer: &'a FractalRenderer,
+    settings: RenderSettings,
+    progress: ProgressBuffers,
+    /// Holds the reference orbit when perturbing, which is only worked out once.
+    descriptor_set: Arc<PersistentDescriptorSet>,
+    reference_len: u32,
+    samples: u64,
+    iterations_done: u32,
+    active_samples: u64,
+}
+
+impl<'a> ProgressiveRender<'a> {
+    /// Starts a render of `settings` with `renderer`, which draws into its image as it goes.
+    /// Nothing runs until the first `step`.
+    pub fn new(
+        context: &Context,
+        renderer: &'a FractalRenderer,
+        settings: &RenderSettings,
+    ) -> Result<Self, String> {
+        let [sample_width, sample_height, _] = renderer.image_view.image().extent();
+        let samples = sample_width as u64 * sample_height as u64;
+        let progress = ProgressBuffers::new(context, samples, renderer.doubles);
+        let (descriptor_set, reference_len) =
+            renderer.descriptor_set(context, settings, Some(&progress))?;
+        Ok(Self {
+            renderer,
+            settings: settings.clone(),
+            progress,
+            descriptor_set,
+            reference_len,
+            samples,
+            iterations_done: 0,
+            active_samples: samples,
+        })
+    }
+
+    /// Runs up to `iterations` more iterations of every sample that's still iterating and
+    /// waits for them to finish.
+    pub fn step(&mut self, context: &Context, iterations: u32) {
+        if self.is_finished() {
+            return;
+        }
+        let steps = [
+            self.iterations_done,
+            self.iterations_done
+                .saturating_add(iterations.max(1))
+                .min(self.settings.iterations),
+        ];
+
+        let mut builder = context.command_buffer_builder();
+        builder
+            .fill_buffer(self.progress.active.clone(), 0)
+            .expect("failed to clear active samples");
+        self.renderer.record_steps(
+            context,
+            &mut builder,
+            &self.settings,
+            self.descriptor_set.clone(),
+            self.reference_len,
+            steps,
+        );
+        context.execute(builder);
+
+        self.iterations_done = steps[1];
+        // the shader only counts when there are steps left, see shader.glsl
+        self.active_samples = if self.iterations_done < self.settings.iterations {
+            self.progress.active.read().expect("could not read buffer")[0] as u64
+        } else {
+            0
+        };
+    }
+
+    /// Iterations every sample has run, or escaped before.
+    pub fn iterations_done(&self) -> u32 {
+        self.iterations_done
+    }
+
+    /// Fraction of the samples that neither escaped nor ran out of iterations yet.
+    pub fn active_fraction(&self) -> f64 {
+        self.active_samples as f64 / self.samples as f64
+    }
+
+    /// Whether every sample escaped or ran out of iterations, so the image is final.
+    pub fn is_finished(&self) -> bool {
+        self.active_samples == 0
+    }
+}
@@ -0,0 +1,260 @@
+#version 460
+
+// REAL_DOUBLE switches the view and orbit math to doubles, which needs `shader_float64`.
+// PERTURBATION iterates each pixel's offset from a reference orbit computed on the CPU instead
+// of the pixel's own point, so the precision only has to cover the offsets. It only renders the
+// mandelbrot set.
+#ifdef REAL_DOUBLE
+#define REAL double
+#define REAL_VEC2 dvec2
+#else
+#define REAL float
+#define REAL_VEC2 vec2
+#endif
+
+layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+#ifdef UNFORMATTED_OUTPUT
+// the output's format is only known at runtime, which needs shaderStorageImageWriteWithoutFormat
+layout(set = 0, binding = 0) uniform writeonly image2D img;
+#else
+layout(set = 0, binding = 0, rgba8) uniform writeonly image2D img;
+#endif
+
+layout(set = 0, binding = 1) readonly buffer Palette {
+    vec4 colors[];
+} palette;
+
+#ifdef PERTURBATION
+// Z_0 (always zero) up to the iteration the reference point escaped or ran out at
+layout(set = 0, binding = 2) readonly buffer Orbit {
+    REAL_VEC2 points[];
+} orbit;
+#endif
+
+// where in its pixel each sample is taken, row by row, see `Supersampling::offsets`
+layout(set = 0, binding = 3) readonly buffer Samples {
+    vec2 offsets[];
+} samples;
+
+// iteration state of every sample between the steps of a render in steps, see
+// `ProgressiveRender`
+struct State {
+    REAL_VEC2 z;
+    // the perturbation delta and where along the reference orbit it is
+    REAL_VEC2 dz;
+    uint i;
+    uint m;
+    uint escaped;
+};
+
+layout(set = 0, binding = 4) buffer Progress {
+    State states[];
+} progress;
+
+// samples still iterating after a step, must start out zero
+layout(set = 0, binding = 5) buffer Active {
+    uint active_samples;
+};
+
+// fractals
+const uint FRACTAL_MANDELBROT = 0;
+const uint FRACTAL_JULIA = 1;
+const uint FRACTAL_BURNING_SHIP = 2;
+const uint FRACTAL_TRICORN = 3;
+const uint FRACTAL_MULTIBROT = 4;
+
+// output modes
+const uint MODE_GRAYSCALE = 0;
+const uint MODE_PALETTE = 1;
+// the iteration count as a little-endian u32 spread over the four channels
+const uint MODE_RAW = 2;
+
+// escaping much further than radius 2 makes the smooth iteration count continuous
+const float BAILOUT = 256.0;
+
+layout(push_constant) uniform Params {
+    // ignored when perturbing, where the reference orbit starts at the center
+    REAL_VEC2 center;
+    // the constant c of julia sets
+    REAL_VEC2 julia_c;
+    // size of a pixel in the complex plane
+    REAL scale;
+    uint max_iterations;
+    uint mode;
+    // fraction of the palette to shift it by
+    float palette_offset;
+    // iterations it takes to go through the whole palette once
+    float palette_period;
+    // number of points in the reference orbit
+    uint reference_len;
+    // a pixel whose value gets this much smaller than the reference's has glitched
+    float glitch_tolerance;
+    uint fractal;
+    // exponent of the multibrot, 2 for every other fractal
+    uint power;
+    // the image holds this many samples across and down for every output pixel
+    uint samples_per_side;
+    // size of the square each sample is moved around in at random, 0 without jitter
+    float jitter;
+    // iterations to resume from and stop at. anything but 0 and max_iterations makes this a
+    // step of a longer render, where samples carry on from and save to their state in between
+    uint step_start;
+    uint step_end;
+} params;
+
+REAL_VEC2 complex_mul(REAL_VEC2 a, REAL_VEC2 b) {
+    return REAL_VEC2(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x);
+}
+
+REAL_VEC2 iterate(REAL_VEC2 z, REAL_VEC2 c) {
+    switch (params.fractal) {
+        case FRACTAL_BURNING_SHIP:
+            z = abs(z);
+            break;
+        case FRACTAL_TRICORN:
+            z.y = -z.y;
+            break;
+        case FRACTAL_MULTIBROT: {
+            REAL_VEC2 product = z;
+            for (uint k = 1; k < params.power; k++) {
+                product = complex_mul(product, z);
+            }
+            return product + c;
+        }
+    }
+    return complex_mul(z, z) + c;
+}
+
+// lowbias32 by Chris Wellons
+uint hash(uint x) {
+    x ^= x >> 16;
+    x *= 0x7feb352du;
+    x ^= x >> 15;
+    x *= 0x846ca68bu;
+    x ^= x >> 16;
+    return x;
+}
+
+// where sample `index` of `pixel` is taken within it
+vec2 sample_offset(uvec2 pixel, uint index) {
+    uint h = hash(pixel.x ^ hash(pixel.y ^ hash(index)));
+    vec2 random = vec2(h >> 8, hash(h) >> 8) / 16777216.0;
+    return samples.offsets[index] + params.jitter * random;
+}
+
+vec3 sample_palette(float t) {
+    uint len = palette.colors.length();
+    float x = fract(t) * float(len);
+    uint i = uint(x) % len;
+    return mix(palette.colors[i].rgb, palette.colors[(i + 1) % len].rgb, fract(x));
+}
+
+void main(){
+    ivec2 size = imageSize(img);
+    if (any(greaterThanEqual(gl_GlobalInvocationID.xy, uvec2(size)))) {
+        return;
+    }
+
+    // each pixel is a square of samples
+    uvec2 pixel = gl_GlobalInvocationID.xy / params.samples_per_side;
+    uvec2 within = gl_GlobalInvocationID.xy - pixel * params.samples_per_side;
+    vec2 sample_pos = sample_offset(pixel, within.y * params.samples_per_side + within.x);
+    REAL_VEC2 pixels = REAL_VEC2(uvec2(size) / params.samples_per_side);
+    REAL_VEC2 offset = (REAL_VEC2(pixel) + REAL_VEC2(sample_pos) - pixels / REAL(2.0)) * params.scale;
+
+    bool stepped = params.step_start > 0 || params.step_end < params.max_iterations;
+    uint state_index = gl_GlobalInvocationID.y * uint(size.x) + gl_GlobalInvocationID.x;
+    State state;
+    if (params.step_start > 0) {
+        state = progress.states[state_index];
+    }
+    bool escaped = params.step_start > 0 && state.escaped != 0;
+
+    REAL_VEC2 z = REAL_VEC2(0.0);
+    uint i = 0;
+#ifdef PERTURBATION
+    // z = orbit[m] + dz, where dz follows dz' = 2 Z dz + dz^2 + dc
+    REAL_VEC2 dc = offset;
+    REAL_VEC2 dz = REAL_VEC2(0.0);
+    uint m = 0;
+    if (params.step_start > 0) {
+        z = state.z;
+        dz = state.dz;
+        i = state.i;
+        m = state.m;
+    }
+    for (; i < params.step_end && !escaped; i++) {
+        REAL_VEC2 reference = orbit.points[m];
+        dz = complex_mul(REAL(2.0) * reference + dz, dz) + dc;
+        m++;
+        reference = orbit.points[m];
+        z = reference + dz;
+
+        REAL z_squared = dot(z, z);
+        if (z_squared > BAILOUT * BAILOUT) {
+            escaped = true;
+            break;
+        }
+
+        // When the pixel passes close to zero its delta swamps the reference's value and the
+        // rounding errors in it show up as glitches. Restarting from the start of the reference
+        // orbit with the full value as the new delta is exact, since Z_0 is zero. The same
+        // happens when the reference orbit runs out, because the reference escaped.
+        REAL glitch_radius = REAL(params.glitch_tolerance) * length(reference);
+        bool glitched = z_squared < dot(dz, dz) || z_squared < glitch_radius * glitch_radius;
+        if (glitched || m == params.reference_len - 1) {
+            dz = z;
+            m = 0;
+        }
+    }
+#else
+    REAL_VEC2 c = params.center + offset;
+    if (params.fractal == FRACTAL_JULIA) {
+        z = c;
+        c = params.julia_c;
+    }
+    if (params.step_start > 0) {
+        z = state.z;
+        i = state.i;
+    }
+    for (; i < params.step_end && !escaped; i++) {
+        z = iterate(z, c);
+
+        if (dot(z, z) > BAILOUT * BAILOUT) {
+            escaped = true;
+            break;
+        }
+    }
+    REAL_VEC2 dz = REAL_VEC2(0.0);
+    uint m = 0;
+#endif
+    if (stepped) {
+        progress.states[state_index] = State(z, dz, i, m, escaped ? 1 : 0);
+        if (!escaped && i < params.max_iterations) {
+            atomicAdd(active_samples, 1u);
+        }
+    }
+    // samples still iterating look like they're inside until they escape
+    bool inside = !escaped;
+
+    vec4 to_write;
+    if (params.mode == MODE_RAW) {
+        to_write = vec4((uvec4(i) >> uvec4(0, 8, 16, 24)) & 0xff) / 255.0;
+    } else {
+        // normalized iteration count, which removes the banding between whole iterations
+        float z_squared = float(dot(z, z));
+        float smooth_i = inside
+            ? float(i)
+            : float(i) + 1.0 - log2(log2(z_squared) / 2.0) / log2(float(params.power));
+        if (params.mode == MODE_PALETTE) {
+            vec3 color = inside
+                ? vec3(0.0)
+                : sample_palette(smooth_i / params.palette_period + params.palette_offset);
+            to_write = vec4(color, 1.0);
+        } else {
+            to_write = vec4(vec3(clamp(smooth_i / float(params.max_iterations), 0.0, 1.0)), 1.0);
+        }
+    }
+    imageStore(img, ivec2(gl_GlobalInvocationID.xy), to_write);
+}
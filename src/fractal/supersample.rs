@@ -0,0 +1,235 @@
+use std::{str::FromStr, sync::Arc};
+
+use image::RgbaImage;
+use vulkano::{
+    buffer::BufferContents,
+    command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer},
+    descriptor_set::WriteDescriptorSet,
+    image::{view::ImageView, Image, ImageUsage},
+    pipeline::{ComputePipeline, Pipeline, PipelineBindPoint},
+};
+
+use crate::{
+    context::Context,
+    transfer::{create_rgba8_image, download_rgba8, upload_rgba8},
+};
+
+/// Most samples a pattern may take across and down each pixel.
+pub const MAX_SAMPLES_PER_SIDE: u32 = 8;
+
+/// Where in each pixel samples are taken, parsed from the command line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Supersampling {
+    /// `none`, one sample in the middle of the pixel
+    None,
+    /// `<n>x<n>`, an evenly spaced grid
+    Grid { n: u32 },
+    /// `rotated-grid`, four samples on a tilted grid, so no two share a row or column
+    RotatedGrid,
+    /// `jittered:<n>`, one sample at a random spot in each cell of an `n` by `n` grid, different
+    /// for every pixel
+    Jittered { n: u32 },
+}
+
+impl FromStr for Supersampling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let side = |n: &str| {
+            n.parse::<u32>()
+                .ok()
+                .filter(|n| (1..=MAX_SAMPLES_PER_SIDE).contains(n))
+                .ok_or_else(|| {
+                    format!(
+                        "samples per side of '{}' must be a whole number from 1 to {}",
+                        s, MAX_SAMPLES_PER_SIDE
+                    )
+                })
+        };
+        match s {
+            "none" => Ok(Supersampling::None),
+            "rotated-grid" => Ok(Supersampling::RotatedGrid),
+            _ => {
+                if let Some(n) = s.strip_prefix("jittered:") {
+                    Ok(Supersampling::Jittered { n: side(n)? })
+                } else if let Some((n, m)) = s.split_once('x') {
+                    if n != m {
+                        return Err(format!("grid '{}' must be square", s));
+                    }
+                    Ok(Supersampling::Grid { n: side(n)? })
+                } else {
+                    Err(format!("unknown supersampling '{}'", s))
+                }
+            }
+        }
+    }
+}
+
+impl Supersampling {
+    /// Samples across and down each pixel in the image the samples are rendered to.
+    pub fn samples_per_side(self) -> u32 {
+        match self {
+            Supersampling::None => 1,
+            Supersampling::Grid { n } | Supersampling::Jittered { n } => n,
+            Supersampling::RotatedGrid => 2,
+        }
+    }
+
+    /// Where each sample is taken within its pixel, from `(0, 0)` at the top left to `(1, 1)`
+    /// at the bottom right, row by row. Jittered samples are moved up to `jitter` further.
+    pub fn offsets(self) -> Vec<[f32; 2]> {
+        match self {
+            Supersampling::None => vec![[0.5, 0.5]],
+            Supersampling::RotatedGrid => vec![
+                [0.375, 0.125],
+                [0.875, 0.375],
+                [0.125, 0.625],
+                [0.625, 0.875],
+            ],
+            Supersampling::Grid { n } | Supersampling::Jittered { n } => {
+                let center = if let Supersampling::Grid { .. } = self {
+                    0.5
+                } else {
+                    0.0
+                };
+                (0..n * n)
+                    .map(|i| [i % n, i / n].map(|c| (c as f32 + center) / n as f32))
+                    .collect()
+            }
+        }
+    }
+
+    /// Size of the square each sample is moved around in at random, zero without jitter.
+    pub fn jitter(self) -> f32 {
+        match self {
+            Supersampling::Jittered { n } => 1.0 / n as f32,
+            _ => 0.0,
+        }
+    }
+
+    /// Where sample `index` of pixel `(x, y)` is taken, the same as in shader.glsl.
+    pub fn sample_offset(self, x: u32, y: u32, index: u32) -> [f32; 2] {
+        let offset = self.offsets()[index as usize];
+        if self.jitter() == 0.0 {
+            return offset;
+        }
+        let h = hash(x ^ hash(y ^ hash(index)));
+        let random = [h, hash(h)].map(|h| (h >> 8) as f32 / 16777216.0);
+        [0, 1].map(|i| offset[i] + self.jitter() * random[i])
+    }
+}
+
+/// lowbias32 by Chris Wellons, the same as `hash` in shader.glsl.
+fn hash(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846ca68b);
+    x ^= x >> 16;
+    x
+}
+
+#[derive(BufferContents, Clone, Copy)]
+#[repr(C)]
+struct DownsampleParams {
+    factor: u32,
+}
+
+/// Averages every `factor` by `factor` block of pixels of an image into one pixel.
+pub struct Downsampler {
+    pipeline: Arc<ComputePipeline>,
+}
+
+impl Downsampler {
+    /// Sets up for `R8G8B8A8_UNORM` images.
+    pub fn new(context: &Context) -> Self {
+        let shader =
+            shaders::rgba8::load(context.device.clone()).expect("failed to create shader module");
+        Self {
+            pipeline: context.compute_pipeline(shader),
+        }
+    }
+
+    /// Sets up for images of any normalized or float color format, which needs the
+    /// `shader_storage_image_read_without_format` and `shader_storage_image_write_without_format`
+    /// features.
+    pub fn unformatted(context: &Context) -> Self {
+        let shader = shaders::unformatted::load(context.device.clone())
+            .expect("failed to create shader module");
+        Self {
+            pipeline: context.compute_pipeline(shader),
+        }
+    }
+
+    /// Records downsampling `src` into `dst`, which is `factor` times smaller each way. Both
+    /// need `STORAGE` usage.
+    pub fn record(
+        &self,
+        context: &Context,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        src: Arc<Image>,
+        dst: Arc<Image>,
+        factor: u32,
+    ) {
+        let [width, height, _] = dst.extent();
+        let writes = [src, dst].into_iter().enumerate().map(|(binding, image)| {
+            let view = ImageView::new_default(image).expect("could not create image view");
+            WriteDescriptorSet::image_view(binding as u32, view)
+        });
+        let descriptor_set = context.descriptor_set(&self.pipeline, writes);
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())
+            .expect("failed to bind pipeline")
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.pipeline.layout().clone(),
+                0,
+                descriptor_set,
+            )
+            .expect("failed to bind descriptor set")
+            .push_constants(
+                self.pipeline.layout().clone(),
+                0,
+                DownsampleParams { factor },
+            )
+            .expect("failed to push downsample parameters")
+            .dispatch([width.div_ceil(8), height.div_ceil(8), 1])
+            .expect("failed to dispatch work groups");
+    }
+
+    /// Uploads `input`, downsamples it on the GPU and reads the result back. Rows and columns
+    /// past the last whole block are dropped.
+    pub fn apply(&self, context: &Context, input: &RgbaImage, factor: u32) -> RgbaImage {
+        let usage = ImageUsage::STORAGE | ImageUsage::TRANSFER_SRC;
+        let src = upload_rgba8(context, input, usage);
+        let dst = create_rgba8_image(
+            context,
+            input.width() / factor,
+            input.height() / factor,
+            usage,
+        );
+
+        let mut builder = context.command_buffer_builder();
+        self.record(context, &mut builder, src, dst.clone(), factor);
+        context.execute(builder);
+
+        download_rgba8(context, dst)
+    }
+}
+
+mod shaders {
+    pub mod rgba8 {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/fractal/downsample.glsl"
+        }
+    }
+
+    pub mod unformatted {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/fractal/downsample.glsl",
+            define: [("UNFORMATTED", "")]
+        }
+    }
+}
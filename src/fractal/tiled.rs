@@ -0,0 +1,97 @@
+use image::RgbaImage;
+
+use crate::context::Context;
+
+use super::{FractalRenderer, Precision, RenderSettings};
+
+/// Renders images bigger than a device image can be, or than fit in memory, one tile at a time.
+/// Every tile is the same size, so a single `FractalRenderer` draws them all, and tiles on the
+/// right and bottom edges are cropped.
+pub struct TiledRenderer {
+    renderer: FractalRenderer,
+    tile_width: u32,
+    tile_height: u32,
+}
+
+impl TiledRenderer {
+    /// Sets up for tiles of at most `tile_size` pixels square, shrunk to fit the image and, with
+    /// all their samples, the device's largest image.
+    pub fn new(
+        context: &Context,
+        settings: &RenderSettings,
+        tile_size: u32,
+    ) -> Result<Self, String> {
+        let max_size = context
+            .device
+            .physical_device()
+            .properties()
+            .max_image_dimension2_d;
+        let tile_size = tile_size.min(max_size / settings.supersampling.samples_per_side());
+        let tile_width = tile_size.min(settings.width);
+        let tile_height = tile_size.min(settings.height);
+        // every tile has the image's scale, so they all resolve to the same precision
+        let renderer =
+            FractalRenderer::new(context, &settings.tile(0, 0, tile_width, tile_height))?;
+        Ok(Self {
+            renderer,
+            tile_width,
+            tile_height,
+        })
+    }
+
+    /// The precision `Auto` resolved to.
+    pub fn precision(&self) -> Precision {
+        self.renderer.precision()
+    }
+
+    /// Whether the shader does its math in doubles.
+    pub fn uses_doubles(&self) -> bool {
+        self.renderer.uses_doubles()
+    }
+
+    /// Width and height of each tile.
+    pub fn tile_size(&self) -> [u32; 2] {
+        [self.tile_width, self.tile_height]
+    }
+
+    /// Renders `settings` a row of tiles at a time, top to bottom, handing `write_rows` the
+    /// RGBA8 pixels of the full width rows each row of tiles covers. Only one row of tiles is
+    /// held in memory.
+    pub fn render(
+        &self,
+        context: &Context,
+        settings: &RenderSettings,
+        mut write_rows: impl FnMut(&[u8]) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let row_bytes = settings.width as usize * 4;
+        let mut band = vec![0; row_bytes * self.tile_height as usize];
+        for y in (0..settings.height).step_by(self.tile_height as usize) {
+            let rows = self.tile_height.min(settings.height - y);
+            for x in (0..settings.width).step_by(self.tile_width as usize) {
+                let tile = self.renderer.render(
+                    context,
+                    &settings.tile(x, y, self.tile_width, self.tile_height),
+                )?;
+                copy_tile(&tile, &mut band, row_bytes, x, rows);
+            }
+            write_rows(&band[..row_bytes * rows as usize])?;
+        }
+        Ok(())
+    }
+}
+
+/// Copies the first `rows` rows of `tile` into `band` at column `x`, dropping whatever hangs
+/// off the right of the image.
+fn copy_tile(tile: &RgbaImage, band: &mut [u8], row_bytes: usize, x: u32, rows: u32) {
+    let tile_bytes = tile.width() as usize * 4;
+    let start = x as usize * 4;
+    let len = tile_bytes.min(row_bytes - start);
+    for (row, tile_row) in tile
+        .chunks_exact(tile_bytes)
+        .take(rows as usize)
+        .enumerate()
+    {
+        let offset = row * row_bytes + start;
+        band[offset..offset + len].copy_from_slice(&tile_row[..len]);
+    }
+}
@@ -0,0 +1,40 @@
+#version 460
+
+// Turns the hits of a Buddhabrot into colors, scaling each channel by its brightest pixel and
+// bending the result with a power curve so faint orbits still show.
+
+layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+layout(set = 0, binding = 0, rgba8) uniform writeonly image2D img;
+
+// width * height hits for red, then green, then blue
+layout(set = 0, binding = 1) readonly buffer Hits {
+    uint hits[];
+};
+
+layout(set = 0, binding = 2) readonly buffer Peaks {
+    uint peaks[3];
+};
+
+layout(push_constant) uniform ToneMapParams {
+    // 1 / gamma
+    float exponent;
+} params;
+
+void main() {
+    ivec2 pos = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = imageSize(img);
+    if (any(greaterThanEqual(pos, size))) {
+        return;
+    }
+
+    uint pixels = uint(size.x * size.y);
+    uint pixel = uint(pos.y * size.x + pos.x);
+    vec3 color = vec3(0.0);
+    for (uint k = 0; k < 3; k++) {
+        if (peaks[k] > 0) {
+            color[k] = pow(float(hits[k * pixels + pixel]) / float(peaks[k]), params.exponent);
+        }
+    }
+    imageStore(img, pos, vec4(color, 1.0));
+}
@@ -0,0 +1,53 @@
+#version 460
+
+layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+#ifdef VOLUME
+#define IMAGE image3D
+#else
+#define IMAGE image2DArray
+#endif
+
+#ifdef UNFORMATTED
+// the images' format is only known at runtime, which needs shaderStorageImageReadWithoutFormat
+// and shaderStorageImageWriteWithoutFormat
+#extension GL_EXT_shader_image_load_formatted : require
+layout(set = 0, binding = 0) uniform readonly IMAGE src;
+layout(set = 0, binding = 1) uniform writeonly IMAGE dst;
+#else
+layout(set = 0, binding = 0, rgba8) uniform readonly IMAGE src;
+layout(set = 0, binding = 1, rgba8) uniform writeonly IMAGE dst;
+#endif
+
+layout(push_constant) uniform BlurParams {
+    int radius;
+} params;
+
+// averages the pixels up to radius away, leaving out the ones past the edges
+void main() {
+    ivec3 pos = ivec3(gl_GlobalInvocationID);
+    ivec3 size = imageSize(src);
+    if (any(greaterThanEqual(pos, size))) {
+        return;
+    }
+
+#ifdef VOLUME
+    // neighbouring slices of a volume are blurred together
+    ivec3 lo = max(pos - params.radius, ivec3(0));
+    ivec3 hi = min(pos + params.radius, size - 1);
+#else
+    // but layers of an array, and faces of a cubemap, are separate images
+    ivec3 lo = ivec3(max(pos.xy - params.radius, ivec2(0)), pos.z);
+    ivec3 hi = ivec3(min(pos.xy + params.radius, size.xy - 1), pos.z);
+#endif
+    vec4 sum = vec4(0.0);
+    for (int z = lo.z; z <= hi.z; z++) {
+        for (int y = lo.y; y <= hi.y; y++) {
+            for (int x = lo.x; x <= hi.x; x++) {
+                sum += imageLoad(src, ivec3(x, y, z));
+            }
+        }
+    }
+    ivec3 count = hi - lo + 1;
+    imageStore(dst, pos, sum / float(count.x * count.y * count.z));
+}
@@ -0,0 +1,45 @@
+#version 460
+
+layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+// 2D arrays and cubemaps are both written as a 2D array of every layer, while volumes are
+// written as a 3D image. either way pixels are addressed by x, y and layer or slice
+#ifdef VOLUME
+#define IMAGE image3D
+#else
+#define IMAGE image2DArray
+#endif
+
+#ifdef UNFORMATTED
+// the image's format is only known at runtime, which needs shaderStorageImageWriteWithoutFormat
+layout(set = 0, binding = 0) uniform writeonly IMAGE dst;
+#else
+layout(set = 0, binding = 0, rgba8) uniform writeonly IMAGE dst;
+#endif
+
+#define GRADIENT 0u
+#define SPHERE 1u
+
+layout(push_constant) uniform FillParams {
+    uint pattern;
+} params;
+
+void main() {
+    ivec3 pos = ivec3(gl_GlobalInvocationID);
+    ivec3 size = imageSize(dst);
+    if (any(greaterThanEqual(pos, size))) {
+        return;
+    }
+
+    // the center of the pixel in [0, 1] on every axis, with layers standing in for depth
+    vec3 p = (vec3(pos) + 0.5) / vec3(size);
+    vec4 color;
+    if (params.pattern == SPHERE) {
+        // densest in the middle, fading to nothing at the middle of each side
+        float density = clamp(1.0 - 2.0 * distance(p, vec3(0.5)), 0.0, 1.0);
+        color = vec4(vec3(density), 1.0);
+    } else {
+        color = vec4(p, 1.0);
+    }
+    imageStore(dst, pos, color);
+}
@@ -0,0 +1,551 @@
+//! Images with more than one layer or slice: 2D arrays, 3D volumes and cubemaps, and compute
+//! shaders that fill and blur every layer of them at once.
+
+use std::{str::FromStr, sync::Arc};
+
+use image::{Rgba, Rgba32FImage};
+use vulkano::{
+    buffer::BufferContents,
+    command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer},
+    descriptor_set::WriteDescriptorSet,
+    format::{Format, FormatFeatures},
+    image::{
+        view::{ImageView, ImageViewCreateInfo, ImageViewType},
+        Image, ImageCreateFlags, ImageCreateInfo, ImageType, ImageUsage,
+    },
+    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter},
+    pipeline::{ComputePipeline, Pipeline, PipelineBindPoint},
+};
+
+use crate::{
+    context::Context,
+    format::{check_format_support, ColorFormat},
+    transfer::usage_features,
+};
+
+/// How the layers of an image are arranged, parsed from the command line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    /// `array:<layers>`, a 2D array
+    Array { layers: u32 },
+    /// `volume:<depth>`, a 3D image
+    Volume { depth: u32 },
+    /// `cube` or `cube:<layers>`, an array of `layers` cubemaps with 6 square faces each
+    Cube { layers: u32 },
+}
+
+impl FromStr for Shape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, count) = s.split_once(':').unwrap_or((s, ""));
+        let count = match (name, count) {
+            ("cube", "") => 1,
+            (_, count) => count
+                .parse()
+                .ok()
+                .filter(|&count: &u32| count > 0)
+                .ok_or_else(|| format!("'{}' needs a positive count, like '{}:4'", s, name))?,
+        };
+        match name {
+            "array" => Ok(Shape::Array { layers: count }),
+            "volume" => Ok(Shape::Volume { depth: count }),
+            "cube" => Ok(Shape::Cube { layers: count }),
+            _ => Err(format!("unknown shape '{}'", s)),
+        }
+    }
+}
+
+impl Shape {
+    /// Extent of images of this shape with layers or slices of the given size.
+    pub fn extent(self, width: u32, height: u32) -> [u32; 3] {
+        match self {
+            Shape::Volume { depth } => [width, height, depth],
+            _ => [width, height, 1],
+        }
+    }
+
+    /// Array layers of images of this shape, 6 for each cubemap, or `None` when there are too
+    /// many to count.
+    pub fn array_layers(self) -> Option<u32> {
+        match self {
+            Shape::Array { layers } => Some(layers),
+            Shape::Volume { .. } => Some(1),
+            Shape::Cube { layers } => layers.checked_mul(6),
+        }
+    }
+
+    /// 2D images in the shape, which the shaders see as layers or slices, or `None` when there
+    /// are too many to count.
+    pub fn slices(self) -> Option<u32> {
+        match self {
+            Shape::Volume { depth } => Some(depth),
+            _ => self.array_layers(),
+        }
+    }
+
+    pub fn image_type(self) -> ImageType {
+        match self {
+            Shape::Volume { .. } => ImageType::Dim3d,
+            _ => ImageType::Dim2d,
+        }
+    }
+
+    pub fn flags(self) -> ImageCreateFlags {
+        match self {
+            Shape::Cube { .. } => ImageCreateFlags::CUBE_COMPATIBLE,
+            _ => ImageCreateFlags::empty(),
+        }
+    }
+
+    /// The shape of `image`, telling cubemaps apart by `CUBE_COMPATIBLE`.
+    pub fn of(image: &Image) -> Self {
+        let layers = image.array_layers();
+        if image.image_type() == ImageType::Dim3d {
+            Shape::Volume {
+                depth: image.extent()[2],
+            }
+        } else if image.flags().intersects(ImageCreateFlags::CUBE_COMPATIBLE)
+            && layers.is_multiple_of(6)
+        {
+            Shape::Cube { layers: layers / 6 }
+        } else {
+            Shape::Array { layers }
+        }
+    }
+}
+
+/// Creates an image of `shape` with one mip level, checking the device can use images of
+/// `format` for `usage`. Cubemap faces have to be square.
+pub fn create_layered_image(
+    context: &Context,
+    format: ColorFormat,
+    width: u32,
+    height: u32,
+    shape: Shape,
+    usage: ImageUsage,
+) -> Result<Arc<Image>, String> {
+    if matches!(shape, Shape::Cube { .. }) && width != height {
+        return Err(format!(
+            "cubemap faces have to be square, not {}x{}",
+            width, height
+        ));
+    }
+    let max_layers = context
+        .device
+        .physical_device()
+        .properties()
+        .max_image_array_layers;
+    let array_layers = shape
+        .array_layers()
+        .filter(|&layers| layers <= max_layers)
+        .ok_or_else(|| {
+            format!(
+                "{:?} needs more than the {} array layers the device allows",
+                shape, max_layers
+            )
+        })?;
+    check_format_support(
+        context.device.physical_device(),
+        format.format(),
+        usage_features(usage),
+    )?;
+    Image::new(
+        context.memory_allocator.clone(),
+        ImageCreateInfo {
+            flags: shape.flags(),
+            image_type: shape.image_type(),
+            format: format.format(),
+            extent: shape.extent(width, height),
+            array_layers,
+            usage,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+            ..Default::default()
+        },
+    )
+    .map_err(|e| format!("could not create image: {}", e))
+}
+
+/// What `LayeredProcessor::fill` draws, parsed from the command line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pattern {
+    /// `gradient`, red, green and blue going from 0 to 1 across the width, height and layers
+    Gradient,
+    /// `sphere`, a ball of white fading out from the middle of the image
+    Sphere,
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gradient" => Ok(Pattern::Gradient),
+            "sphere" => Ok(Pattern::Sphere),
+            _ => Err(format!("unknown pattern '{}'", s)),
+        }
+    }
+}
+
+#[derive(BufferContents, Clone, Copy)]
+#[repr(C)]
+struct FillParams {
+    pattern: u32,
+}
+
+#[derive(BufferContents, Clone, Copy)]
+#[repr(C)]
+struct BlurParams {
+    radius: i32,
+}
+
+/// The shaders for one kind of storage image.
+struct Pipelines {
+    fill_array: Arc<ComputePipeline>,
+    fill_volume: Arc<ComputePipeline>,
+    blur_array: Arc<ComputePipeline>,
+    blur_volume: Arc<ComputePipeline>,
+}
+
+impl Pipelines {
+    fn fill(&self, shape: Shape) -> &Arc<ComputePipeline> {
+        match shape {
+            Shape::Volume { .. } => &self.fill_volume,
+            _ => &self.fill_array,
+        }
+    }
+
+    fn blur(&self, shape: Shape) -> &Arc<ComputePipeline> {
+        match shape {
+            Shape::Volume { .. } => &self.blur_volume,
+            _ => &self.blur_array,
+        }
+    }
+}
+
+/// Fills and blurs every layer, face or slice of an image in one dispatch.
+pub struct LayeredProcessor {
+    rgba8: Pipelines,
+    /// Only there when the device has the features to read and write storage images without a
+    /// format.
+    unformatted: Option<Pipelines>,
+}
+
+impl LayeredProcessor {
+    pub fn new(context: &Context) -> Self {
+        let pipeline = |shader: Result<_, _>| {
+            context.compute_pipeline(shader.expect("failed to create shader module"))
+        };
+        let rgba8 = Pipelines {
+            fill_array: pipeline(shaders::fill_array_rgba8::load(context.device.clone())),
+            fill_volume: pipeline(shaders::fill_volume_rgba8::load(context.device.clone())),
+            blur_array: pipeline(shaders::blur_array_rgba8::load(context.device.clone())),
+            blur_volume: pipeline(shaders::blur_volume_rgba8::load(context.device.clone())),
+        };
+        let features = context.device.enabled_features();
+        let unformatted = (features.shader_storage_image_read_without_format
+            && features.shader_storage_image_write_without_format)
+            .then(|| Pipelines {
+                fill_array: pipeline(shaders::fill_array::load(context.device.clone())),
+                fill_volume: pipeline(shaders::fill_volume::load(context.device.clone())),
+                blur_array: pipeline(shaders::blur_array::load(context.device.clone())),
+                blur_volume: pipeline(shaders::blur_volume::load(context.device.clone())),
+            });
+        Self { rgba8, unformatted }
+    }
+
+    /// Whether images of `format` can be filled and blurred on this device, and why not if they
+    /// can't.
+    pub fn check_support(&self, context: &Context, format: ColorFormat) -> Result<(), String> {
+        check_format_support(
+            context.device.physical_device(),
+            format.format(),
+            FormatFeatures::STORAGE_IMAGE,
+        )?;
+        if format != ColorFormat::Rgba8 && self.unformatted.is_none() {
+            return Err(format!(
+                "processing {:?} in a compute shader needs the \
+                 shader_storage_image_read_without_format and \
+                 shader_storage_image_write_without_format features",
+                format.format()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Creates an image of `shape` the shaders can write, that can also be copied to and from.
+    pub fn create(
+        &self,
+        context: &Context,
+        format: ColorFormat,
+        width: u32,
+        height: u32,
+        shape: Shape,
+    ) -> Result<Arc<Image>, String> {
+        self.check_support(context, format)?;
+        create_layered_image(
+            context,
+            format,
+            width,
+            height,
+            shape,
+            ImageUsage::STORAGE | ImageUsage::TRANSFER_SRC | ImageUsage::TRANSFER_DST,
+        )
+    }
+
+    /// Draws `pattern` into every layer of `image`, made by `create`, and waits for it.
+    pub fn fill(&self, context: &Context, image: Arc<Image>, pattern: Pattern) {
+        let mut builder = context.command_buffer_builder();
+        self.record_fill(context, &mut builder, image, pattern);
+        context.execute(builder);
+    }
+
+    /// Blurs `image`, made by `create`, into a new image of the same shape and waits for it.
+    /// Each pixel becomes the average of those up to `radius` away in x and y, and in z too for
+    /// volumes.
+    pub fn blur(&self, context: &Context, image: Arc<Image>, radius: u32) -> Arc<Image> {
+        let dst = Image::new(
+            context.memory_allocator.clone(),
+            ImageCreateInfo {
+                flags: image.flags(),
+                image_type: image.image_type(),
+                format: image.format(),
+                extent: image.extent(),
+                array_layers: image.array_layers(),
+                usage: image.usage(),
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..Default::default()
+            },
+        )
+        .expect("could not create image");
+        let mut builder = context.command_buffer_builder();
+        self.record_blur(context, &mut builder, image, dst.clone(), radius);
+        context.execute(builder);
+        dst
+    }
+
+    /// Records `fill`.
+    pub fn record_fill(
+        &self,
+        context: &Context,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image: Arc<Image>,
+        pattern: Pattern,
+    ) {
+        let shape = Shape::of(&image);
+        let pipeline = self.pipelines(&image).fill(shape);
+        // one of depth and layers is 1
+        let [width, height, depth] = image.extent();
+        let slices = depth * image.array_layers();
+        let descriptor_set = context.descriptor_set(
+            pipeline,
+            [WriteDescriptorSet::image_view(0, storage_view(image))],
+        );
+        builder
+            .bind_pipeline_compute(pipeline.clone())
+            .expect("failed to bind pipeline")
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                descriptor_set,
+            )
+            .expect("failed to bind descriptor set")
+            .push_constants(
+                pipeline.layout().clone(),
+                0,
+                FillParams {
+                    pattern: pattern as u32,
+                },
+            )
+            .expect("failed to push fill parameters")
+            .dispatch([width.div_ceil(8), height.div_ceil(8), slices])
+            .expect("failed to dispatch work groups");
+    }
+
+    /// Records `blur` from `src` into `dst`, both made by `create` with the same shape.
+    pub fn record_blur(
+        &self,
+        context: &Context,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        src: Arc<Image>,
+        dst: Arc<Image>,
+        radius: u32,
+    ) {
+        let shape = Shape::of(&src);
+        let pipeline = self.pipelines(&src).blur(shape);
+        let [width, height, depth] = src.extent();
+        let slices = depth * src.array_layers();
+        let descriptor_set = context.descriptor_set(
+            pipeline,
+            [
+                WriteDescriptorSet::image_view(0, storage_view(src)),
+                WriteDescriptorSet::image_view(1, storage_view(dst)),
+            ],
+        );
+        builder
+            .bind_pipeline_compute(pipeline.clone())
+            .expect("failed to bind pipeline")
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                descriptor_set,
+            )
+            .expect("failed to bind descriptor set")
+            .push_constants(
+                pipeline.layout().clone(),
+                0,
+                BlurParams {
+                    radius: radius as i32,
+                },
+            )
+            .expect("failed to push blur parameters")
+            .dispatch([width.div_ceil(8), height.div_ceil(8), slices])
+            .expect("failed to dispatch work groups");
+    }
+
+    fn pipelines(&self, image: &Image) -> &Pipelines {
+        match image.format() {
+            Format::R8G8B8A8_UNORM => &self.rgba8,
+            _ => self
+                .unformatted
+                .as_ref()
+                .expect("no shader for images of this format"),
+        }
+    }
+}
+
+/// A view of the first mip level of `image` as the shaders see it: the whole volume, or every
+/// layer as a 2D array, cubemap faces included.
+fn storage_view(image: Arc<Image>) -> Arc<ImageView> {
+    let mut create_info = ImageViewCreateInfo::from_image(&image);
+    create_info.view_type = match image.image_type() {
+        ImageType::Dim3d => ImageViewType::Dim3d,
+        _ => ImageViewType::Dim2dArray,
+    };
+    create_info.subresource_range.mip_levels = 0..1;
+    ImageView::new(image, create_info).expect("could not create image view")
+}
+
+/// Every layer, face or slice `fill` draws for `pattern` in images of `shape`, in the order
+/// they're stored.
+pub fn fill_cpu(width: u32, height: u32, shape: Shape, pattern: Pattern) -> Vec<Rgba32FImage> {
+    let slices = shape.slices().expect("too many slices to draw");
+    (0..slices)
+        .map(|z| {
+            Rgba32FImage::from_fn(width, height, |x, y| {
+                let p = [(x, width), (y, height), (z, slices)]
+                    .map(|(i, size)| (i as f32 + 0.5) / size as f32);
+                match pattern {
+                    Pattern::Gradient => Rgba([p[0], p[1], p[2], 1.0]),
+                    Pattern::Sphere => {
+                        let distance = p.iter().map(|c| (c - 0.5).powi(2)).sum::<f32>().sqrt();
+                        let density = (1.0 - 2.0 * distance).clamp(0.0, 1.0);
+                        Rgba([density, density, density, 1.0])
+                    }
+                }
+            })
+        })
+        .collect()
+}
+
+/// Blurs `slices` the same as `blur` does images of `shape`.
+pub fn blur_cpu(slices: &[Rgba32FImage], shape: Shape, radius: u32) -> Vec<Rgba32FImage> {
+    let radius = radius as i64;
+    let depth = slices.len() as i64;
+    let volume = matches!(shape, Shape::Volume { .. });
+    (0..depth)
+        .map(|z| {
+            let (width, height) = slices[z as usize].dimensions();
+            let range = |i: i64, size: i64| (i - radius).max(0)..=(i + radius).min(size - 1);
+            let depth_range = if volume { range(z, depth) } else { z..=z };
+            Rgba32FImage::from_fn(width, height, |x, y| {
+                let mut sum = [0.0; 4];
+                let mut count = 0.0;
+                for sz in depth_range.clone() {
+                    for sy in range(y as i64, height as i64) {
+                        for sx in range(x as i64, width as i64) {
+                            let pixel = slices[sz as usize].get_pixel(sx as u32, sy as u32);
+                            for (sum, c) in sum.iter_mut().zip(pixel.0) {
+                                *sum += c;
+                            }
+                            count += 1.0;
+                        }
+                    }
+                }
+                Rgba(sum.map(|c| c / count))
+            })
+        })
+        .collect()
+}
+
+mod shaders {
+    pub mod fill_array_rgba8 {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/layered/fill.glsl"
+        }
+    }
+
+    pub mod fill_volume_rgba8 {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/layered/fill.glsl",
+            define: [("VOLUME", "")]
+        }
+    }
+
+    pub mod blur_array_rgba8 {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/layered/blur.glsl"
+        }
+    }
+
+    pub mod blur_volume_rgba8 {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/layered/blur.glsl",
+            define: [("VOLUME", "")]
+        }
+    }
+
+    pub mod fill_array {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/layered/fill.glsl",
+            define: [("UNFORMATTED", "")]
+        }
+    }
+
+    pub mod fill_volume {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/layered/fill.glsl",
+            define: [("VOLUME", ""), ("UNFORMATTED", "")]
+        }
+    }
+
+    pub mod blur_array {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/layered/blur.glsl",
+            define: [("UNFORMATTED", "")]
+        }
+    }
+
+    pub mod blur_volume {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/layered/blur.glsl",
+            define: [("VOLUME", ""), ("UNFORMATTED", "")]
+        }
+    }
+}
@@ -0,0 +1,16 @@
+//! Code shared between the examples and the tests: device setup, moving images between the host
+//! and the GPU, and the compute workloads more than one place needs.
+
+pub mod compare;
+pub mod context;
+pub mod convert;
+pub mod filters;
+pub mod format;
+pub mod layered;
+pub mod fractal;
+pub mod mipmap;
+pub mod output;
+pub mod split;
+pub mod stats;
+pub mod texture;
+pub mod transfer;
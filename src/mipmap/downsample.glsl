@@ -0,0 +1,39 @@
+#version 460
+
+layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+#ifdef UNFORMATTED
+// the images' format is only known at runtime, which needs shaderStorageImageReadWithoutFormat
+// and shaderStorageImageWriteWithoutFormat
+#extension GL_EXT_shader_image_load_formatted : require
+layout(set = 0, binding = 0) uniform readonly image2D src;
+layout(set = 0, binding = 1) uniform writeonly image2D dst;
+#else
+layout(set = 0, binding = 0, rgba8) uniform readonly image2D src;
+layout(set = 0, binding = 1, rgba8) uniform writeonly image2D dst;
+#endif
+
+// box filters src, one mip level, into dst, one level smaller
+void main() {
+    ivec2 pos = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 dst_size = imageSize(dst);
+    if (any(greaterThanEqual(pos, dst_size))) {
+        return;
+    }
+
+    // each pixel of dst covers a scale by scale area of src, which only lines up with src's
+    // pixels when its sides are even, so pixels on the edges of the area are weighted by how
+    // much of them it covers
+    vec2 scale = vec2(imageSize(src)) / vec2(dst_size);
+    vec2 lo = vec2(pos) * scale;
+    vec2 hi = lo + scale;
+    vec4 sum = vec4(0.0);
+    for (int y = int(lo.y); y < int(ceil(hi.y)); y++) {
+        float weight_y = min(hi.y, float(y + 1)) - max(lo.y, float(y));
+        for (int x = int(lo.x); x < int(ceil(hi.x)); x++) {
+            float weight_x = min(hi.x, float(x + 1)) - max(lo.x, float(x));
+            sum += weight_x * weight_y * imageLoad(src, ivec2(x, y));
+        }
+    }
+    imageStore(dst, pos, sum / (scale.x * scale.y));
+}
@@ -0,0 +1,280 @@
+use std::{str::FromStr, sync::Arc};
+
+use image::{DynamicImage, Rgba, Rgba32FImage};
+use vulkano::{
+    command_buffer::{
+        AutoCommandBufferBuilder, BlitImageInfo, ImageBlit, PrimaryAutoCommandBuffer,
+    },
+    descriptor_set::WriteDescriptorSet,
+    format::{Format, FormatFeatures},
+    image::{
+        sampler::Filter,
+        view::{ImageView, ImageViewCreateInfo},
+        Image, ImageAspects, ImageSubresourceLayers, ImageUsage,
+    },
+    pipeline::{ComputePipeline, Pipeline, PipelineBindPoint},
+};
+
+use crate::{
+    context::Context,
+    format::ColorFormat,
+    transfer::{read_back, upload_converted_mipmapped, Readback, Subresource},
+};
+
+/// Mip levels in a full chain for an image of the given size, down to 1x1.
+pub fn mip_levels(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// Size of mip level `level` of an image of the given size.
+pub fn level_extent(width: u32, height: u32, level: u32) -> [u32; 2] {
+    [width, height].map(|side| (side >> level).max(1))
+}
+
+/// How the levels after the first are worked out, parsed from the command line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MipmapMethod {
+    /// `blit`, `blit_image` with linear filtering, which needs the format to support blits and
+    /// linear filtering
+    Blit,
+    /// `compute`, a box filter in a compute shader, which needs the format to support storage
+    /// images
+    Compute,
+}
+
+impl FromStr for MipmapMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blit" => Ok(MipmapMethod::Blit),
+            "compute" => Ok(MipmapMethod::Compute),
+            _ => Err(format!("unknown mipmap method '{}'", s)),
+        }
+    }
+}
+
+/// Fills in the mip chains of images from their first level.
+pub struct MipmapGenerator {
+    rgba8: Arc<ComputePipeline>,
+    /// Only there when the device has the features to read and write storage images without a
+    /// format.
+    unformatted: Option<Arc<ComputePipeline>>,
+}
+
+impl MipmapGenerator {
+    pub fn new(context: &Context) -> Self {
+        let rgba8 =
+            shaders::rgba8::load(context.device.clone()).expect("failed to create shader module");
+        let features = context.device.enabled_features();
+        let unformatted = (features.shader_storage_image_read_without_format
+            && features.shader_storage_image_write_without_format)
+            .then(|| {
+                let shader = shaders::unformatted::load(context.device.clone())
+                    .expect("failed to create shader module");
+                context.compute_pipeline(shader)
+            });
+        Self {
+            rgba8: context.compute_pipeline(rgba8),
+            unformatted,
+        }
+    }
+
+    /// Whether `method` works for images of `format` on this device, and why not if it doesn't.
+    pub fn check_support(
+        &self,
+        context: &Context,
+        format: ColorFormat,
+        method: MipmapMethod,
+    ) -> Result<(), String> {
+        let physical_device = context.device.physical_device();
+        match method {
+            MipmapMethod::Blit => format.check_support(
+                physical_device,
+                FormatFeatures::BLIT_SRC
+                    | FormatFeatures::BLIT_DST
+                    | FormatFeatures::SAMPLED_IMAGE_FILTER_LINEAR,
+            ),
+            MipmapMethod::Compute => {
+                format.check_support(physical_device, FormatFeatures::STORAGE_IMAGE)?;
+                if format != ColorFormat::Rgba8 && self.unformatted.is_none() {
+                    return Err(format!(
+                        "downsampling {:?} in a compute shader needs the \
+                         shader_storage_image_read_without_format and \
+                         shader_storage_image_write_without_format features",
+                        format.format()
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Blits when the format allows, otherwise falls back to the compute shader.
+    pub fn pick_method(
+        &self,
+        context: &Context,
+        format: ColorFormat,
+    ) -> Result<MipmapMethod, String> {
+        self.check_support(context, format, MipmapMethod::Blit)
+            .map(|_| MipmapMethod::Blit)
+            .or_else(|blit_error| {
+                self.check_support(context, format, MipmapMethod::Compute)
+                    .map(|_| MipmapMethod::Compute)
+                    .map_err(|e| format!("{}, and {}", blit_error, e))
+            })
+    }
+
+    /// Uploads `pixels` as the first level of a new image of `format` with a full mip chain,
+    /// generates the rest with `method` and waits for it to finish. The image has `TRANSFER_SRC`
+    /// usage, on top of whatever `method` needs.
+    pub fn generate(
+        &self,
+        context: &Context,
+        pixels: &DynamicImage,
+        format: ColorFormat,
+        method: MipmapMethod,
+    ) -> Result<Arc<Image>, String> {
+        self.check_support(context, format, method)?;
+        let usage = match method {
+            MipmapMethod::Blit => ImageUsage::TRANSFER_SRC | ImageUsage::TRANSFER_DST,
+            MipmapMethod::Compute => ImageUsage::TRANSFER_SRC | ImageUsage::STORAGE,
+        };
+        let levels = mip_levels(pixels.width(), pixels.height());
+        let image = upload_converted_mipmapped(context, pixels, format, levels, usage)?;
+
+        let mut builder = context.command_buffer_builder();
+        self.record(context, &mut builder, image.clone(), method);
+        context.execute(builder);
+
+        Ok(image)
+    }
+
+    /// Records filling in every level of `image` after the first, each from the one before.
+    /// The image needs `TRANSFER_SRC` and `TRANSFER_DST` usage to blit, or `STORAGE` usage for
+    /// the compute shader.
+    pub fn record(
+        &self,
+        context: &Context,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image: Arc<Image>,
+        method: MipmapMethod,
+    ) {
+        let [width, height, _] = image.extent();
+        for level in 1..image.mip_levels() {
+            let [src_width, src_height] = level_extent(width, height, level - 1);
+            let [dst_width, dst_height] = level_extent(width, height, level);
+            match method {
+                MipmapMethod::Blit => {
+                    let subresource = |mip_level| ImageSubresourceLayers {
+                        aspects: ImageAspects::COLOR,
+                        mip_level,
+                        array_layers: 0..1,
+                    };
+                    builder
+                        .blit_image(BlitImageInfo {
+                            regions: [ImageBlit {
+                                src_subresource: subresource(level - 1),
+                                src_offsets: [[0, 0, 0], [src_width, src_height, 1]],
+                                dst_subresource: subresource(level),
+                                dst_offsets: [[0, 0, 0], [dst_width, dst_height, 1]],
+                                ..Default::default()
+                            }]
+                            .into(),
+                            filter: Filter::Linear,
+                            ..BlitImageInfo::images(image.clone(), image.clone())
+                        })
+                        .expect("failed to blit mip level");
+                }
+                MipmapMethod::Compute => {
+                    let pipeline = match image.format() {
+                        Format::R8G8B8A8_UNORM => &self.rgba8,
+                        _ => self
+                            .unformatted
+                            .as_ref()
+                            .expect("no shader for images of this format"),
+                    };
+                    let writes =
+                        [level - 1, level]
+                            .into_iter()
+                            .enumerate()
+                            .map(|(binding, level)| {
+                                let mut create_info = ImageViewCreateInfo::from_image(&image);
+                                create_info.subresource_range.mip_levels = level..level + 1;
+                                let view = ImageView::new(image.clone(), create_info)
+                                    .expect("could not create image view");
+                                WriteDescriptorSet::image_view(binding as u32, view)
+                            });
+                    let descriptor_set = context.descriptor_set(pipeline, writes);
+                    builder
+                        .bind_pipeline_compute(pipeline.clone())
+                        .expect("failed to bind pipeline")
+                        .bind_descriptor_sets(
+                            PipelineBindPoint::Compute,
+                            pipeline.layout().clone(),
+                            0,
+                            descriptor_set,
+                        )
+                        .expect("failed to bind descriptor set")
+                        .dispatch([dst_width.div_ceil(8), dst_height.div_ceil(8), 1])
+                        .expect("failed to dispatch work groups");
+                }
+            }
+        }
+    }
+}
+
+/// Reads back every mip level of `image`, first to last.
+pub fn read_back_levels(context: &Context, image: Arc<Image>) -> Result<Vec<Readback>, String> {
+    (0..image.mip_levels())
+        .map(|mip_level| {
+            let subresource = Subresource {
+                mip_level,
+                array_layer: 0,
+            };
+            read_back(context, image.clone(), subresource)
+        })
+        .collect()
+}
+
+/// The next mip level of `image`, box filtered the same as the compute shader.
+pub fn downsample_cpu(image: &Rgba32FImage) -> Rgba32FImage {
+    let (width, height) = image.dimensions();
+    let [dst_width, dst_height] = level_extent(width, height, 1);
+    let scale = [
+        width as f32 / dst_width as f32,
+        height as f32 / dst_height as f32,
+    ];
+    Rgba32FImage::from_fn(dst_width, dst_height, |x, y| {
+        let lo = [x as f32 * scale[0], y as f32 * scale[1]];
+        let hi = [lo[0] + scale[0], lo[1] + scale[1]];
+        let mut sum = [0.0; 4];
+        for sy in lo[1] as u32..hi[1].ceil() as u32 {
+            let weight_y = hi[1].min(sy as f32 + 1.0) - lo[1].max(sy as f32);
+            for sx in lo[0] as u32..hi[0].ceil() as u32 {
+                let weight_x = hi[0].min(sx as f32 + 1.0) - lo[0].max(sx as f32);
+                for (sum, c) in sum.iter_mut().zip(image.get_pixel(sx, sy).0) {
+                    *sum += weight_x * weight_y * c;
+                }
+            }
+        }
+        Rgba(sum.map(|c| c / (scale[0] * scale[1])))
+    })
+}
+
+mod shaders {
+    pub mod rgba8 {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/mipmap/downsample.glsl"
+        }
+    }
+
+    pub mod unformatted {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "src/mipmap/downsample.glsl",
+            define: [("UNFORMATTED", "")]
+        }
+    }
+}
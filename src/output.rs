@@ -0,0 +1,226 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Map, Value};
+
+/// Where an example saves its files, from `--out-dir` and `--overwrite`.
+#[derive(Clone, Debug, Default)]
+pub struct OutputOptions {
+    /// Relative output paths are taken from here, or the working directory when it's empty.
+    pub dir: PathBuf,
+    /// Whether files that are already there may be replaced.
+    pub overwrite: bool,
+}
+
+/// The files one run of an example writes, and a JSON sidecar next to the first of them saying
+/// how they were made.
+pub struct Output {
+    options: OutputOptions,
+    workload: String,
+    arguments: Vec<String>,
+    parameters: Map<String, Value>,
+    started: SystemTime,
+    files: Vec<PathBuf>,
+    sidecar: Option<PathBuf>,
+}
+
+impl Output {
+    /// Starts a run of `workload`, recording the command line and the time for the sidecar.
+    pub fn new(workload: &str, options: OutputOptions) -> Self {
+        Self {
+            options,
+            workload: workload.to_string(),
+            arguments: env::args().skip(1).collect(),
+            parameters: Map::new(),
+            started: SystemTime::now(),
+            files: Vec::new(),
+            sidecar: None,
+        }
+    }
+
+    /// Records a setting of the run in the sidecar.
+    pub fn set_parameter(&mut self, name: &str, value: impl Into<Value>) {
+        self.parameters.insert(name.to_string(), value.into());
+    }
+
+    /// A name made of the workload, `parameters` and when the run started, like
+    /// `mandelbrot-1024x1024-i200-20261018-183500.png`. Anything but letters, digits, `.`, `-`
+    /// and `_` in the parameters becomes `_`.
+    pub fn file_name(&self, parameters: &[String], extension: &str) -> String {
+        let mut parts = vec![self.workload.clone()];
+        parts.extend(parameters.iter().map(|parameter| {
+            parameter
+                .chars()
+                .map(|c| match c {
+                    'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+                    _ => '_',
+                })
+                .collect()
+        }));
+        parts.push(timestamp(self.started));
+        let name = parts.join("-");
+        if extension.is_empty() {
+            name
+        } else {
+            format!("{}.{}", name, extension)
+        }
+    }
+
+    /// Takes `path` relative to the output directory and makes sure it can be written: its
+    /// directory is created, it hasn't been claimed already and, unless overwriting, nothing may
+    /// be there yet. The first path claimed names the sidecar, which is checked the same way: it
+    /// has .json in place of the path's extension, or .sidecar.json when that's already .json.
+    pub fn claim(&mut self, path: &str) -> Result<PathBuf, String> {
+        let path = self.options.dir.join(path);
+        if self.sidecar.is_none() {
+            self.name_sidecar(sidecar_path(&path))?;
+        }
+        self.claim_path(path)
+    }
+
+    /// Like `claim`, for the numbered `files` that stand in for `path`, like the frames of an
+    /// animation. The sidecar is named after `path`, which is returned in the output directory.
+    pub fn claim_sequence(&mut self, path: &str, files: &[String]) -> Result<PathBuf, String> {
+        let path = self.options.dir.join(path);
+        if self.sidecar.is_none() {
+            self.name_sidecar(sidecar_path(&path))?;
+        }
+        for file in files {
+            self.claim_path(self.options.dir.join(file))?;
+        }
+        Ok(path)
+    }
+
+    /// Like `claim`, but for a directory to save files in, which may already exist. The files
+    /// going in it are claimed one by one.
+    pub fn claim_dir(&mut self, path: &str) -> Result<PathBuf, String> {
+        let path = self.options.dir.join(path);
+        fs::create_dir_all(&path)
+            .map_err(|e| format!("could not create '{}': {}", path.display(), e))?;
+        if self.sidecar.is_none() {
+            // directory names may have dots in them, so the extension is added on the end
+            let mut sidecar = path.clone().into_os_string();
+            sidecar.push(".json");
+            self.name_sidecar(PathBuf::from(sidecar))?;
+        }
+        Ok(path)
+    }
+
+    fn claim_path(&mut self, path: PathBuf) -> Result<PathBuf, String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("could not create '{}': {}", parent.display(), e))?;
+        }
+        self.check_free(&path)?;
+        self.files.push(path.clone());
+        Ok(path)
+    }
+
+    fn name_sidecar(&mut self, sidecar: PathBuf) -> Result<(), String> {
+        self.check_free(&sidecar)?;
+        self.sidecar = Some(sidecar);
+        Ok(())
+    }
+
+    fn check_free(&self, path: &Path) -> Result<(), String> {
+        // overwriting doesn't cover files written earlier in the same run
+        if self.sidecar.as_deref() == Some(path) || self.files.iter().any(|file| file == path) {
+            return Err(format!("'{}' would be written twice", path.display()));
+        }
+        if !self.options.overwrite && path.exists() {
+            return Err(format!(
+                "'{}' already exists, pass --overwrite to replace it",
+                path.display()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Writes the sidecar, with the parameters, the command line, the device, how long the work
+    /// took and the files claimed. Does nothing when nothing was claimed.
+    pub fn write_sidecar(&self, device: &str, elapsed: Duration) -> Result<(), String> {
+        let Some(sidecar) = &self.sidecar else {
+            return Ok(());
+        };
+        let files: Vec<String> = self
+            .files
+            .iter()
+            .map(|file| file.display().to_string())
+            .collect();
+        let json = json!({
+            "workload": self.workload,
+            "parameters": self.parameters,
+            "arguments": self.arguments,
+            "device": device,
+            "started": iso_timestamp(self.started),
+            "elapsed_seconds": elapsed.as_secs_f64(),
+            "files": files,
+        });
+        let json = serde_json::to_string_pretty(&json).expect("could not serialize sidecar");
+        fs::write(sidecar, json)
+            .map_err(|e| format!("could not write '{}': {}", sidecar.display(), e))
+    }
+}
+
+/// The sidecar of the file at `path`.
+fn sidecar_path(path: &Path) -> PathBuf {
+    let sidecar = path.with_extension("json");
+    if sidecar == path {
+        path.with_extension("sidecar.json")
+    } else {
+        sidecar
+    }
+}
+
+/// UTC date and time of `time`, as year, month, day, hour, minute and second.
+fn utc(time: SystemTime) -> [u64; 6] {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+
+    // days since 1970-01-01 to a civil date, counting years from March so leap days come last
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as u64;
+    [
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+    ]
+}
+
+/// `time` in UTC as `YYYYMMDD-HHMMSS`, for file names.
+pub fn timestamp(time: SystemTime) -> String {
+    let [year, month, day, hour, minute, second] = utc(time);
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year, month, day, hour, minute, second
+    )
+}
+
+/// `time` in UTC as ISO 8601.
+pub fn iso_timestamp(time: SystemTime) -> String {
+    let [year, month, day, hour, minute, second] = utc(time);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, hour, minute, second
+    )
+}
@@ -0,0 +1,255 @@
+use std::{fmt::Debug, sync::Arc};
+
+use vulkano::{
+    buffer::BufferContents,
+    device::{Device, DeviceExtensions, Features},
+    half::f16,
+    shader::ShaderModule,
+    Validated, Version, VulkanError,
+};
+
+/// How many units of least precision a float result may be off by and still match.
+const MAX_ULPS: u128 = 4;
+
+/// A type the compute kernel can be instantiated for.
+pub trait Element: BufferContents + Copy + Debug {
+    /// Name used to pick the type on the command line.
+    const NAME: &'static str;
+
+    /// Device features the kernel needs for this type.
+    fn required_features() -> Features {
+        Features::empty()
+    }
+
+    /// Device extensions that expose `required_features` on devices older than the Vulkan
+    /// version they were promoted in.
+    fn required_extensions(_api_version: Version) -> DeviceExtensions {
+        DeviceExtensions::empty()
+    }
+
+    fn load_shader(device: Arc<Device>) -> Result<Arc<ShaderModule>, Validated<VulkanError>>;
+
+    /// Value the buffer starts with at `index`.
+    fn from_index(index: u32) -> Self;
+
+    /// CPU version of the kernel, to check the GPU's results against.
+    fn cpu_kernel(self) -> Self;
+
+    /// Whether a value computed by the GPU is close enough to the CPU's.
+    fn matches(gpu: Self, cpu: Self) -> bool;
+}
+
+/// Distance between two floats in units of least precision, given their raw bits.
+fn ulp_distance(a: u64, b: u64, sign_mask: u64) -> u128 {
+    // map sign-magnitude bits onto a line where adjacent floats are adjacent integers
+    let ordered = |bits: u64| {
+        if bits & sign_mask != 0 {
+            -((bits & !sign_mask) as i128)
+        } else {
+            bits as i128
+        }
+    };
+    (ordered(a) - ordered(b)).unsigned_abs()
+}
+
+impl Element for u32 {
+    const NAME: &'static str = "u32";
+
+    fn load_shader(device: Arc<Device>) -> Result<Arc<ShaderModule>, Validated<VulkanError>> {
+        cs_u32::load(device)
+    }
+
+    fn from_index(index: u32) -> Self {
+        index
+    }
+
+    fn cpu_kernel(self) -> Self {
+        self * 12
+    }
+
+    fn matches(gpu: Self, cpu: Self) -> bool {
+        gpu == cpu
+    }
+}
+
+impl Element for i32 {
+    const NAME: &'static str = "i32";
+
+    fn load_shader(device: Arc<Device>) -> Result<Arc<ShaderModule>, Validated<VulkanError>> {
+        cs_i32::load(device)
+    }
+
+    fn from_index(index: u32) -> Self {
+        index as i32 - 32768
+    }
+
+    fn cpu_kernel(self) -> Self {
+        self * 12
+    }
+
+    fn matches(gpu: Self, cpu: Self) -> bool {
+        gpu == cpu
+    }
+}
+
+impl Element for u64 {
+    const NAME: &'static str = "u64";
+
+    fn required_features() -> Features {
+        Features {
+            shader_int64: true,
+            ..Features::empty()
+        }
+    }
+
+    fn load_shader(device: Arc<Device>) -> Result<Arc<ShaderModule>, Validated<VulkanError>> {
+        cs_u64::load(device)
+    }
+
+    fn from_index(index: u32) -> Self {
+        // shift past 32 bits so the kernel has to do real 64-bit arithmetic
+        (index as u64) << 24
+    }
+
+    fn cpu_kernel(self) -> Self {
+        self * 12
+    }
+
+    fn matches(gpu: Self, cpu: Self) -> bool {
+        gpu == cpu
+    }
+}
+
+impl Element for f32 {
+    const NAME: &'static str = "f32";
+
+    fn load_shader(device: Arc<Device>) -> Result<Arc<ShaderModule>, Validated<VulkanError>> {
+        cs_f32::load(device)
+    }
+
+    fn from_index(index: u32) -> Self {
+        index as f32 / 3.0
+    }
+
+    fn cpu_kernel(self) -> Self {
+        self * 12.0
+    }
+
+    fn matches(gpu: Self, cpu: Self) -> bool {
+        !gpu.is_nan()
+            && ulp_distance(gpu.to_bits() as u64, cpu.to_bits() as u64, 1 << 31) <= MAX_ULPS
+    }
+}
+
+impl Element for f64 {
+    const NAME: &'static str = "f64";
+
+    fn required_features() -> Features {
+        Features {
+            shader_float64: true,
+            ..Features::empty()
+        }
+    }
+
+    fn load_shader(device: Arc<Device>) -> Result<Arc<ShaderModule>, Validated<VulkanError>> {
+        cs_f64::load(device)
+    }
+
+    fn from_index(index: u32) -> Self {
+        index as f64 / 3.0
+    }
+
+    fn cpu_kernel(self) -> Self {
+        self * 12.0
+    }
+
+    fn matches(gpu: Self, cpu: Self) -> bool {
+        !gpu.is_nan() && ulp_distance(gpu.to_bits(), cpu.to_bits(), 1 << 63) <= MAX_ULPS
+    }
+}
+
+impl Element for f16 {
+    const NAME: &'static str = "f16";
+
+    fn required_features() -> Features {
+        Features {
+            shader_float16: true,
+            storage_buffer16_bit_access: true,
+            ..Features::empty()
+        }
+    }
+
+    fn required_extensions(api_version: Version) -> DeviceExtensions {
+        DeviceExtensions {
+            khr_16bit_storage: api_version < Version::V1_1,
+            khr_shader_float16_int8: api_version < Version::V1_2,
+            ..DeviceExtensions::empty()
+        }
+    }
+
+    fn load_shader(device: Arc<Device>) -> Result<Arc<ShaderModule>, Validated<VulkanError>> {
+        cs_f16::load(device)
+    }
+
+    fn from_index(index: u32) -> Self {
+        // stay well inside f16's range once multiplied
+        f16::from_f32((index % 2048) as f32 / 3.0)
+    }
+
+    fn cpu_kernel(self) -> Self {
+        f16::from_f32(self.to_f32() * 12.0)
+    }
+
+    fn matches(gpu: Self, cpu: Self) -> bool {
+        !gpu.is_nan()
+            && ulp_distance(gpu.to_bits() as u64, cpu.to_bits() as u64, 1 << 15) <= MAX_ULPS
+    }
+}
+
+mod cs_u32 {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/split/shader.glsl",
+        define: [("ELEMENT", "uint")]
+    }
+}
+
+mod cs_i32 {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/split/shader.glsl",
+        define: [("ELEMENT", "int")]
+    }
+}
+
+mod cs_u64 {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/split/shader.glsl",
+        define: [("ELEMENT", "uint64_t"), ("ELEMENT_INT64", "1")]
+    }
+}
+
+mod cs_f32 {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/split/shader.glsl",
+        define: [("ELEMENT", "float")]
+    }
+}
+
+mod cs_f64 {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/split/shader.glsl",
+        define: [("ELEMENT", "double")]
+    }
+}
+
+mod cs_f16 {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/split/shader.glsl",
+        define: [("ELEMENT", "float16_t"), ("ELEMENT_FLOAT16", "1")]
+    }
+}
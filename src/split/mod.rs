@@ -0,0 +1,66 @@
+//! Dividing a job between several devices, and a kernel to divide, run by a `Worker` on each.
+
+use std::ops::Range;
+
+pub mod element;
+pub mod worker;
+
+pub use element::Element;
+pub use worker::Worker;
+
+/// Splits work groups `0..total` into consecutive ranges, one per device, sized in proportion to
+/// each device's throughput. Devices that would get nothing, because they're too slow or there
+/// are more devices than groups, are left out, so every range comes with the index of the
+/// device it belongs to. Throughputs that aren't positive count as zero, and when none are, the
+/// groups are split evenly. Infinite ones count as the largest finite throughput there is.
+pub fn split_work_groups(total: u32, throughputs: &[f64]) -> Vec<(usize, Range<u32>)> {
+    let weights: Vec<f64> = throughputs
+        .iter()
+        .map(|&t| if t > 0.0 { t.min(f64::MAX) } else { 0.0 })
+        .collect();
+    // scale down first so the sum can't overflow
+    let largest = weights.iter().copied().fold(0.0, f64::max);
+    let weights: Vec<f64> = if largest > 0.0 {
+        weights.iter().map(|w| w / largest).collect()
+    } else {
+        vec![1.0; weights.len()]
+    };
+    let sum: f64 = weights.iter().sum();
+    let shares: Vec<f64> = weights.iter().map(|w| total as f64 * w / sum).collect();
+
+    // round down, never handing out more than there is even if the shares rounded up
+    let mut assigned = 0;
+    let mut counts: Vec<u32> = shares
+        .iter()
+        .map(|share| {
+            let count = (share.floor() as u32).min(total - assigned);
+            assigned += count;
+            count
+        })
+        .collect();
+
+    // hand out whatever rounding down left over, largest remainders first
+    let mut by_remainder: Vec<usize> = (0..shares.len()).collect();
+    by_remainder.sort_by(|&a, &b| {
+        (shares[b] - shares[b].floor()).total_cmp(&(shares[a] - shares[a].floor()))
+    });
+    for &i in by_remainder
+        .iter()
+        .cycle()
+        .take((total - assigned) as usize)
+    {
+        counts[i] += 1;
+    }
+
+    let mut start = 0;
+    counts
+        .into_iter()
+        .enumerate()
+        .filter(|&(_, count)| count > 0)
+        .map(|(device, count)| {
+            let range = start..start + count;
+            start += count;
+            (device, range)
+        })
+        .collect()
+}
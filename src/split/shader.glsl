@@ -0,0 +1,25 @@
+#version 460
+
+// `ELEMENT` is set by each instantiation of this kernel, along with a flag
+// enabling the extension its type needs.
+#if defined(ELEMENT_INT64)
+#extension GL_EXT_shader_explicit_arithmetic_types_int64 : require
+#elif defined(ELEMENT_FLOAT16)
+#extension GL_EXT_shader_explicit_arithmetic_types_float16 : require
+#extension GL_EXT_shader_16bit_storage : require
+#endif
+
+#ifndef ELEMENT
+#define ELEMENT uint
+#endif
+
+layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+layout(set = 0, binding = 0) buffer Data {
+    ELEMENT data[];
+} buf;
+
+void main(){
+    uint idx = gl_GlobalInvocationID.x;
+    buf.data[idx] *= ELEMENT(12);
+}
@@ -0,0 +1,226 @@
+use std::{marker::PhantomData, sync::Arc, time::SystemTime};
+
+use vulkano::{
+    buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer},
+    command_buffer::{
+        allocator::{StandardCommandBufferAllocator, StandardCommandBufferAllocatorCreateInfo},
+        AutoCommandBufferBuilder, CommandBufferExecFuture, CommandBufferUsage,
+    },
+    descriptor_set::{
+        allocator::StandardDescriptorSetAllocator, PersistentDescriptorSet, WriteDescriptorSet,
+    },
+    device::{
+        physical::PhysicalDevice, Device, DeviceCreateInfo, Queue, QueueCreateInfo, QueueFlags,
+    },
+    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
+    pipeline::{
+        compute::ComputePipelineCreateInfo, layout::PipelineDescriptorSetLayoutCreateInfo,
+        ComputePipeline, Pipeline, PipelineBindPoint, PipelineLayout,
+        PipelineShaderStageCreateInfo,
+    },
+    sync::{
+        self,
+        future::{FenceSignalFuture, NowFuture},
+        GpuFuture,
+    },
+};
+
+use super::element::Element;
+
+/// Number of elements each work group of the kernel processes, matching `local_size_x`.
+pub const WORK_GROUP_SIZE: u32 = 64;
+
+/// Work groups dispatched when measuring how fast a device is.
+const CALIBRATION_WORK_GROUPS: u32 = 256;
+
+pub type DispatchFuture = FenceSignalFuture<CommandBufferExecFuture<NowFuture>>;
+
+/// A logical device with everything needed to run the kernel on it.
+pub struct Worker<T> {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    memory_allocator: Arc<StandardMemoryAllocator>,
+    command_buffer_allocator: StandardCommandBufferAllocator,
+    descriptor_set_allocator: StandardDescriptorSetAllocator,
+    compute_pipeline: Arc<ComputePipeline>,
+    element: PhantomData<T>,
+}
+
+impl<T: Element> Worker<T> {
+    /// Creates a new logical device on `physical_device`. Calling this more than once with the
+    /// same physical device gives independent workers sharing the hardware.
+    pub fn new(physical_device: Arc<PhysicalDevice>) -> Result<Self, String> {
+        let device_name = physical_device.properties().device_name.clone();
+
+        // setup device
+        let queue_family_index = physical_device
+            .queue_family_properties()
+            .iter()
+            .position(|queue_family_properties| {
+                queue_family_properties
+                    .queue_flags
+                    .contains(QueueFlags::COMPUTE)
+            })
+            .ok_or_else(|| format!("device '{}' has no compute queue family", device_name))?
+            as u32;
+
+        // only enable the features this element type needs, bailing out if the device lacks them
+        let required_features = T::required_features();
+        if !physical_device
+            .supported_features()
+            .contains(&required_features)
+        {
+            let missing_features =
+                required_features.difference(physical_device.supported_features());
+            return Err(format!(
+                "device '{}' cannot run the kernel with {} elements, missing features: {:?}",
+                device_name,
+                T::NAME,
+                missing_features
+            ));
+        }
+        let required_extensions = T::required_extensions(physical_device.api_version());
+        let (device, mut queues) = Device::new(
+            physical_device,
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                enabled_extensions: required_extensions,
+                enabled_features: required_features,
+                ..Default::default()
+            },
+        )
+        .map_err(|e| format!("failed to create device on '{}': {}", device_name, e))?;
+        let queue = queues.next().unwrap();
+        let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
+
+        // setup compute pipeline
+        let shader = T::load_shader(device.clone()).expect("failed to create shader module");
+        let entry_point = shader
+            .entry_point("main")
+            .expect("failed to create entry point");
+        let stage = PipelineShaderStageCreateInfo::new(entry_point);
+        let layout = PipelineLayout::new(
+            device.clone(),
+            PipelineDescriptorSetLayoutCreateInfo::from_stages([&stage])
+                .into_pipeline_layout_create_info(device.clone())
+                .expect("could not create pipeline layout info"),
+        )
+        .expect("could not create pipeline layout");
+        let compute_pipeline = ComputePipeline::new(
+            device.clone(),
+            None,
+            ComputePipelineCreateInfo::stage_layout(stage, layout),
+        )
+        .expect("failed to create compute pipeline");
+
+        // setup allocators
+        let command_buffer_allocator = StandardCommandBufferAllocator::new(
+            device.clone(),
+            StandardCommandBufferAllocatorCreateInfo::default(),
+        );
+        let descriptor_set_allocator =
+            StandardDescriptorSetAllocator::new(device.clone(), Default::default());
+
+        Ok(Self {
+            device,
+            queue,
+            memory_allocator,
+            command_buffer_allocator,
+            descriptor_set_allocator,
+            compute_pipeline,
+            element: PhantomData,
+        })
+    }
+
+    pub fn device_name(&self) -> &str {
+        &self.device.physical_device().properties().device_name
+    }
+
+    /// Copies `data` into a new storage buffer owned by this worker's device.
+    pub fn upload(&self, data: &[T]) -> Subbuffer<[T]> {
+        Buffer::from_iter(
+            self.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            data.iter().copied(),
+        )
+        .expect("failed to create data buffer")
+    }
+
+    /// Submits the kernel over all of `buffer`, whose length must be a multiple of
+    /// `WORK_GROUP_SIZE`. Wait on the returned future before reading the buffer.
+    pub fn dispatch(&self, buffer: Subbuffer<[T]>) -> DispatchFuture {
+        // setup descriptor
+        let descriptor_set_layout_index = 0;
+        let descriptor_set_layout = self
+            .compute_pipeline
+            .layout()
+            .set_layouts()
+            .get(descriptor_set_layout_index)
+            .expect("could not get correct descriptor set");
+        let work_group_counts = [buffer.len() as u32 / WORK_GROUP_SIZE, 1, 1];
+        let descriptor_set = PersistentDescriptorSet::new(
+            &self.descriptor_set_allocator,
+            descriptor_set_layout.clone(),
+            [WriteDescriptorSet::buffer(0, buffer)],
+            [],
+        )
+        .expect("failed to create descriptor set");
+
+        // build command buffer
+        let mut command_buffer_builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .expect("failed to create command buffer builder");
+        command_buffer_builder
+            .bind_pipeline_compute(self.compute_pipeline.clone())
+            .expect("failed to bind pipeline command buffer builder")
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.compute_pipeline.layout().clone(),
+                descriptor_set_layout_index as u32,
+                descriptor_set,
+            )
+            .expect("failed to bind command buffer to descriptor sets")
+            .dispatch(work_group_counts)
+            .expect("failed to dispatch work groups");
+        let command_buffer = command_buffer_builder
+            .build()
+            .expect("failed to build command buffer");
+
+        // submit command buffer
+        sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), command_buffer)
+            .expect("failed to execute command buffer")
+            .then_signal_fence_and_flush()
+            .expect("failed to signal fence and flush")
+    }
+
+    /// Elements per second this device gets through, timed on a throwaway buffer.
+    pub fn measure_throughput(&self) -> f64 {
+        let data: Vec<T> = (0..CALIBRATION_WORK_GROUPS * WORK_GROUP_SIZE)
+            .map(T::from_index)
+            .collect();
+
+        // the first dispatch pays for warming up the pipeline, so only time the second one
+        self.dispatch(self.upload(&data)).wait(None).unwrap();
+        let buffer = self.upload(&data);
+        let start = SystemTime::now();
+        self.dispatch(buffer).wait(None).unwrap();
+        let elapsed = start.elapsed().expect("could not elapse calibration time");
+
+        data.len() as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
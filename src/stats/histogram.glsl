@@ -0,0 +1,63 @@
+#version 460
+
+// one invocation per histogram bin, so each can clear and flush its own bin
+layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+
+layout(set = 0, binding = 0, rgba8) uniform readonly image2D img;
+
+layout(set = 0, binding = 1) buffer Stats {
+    uint histogram[4 * 256];
+    uint min_value[4];
+    uint max_value[4];
+    // 64-bit sums, split in two since not every device has 64-bit atomics
+    uint sum_low[4];
+    uint sum_high[4];
+} stats;
+
+shared uint local_histogram[4 * 256];
+shared uint local_min[4];
+shared uint local_max[4];
+shared uint local_sum[4];
+
+void main() {
+    uint bin = gl_LocalInvocationIndex;
+    for (uint c = 0; c < 4; c++) {
+        local_histogram[c * 256 + bin] = 0;
+    }
+    if (bin < 4) {
+        local_min[bin] = 255;
+        local_max[bin] = 0;
+        local_sum[bin] = 0;
+    }
+    barrier();
+
+    // gather this work group's pixels in shared memory first, to keep global atomics down
+    ivec2 pos = ivec2(gl_GlobalInvocationID.xy);
+    if (all(lessThan(pos, imageSize(img)))) {
+        uvec4 value = uvec4(round(imageLoad(img, pos) * 255.0));
+        for (uint c = 0; c < 4; c++) {
+            atomicAdd(local_histogram[c * 256 + value[c]], 1u);
+            atomicMin(local_min[c], value[c]);
+            atomicMax(local_max[c], value[c]);
+            atomicAdd(local_sum[c], value[c]);
+        }
+    }
+    barrier();
+
+    for (uint c = 0; c < 4; c++) {
+        uint count = local_histogram[c * 256 + bin];
+        if (count != 0) {
+            atomicAdd(stats.histogram[c * 256 + bin], count);
+        }
+    }
+    if (bin < 4) {
+        atomicMin(stats.min_value[bin], local_min[bin]);
+        atomicMax(stats.max_value[bin], local_max[bin]);
+
+        // carry into the high half whenever the low half wraps around
+        uint previous = atomicAdd(stats.sum_low[bin], local_sum[bin]);
+        if (previous + local_sum[bin] < previous) {
+            atomicAdd(stats.sum_high[bin], 1u);
+        }
+    }
+}
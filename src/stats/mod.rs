@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use image::{Rgba, RgbaImage};
+use serde_json::{json, Map, Value};
+use vulkano::{
+    buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage},
+    descriptor_set::WriteDescriptorSet,
+    image::{view::ImageView, Image},
+    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter},
+    pipeline::{ComputePipeline, Pipeline, PipelineBindPoint},
+};
+
+use crate::context::Context;
+
+pub const CHANNEL_NAMES: [&str; 4] = ["r", "g", "b", "a"];
+
+/// Percentiles reported when none are asked for.
+pub const DEFAULT_PERCENTILES: [f64; 7] = [1.0, 5.0, 25.0, 50.0, 75.0, 95.0, 99.0];
+
+/// Height of each channel's panel in `ImageStats::histogram_image`.
+const PANEL_HEIGHT: u32 = 100;
+
+/// Matches the `Stats` block in histogram.glsl.
+#[derive(BufferContents)]
+#[repr(C)]
+struct StatsBuffer {
+    histogram: [[u32; 256]; 4],
+    min_value: [u32; 4],
+    max_value: [u32; 4],
+    sum_low: [u32; 4],
+    sum_high: [u32; 4],
+}
+
+/// Statistics of one channel of an 8-bit image.
+#[derive(Clone, Debug)]
+pub struct ChannelStats {
+    pub histogram: [u32; 256],
+    pub min: u8,
+    pub max: u8,
+    pub mean: f64,
+}
+
+impl ChannelStats {
+    /// Smallest value that at least `percentile` percent of the pixels are less than or equal to.
+    pub fn percentile(&self, percentile: f64) -> u8 {
+        let total: u64 = self.histogram.iter().map(|&c| c as u64).sum();
+        let rank = ((percentile / 100.0 * total as f64).ceil() as u64).max(1);
+        let mut cumulative = 0;
+        for (value, &count) in self.histogram.iter().enumerate() {
+            cumulative += count as u64;
+            if cumulative >= rank {
+                return value as u8;
+            }
+        }
+        self.max
+    }
+}
+
+/// Per-channel statistics of an 8-bit RGBA image.
+#[derive(Clone, Debug)]
+pub struct ImageStats {
+    pub width: u32,
+    pub height: u32,
+    pub channels: [ChannelStats; 4],
+}
+
+impl ImageStats {
+    pub fn to_json(&self, percentiles: &[f64]) -> Value {
+        let channels: Map<String, Value> = CHANNEL_NAMES
+            .iter()
+            .zip(&self.channels)
+            .map(|(name, channel)| {
+                let percentiles: Map<String, Value> = percentiles
+                    .iter()
+                    .map(|&p| (p.to_string(), json!(channel.percentile(p))))
+                    .collect();
+                let stats = json!({
+                    "min": channel.min,
+                    "max": channel.max,
+                    "mean": channel.mean,
+                    "percentiles": percentiles,
+                    "histogram": channel.histogram.to_vec(),
+                });
+                (name.to_string(), stats)
+            })
+            .collect();
+        json!({
+            "width": self.width,
+            "height": self.height,
+            "channels": channels,
+        })
+    }
+
+    /// Draws every channel's histogram as bars, one panel per channel from top to bottom.
+    pub fn histogram_image(&self) -> RgbaImage {
+        let colors = [
+            Rgba([220, 60, 60, 255]),
+            Rgba([60, 200, 60, 255]),
+            Rgba([70, 110, 230, 255]),
+            Rgba([200, 200, 200, 255]),
+        ];
+        let background = Rgba([24, 24, 24, 255]);
+        RgbaImage::from_fn(256, PANEL_HEIGHT * 4, |x, y| {
+            let channel = (y / PANEL_HEIGHT) as usize;
+            let histogram = &self.channels[channel].histogram;
+            let tallest = *histogram.iter().max().unwrap_or(&0) as f64;
+            let count = histogram[x as usize];
+            let bar_height = if count == 0 {
+                0
+            } else {
+                ((count as f64 / tallest * PANEL_HEIGHT as f64).round() as u32).max(1)
+            };
+            let height_from_bottom = PANEL_HEIGHT - y % PANEL_HEIGHT;
+            if height_from_bottom <= bar_height {
+                colors[channel]
+            } else {
+                background
+            }
+        })
+    }
+}
+
+/// Computes histograms and statistics of `R8G8B8A8_UNORM` storage images on the GPU.
+pub struct StatsPipeline {
+    pipeline: Arc<ComputePipeline>,
+}
+
+impl StatsPipeline {
+    pub fn new(context: &Context) -> Self {
+        let shader =
+            histogram::load(context.device.clone()).expect("failed to create shader module");
+        Self {
+            pipeline: context.compute_pipeline(shader),
+        }
+    }
+
+    /// Gathers statistics of `image`, which must have been created with `STORAGE` usage.
+    pub fn compute(&self, context: &Context, image: Arc<Image>) -> ImageStats {
+        let [width, height, _] = image.extent();
+        let stats_buffer = Buffer::from_data(
+            context.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            StatsBuffer {
+                histogram: [[0; 256]; 4],
+                min_value: [255; 4],
+                max_value: [0; 4],
+                sum_low: [0; 4],
+                sum_high: [0; 4],
+            },
+        )
+        .expect("failed to create stats buffer");
+
+        // setup descriptor
+        let image_view = ImageView::new_default(image).expect("could not create image view");
+        let descriptor_set = context.descriptor_set(
+            &self.pipeline,
+            [
+                WriteDescriptorSet::image_view(0, image_view),
+                WriteDescriptorSet::buffer(1, stats_buffer.clone()),
+            ],
+        );
+
+        // dispatch
+        let mut builder = context.command_buffer_builder();
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())
+            .expect("failed to bind pipeline")
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.pipeline.layout().clone(),
+                0,
+                descriptor_set,
+            )
+            .expect("failed to bind descriptor set")
+            .dispatch([width.div_ceil(16), height.div_ceil(16), 1])
+            .expect("failed to dispatch work groups");
+        context.execute(builder);
+
+        // derive everything else on the host
+        let stats = stats_buffer.read().expect("failed to read stats buffer");
+        let pixel_count = width as f64 * height as f64;
+        let channels = [0, 1, 2, 3].map(|c| {
+            let sum = ((stats.sum_high[c] as u64) << 32) | stats.sum_low[c] as u64;
+            ChannelStats {
+                histogram: stats.histogram[c],
+                min: stats.min_value[c] as u8,
+                max: stats.max_value[c] as u8,
+                mean: sum as f64 / pixel_count,
+            }
+        });
+
+        ImageStats {
+            width,
+            height,
+            channels,
+        }
+    }
+}
+
+/// Gathers the statistics `StatsPipeline` does, on the CPU.
+pub fn stats_cpu(image: &RgbaImage) -> ImageStats {
+    let (width, height) = image.dimensions();
+    let pixel_count = width as f64 * height as f64;
+    let channels = [0, 1, 2, 3].map(|c| {
+        let mut histogram = [0; 256];
+        let mut sum = 0;
+        for pixel in image.pixels() {
+            histogram[pixel[c] as usize] += 1;
+            sum += pixel[c] as u64;
+        }
+        ChannelStats {
+            histogram,
+            // an empty image keeps the values the device starts from
+            min: histogram.iter().position(|&count| count > 0).unwrap_or(255) as u8,
+            max: histogram.iter().rposition(|&count| count > 0).unwrap_or(0) as u8,
+            mean: sum as f64 / pixel_count,
+        }
+    });
+    ImageStats {
+        width,
+        height,
+        channels,
+    }
+}
+
+mod histogram {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/stats/histogram.glsl"
+    }
+}
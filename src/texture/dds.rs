@@ -0,0 +1,314 @@
+//! DirectDraw Surface files. They're written with the DX10 header, which can describe every
+//! format here, and read with it or with the older FourCCs and bit masks.
+
+use vulkano::format::Format;
+
+use super::{Reader, Texture};
+
+const MAGIC: &[u8; 4] = b"DDS ";
+const HEADER_SIZE: u32 = 124;
+const PIXEL_FORMAT_SIZE: u32 = 32;
+
+// header flags
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PITCH: u32 = 0x8;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_LINEARSIZE: u32 = 0x80000;
+const DDSD_DEPTH: u32 = 0x800000;
+
+// pixel format flags
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDPF_LUMINANCE: u32 = 0x20000;
+
+const DDSCAPS_COMPLEX: u32 = 0x8;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+const DDSCAPS_MIPMAP: u32 = 0x400000;
+/// The cubemap flag with every one of the faces.
+const DDSCAPS2_CUBEMAP_ALL_FACES: u32 = 0xfe00;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+
+const DIMENSION_TEXTURE2D: u32 = 3;
+const DIMENSION_TEXTURE3D: u32 = 4;
+const MISC_TEXTURECUBE: u32 = 0x4;
+
+/// DXGI formats and the Vulkan formats they are.
+const DXGI_FORMATS: &[(u32, Format)] = &[
+    (2, Format::R32G32B32A32_SFLOAT),
+    (6, Format::R32G32B32_SFLOAT),
+    (10, Format::R16G16B16A16_SFLOAT),
+    (11, Format::R16G16B16A16_UNORM),
+    (16, Format::R32G32_SFLOAT),
+    (24, Format::A2B10G10R10_UNORM_PACK32),
+    (26, Format::B10G11R11_UFLOAT_PACK32),
+    (28, Format::R8G8B8A8_UNORM),
+    (29, Format::R8G8B8A8_SRGB),
+    (34, Format::R16G16_SFLOAT),
+    (35, Format::R16G16_UNORM),
+    (41, Format::R32_SFLOAT),
+    (49, Format::R8G8_UNORM),
+    (54, Format::R16_SFLOAT),
+    (56, Format::R16_UNORM),
+    (61, Format::R8_UNORM),
+    (67, Format::E5B9G9R9_UFLOAT_PACK32),
+    (71, Format::BC1_RGBA_UNORM_BLOCK),
+    (72, Format::BC1_RGBA_SRGB_BLOCK),
+    (74, Format::BC2_UNORM_BLOCK),
+    (75, Format::BC2_SRGB_BLOCK),
+    (77, Format::BC3_UNORM_BLOCK),
+    (78, Format::BC3_SRGB_BLOCK),
+    (80, Format::BC4_UNORM_BLOCK),
+    (81, Format::BC4_SNORM_BLOCK),
+    (83, Format::BC5_UNORM_BLOCK),
+    (84, Format::BC5_SNORM_BLOCK),
+    (87, Format::B8G8R8A8_UNORM),
+    (91, Format::B8G8R8A8_SRGB),
+    (95, Format::BC6H_UFLOAT_BLOCK),
+    (96, Format::BC6H_SFLOAT_BLOCK),
+    (98, Format::BC7_UNORM_BLOCK),
+    (99, Format::BC7_SRGB_BLOCK),
+];
+
+/// FourCCs written before the DX10 header, including the D3D format numbers some writers put
+/// there instead.
+const FOURCC_FORMATS: &[(&[u8; 4], Format)] = &[
+    (b"DXT1", Format::BC1_RGBA_UNORM_BLOCK),
+    (b"DXT2", Format::BC2_UNORM_BLOCK),
+    (b"DXT3", Format::BC2_UNORM_BLOCK),
+    (b"DXT4", Format::BC3_UNORM_BLOCK),
+    (b"DXT5", Format::BC3_UNORM_BLOCK),
+    (b"ATI1", Format::BC4_UNORM_BLOCK),
+    (b"BC4U", Format::BC4_UNORM_BLOCK),
+    (b"BC4S", Format::BC4_SNORM_BLOCK),
+    (b"ATI2", Format::BC5_UNORM_BLOCK),
+    (b"BC5U", Format::BC5_UNORM_BLOCK),
+    (b"BC5S", Format::BC5_SNORM_BLOCK),
+    (&36u32.to_le_bytes(), Format::R16G16B16A16_UNORM),
+    (&111u32.to_le_bytes(), Format::R16_SFLOAT),
+    (&112u32.to_le_bytes(), Format::R16G16_SFLOAT),
+    (&113u32.to_le_bytes(), Format::R16G16B16A16_SFLOAT),
+    (&114u32.to_le_bytes(), Format::R32_SFLOAT),
+    (&115u32.to_le_bytes(), Format::R32G32_SFLOAT),
+    (&116u32.to_le_bytes(), Format::R32G32B32A32_SFLOAT),
+];
+
+/// Bit count and red, green, blue and alpha masks of uncompressed formats without a FourCC.
+const MASK_FORMATS: &[(u32, [u32; 4], Format)] = &[
+    (
+        32,
+        [0xff, 0xff00, 0xff0000, 0xff000000],
+        Format::R8G8B8A8_UNORM,
+    ),
+    (
+        32,
+        [0xff0000, 0xff00, 0xff, 0xff000000],
+        Format::B8G8R8A8_UNORM,
+    ),
+    (8, [0xff, 0, 0, 0], Format::R8_UNORM),
+    (16, [0xffff, 0, 0, 0], Format::R16_UNORM),
+];
+
+/// Lays out the file: magic, header and DX10 header, then every mip level of the first layer
+/// and face, then every mip level of the next, and so on.
+pub(super) fn encode(texture: &Texture) -> Result<Vec<u8>, String> {
+    texture.validate()?;
+    let dxgi_format = DXGI_FORMATS
+        .iter()
+        .find(|(_, format)| *format == texture.format)
+        .map(|(dxgi_format, _)| *dxgi_format)
+        .ok_or_else(|| format!("can't write {:?} to DDS", texture.format))?;
+    let compressed = texture.format.compression().is_some();
+    let volume = texture.depth > 1;
+    let cube = texture.faces == 6;
+
+    let mut flags = DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT | DDSD_MIPMAPCOUNT;
+    // linear size of the whole first level for compressed formats, otherwise row pitch
+    let pitch_or_linear_size = if compressed {
+        flags |= DDSD_LINEARSIZE;
+        texture.face_size(0) / texture.depth as usize
+    } else {
+        flags |= DDSD_PITCH;
+        texture.width as usize * texture.format.block_size() as usize
+    };
+    if volume {
+        flags |= DDSD_DEPTH;
+    }
+    let mut caps = DDSCAPS_TEXTURE;
+    if texture.levels.len() > 1 || cube {
+        caps |= DDSCAPS_COMPLEX;
+    }
+    if texture.levels.len() > 1 {
+        caps |= DDSCAPS_MIPMAP;
+    }
+    let caps2 = if cube {
+        DDSCAPS2_CUBEMAP_ALL_FACES
+    } else if volume {
+        DDSCAPS2_VOLUME
+    } else {
+        0
+    };
+
+    let data_size: usize = texture.levels.iter().map(Vec::len).sum();
+    let mut file = Vec::with_capacity(4 + HEADER_SIZE as usize + 20 + data_size);
+    file.extend(MAGIC);
+    let mut header = vec![
+        HEADER_SIZE,
+        flags,
+        texture.height,
+        texture.width,
+        pitch_or_linear_size as u32,
+        texture.depth,
+        texture.levels.len() as u32,
+    ];
+    header.extend([0; 11]);
+    // pixel format, saying the DX10 header follows
+    header.extend([PIXEL_FORMAT_SIZE, DDPF_FOURCC, u32::from_le_bytes(*b"DX10")]);
+    header.extend([0; 5]);
+    header.extend([caps, caps2, 0, 0, 0]);
+    // DX10 header
+    header.extend([
+        dxgi_format,
+        if volume {
+            DIMENSION_TEXTURE3D
+        } else {
+            DIMENSION_TEXTURE2D
+        },
+        if cube { MISC_TEXTURECUBE } else { 0 },
+        texture.layers,
+        0,
+    ]);
+    for value in header {
+        file.extend(value.to_le_bytes());
+    }
+
+    for image in 0..(texture.layers * texture.faces) as usize {
+        for (level, bytes) in texture.levels.iter().enumerate() {
+            let face_size = texture.face_size(level as u32);
+            file.extend(&bytes[image * face_size..(image + 1) * face_size]);
+        }
+    }
+    Ok(file)
+}
+
+pub(super) fn decode(bytes: &[u8]) -> Result<Texture, String> {
+    let mut reader = Reader::new(bytes);
+    if reader.bytes(MAGIC.len())? != MAGIC {
+        return Err("not a DDS file".to_string());
+    }
+    let [size, flags, height, width, _pitch_or_linear_size, depth, levels] =
+        [(); 7].map(|_| reader.u32());
+    if size? != HEADER_SIZE {
+        return Err("the DDS header is the wrong size".to_string());
+    }
+    reader.bytes(11 * 4)?;
+    let [_size, pixel_flags, fourcc, bit_count, red, green, blue, alpha] =
+        [(); 8].map(|_| reader.u32());
+    let [_caps, caps2, _caps3, _caps4, _reserved] = [(); 5].map(|_| reader.u32());
+    let (flags, pixel_flags, fourcc, caps2) = (flags?, pixel_flags?, fourcc?.to_le_bytes(), caps2?);
+
+    let mut texture = Texture {
+        format: Format::UNDEFINED,
+        width: width?,
+        height: height?,
+        depth: if flags & DDSD_DEPTH != 0 || caps2 & DDSCAPS2_VOLUME != 0 {
+            depth?.max(1)
+        } else {
+            1
+        },
+        layers: 1,
+        faces: 1,
+        levels: Vec::new(),
+    };
+    if caps2 & DDSCAPS2_CUBEMAP != 0 {
+        if caps2 & DDSCAPS2_CUBEMAP_ALL_FACES != DDSCAPS2_CUBEMAP_ALL_FACES {
+            return Err("cubemaps without every face aren't supported".to_string());
+        }
+        texture.faces = 6;
+    }
+
+    // X8R8G8B8 and the like are read as formats with alpha, with padding where alpha would be
+    let mut padded_alpha = false;
+    texture.format = if pixel_flags & DDPF_FOURCC != 0 && &fourcc == b"DX10" {
+        let [dxgi_format, dimension, misc, array_size, _misc2] = [(); 5].map(|_| reader.u32());
+        let dxgi_format = dxgi_format?;
+        match dimension? {
+            DIMENSION_TEXTURE2D => texture.depth = 1,
+            DIMENSION_TEXTURE3D => {}
+            dimension => return Err(format!("resource dimension {} isn't supported", dimension)),
+        }
+        if misc? & MISC_TEXTURECUBE != 0 {
+            texture.faces = 6;
+        }
+        texture.layers = array_size?.max(1);
+        DXGI_FORMATS
+            .iter()
+            .find(|(format, _)| *format == dxgi_format)
+            .map(|(_, format)| *format)
+            .ok_or_else(|| format!("unsupported DXGI format {}", dxgi_format))?
+    } else if pixel_flags & DDPF_FOURCC != 0 {
+        FOURCC_FORMATS
+            .iter()
+            .find(|(code, _)| **code == fourcc)
+            .map(|(_, format)| *format)
+            .ok_or_else(|| format!("unsupported FourCC {:?}", String::from_utf8_lossy(&fourcc)))?
+    } else if pixel_flags & (DDPF_RGB | DDPF_LUMINANCE) != 0 {
+        let (bit_count, masks) = (bit_count?, [red?, green?, blue?, alpha?]);
+        let (_, format_masks, format) = MASK_FORMATS
+            .iter()
+            .find(|(bits, format_masks, _)| {
+                // a format without alpha still fits one with it
+                *bits == bit_count
+                    && masks[..3] == format_masks[..3]
+                    && (masks[3] == format_masks[3] || masks[3] == 0)
+            })
+            .ok_or_else(|| {
+                format!(
+                    "unsupported {}-bit pixel format with masks {:x?}",
+                    bit_count, masks
+                )
+            })?;
+        padded_alpha = masks[3] != format_masks[3];
+        *format
+    } else {
+        return Err("unsupported pixel format".to_string());
+    };
+    let levels = if flags & DDSD_MIPMAPCOUNT != 0 {
+        levels?.max(1)
+    } else {
+        1
+    };
+
+    // the header can't be trusted until it's known to fit in the file
+    texture.validate_layout(levels as usize)?;
+    let data_size = (0..levels).try_fold(0usize, |size, level| {
+        size.checked_add(texture.level_size(level))
+    });
+    if data_size.is_none_or(|size| size > reader.remaining()) {
+        return Err("the file ends too soon".to_string());
+    }
+
+    // gather each layer and face's levels back into whole levels
+    texture.levels = (0..levels)
+        .map(|level| Vec::with_capacity(texture.level_size(level)))
+        .collect();
+    for _ in 0..texture.layers * texture.faces {
+        for level in 0..levels {
+            let face = reader.bytes(texture.face_size(level))?;
+            texture.levels[level as usize].extend(face);
+        }
+    }
+    // the padding is often 0, which would make the texture see-through
+    if padded_alpha {
+        for level in &mut texture.levels {
+            for pixel in level.chunks_exact_mut(4) {
+                pixel[3] = 255;
+            }
+        }
+    }
+    texture.validate()?;
+    Ok(texture)
+}
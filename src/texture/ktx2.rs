@@ -0,0 +1,394 @@
+//! KTX 2.0 files, without supercompression.
+
+use vulkano::format::Format;
+
+use super::{Reader, Texture};
+use crate::transfer::lcm;
+
+const IDENTIFIER: [u8; 12] = [
+    0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a,
+];
+
+/// Identifier, header and index, before the level index.
+const HEADER_SIZE: usize = 80;
+
+/// Size of each level index entry.
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+/// Channel ids of the RGBSDA color model.
+const RGBA: &[u8] = &[0, 1, 2, 15];
+const BGRA: &[u8] = &[2, 1, 0, 15];
+
+/// How the values of a format's channels are stored.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Channels {
+    Unorm { bits: u8 },
+    Srgb,
+    Float { bits: u8 },
+}
+
+impl Channels {
+    fn bits(self) -> u8 {
+        match self {
+            Channels::Unorm { bits } | Channels::Float { bits } => bits,
+            Channels::Srgb => 8,
+        }
+    }
+}
+
+/// Channel ids, in the order they're laid out, and type of the formats a data format
+/// descriptor can be written for.
+fn channels(format: Format) -> Option<(&'static [u8], Channels)> {
+    let unorm8 = Channels::Unorm { bits: 8 };
+    let unorm16 = Channels::Unorm { bits: 16 };
+    let float16 = Channels::Float { bits: 16 };
+    let float32 = Channels::Float { bits: 32 };
+    Some(match format {
+        Format::R8_UNORM => (&RGBA[..1], unorm8),
+        Format::R8G8_UNORM => (&RGBA[..2], unorm8),
+        Format::R8G8B8A8_UNORM => (RGBA, unorm8),
+        Format::B8G8R8A8_UNORM => (BGRA, unorm8),
+        Format::R8_SRGB => (&RGBA[..1], Channels::Srgb),
+        Format::R8G8_SRGB => (&RGBA[..2], Channels::Srgb),
+        Format::R8G8B8A8_SRGB => (RGBA, Channels::Srgb),
+        Format::B8G8R8A8_SRGB => (BGRA, Channels::Srgb),
+        Format::R16_UNORM => (&RGBA[..1], unorm16),
+        Format::R16G16_UNORM => (&RGBA[..2], unorm16),
+        Format::R16G16B16A16_UNORM => (RGBA, unorm16),
+        Format::R16_SFLOAT => (&RGBA[..1], float16),
+        Format::R16G16_SFLOAT => (&RGBA[..2], float16),
+        Format::R16G16B16A16_SFLOAT => (RGBA, float16),
+        Format::R32_SFLOAT => (&RGBA[..1], float32),
+        Format::R32G32_SFLOAT => (&RGBA[..2], float32),
+        Format::R32G32B32_SFLOAT => (&RGBA[..3], float32),
+        Format::R32G32B32A32_SFLOAT => (RGBA, float32),
+        _ => return None,
+    })
+}
+
+/// Lays out the file: header, level index and data format descriptor, then the levels
+/// smallest first as the spec asks.
+pub(super) fn encode(texture: &Texture) -> Result<Vec<u8>, String> {
+    texture.validate()?;
+    let (ids, kind) = channels(texture.format)
+        .ok_or_else(|| format!("can't write {:?} to KTX2", texture.format))?;
+    let pixel_size = texture.format.block_size() as usize;
+
+    let dfd = data_format_descriptor(ids, kind, pixel_size);
+    let dfd_offset = HEADER_SIZE + LEVEL_INDEX_ENTRY_SIZE * texture.levels.len();
+    // levels start on a multiple of the pixel size and of 4
+    let alignment = lcm(pixel_size as u64, 4) as usize;
+    let mut offsets = vec![0; texture.levels.len()];
+    let mut end = dfd_offset + dfd.len();
+    for (level, bytes) in texture.levels.iter().enumerate().rev() {
+        end = end.next_multiple_of(alignment);
+        offsets[level] = end;
+        end += bytes.len();
+    }
+
+    let mut file = Vec::with_capacity(end);
+    file.extend(IDENTIFIER);
+    for value in [
+        texture.format as i32 as u32,
+        kind.bits() as u32 / 8,
+        texture.width,
+        texture.height,
+        // 0 for 2D and for not an array
+        if texture.depth > 1 { texture.depth } else { 0 },
+        if texture.layers > 1 {
+            texture.layers
+        } else {
+            0
+        },
+        texture.faces,
+        texture.levels.len() as u32,
+        0, // no supercompression
+        dfd_offset as u32,
+        dfd.len() as u32,
+        0, // no key/value data
+        0,
+    ] {
+        file.extend(value.to_le_bytes());
+    }
+    file.extend([0u64; 2].map(u64::to_le_bytes).concat()); // no supercompression data
+    for (offset, bytes) in offsets.iter().zip(&texture.levels) {
+        let length = bytes.len() as u64;
+        for value in [*offset as u64, length, length] {
+            file.extend(value.to_le_bytes());
+        }
+    }
+    file.extend(dfd);
+    for (level, bytes) in texture.levels.iter().enumerate().rev() {
+        file.resize(offsets[level], 0);
+        file.extend(bytes);
+    }
+    Ok(file)
+}
+
+/// Reads any format Vulkan has, going by the header's `vkFormat` rather than the data format
+/// descriptor.
+pub(super) fn decode(bytes: &[u8]) -> Result<Texture, String> {
+    let mut reader = Reader::new(bytes);
+    if reader.bytes(IDENTIFIER.len())? != IDENTIFIER {
+        return Err("not a KTX2 file".to_string());
+    }
+    let [vk_format, _type_size, width, height, depth, layers, faces, levels, supercompression] =
+        [(); 9].map(|_| reader.u32());
+    let vk_format = vk_format?;
+    let format = VK_FORMATS
+        .iter()
+        .copied()
+        .find(|&format| format as i32 as u32 == vk_format)
+        .ok_or_else(|| format!("unsupported vkFormat {}", vk_format))?;
+    if supercompression? != 0 {
+        return Err("supercompressed KTX2 files aren't supported".to_string());
+    }
+    let mut texture = Texture {
+        format,
+        width: width?,
+        // 0 for 1D, 2D and not an array, which are all 1
+        height: height?.max(1),
+        depth: depth?.max(1),
+        layers: layers?.max(1),
+        faces: faces?,
+        levels: Vec::new(),
+    };
+    // 0 asks for a mip chain to be generated, which leaves the first level
+    let levels = levels?.max(1);
+    texture.validate_layout(levels as usize)?;
+
+    // skip the rest of the index, data format descriptor and key/value data aren't needed
+    reader.bytes(HEADER_SIZE - reader.offset)?;
+    for level in 0..levels {
+        let offset = reader.u64()?;
+        let length = reader.u64()?;
+        let _uncompressed_length = reader.u64()?;
+        let expected = texture.level_size(level);
+        if length != expected as u64 {
+            return Err(format!(
+                "mip level {} is {} bytes, but should be {}",
+                level, length, expected
+            ));
+        }
+        let data = offset
+            .checked_add(length)
+            .and_then(|end| Some(usize::try_from(offset).ok()?..usize::try_from(end).ok()?))
+            .and_then(|range| bytes.get(range))
+            .ok_or_else(|| format!("mip level {} is past the end of the file", level))?;
+        texture.levels.push(data.to_vec());
+    }
+    texture.validate()?;
+    Ok(texture)
+}
+
+/// A basic data format descriptor block for channels `ids` of `kind`, preceded by the total
+/// size of the descriptor.
+fn data_format_descriptor(ids: &[u8], kind: Channels, pixel_size: usize) -> Vec<u8> {
+    let block_size = 24 + 16 * ids.len();
+    let mut dfd = Vec::with_capacity(4 + block_size);
+    dfd.extend((4 + block_size as u32).to_le_bytes());
+    dfd.extend(0u32.to_le_bytes()); // Khronos vendor, basic descriptor type
+    dfd.extend(2u16.to_le_bytes()); // version 1.3
+    dfd.extend((block_size as u16).to_le_bytes());
+    let transfer_function = if kind == Channels::Srgb { 2 } else { 1 };
+    // RGBSDA color model, BT.709 primaries, straight alpha and 1x1x1x1 texel blocks
+    dfd.extend([1, 1, transfer_function, 0, 0, 0, 0, 0]);
+    dfd.extend([pixel_size as u8, 0, 0, 0, 0, 0, 0, 0]);
+
+    let bits = kind.bits();
+    for (i, &id) in ids.iter().enumerate() {
+        let (flags, lower, upper) = match kind {
+            // float and signed, with the range of -1.0 to 1.0 as 32-bit floats
+            Channels::Float { .. } => (0xc0, (-1.0f32).to_bits(), 1.0f32.to_bits()),
+            // alpha is never sRGB encoded
+            Channels::Srgb if id == 15 => (0x10, 0, 255),
+            Channels::Unorm { .. } | Channels::Srgb => (0, 0, ((1u64 << bits) - 1) as u32),
+        };
+        dfd.extend((i as u16 * bits as u16).to_le_bytes());
+        dfd.extend([bits - 1, id | flags, 0, 0, 0, 0]);
+        dfd.extend(lower.to_le_bytes());
+        dfd.extend(upper.to_le_bytes());
+    }
+    dfd
+}
+
+/// The color formats of core Vulkan, which `Format` is numbered like, for reading `vkFormat`.
+const VK_FORMATS: &[Format] = &[
+    Format::R4G4_UNORM_PACK8,
+    Format::R4G4B4A4_UNORM_PACK16,
+    Format::B4G4R4A4_UNORM_PACK16,
+    Format::R5G6B5_UNORM_PACK16,
+    Format::B5G6R5_UNORM_PACK16,
+    Format::R5G5B5A1_UNORM_PACK16,
+    Format::B5G5R5A1_UNORM_PACK16,
+    Format::A1R5G5B5_UNORM_PACK16,
+    Format::R8_UNORM,
+    Format::R8_SNORM,
+    Format::R8_USCALED,
+    Format::R8_SSCALED,
+    Format::R8_UINT,
+    Format::R8_SINT,
+    Format::R8_SRGB,
+    Format::R8G8_UNORM,
+    Format::R8G8_SNORM,
+    Format::R8G8_USCALED,
+    Format::R8G8_SSCALED,
+    Format::R8G8_UINT,
+    Format::R8G8_SINT,
+    Format::R8G8_SRGB,
+    Format::R8G8B8_UNORM,
+    Format::R8G8B8_SNORM,
+    Format::R8G8B8_USCALED,
+    Format::R8G8B8_SSCALED,
+    Format::R8G8B8_UINT,
+    Format::R8G8B8_SINT,
+    Format::R8G8B8_SRGB,
+    Format::B8G8R8_UNORM,
+    Format::B8G8R8_SNORM,
+    Format::B8G8R8_USCALED,
+    Format::B8G8R8_SSCALED,
+    Format::B8G8R8_UINT,
+    Format::B8G8R8_SINT,
+    Format::B8G8R8_SRGB,
+    Format::R8G8B8A8_UNORM,
+    Format::R8G8B8A8_SNORM,
+    Format::R8G8B8A8_USCALED,
+    Format::R8G8B8A8_SSCALED,
+    Format::R8G8B8A8_UINT,
+    Format::R8G8B8A8_SINT,
+    Format::R8G8B8A8_SRGB,
+    Format::B8G8R8A8_UNORM,
+    Format::B8G8R8A8_SNORM,
+    Format::B8G8R8A8_USCALED,
+    Format::B8G8R8A8_SSCALED,
+    Format::B8G8R8A8_UINT,
+    Format::B8G8R8A8_SINT,
+    Format::B8G8R8A8_SRGB,
+    Format::A8B8G8R8_UNORM_PACK32,
+    Format::A8B8G8R8_SNORM_PACK32,
+    Format::A8B8G8R8_USCALED_PACK32,
+    Format::A8B8G8R8_SSCALED_PACK32,
+    Format::A8B8G8R8_UINT_PACK32,
+    Format::A8B8G8R8_SINT_PACK32,
+    Format::A8B8G8R8_SRGB_PACK32,
+    Format::A2R10G10B10_UNORM_PACK32,
+    Format::A2R10G10B10_SNORM_PACK32,
+    Format::A2R10G10B10_USCALED_PACK32,
+    Format::A2R10G10B10_SSCALED_PACK32,
+    Format::A2R10G10B10_UINT_PACK32,
+    Format::A2R10G10B10_SINT_PACK32,
+    Format::A2B10G10R10_UNORM_PACK32,
+    Format::A2B10G10R10_SNORM_PACK32,
+    Format::A2B10G10R10_USCALED_PACK32,
+    Format::A2B10G10R10_SSCALED_PACK32,
+    Format::A2B10G10R10_UINT_PACK32,
+    Format::A2B10G10R10_SINT_PACK32,
+    Format::R16_UNORM,
+    Format::R16_SNORM,
+    Format::R16_USCALED,
+    Format::R16_SSCALED,
+    Format::R16_UINT,
+    Format::R16_SINT,
+    Format::R16_SFLOAT,
+    Format::R16G16_UNORM,
+    Format::R16G16_SNORM,
+    Format::R16G16_USCALED,
+    Format::R16G16_SSCALED,
+    Format::R16G16_UINT,
+    Format::R16G16_SINT,
+    Format::R16G16_SFLOAT,
+    Format::R16G16B16_UNORM,
+    Format::R16G16B16_SNORM,
+    Format::R16G16B16_USCALED,
+    Format::R16G16B16_SSCALED,
+    Format::R16G16B16_UINT,
+    Format::R16G16B16_SINT,
+    Format::R16G16B16_SFLOAT,
+    Format::R16G16B16A16_UNORM,
+    Format::R16G16B16A16_SNORM,
+    Format::R16G16B16A16_USCALED,
+    Format::R16G16B16A16_SSCALED,
+    Format::R16G16B16A16_UINT,
+    Format::R16G16B16A16_SINT,
+    Format::R16G16B16A16_SFLOAT,
+    Format::R32_UINT,
+    Format::R32_SINT,
+    Format::R32_SFLOAT,
+    Format::R32G32_UINT,
+    Format::R32G32_SINT,
+    Format::R32G32_SFLOAT,
+    Format::R32G32B32_UINT,
+    Format::R32G32B32_SINT,
+    Format::R32G32B32_SFLOAT,
+    Format::R32G32B32A32_UINT,
+    Format::R32G32B32A32_SINT,
+    Format::R32G32B32A32_SFLOAT,
+    Format::R64_UINT,
+    Format::R64_SINT,
+    Format::R64_SFLOAT,
+    Format::R64G64_UINT,
+    Format::R64G64_SINT,
+    Format::R64G64_SFLOAT,
+    Format::R64G64B64_UINT,
+    Format::R64G64B64_SINT,
+    Format::R64G64B64_SFLOAT,
+    Format::R64G64B64A64_UINT,
+    Format::R64G64B64A64_SINT,
+    Format::R64G64B64A64_SFLOAT,
+    Format::B10G11R11_UFLOAT_PACK32,
+    Format::E5B9G9R9_UFLOAT_PACK32,
+    Format::BC1_RGB_UNORM_BLOCK,
+    Format::BC1_RGB_SRGB_BLOCK,
+    Format::BC1_RGBA_UNORM_BLOCK,
+    Format::BC1_RGBA_SRGB_BLOCK,
+    Format::BC2_UNORM_BLOCK,
+    Format::BC2_SRGB_BLOCK,
+    Format::BC3_UNORM_BLOCK,
+    Format::BC3_SRGB_BLOCK,
+    Format::BC4_UNORM_BLOCK,
+    Format::BC4_SNORM_BLOCK,
+    Format::BC5_UNORM_BLOCK,
+    Format::BC5_SNORM_BLOCK,
+    Format::BC6H_UFLOAT_BLOCK,
+    Format::BC6H_SFLOAT_BLOCK,
+    Format::BC7_UNORM_BLOCK,
+    Format::BC7_SRGB_BLOCK,
+    Format::ETC2_R8G8B8_UNORM_BLOCK,
+    Format::ETC2_R8G8B8_SRGB_BLOCK,
+    Format::ETC2_R8G8B8A1_UNORM_BLOCK,
+    Format::ETC2_R8G8B8A1_SRGB_BLOCK,
+    Format::ETC2_R8G8B8A8_UNORM_BLOCK,
+    Format::ETC2_R8G8B8A8_SRGB_BLOCK,
+    Format::EAC_R11_UNORM_BLOCK,
+    Format::EAC_R11_SNORM_BLOCK,
+    Format::EAC_R11G11_UNORM_BLOCK,
+    Format::EAC_R11G11_SNORM_BLOCK,
+    Format::ASTC_4x4_UNORM_BLOCK,
+    Format::ASTC_4x4_SRGB_BLOCK,
+    Format::ASTC_5x4_UNORM_BLOCK,
+    Format::ASTC_5x4_SRGB_BLOCK,
+    Format::ASTC_5x5_UNORM_BLOCK,
+    Format::ASTC_5x5_SRGB_BLOCK,
+    Format::ASTC_6x5_UNORM_BLOCK,
+    Format::ASTC_6x5_SRGB_BLOCK,
+    Format::ASTC_6x6_UNORM_BLOCK,
+    Format::ASTC_6x6_SRGB_BLOCK,
+    Format::ASTC_8x5_UNORM_BLOCK,
+    Format::ASTC_8x5_SRGB_BLOCK,
+    Format::ASTC_8x6_UNORM_BLOCK,
+    Format::ASTC_8x6_SRGB_BLOCK,
+    Format::ASTC_8x8_UNORM_BLOCK,
+    Format::ASTC_8x8_SRGB_BLOCK,
+    Format::ASTC_10x5_UNORM_BLOCK,
+    Format::ASTC_10x5_SRGB_BLOCK,
+    Format::ASTC_10x6_UNORM_BLOCK,
+    Format::ASTC_10x6_SRGB_BLOCK,
+    Format::ASTC_10x8_UNORM_BLOCK,
+    Format::ASTC_10x8_SRGB_BLOCK,
+    Format::ASTC_10x10_UNORM_BLOCK,
+    Format::ASTC_10x10_SRGB_BLOCK,
+    Format::ASTC_12x10_UNORM_BLOCK,
+    Format::ASTC_12x10_SRGB_BLOCK,
+    Format::ASTC_12x12_UNORM_BLOCK,
+    Format::ASTC_12x12_SRGB_BLOCK,
+];
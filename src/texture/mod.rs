@@ -0,0 +1,452 @@
+//! Textures as they are on the device, with every mip level, array layer and cubemap face, and
+//! the KTX2 and DDS files they're saved in.
+
+use std::{fs, sync::Arc};
+
+use image::DynamicImage;
+use vulkano::{
+    buffer::{Buffer, BufferCreateInfo, BufferUsage},
+    command_buffer::{BufferImageCopy, CopyBufferToImageInfo, CopyImageToBufferInfo},
+    format::Format,
+    image::{
+        Image, ImageAspects, ImageCreateFlags, ImageCreateInfo, ImageSubresourceLayers, ImageType,
+        ImageUsage,
+    },
+    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter},
+};
+
+use crate::{
+    context::Context,
+    format::check_format_support,
+    transfer::{lcm, usage_features, Readback},
+};
+
+mod dds;
+mod ktx2;
+
+/// A 2D or 3D texture of any uncompressed or block-compressed color format.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Texture {
+    pub format: Format,
+    pub width: u32,
+    pub height: u32,
+    /// 1 unless it's a 3D texture.
+    pub depth: u32,
+    /// Array layers, 1 unless it's an array. Each layer of a cubemap has every face.
+    pub layers: u32,
+    /// 6 for cubemaps, otherwise 1.
+    pub faces: u32,
+    /// Pixels of each mip level, largest first. Each level has every layer one after the other,
+    /// each of those every face, and each of those every depth slice, all tightly packed.
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl Texture {
+    /// A 2D texture with one mip level, holding what was read back.
+    pub fn from_readback(readback: Readback) -> Self {
+        Self {
+            format: readback.format,
+            width: readback.width,
+            height: readback.height,
+            depth: 1,
+            layers: 1,
+            faces: 1,
+            levels: vec![readback.into_packed()],
+        }
+    }
+
+    /// Size of mip level `level`.
+    pub fn level_extent(&self, level: u32) -> [u32; 3] {
+        [self.width, self.height, self.depth].map(|side| (side >> level).max(1))
+    }
+
+    /// Bytes one face of one layer of mip level `level` takes up, with every depth slice.
+    /// Panics when that doesn't fit in a `usize`, which textures that pass `validate` never do.
+    pub fn face_size(&self, level: u32) -> usize {
+        self.checked_face_size(level)
+            .expect("texture is too big to address")
+    }
+
+    /// Bytes mip level `level` takes up, with every layer and face. Panics like `face_size`.
+    pub fn level_size(&self, level: u32) -> usize {
+        self.checked_level_size(level)
+            .expect("texture is too big to address")
+    }
+
+    fn checked_face_size(&self, level: u32) -> Option<usize> {
+        let extent = self.level_extent(level);
+        let block_extent = self.format.block_extent();
+        (0..3)
+            .map(|i| extent[i].div_ceil(block_extent[i]) as usize)
+            .try_fold(self.format.block_size() as usize, usize::checked_mul)
+    }
+
+    fn checked_level_size(&self, level: u32) -> Option<usize> {
+        let images = self.layers.checked_mul(self.faces)?;
+        self.checked_face_size(level)?.checked_mul(images as usize)
+    }
+
+    /// One face of one layer of mip level `level`, as if it was read back, or `None` if the
+    /// texture doesn't have it. Only the first depth slice of 3D textures is there.
+    pub fn image(&self, level: u32, layer: u32, face: u32) -> Option<Readback> {
+        if level as usize >= self.levels.len() || layer >= self.layers || face >= self.faces {
+            return None;
+        }
+        let [width, height, depth] = self.level_extent(level);
+        let face_size = self.face_size(level);
+        let start = (layer * self.faces + face) as usize * face_size;
+        let slice_size = face_size / depth as usize;
+        Some(Readback {
+            width,
+            height,
+            format: self.format,
+            row_pitch: slice_size / height.div_ceil(self.format.block_extent()[1]) as usize,
+            bytes: self.levels[level as usize][start..start + slice_size].to_vec(),
+        })
+    }
+
+    /// Every 2D image in mip level `level`, each depth slice of each face of each layer, in the
+    /// order they're stored.
+    pub fn slices(&self, level: u32) -> Vec<Readback> {
+        let Some(bytes) = self.levels.get(level as usize) else {
+            return Vec::new();
+        };
+        let [width, height, depth] = self.level_extent(level);
+        let slice_size = self.face_size(level) / depth as usize;
+        let rows = height.div_ceil(self.format.block_extent()[1]) as usize;
+        bytes
+            .chunks_exact(slice_size)
+            .map(|slice| Readback {
+                width,
+                height,
+                format: self.format,
+                row_pitch: slice_size / rows,
+                bytes: slice.to_vec(),
+            })
+            .collect()
+    }
+
+    /// Like `image`, but converted like `Readback::to_dynamic_image`.
+    pub fn to_dynamic_image(
+        &self,
+        level: u32,
+        layer: u32,
+        face: u32,
+    ) -> Result<DynamicImage, String> {
+        self.image(level, layer, face)
+            .ok_or_else(|| {
+                format!(
+                    "the texture has no face {} of layer {} of mip level {}",
+                    face, layer, level
+                )
+            })?
+            .to_dynamic_image()
+    }
+
+    /// Checks that the texture is one Vulkan can have and the levels are the right size.
+    pub fn validate(&self) -> Result<(), String> {
+        self.validate_layout(self.levels.len())?;
+        for (level, bytes) in self.levels.iter().enumerate() {
+            let expected = self.level_size(level as u32);
+            if bytes.len() != expected {
+                return Err(format!(
+                    "mip level {} is {} bytes, but should be {}",
+                    level,
+                    bytes.len(),
+                    expected
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks everything `validate` does but the levels' bytes, for a texture that will have
+    /// `levels` mip levels. Once it passes, level sizes can be worked out without overflowing,
+    /// so decoders check headers with it before trusting them.
+    fn validate_layout(&self, levels: usize) -> Result<(), String> {
+        let format = self.format;
+        if format == Format::UNDEFINED
+            || !format.planes().is_empty()
+            || format.aspects() != ImageAspects::COLOR
+        {
+            return Err(format!("{:?} textures aren't supported", format));
+        }
+        if self.width == 0 || self.height == 0 || self.depth == 0 || self.layers == 0 {
+            return Err("textures can't be empty".to_string());
+        }
+        if self.faces != 1 && self.faces != 6 {
+            return Err(format!("textures have 1 or 6 faces, not {}", self.faces));
+        }
+        if self.faces == 6 && (self.width != self.height || self.depth != 1) {
+            return Err("cubemap faces have to be square and 2D".to_string());
+        }
+        if self.depth > 1 && self.layers > 1 {
+            return Err("3D textures can't be arrays".to_string());
+        }
+        let full_chain = 32 - self.width.max(self.height).max(self.depth).leading_zeros();
+        if levels == 0 || levels > full_chain as usize {
+            return Err(format!(
+                "a {}x{}x{} texture has from 1 to {} mip levels, not {}",
+                self.width, self.height, self.depth, full_chain, levels
+            ));
+        }
+        // every level after the first is smaller
+        if self.checked_level_size(0).is_none() {
+            return Err(format!(
+                "a {}x{}x{} texture with {} layers of {} faces is too big",
+                self.width, self.height, self.depth, self.layers, self.faces
+            ));
+        }
+        Ok(())
+    }
+
+    /// Reads a KTX2 or DDS file, picked by the extension of `path`.
+    pub fn open(path: &str) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|e| format!("could not open '{}': {}", path, e))?;
+        match extension(path).as_deref() {
+            Some("ktx2") => Self::from_ktx2(&bytes),
+            Some("dds") => Self::from_dds(&bytes),
+            _ => Err(format!(
+                "don't know how to open '{}' as a texture, use .ktx2 or .dds",
+                path
+            )),
+        }
+        .map_err(|e| format!("could not read '{}': {}", path, e))
+    }
+
+    /// Writes a KTX2 or DDS file, picked by the extension of `path`.
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let bytes = match extension(path).as_deref() {
+            Some("ktx2") => self.to_ktx2(),
+            Some("dds") => self.to_dds(),
+            _ => Err(format!(
+                "don't know how to save a texture to '{}', use .ktx2 or .dds",
+                path
+            )),
+        }?;
+        fs::write(path, bytes).map_err(|e| format!("could not save '{}': {}", path, e))
+    }
+
+    pub fn from_ktx2(bytes: &[u8]) -> Result<Self, String> {
+        ktx2::decode(bytes)
+    }
+
+    pub fn to_ktx2(&self) -> Result<Vec<u8>, String> {
+        ktx2::encode(self)
+    }
+
+    pub fn from_dds(bytes: &[u8]) -> Result<Self, String> {
+        dds::decode(bytes)
+    }
+
+    pub fn to_dds(&self) -> Result<Vec<u8>, String> {
+        dds::encode(self)
+    }
+
+    /// Copies every level, layer and face into a new device image through a staging buffer.
+    /// Cubemaps get `CUBE_COMPATIBLE` and `usage` gets `TRANSFER_DST` added to it. Fails when
+    /// the device can't use images of the format that way.
+    pub fn upload(&self, context: &Context, usage: ImageUsage) -> Result<Arc<Image>, String> {
+        self.validate()?;
+        let usage = usage | ImageUsage::TRANSFER_DST;
+        check_format_support(
+            context.device.physical_device(),
+            self.format,
+            usage_features(usage),
+        )?;
+        let image = Image::new(
+            context.memory_allocator.clone(),
+            ImageCreateInfo {
+                flags: if self.faces == 6 {
+                    ImageCreateFlags::CUBE_COMPATIBLE
+                } else {
+                    ImageCreateFlags::empty()
+                },
+                image_type: self.image_type(),
+                format: self.format,
+                extent: [self.width, self.height, self.depth],
+                array_layers: self.layers * self.faces,
+                mip_levels: self.levels.len() as u32,
+                usage,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..Default::default()
+            },
+        )
+        .map_err(|e| format!("could not create image: {}", e))?;
+
+        let (offsets, size) = self.level_offsets();
+        let mut bytes = vec![0; size as usize];
+        for (level, &offset) in self.levels.iter().zip(&offsets) {
+            bytes[offset as usize..offset as usize + level.len()].copy_from_slice(level);
+        }
+        let staging_buffer = Buffer::from_iter(
+            context.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            bytes,
+        )
+        .expect("failed to create staging buffer");
+
+        let mut builder = context.command_buffer_builder();
+        builder
+            .copy_buffer_to_image(CopyBufferToImageInfo {
+                regions: self.copy_regions(&offsets).into(),
+                ..CopyBufferToImageInfo::buffer_image(staging_buffer, image.clone())
+            })
+            .expect("failed to copy buffer to image");
+        context.execute(builder);
+
+        Ok(image)
+    }
+
+    /// Copies every level, layer and face of `image`, created with `TRANSFER_SRC` usage, back
+    /// to the host. Images with `CUBE_COMPATIBLE` come back as cubemaps.
+    pub fn download(context: &Context, image: Arc<Image>) -> Result<Self, String> {
+        if !image.usage().intersects(ImageUsage::TRANSFER_SRC) {
+            return Err("the image needs TRANSFER_SRC usage to be read back".to_string());
+        }
+        let [width, height, depth] = image.extent();
+        let faces = if image.flags().intersects(ImageCreateFlags::CUBE_COMPATIBLE)
+            && image.array_layers().is_multiple_of(6)
+        {
+            6
+        } else {
+            1
+        };
+        let mut texture = Self {
+            format: image.format(),
+            width,
+            height,
+            depth,
+            layers: image.array_layers() / faces,
+            faces,
+            levels: Vec::new(),
+        };
+        texture.levels = (0..image.mip_levels())
+            .map(|level| vec![0; texture.level_size(level)])
+            .collect();
+        texture.validate()?;
+
+        let (offsets, size) = texture.level_offsets();
+        let buf = Buffer::new_slice::<u8>(
+            context.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            size,
+        )
+        .expect("could not create buffer");
+
+        let mut builder = context.command_buffer_builder();
+        builder
+            .copy_image_to_buffer(CopyImageToBufferInfo {
+                regions: texture.copy_regions(&offsets).into(),
+                ..CopyImageToBufferInfo::image_buffer(image, buf.clone())
+            })
+            .expect("failed to copy image to buffer");
+        context.execute(builder);
+
+        let buf_content = buf.read().expect("could not read buffer");
+        for (level, &offset) in texture.levels.iter_mut().zip(&offsets) {
+            let start = offset as usize;
+            let end = start + level.len();
+            level.copy_from_slice(&buf_content[start..end]);
+        }
+        Ok(texture)
+    }
+
+    fn image_type(&self) -> ImageType {
+        if self.depth > 1 {
+            ImageType::Dim3d
+        } else {
+            ImageType::Dim2d
+        }
+    }
+
+    /// Where each level goes in a staging buffer, each starting on a multiple of the block size
+    /// and of 4 as copies need, and how big the buffer is.
+    fn level_offsets(&self) -> (Vec<u64>, u64) {
+        let alignment = lcm(self.format.block_size(), 4);
+        let mut size = 0u64;
+        let offsets = (0..self.levels.len() as u32)
+            .map(|level| {
+                let offset = size.next_multiple_of(alignment);
+                size = offset + self.level_size(level) as u64;
+                offset
+            })
+            .collect();
+        (offsets, size)
+    }
+
+    fn copy_regions(&self, offsets: &[u64]) -> Vec<BufferImageCopy> {
+        offsets
+            .iter()
+            .enumerate()
+            .map(|(level, &offset)| BufferImageCopy {
+                buffer_offset: offset,
+                image_subresource: ImageSubresourceLayers {
+                    aspects: ImageAspects::COLOR,
+                    mip_level: level as u32,
+                    array_layers: 0..self.layers * self.faces,
+                },
+                image_extent: self.level_extent(level as u32),
+                ..Default::default()
+            })
+            .collect()
+    }
+}
+
+fn extension(path: &str) -> Option<String> {
+    path.rsplit_once('.').map(|(_, e)| e.to_lowercase())
+}
+
+/// Reads little-endian numbers out of a file, failing instead of reading past its end.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, offset: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .offset
+            .checked_add(len)
+            .and_then(|end| self.bytes.get(self.offset..end))
+            .ok_or_else(|| "the file ends too soon".to_string())?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    /// Bytes left to read.
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.offset
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+}
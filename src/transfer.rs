@@ -0,0 +1,434 @@
+use std::sync::Arc;
+
+use image::{DynamicImage, ImageBuffer, RgbaImage};
+use vulkano::{
+    buffer::{Buffer, BufferCreateInfo, BufferUsage},
+    command_buffer::{BufferImageCopy, CopyBufferToImageInfo, CopyImageToBufferInfo},
+    format::{Format, FormatFeatures},
+    half::f16,
+    image::{Image, ImageAspects, ImageCreateInfo, ImageSubresourceLayers, ImageType, ImageUsage},
+    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter},
+};
+
+use crate::{context::Context, format::ColorFormat};
+
+/// Creates an `R8G8B8A8_UNORM` image of the given size on the device.
+pub fn create_rgba8_image(
+    context: &Context,
+    width: u32,
+    height: u32,
+    usage: ImageUsage,
+) -> Arc<Image> {
+    create_image(context, Format::R8G8B8A8_UNORM, width, height, usage)
+}
+
+/// Creates a 2D image of `format` and the given size on the device.
+pub fn create_image(
+    context: &Context,
+    format: Format,
+    width: u32,
+    height: u32,
+    usage: ImageUsage,
+) -> Arc<Image> {
+    create_mipmapped_image(context, format, width, height, 1, usage)
+}
+
+/// Like `create_image`, but with `mip_levels` mip levels.
+pub fn create_mipmapped_image(
+    context: &Context,
+    format: Format,
+    width: u32,
+    height: u32,
+    mip_levels: u32,
+    usage: ImageUsage,
+) -> Arc<Image> {
+    Image::new(
+        context.memory_allocator.clone(),
+        ImageCreateInfo {
+            image_type: ImageType::Dim2d,
+            format,
+            extent: [width, height, 1],
+            mip_levels,
+            usage,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+            ..Default::default()
+        },
+    )
+    .expect("failed to create image")
+}
+
+/// Copies `pixels` into a new device image through a staging buffer. `usage` gets
+/// `TRANSFER_DST` added to it.
+pub fn upload_rgba8(context: &Context, pixels: &RgbaImage, usage: ImageUsage) -> Arc<Image> {
+    upload(
+        context,
+        Format::R8G8B8A8_UNORM,
+        pixels.width(),
+        pixels.height(),
+        pixels.as_raw(),
+        usage,
+    )
+}
+
+/// Like `upload_rgba8`, but for tightly packed `bytes` of any `format`.
+pub fn upload(
+    context: &Context,
+    format: Format,
+    width: u32,
+    height: u32,
+    bytes: &[u8],
+    usage: ImageUsage,
+) -> Arc<Image> {
+    upload_mipmapped(context, format, width, height, 1, bytes, usage)
+}
+
+/// Like `upload`, but into the first of `mip_levels` mip levels, leaving the rest undefined.
+pub fn upload_mipmapped(
+    context: &Context,
+    format: Format,
+    width: u32,
+    height: u32,
+    mip_levels: u32,
+    bytes: &[u8],
+    usage: ImageUsage,
+) -> Arc<Image> {
+    let image = create_mipmapped_image(
+        context,
+        format,
+        width,
+        height,
+        mip_levels,
+        usage | ImageUsage::TRANSFER_DST,
+    );
+    let staging_buffer = Buffer::from_iter(
+        context.memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_SRC,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        },
+        bytes.iter().copied(),
+    )
+    .expect("failed to create staging buffer");
+
+    let mut builder = context.command_buffer_builder();
+    builder
+        .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(
+            staging_buffer,
+            image.clone(),
+        ))
+        .expect("failed to copy buffer to image");
+    context.execute(builder);
+
+    image
+}
+
+/// Decodes the image file at `path`, in any format the `image` crate reads such as PNG, JPEG,
+/// BMP, TGA or WebP, and uploads it like `upload_converted`.
+pub fn load_image(
+    context: &Context,
+    path: &str,
+    format: ColorFormat,
+    usage: ImageUsage,
+) -> Result<Arc<Image>, String> {
+    let pixels = image::open(path).map_err(|e| format!("could not open '{}': {}", path, e))?;
+    upload_converted(context, &pixels, format, usage)
+}
+
+/// Converts `pixels` to `format` and copies them into a new device image of the same size
+/// through a staging buffer, taking them as sRGB encoded for sRGB formats. `usage` gets
+/// `TRANSFER_DST` added to it. Fails when the device can't use images of `format` that way or
+/// that big.
+pub fn upload_converted(
+    context: &Context,
+    pixels: &DynamicImage,
+    format: ColorFormat,
+    usage: ImageUsage,
+) -> Result<Arc<Image>, String> {
+    upload_converted_mipmapped(context, pixels, format, 1, usage)
+}
+
+/// Like `upload_converted`, but into the first of `mip_levels` mip levels, leaving the rest
+/// undefined.
+pub fn upload_converted_mipmapped(
+    context: &Context,
+    pixels: &DynamicImage,
+    format: ColorFormat,
+    mip_levels: u32,
+    usage: ImageUsage,
+) -> Result<Arc<Image>, String> {
+    let physical_device = context.device.physical_device();
+    format.check_support(
+        physical_device,
+        FormatFeatures::TRANSFER_DST | usage_features(usage),
+    )?;
+    let max_size = physical_device.properties().max_image_dimension2_d;
+    if pixels.width().max(pixels.height()) > max_size {
+        return Err(format!(
+            "{}x{} doesn't fit in the device's largest image of {}x{}",
+            pixels.width(),
+            pixels.height(),
+            max_size,
+            max_size
+        ));
+    }
+
+    let bytes = format.encode_file(pixels);
+    Ok(upload_mipmapped(
+        context,
+        format.format(),
+        pixels.width(),
+        pixels.height(),
+        mip_levels,
+        &bytes,
+        usage,
+    ))
+}
+
+/// Format features optimally tiled images need for `usage`.
+pub(crate) fn usage_features(usage: ImageUsage) -> FormatFeatures {
+    let mut features = FormatFeatures::empty();
+    if usage.intersects(ImageUsage::TRANSFER_SRC) {
+        features |= FormatFeatures::TRANSFER_SRC;
+    }
+    if usage.intersects(ImageUsage::TRANSFER_DST) {
+        features |= FormatFeatures::TRANSFER_DST;
+    }
+    if usage.intersects(ImageUsage::SAMPLED) {
+        features |= FormatFeatures::SAMPLED_IMAGE;
+    }
+    if usage.intersects(ImageUsage::STORAGE) {
+        features |= FormatFeatures::STORAGE_IMAGE;
+    }
+    features
+}
+
+/// Copies an `R8G8B8A8_UNORM` device image, created with `TRANSFER_SRC` usage, back to the host.
+/// Panics for other formats, use `read_back` for those.
+pub fn download_rgba8(context: &Context, image: Arc<Image>) -> RgbaImage {
+    assert_eq!(
+        image.format(),
+        Format::R8G8B8A8_UNORM,
+        "download_rgba8 needs an R8G8B8A8_UNORM image"
+    );
+    let [width, height, _] = image.extent();
+    RgbaImage::from_raw(width, height, download(context, image))
+        .expect("failed to create image from buffer")
+}
+
+/// Like `download_rgba8`, but returns the tightly packed bytes of the first mip level and array
+/// layer of an image of any format `read_back` supports.
+pub fn download(context: &Context, image: Arc<Image>) -> Vec<u8> {
+    read_back(context, image, Subresource::default())
+        .expect("could not read back image")
+        .into_packed()
+}
+
+/// Which mip level and array layer of an image to read back.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Subresource {
+    pub mip_level: u32,
+    pub array_layer: u32,
+}
+
+/// Pixels read back from one subresource of an image, as they were copied into the buffer.
+#[derive(Clone, Debug)]
+pub struct Readback {
+    pub width: u32,
+    pub height: u32,
+    pub format: Format,
+    /// Bytes from the start of one row to the start of the next, which can be more than
+    /// `width` pixels take up.
+    pub row_pitch: usize,
+    pub bytes: Vec<u8>,
+}
+
+impl Readback {
+    /// Bytes each pixel takes up.
+    pub fn pixel_size(&self) -> usize {
+        self.format.block_size() as usize
+    }
+
+    /// The bytes of row `y`, without any padding after them.
+    pub fn row(&self, y: u32) -> &[u8] {
+        let start = y as usize * self.row_pitch;
+        &self.bytes[start..start + self.width as usize * self.pixel_size()]
+    }
+
+    /// The pixels with the padding between rows removed.
+    pub fn into_packed(self) -> Vec<u8> {
+        if self.row_pitch == self.width as usize * self.pixel_size() {
+            return self.bytes;
+        }
+        (0..self.height)
+            .flat_map(|y| self.row(y))
+            .copied()
+            .collect()
+    }
+
+    /// Converts to the closest `DynamicImage`. sRGB formats keep their encoded values, BGRA is
+    /// swizzled to RGBA and half floats widen to `f32`. Fails for formats `image` has nothing
+    /// close to.
+    pub fn to_dynamic_image(&self) -> Result<DynamicImage, String> {
+        let (width, height) = (self.width, self.height);
+        let bytes = || (0..height).flat_map(|y| self.row(y)).copied();
+        let u16s = || {
+            (0..height)
+                .flat_map(|y| self.row(y).chunks_exact(2))
+                .map(|c| u16::from_ne_bytes([c[0], c[1]]))
+        };
+        let f16s = || {
+            (0..height)
+                .flat_map(|y| self.row(y).chunks_exact(2))
+                .map(|c| f16::from_ne_bytes([c[0], c[1]]).to_f32())
+        };
+        let f32s = || {
+            (0..height)
+                .flat_map(|y| self.row(y).chunks_exact(4))
+                .map(|c| f32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+        };
+        let image = match self.format {
+            Format::R8_UNORM | Format::R8_SRGB => {
+                ImageBuffer::from_raw(width, height, bytes().collect())
+                    .map(DynamicImage::ImageLuma8)
+            }
+            Format::R8G8B8_UNORM | Format::R8G8B8_SRGB => {
+                ImageBuffer::from_raw(width, height, bytes().collect()).map(DynamicImage::ImageRgb8)
+            }
+            Format::R8G8B8A8_UNORM | Format::R8G8B8A8_SRGB => {
+                ImageBuffer::from_raw(width, height, bytes().collect())
+                    .map(DynamicImage::ImageRgba8)
+            }
+            Format::B8G8R8A8_UNORM | Format::B8G8R8A8_SRGB => {
+                let mut pixels: Vec<u8> = bytes().collect();
+                pixels.chunks_exact_mut(4).for_each(|p| p.swap(0, 2));
+                ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8)
+            }
+            Format::R16_UNORM => ImageBuffer::from_raw(width, height, u16s().collect())
+                .map(DynamicImage::ImageLuma16),
+            Format::R16G16B16_UNORM => {
+                ImageBuffer::from_raw(width, height, u16s().collect()).map(DynamicImage::ImageRgb16)
+            }
+            Format::R16G16B16A16_UNORM => ImageBuffer::from_raw(width, height, u16s().collect())
+                .map(DynamicImage::ImageRgba16),
+            Format::R16G16B16_SFLOAT => ImageBuffer::from_raw(width, height, f16s().collect())
+                .map(DynamicImage::ImageRgb32F),
+            Format::R16G16B16A16_SFLOAT => ImageBuffer::from_raw(width, height, f16s().collect())
+                .map(DynamicImage::ImageRgba32F),
+            Format::R32G32B32_SFLOAT => ImageBuffer::from_raw(width, height, f32s().collect())
+                .map(DynamicImage::ImageRgb32F),
+            Format::R32G32B32A32_SFLOAT => ImageBuffer::from_raw(width, height, f32s().collect())
+                .map(DynamicImage::ImageRgba32F),
+            format => return Err(format!("can't convert {:?} to an image", format)),
+        };
+        Ok(image.expect("wrong number of bytes for image"))
+    }
+}
+
+/// Copies one mip level and array layer of a 2D image, created with `TRANSFER_SRC` usage, back
+/// to the host. Rows are padded to the device's optimal row pitch alignment. Fails for
+/// compressed, multi-planar and depth/stencil formats, and subresources the image doesn't have.
+pub fn read_back(
+    context: &Context,
+    image: Arc<Image>,
+    subresource: Subresource,
+) -> Result<Readback, String> {
+    let format = image.format();
+    if image.image_type() != ImageType::Dim2d {
+        return Err(format!("can't read back {:?} images", image.image_type()));
+    }
+    if !image.usage().intersects(ImageUsage::TRANSFER_SRC) {
+        return Err("the image needs TRANSFER_SRC usage to be read back".to_string());
+    }
+    if format.compression().is_some()
+        || !format.planes().is_empty()
+        || format.aspects() != ImageAspects::COLOR
+    {
+        return Err(format!("can't read back {:?} images", format));
+    }
+    let Subresource {
+        mip_level,
+        array_layer,
+    } = subresource;
+    if mip_level >= image.mip_levels() || array_layer >= image.array_layers() {
+        return Err(format!(
+            "the image has {} mip levels and {} array layers, so there's no mip level {} of \
+             layer {}",
+            image.mip_levels(),
+            image.array_layers(),
+            mip_level,
+            array_layer
+        ));
+    }
+
+    let [width, height, _] = image.extent().map(|e| (e >> mip_level).max(1));
+    let pixel_size = format.block_size();
+    let alignment = context
+        .device
+        .physical_device()
+        .properties()
+        .optimal_buffer_copy_row_pitch_alignment
+        .as_devicesize();
+    // the pitch has to be whole pixels as well as aligned
+    let row_pitch = (width as u64 * pixel_size).next_multiple_of(lcm(alignment, pixel_size));
+    let size = row_pitch * (height - 1) as u64 + width as u64 * pixel_size;
+    let buf = Buffer::new_slice::<u8>(
+        context.memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+            ..Default::default()
+        },
+        size,
+    )
+    .expect("could not create buffer");
+
+    let mut builder = context.command_buffer_builder();
+    builder
+        .copy_image_to_buffer(CopyImageToBufferInfo {
+            regions: [BufferImageCopy {
+                buffer_row_length: (row_pitch / pixel_size) as u32,
+                image_subresource: ImageSubresourceLayers {
+                    aspects: ImageAspects::COLOR,
+                    mip_level,
+                    array_layers: array_layer..array_layer + 1,
+                },
+                image_extent: [width, height, 1],
+                ..Default::default()
+            }]
+            .into(),
+            ..CopyImageToBufferInfo::image_buffer(image, buf.clone())
+        })
+        .expect("failed to copy image to buffer");
+    context.execute(builder);
+
+    let bytes = buf.read().expect("could not read buffer").to_vec();
+    Ok(Readback {
+        width,
+        height,
+        format,
+        row_pitch: row_pitch as usize,
+        bytes,
+    })
+}
+
+pub(crate) fn lcm(a: u64, b: u64) -> u64 {
+    let gcd = |mut a: u64, mut b: u64| {
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    };
+    a / gcd(a, b) * b
+}
@@ -0,0 +1,76 @@
+use vulkan_test::fractal::buddhabrot::{
+    accumulate_cpu, BuddhabrotRenderer, BuddhabrotSettings, IterationLimits,
+};
+
+mod common;
+
+fn settings() -> BuddhabrotSettings {
+    BuddhabrotSettings {
+        width: 61,
+        height: 47,
+        limits: IterationLimits([20, 100, 500]),
+        samples: 20_000,
+        seed: 7,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn longer_limits_count_every_shorter_orbit() {
+    let hits = accumulate_cpu(&settings());
+    let pixels = 61 * 47;
+    let [red, green, blue] = [0, 1, 2].map(|k| &hits[k * pixels..(k + 1) * pixels]);
+    assert!(red.iter().zip(green).all(|(r, g)| r <= g));
+    assert!(green.iter().zip(blue).all(|(g, b)| g <= b));
+    assert!(red.iter().sum::<u32>() > 0);
+}
+
+#[test]
+fn same_seed_renders_the_same() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let settings = settings();
+    let renderer = BuddhabrotRenderer::new(&context, &settings).unwrap();
+
+    let first = renderer.render(&context, &settings);
+    let second = renderer.render(&context, &settings);
+    assert_eq!(first, second);
+
+    let reseeded = renderer.render(
+        &context,
+        &BuddhabrotSettings {
+            seed: 8,
+            ..settings
+        },
+    );
+    assert_ne!(first, reseeded);
+}
+
+#[test]
+fn hits_match_cpu() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let settings = settings();
+    let renderer = BuddhabrotRenderer::new(&context, &settings).unwrap();
+    renderer.accumulate(&context, &settings);
+    let gpu = renderer.hits(&context);
+    let cpu = accumulate_cpu(&settings);
+
+    // orbits are chaotic, so rounding differences move some hits around, but barely change
+    // how many there are
+    let pixels = 61 * 47;
+    for k in 0..3 {
+        let [gpu_total, cpu_total] =
+            [&gpu, &cpu].map(|hits| hits[k * pixels..(k + 1) * pixels].iter().sum::<u32>());
+        let difference = (gpu_total as f64 - cpu_total as f64).abs() / cpu_total as f64;
+        assert!(
+            difference < 0.02,
+            "channel {} has {} hits on the GPU and {} on the CPU",
+            k,
+            gpu_total,
+            cpu_total
+        );
+    }
+}
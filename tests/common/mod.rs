@@ -0,0 +1,38 @@
+// every test crate builds its own copy of this module and uses only some of it
+#![allow(dead_code)]
+
+use std::env;
+
+use image::{Rgba, RgbaImage};
+use vulkan_test::context::Context;
+use vulkano::device::Features;
+
+/// Sets up a device, or returns `None` when the machine has no Vulkan implementation at all so
+/// GPU tests can be skipped instead of failing. With `REQUIRE_VULKAN` set they fail instead, so a
+/// run that was meant to use the GPU can't pass without it.
+pub fn context() -> Option<Context> {
+    context_with_optional(Features::empty())
+}
+
+/// Like `context`, also turning on whichever of the `optional` features the device supports.
+pub fn context_with_optional(optional: Features) -> Option<Context> {
+    match Context::with_optional_features(Features::empty(), optional) {
+        Ok(context) => Some(context),
+        Err(e) if env::var_os("REQUIRE_VULKAN").is_some() => {
+            panic!("no Vulkan device and REQUIRE_VULKAN is set: {}", e)
+        }
+        Err(e) => {
+            eprintln!("skipping GPU test: {}", e);
+            None
+        }
+    }
+}
+
+/// A small image with gradients, hard edges and varying alpha. Tests give it odd sizes like
+/// 37x23, so the last work group of each row and column hangs off the edge.
+pub fn test_image(width: u32, height: u32) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| {
+        let edge = if (x / 6 + y / 5) % 2 == 0 { 200 } else { 30 };
+        Rgba([(x * 7) as u8, (y * 11) as u8, edge, 255 - (x + y) as u8])
+    })
+}
@@ -0,0 +1,100 @@
+use image::{DynamicImage, Rgba, Rgba32FImage};
+use vulkan_test::compare::{compare_cpu, heat_map_cpu, ImageComparer, Threshold};
+
+mod common;
+
+fn gray(value: f32) -> Rgba32FImage {
+    Rgba32FImage::from_pixel(20, 12, Rgba([value, value, value, 1.0]))
+}
+
+#[test]
+fn thresholds_parse() {
+    assert_eq!("psnr:40".parse(), Ok(Threshold::Psnr(40.0)));
+    assert_eq!("max-error:0.01".parse(), Ok(Threshold::MaxError(0.01)));
+    for bad in ["psnr", "psnr:x", "mse:0.1"] {
+        assert!(bad.parse::<Threshold>().is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn identical_images_are_perfect() {
+    let metrics = compare_cpu(&gray(0.3), &gray(0.3)).unwrap();
+    assert_eq!(metrics.largest_error(), 0.0);
+    assert_eq!(metrics.color_rmse(), 0.0);
+    assert_eq!(metrics.psnr(), f64::INFINITY);
+    assert!((metrics.ssim - 1.0).abs() < 1e-9);
+    let strict = [
+        Threshold::MaxError(0.0),
+        Threshold::Psnr(100.0),
+        Threshold::Ssim(1.0 - 1e-9),
+    ];
+    assert!(metrics.check(&strict).is_ok());
+}
+
+#[test]
+fn metrics_follow_the_difference() {
+    let metrics = compare_cpu(&gray(0.5), &gray(0.6)).unwrap();
+    assert!((metrics.largest_error() - 0.1).abs() < 1e-6);
+    assert!((metrics.color_rmse() - 0.1).abs() < 1e-6);
+    assert!((metrics.psnr() - 20.0).abs() < 1e-4);
+    assert_eq!(metrics.rmse[3], 0.0);
+    assert!(metrics.ssim < 1.0);
+    assert!(metrics
+        .check(&[Threshold::MaxError(0.2), Threshold::Psnr(15.0)])
+        .is_ok());
+    assert!(metrics.check(&[Threshold::Rmse(0.05)]).is_err());
+    assert!(compare_cpu(&gray(0.5), &Rgba32FImage::new(4, 4)).is_err());
+
+    let heat_map = heat_map_cpu(
+        &Rgba32FImage::from_pixel(1, 1, Rgba([0.05, 0.0, 0.0, 0.0])),
+        10.0,
+    );
+    assert_eq!(heat_map.get_pixel(0, 0).0, [255, 128, 0, 255]);
+}
+
+#[test]
+fn gpu_matches_cpu() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let reference = Rgba32FImage::from_fn(37, 29, |x, y| {
+        Rgba([
+            x as f32 / 36.0,
+            y as f32 / 28.0,
+            ((x * y) % 7) as f32 / 6.0,
+            1.0,
+        ])
+    });
+    let image = Rgba32FImage::from_fn(37, 29, |x, y| {
+        let mut pixel = *reference.get_pixel(x, y);
+        if (x + 3 * y) % 5 == 0 {
+            pixel[0] = (pixel[0] + 0.2).min(1.0);
+            pixel[3] = 0.5;
+        }
+        pixel
+    });
+
+    let comparer = ImageComparer::new(&context);
+    let comparison = comparer
+        .compare_images(
+            &context,
+            &DynamicImage::ImageRgba32F(image.clone()),
+            &DynamicImage::ImageRgba32F(reference.clone()),
+            4.0,
+        )
+        .unwrap();
+    let expected = compare_cpu(&image, &reference).unwrap();
+    let metrics = &comparison.metrics;
+    assert_eq!(metrics.max_error, expected.max_error);
+    for (gpu, cpu) in metrics.rmse.iter().zip(expected.rmse) {
+        assert!((gpu - cpu).abs() < 1e-5, "{} {}", gpu, cpu);
+    }
+    assert!((metrics.ssim - expected.ssim).abs() < 1e-4);
+
+    let heat_map = heat_map_cpu(&comparison.difference, 4.0);
+    for (gpu, cpu) in comparison.heat_map.pixels().zip(heat_map.pixels()) {
+        for (g, c) in gpu.0.into_iter().zip(cpu.0) {
+            assert!(g.abs_diff(c) <= 1);
+        }
+    }
+}
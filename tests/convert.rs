@@ -0,0 +1,221 @@
+use image::{imageops, DynamicImage, RgbaImage};
+use vulkan_test::{
+    context::Context,
+    convert::{
+        convert_cpu, max_difference, Conversion, ConvertMethod, Converter, Crop, ResizeFilter,
+        Swizzle,
+    },
+    format::ColorFormat,
+};
+use vulkano::device::Features;
+
+mod common;
+
+/// Runs `conversion` with nearest filtering on the CPU and rounds back to 8 bits.
+fn nearest(input: &RgbaImage, conversion: Conversion) -> RgbaImage {
+    let conversion = Conversion {
+        filter: ResizeFilter::Nearest,
+        ..conversion
+    };
+    let output = convert_cpu(&DynamicImage::ImageRgba8(input.clone()), &conversion).unwrap();
+    DynamicImage::ImageRgba32F(output).to_rgba8()
+}
+
+#[test]
+fn options_parse() {
+    assert_eq!(
+        "1,2,30,40".parse::<Crop>(),
+        Ok(Crop {
+            x: 1,
+            y: 2,
+            width: 30,
+            height: 40
+        })
+    );
+    assert!("1,2,0,40".parse::<Crop>().is_err());
+    assert_eq!("bgr1".parse::<Swizzle>(), Ok(Swizzle([2, 1, 0, 5])));
+    assert!("rgb".parse::<Swizzle>().is_err());
+    assert_eq!("lanczos".parse::<ResizeFilter>(), Ok(ResizeFilter::Lanczos));
+}
+
+#[test]
+fn geometry_matches_image_crate() {
+    let input = common::test_image(37, 23);
+    let crop = Crop {
+        x: 3,
+        y: 4,
+        width: 20,
+        height: 11,
+    };
+    let cropped = imageops::crop_imm(&input, 3, 4, 20, 11).to_image();
+    let conversion = |quarter_turns, flip_horizontal, flip_vertical| Conversion {
+        crop: Some(crop),
+        quarter_turns,
+        flip_horizontal,
+        flip_vertical,
+        ..Default::default()
+    };
+
+    assert_eq!(nearest(&input, conversion(0, false, false)), cropped);
+    assert_eq!(
+        nearest(&input, conversion(1, false, false)),
+        imageops::rotate90(&cropped)
+    );
+    assert_eq!(
+        nearest(&input, conversion(2, false, false)),
+        imageops::rotate180(&cropped)
+    );
+    assert_eq!(
+        nearest(&input, conversion(3, false, false)),
+        imageops::rotate270(&cropped)
+    );
+    assert_eq!(
+        nearest(&input, conversion(1, true, false)),
+        imageops::flip_horizontal(&imageops::rotate90(&cropped))
+    );
+    assert_eq!(
+        nearest(&input, conversion(0, false, true)),
+        imageops::flip_vertical(&cropped)
+    );
+}
+
+#[test]
+fn sizes_follow_crop_and_rotation() {
+    let conversion = Conversion {
+        crop: Some(Crop {
+            x: 30,
+            y: 0,
+            width: 7,
+            height: 20,
+        }),
+        quarter_turns: 3,
+        ..Default::default()
+    };
+    assert_eq!(conversion.output_size(37, 23), Ok([20, 7]));
+    assert!(conversion.output_size(36, 23).is_err());
+    let resized = Conversion {
+        size: Some([5, 6]),
+        ..conversion
+    };
+    assert_eq!(resized.output_size(37, 23), Ok([5, 6]));
+}
+
+/// A device that can also store to images without a format, when it supports that.
+fn context() -> Option<Context> {
+    common::context_with_optional(Features {
+        shader_storage_image_write_without_format: true,
+        ..Features::empty()
+    })
+}
+
+/// Checks `conversion` run with `method` against the CPU, unless the device can't run it.
+fn check_against_cpu(
+    context: &Context,
+    converter: &Converter,
+    conversion: &Conversion,
+    method: ConvertMethod,
+) {
+    if let Err(e) = converter.check_support(context, conversion, method) {
+        eprintln!("skipping {:?} with {:?}: {}", conversion, method, e);
+        return;
+    }
+    let input = DynamicImage::ImageRgba8(common::test_image(37, 23));
+    let readback = converter
+        .convert(context, &input, conversion, method)
+        .unwrap();
+    let expected = convert_cpu(&input, conversion).unwrap();
+    assert_eq!(
+        (readback.width, readback.height),
+        expected.dimensions(),
+        "{:?}",
+        conversion
+    );
+    let actual = conversion
+        .to
+        .decode(readback.width, readback.height, &readback.into_packed());
+    let difference = max_difference(conversion.to, &actual, &expected);
+    assert!(
+        difference <= 2.0 / 255.0,
+        "{:?} with {:?} is off by {}",
+        conversion,
+        method,
+        difference
+    );
+}
+
+#[test]
+fn compute_matches_cpu() {
+    let Some(context) = context() else {
+        return;
+    };
+    let converter = Converter::new(&context);
+    let conversions = [
+        Conversion {
+            size: Some([80, 50]),
+            filter: ResizeFilter::Bicubic,
+            ..Default::default()
+        },
+        Conversion {
+            size: Some([9, 6]),
+            filter: ResizeFilter::Lanczos,
+            to: ColorFormat::Rgba16Float,
+            ..Default::default()
+        },
+        Conversion {
+            crop: Some(Crop {
+                x: 5,
+                y: 2,
+                width: 17,
+                height: 19,
+            }),
+            quarter_turns: 1,
+            flip_vertical: true,
+            swizzle: Swizzle([2, 1, 0, 5]),
+            from: ColorFormat::Rgba8Srgb,
+            to: ColorFormat::Rgba8Srgb,
+            ..Default::default()
+        },
+        Conversion {
+            size: Some([20, 30]),
+            filter: ResizeFilter::Nearest,
+            from: ColorFormat::Rgba8Srgb,
+            to: ColorFormat::Rgba32Float,
+            ..Default::default()
+        },
+    ];
+    for conversion in &conversions {
+        check_against_cpu(&context, &converter, conversion, ConvertMethod::Compute);
+    }
+}
+
+#[test]
+fn blit_matches_cpu() {
+    let Some(context) = context() else {
+        return;
+    };
+    let converter = Converter::new(&context);
+    let conversions = [
+        Conversion {
+            size: Some([74, 46]),
+            ..Default::default()
+        },
+        Conversion {
+            crop: Some(Crop {
+                x: 1,
+                y: 1,
+                width: 32,
+                height: 16,
+            }),
+            quarter_turns: 2,
+            flip_horizontal: true,
+            size: Some([16, 8]),
+            filter: ResizeFilter::Nearest,
+            from: ColorFormat::Rgba8Srgb,
+            to: ColorFormat::Rgba16,
+            ..Default::default()
+        },
+    ];
+    for conversion in &conversions {
+        check_against_cpu(&context, &converter, conversion, ConvertMethod::Blit);
+    }
+}
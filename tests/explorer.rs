@@ -0,0 +1,181 @@
+use vulkan_test::{
+    format::ColorFormat,
+    fractal::{
+        explorer::Explorer,
+        iteration_counts,
+        palette::{Palette, BUILTIN_NAMES},
+        ColorMode, Precision, RenderSettings,
+    },
+};
+use vulkano::device::Features;
+
+mod common;
+
+/// Fraction of the overlapping pixels that may differ between a render and a panned one. The
+/// center moves by a whole number of pixels, but in single precision the points of the pixels
+/// round differently.
+const MAX_MISMATCHED_FRACTION: f64 = 0.02;
+
+fn settings() -> RenderSettings {
+    RenderSettings {
+        width: 64,
+        height: 48,
+        center: [-0.75, 0.0],
+        zoom: 0.8,
+        iterations: 100,
+        color: ColorMode::Raw,
+        precision: Precision::Single,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn zooming_keeps_the_point_under_the_cursor() {
+    let mut explorer = Explorer::new(settings());
+    let before = explorer.settings().pixel_point(10, 30);
+    explorer.zoom_at(10.5, 30.5, 3.0);
+    let after = explorer.settings().pixel_point(10, 30);
+    for i in 0..2 {
+        assert!(
+            (before[i] - after[i]).abs() < 1e-12,
+            "{:?} moved to {:?}",
+            before,
+            after
+        );
+    }
+    assert!((explorer.settings().zoom - 2.4).abs() < 1e-12);
+}
+
+#[test]
+fn only_renders_when_the_view_changes() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let mut explorer = Explorer::new(settings());
+    assert!(explorer.needs_render());
+    explorer.render(&context).unwrap();
+    assert!(!explorer.needs_render());
+
+    // going nowhere isn't a change
+    explorer.pan(0.0, 0.0);
+    explorer.zoom_at(5.0, 5.0, 1.0);
+    assert!(!explorer.needs_render());
+
+    explorer.pan(3.0, -2.0);
+    assert!(explorer.needs_render());
+    explorer.render(&context).unwrap();
+    explorer.scale_iterations(2.0);
+    assert!(explorer.needs_render());
+    assert_eq!(explorer.settings().iterations, 200);
+}
+
+#[test]
+fn panning_moves_the_render() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let (dx, dy) = (8, 5);
+    let mut explorer = Explorer::new(settings());
+    let before = explorer.render(&context).unwrap();
+    explorer.pan(dx as f64, dy as f64);
+    let after = explorer.render(&context).unwrap();
+
+    // what was at (x, y) is now at (x + dx, y + dy)
+    let (width, height) = before.dimensions();
+    let before = iteration_counts(&before);
+    let after = iteration_counts(&after);
+    let mut mismatched = 0;
+    for y in 0..height - dy {
+        for x in 0..width - dx {
+            let moved = ((y + dy) * width + x + dx) as usize;
+            if before[(y * width + x) as usize] != after[moved] {
+                mismatched += 1;
+            }
+        }
+    }
+    let overlap = (width - dx) * (height - dy);
+    assert!(
+        mismatched as f64 <= overlap as f64 * MAX_MISMATCHED_FRACTION,
+        "{} of {} pixels didn't move with the view",
+        mismatched,
+        overlap
+    );
+}
+
+#[test]
+fn renders_other_formats_as_rgba8() {
+    let Some(context) = common::context_with_optional(Features {
+        shader_storage_image_write_without_format: true,
+        ..Features::empty()
+    }) else {
+        return;
+    };
+    let settings = RenderSettings {
+        color: ColorMode::Palette,
+        ..settings()
+    };
+    let reference = Explorer::new(settings.clone()).render(&context).unwrap();
+
+    for format in [ColorFormat::Rgba16, ColorFormat::Rgba16Float] {
+        let mut explorer = Explorer::new(RenderSettings {
+            format,
+            ..settings.clone()
+        });
+        let image = match explorer.render(&context) {
+            Ok(image) => image,
+            Err(e) => {
+                eprintln!("skipping {:?}: {}", format, e);
+                continue;
+            }
+        };
+        assert_eq!(image.dimensions(), reference.dimensions());
+        // both round to the nearest step, from slightly different colors
+        let max_error = reference
+            .as_raw()
+            .iter()
+            .zip(image.as_raw())
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap();
+        assert!(max_error <= 1, "{:?} is off by {}", format, max_error);
+    }
+}
+
+#[test]
+fn failed_renders_only_undo_the_view() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    // only the mandelbrot set zooms deeper than doubles
+    let mut explorer = Explorer::new(RenderSettings {
+        fractal: "julia:-0.8,0.156".parse().unwrap(),
+        precision: Precision::Auto,
+        color: ColorMode::Palette,
+        ..settings()
+    });
+    explorer.render(&context).unwrap();
+    let zoom = explorer.settings().zoom;
+
+    explorer.next_palette();
+    explorer.resize(32, 24);
+    explorer.zoom_at(0.0, 0.0, 1e20);
+    assert!(explorer.render(&context).is_err());
+    assert_eq!(explorer.settings().zoom, zoom);
+    assert_eq!(
+        [explorer.settings().width, explorer.settings().height],
+        [32, 24]
+    );
+    let palette = |name| format!("{:?}", Palette::builtin(name).unwrap());
+    assert_eq!(
+        format!("{:?}", explorer.settings().palette),
+        palette(BUILTIN_NAMES[1])
+    );
+
+    // the next palette is the one after what's shown
+    explorer.render(&context).unwrap();
+    explorer.next_palette();
+    assert_eq!(
+        format!("{:?}", explorer.settings().palette),
+        palette(BUILTIN_NAMES[2])
+    );
+}
@@ -0,0 +1,55 @@
+use vulkan_test::filters::{max_channel_difference, Filter, FilterPipelines};
+
+mod common;
+
+/// How far a single filter on the GPU may drift from the CPU reference, in 8-bit levels.
+const TOLERANCE: u8 = 2;
+
+fn check_matches_cpu(filter: &str) {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let filter: Filter = filter.parse().unwrap();
+    let input = common::test_image(37, 23);
+
+    let gpu = FilterPipelines::new(&context).apply(&context, &input, &[filter]);
+    let cpu = filter.apply_cpu(&input);
+
+    let difference = max_channel_difference(&gpu, &cpu);
+    assert!(
+        difference <= TOLERANCE,
+        "{:?} differs from the CPU reference by {}",
+        filter,
+        difference
+    );
+}
+
+#[test]
+fn blur_matches_cpu() {
+    check_matches_cpu("blur:1.5");
+}
+
+#[test]
+fn sobel_matches_cpu() {
+    check_matches_cpu("sobel");
+}
+
+#[test]
+fn grayscale_matches_cpu() {
+    check_matches_cpu("grayscale");
+}
+
+#[test]
+fn brightness_contrast_matches_cpu() {
+    check_matches_cpu("brightness-contrast:0.1,1.4");
+}
+
+#[test]
+fn gamma_matches_cpu() {
+    check_matches_cpu("gamma:2.2");
+}
+
+#[test]
+fn unsharp_matches_cpu() {
+    check_matches_cpu("unsharp:1.0,1.5");
+}
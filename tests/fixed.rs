@@ -0,0 +1,110 @@
+use vulkan_test::fractal::fixed::{reference_orbit, Fixed};
+
+/// 2^-40, written out exactly.
+const TINY: &str = "0.0000000000009094947017729282379150390625";
+
+fn fixed(text: &str) -> Fixed {
+    Fixed::parse(text, 2).unwrap()
+}
+
+#[test]
+fn parses_decimals() {
+    assert_eq!(fixed("3.5").to_decimal(), "3.500000000000000000000");
+    assert_eq!(fixed("-0.75").to_decimal(), "-0.750000000000000000000");
+    assert_eq!(fixed("+.0625").to_f64(), 0.0625);
+    assert_eq!(fixed("12.").to_f64(), 12.0);
+    for bad in ["", "-", ".", "1.2.3", "0x10", "1e5", "- 1", "99999999999"] {
+        assert!(Fixed::parse(bad, 2).is_err(), "{:?} parsed", bad);
+    }
+}
+
+#[test]
+fn decimals_round_trip() {
+    for text in [
+        "0.1",
+        "-0.7436438870371587047521915",
+        "1.0000000000000000001",
+        TINY,
+    ] {
+        for fraction_limbs in [1, 2, 4] {
+            let number = Fixed::parse(text, fraction_limbs).unwrap();
+            let decimal = number.to_decimal();
+            let again = Fixed::parse(&decimal, fraction_limbs).unwrap();
+            assert_eq!(
+                again.to_decimal(),
+                decimal,
+                "{} in {} limbs",
+                text,
+                fraction_limbs
+            );
+        }
+    }
+    // digits past what an f64 holds are kept
+    let precise = Fixed::parse("0.1000000000000000000001", 3).unwrap();
+    assert!(precise.sub(&Fixed::parse("0.1", 3).unwrap()).to_f64() > 0.0);
+}
+
+#[test]
+fn adds_and_subtracts_across_signs() {
+    let cases = [
+        ("1.25", "3.5"),
+        ("1.25", "-3.5"),
+        ("-1.25", "3.5"),
+        ("-1.25", "-3.5"),
+        ("3.5", "-1.25"),
+        ("0.75", "0.75"),
+    ];
+    for (a, b) in cases {
+        let (x, y) = (fixed(a), fixed(b));
+        let (a, b): (f64, f64) = (a.parse().unwrap(), b.parse().unwrap());
+        assert_eq!(x.add(&y).to_f64(), a + b, "{} + {}", a, b);
+        assert_eq!(x.sub(&y).to_f64(), a - b, "{} - {}", a, b);
+    }
+    // borrows carry across limbs
+    let difference = fixed("1").sub(&fixed(TINY));
+    assert_eq!(difference.to_f64(), 1.0 - 2f64.powi(-40));
+    assert_eq!(
+        difference.add(&fixed(TINY)).to_decimal(),
+        fixed("1").to_decimal()
+    );
+}
+
+#[test]
+fn multiplies_like_f64_and_truncates() {
+    for (a, b) in [("-1.5", "2.5"), ("0.3", "-0.7"), ("-1.1", "-1.9")] {
+        let product = fixed(a).mul(&fixed(b)).to_f64();
+        let expected = a.parse::<f64>().unwrap() * b.parse::<f64>().unwrap();
+        assert!(
+            (product - expected).abs() < 1e-15,
+            "{} * {} is {}, not {}",
+            a,
+            b,
+            product,
+            expected
+        );
+    }
+    // 2^-80 needs more than two fractional limbs
+    let tiny = fixed(TINY);
+    assert_eq!(tiny.mul(&tiny).to_f64(), 0.0);
+    let tiny = Fixed::parse(TINY, 3).unwrap();
+    assert_eq!(tiny.mul(&tiny).to_f64(), 2f64.powi(-80));
+}
+
+#[test]
+fn reference_orbits_follow_the_iteration() {
+    // 0, 1, 2, 5 escapes
+    let orbit = reference_orbit(&[fixed("1"), fixed("0")], 100, 2.0);
+    assert_eq!(orbit, [[0.0, 0.0], [1.0, 0.0], [2.0, 0.0], [5.0, 0.0]]);
+    // -1 cycles forever, so the orbit stops at the iteration limit
+    let orbit = reference_orbit(&[fixed("-1"), fixed("0")], 10, 2.0);
+    assert_eq!(orbit.len(), 11);
+    assert_eq!(orbit[9], [-1.0, 0.0]);
+
+    let c = [-0.75, 0.1];
+    let orbit = reference_orbit(&[fixed("-0.75"), fixed("0.1")], 50, 2.0);
+    let mut z = [0.0f64, 0.0];
+    for point in orbit {
+        assert!((point[0] - z[0]).abs() < 1e-9 && (point[1] - z[1]).abs() < 1e-9);
+        z = [z[0] * z[0] - z[1] * z[1] + c[0], 2.0 * z[0] * z[1] + c[1]];
+    }
+}
@@ -0,0 +1,103 @@
+use image::{DynamicImage, Rgba, Rgba32FImage, RgbaImage};
+use vulkan_test::{
+    format::{srgb_to_linear, ColorFormat},
+    fractal::{ColorMode, FractalRenderer, RenderSettings},
+};
+use vulkano::device::Features;
+
+mod common;
+
+const FORMATS: [ColorFormat; 4] = [
+    ColorFormat::Rgba8,
+    ColorFormat::Rgba16,
+    ColorFormat::Rgba16Float,
+    ColorFormat::Rgba32Float,
+];
+
+#[test]
+fn encoding_round_trips() {
+    let image = Rgba32FImage::from_fn(7, 5, |x, y| {
+        Rgba([x as f32 / 6.0, y as f32 / 4.0, 0.5, 1.0])
+    });
+    for format in FORMATS {
+        let bytes = format.encode(&image);
+        assert_eq!(bytes.len(), 7 * 5 * format.pixel_size());
+        let decoded = format.decode(7, 5, &bytes);
+        // half a step of the coarsest format, rgba8
+        let max_error = image
+            .as_raw()
+            .iter()
+            .zip(decoded.as_raw())
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max);
+        assert!(
+            max_error <= 0.5 / 255.0 + 1e-6,
+            "{:?} is off by {}",
+            format,
+            max_error
+        );
+    }
+}
+
+#[test]
+fn srgb_round_trips_every_byte() {
+    let file = RgbaImage::from_fn(16, 16, |x, y| {
+        let c = (y * 16 + x) as u8;
+        Rgba([c, 255 - c, c / 2, c])
+    });
+    let format = ColorFormat::Rgba8Srgb;
+    let bytes = format.encode_file(&DynamicImage::ImageRgba8(file.clone()));
+    assert_eq!(&bytes, file.as_raw());
+    let linear = format.decode(16, 16, &bytes);
+    // mid gray is about a fifth of the light of white
+    assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+    assert_eq!(format.encode(&linear), bytes);
+}
+
+#[test]
+fn formats_render_the_same_colors() {
+    let Some(context) = common::context_with_optional(Features {
+        shader_storage_image_write_without_format: true,
+        ..Features::empty()
+    }) else {
+        return;
+    };
+    let settings = RenderSettings {
+        width: 61,
+        height: 47,
+        zoom: 0.8,
+        iterations: 100,
+        color: ColorMode::Palette,
+        ..Default::default()
+    };
+    let renderer = FractalRenderer::new(&context, &settings).unwrap();
+    let reference = renderer.render_float(&context, &settings).unwrap();
+
+    for format in &FORMATS[1..] {
+        let settings = RenderSettings {
+            format: *format,
+            ..settings.clone()
+        };
+        let renderer = match FractalRenderer::new(&context, &settings) {
+            Ok(renderer) => renderer,
+            Err(e) => {
+                eprintln!("skipping {:?}: {}", format, e);
+                continue;
+            }
+        };
+        let image = renderer.render_float(&context, &settings).unwrap();
+        // rgba8 rounds to the nearest step
+        let max_error = reference
+            .as_raw()
+            .iter()
+            .zip(image.as_raw())
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max);
+        assert!(
+            max_error <= 0.5 / 255.0 + 1e-3,
+            "{:?} is off by {}",
+            format,
+            max_error
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use image::{Rgba, RgbaImage};
+use vulkan_test::fractal::{
+    iteration_counts,
+    progressive::ProgressiveRender,
+    render_cpu,
+    supersample::{Downsampler, Supersampling},
+    tiled::TiledRenderer,
+    ColorMode, FractalRenderer, Precision, RenderSettings,
+};
+use vulkano::device::Features;
+
+mod common;
+
+/// Fraction of pixels whose iteration count may differ from the CPU reference. The GPU iterates
+/// in single precision and the CPU in double, so points right on the edge of escaping can land
+/// either side of it.
+const MAX_MISMATCHED_FRACTION: f64 = 0.02;
+
+fn check_matches_cpu(fractal: &str, center: [f64; 2], zoom: f64) {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let settings = RenderSettings {
+        fractal: fractal.parse().unwrap(),
+        width: 61,
+        height: 47,
+        center,
+        zoom,
+        iterations: 100,
+        color: ColorMode::Raw,
+        precision: Precision::Single,
+        ..Default::default()
+    };
+
+    let renderer = FractalRenderer::new(&context, &settings).unwrap();
+    let gpu = iteration_counts(&renderer.render(&context, &settings).unwrap());
+    let cpu = iteration_counts(&render_cpu(&settings));
+
+    let mismatched = gpu.iter().zip(&cpu).filter(|(g, c)| g != c).count();
+    let allowed = (gpu.len() as f64 * MAX_MISMATCHED_FRACTION) as usize;
+    assert!(
+        mismatched <= allowed,
+        "{} of {} pixels of {} differ from the CPU reference",
+        mismatched,
+        gpu.len(),
+        fractal
+    );
+}
+
+#[test]
+fn mandelbrot_matches_cpu() {
+    check_matches_cpu("mandelbrot", [-0.75, 0.0], 0.8);
+}
+
+#[test]
+fn julia_matches_cpu() {
+    check_matches_cpu("julia:-0.8,0.156", [0.0, 0.0], 0.7);
+}
+
+#[test]
+fn burning_ship_matches_cpu() {
+    check_matches_cpu("burning-ship", [-0.5, -0.5], 0.6);
+}
+
+#[test]
+fn tricorn_matches_cpu() {
+    check_matches_cpu("tricorn", [0.0, 0.0], 0.7);
+}
+
+#[test]
+fn multibrot_matches_cpu() {
+    check_matches_cpu("multibrot:3", [0.0, 0.0], 0.7);
+}
+
+#[test]
+fn perturbation_matches_double() {
+    let Some(context) = common::context_with_optional(Features {
+        shader_float64: true,
+        ..Features::empty()
+    }) else {
+        return;
+    };
+    if !context.device.enabled_features().shader_float64 {
+        eprintln!("skipping: the device does not support double precision");
+        return;
+    }
+    // deep enough that single precision is long gone, but doubles still have bits to spare
+    let settings = RenderSettings {
+        width: 61,
+        height: 47,
+        center: [-0.743643887037151, 0.131825904205330],
+        zoom: 1e6,
+        iterations: 500,
+        color: ColorMode::Raw,
+        ..Default::default()
+    };
+    let render = |precision| {
+        let settings = RenderSettings {
+            precision,
+            ..settings.clone()
+        };
+        let renderer = FractalRenderer::new(&context, &settings).unwrap();
+        assert_eq!(renderer.precision(), precision);
+        iteration_counts(&renderer.render(&context, &settings).unwrap())
+    };
+    let double = render(Precision::Double);
+    let perturbation = render(Precision::Perturbation);
+
+    let mismatched = double
+        .iter()
+        .zip(&perturbation)
+        .filter(|(d, p)| d != p)
+        .count();
+    let allowed = (double.len() as f64 * MAX_MISMATCHED_FRACTION) as usize;
+    assert!(
+        mismatched <= allowed,
+        "{} of {} pixels differ between perturbation and double precision",
+        mismatched,
+        double.len()
+    );
+}
+
+#[test]
+fn tiles_cover_the_same_points() {
+    let settings = RenderSettings {
+        width: 61,
+        height: 47,
+        center: [-0.75, 0.1],
+        zoom: 3.0,
+        ..Default::default()
+    };
+    // hangs off both edges
+    let tile = settings.tile(48, 32, 16, 16);
+    for (x, y) in [(0, 0), (12, 14), (15, 15)] {
+        let expected = settings.pixel_point(48 + x, 32 + y);
+        let actual = tile.pixel_point(x, y);
+        for i in 0..2 {
+            assert!((expected[i] - actual[i]).abs() < 1e-12);
+        }
+    }
+}
+
+#[test]
+fn tiled_render_matches_whole() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let settings = RenderSettings {
+        width: 61,
+        height: 47,
+        center: [-0.75, 0.0],
+        zoom: 0.8,
+        iterations: 100,
+        color: ColorMode::Raw,
+        precision: Precision::Single,
+        ..Default::default()
+    };
+
+    let whole = FractalRenderer::new(&context, &settings)
+        .unwrap()
+        .render(&context, &settings)
+        .unwrap();
+    let mut tiled = Vec::new();
+    TiledRenderer::new(&context, &settings, 16)
+        .unwrap()
+        .render(&context, &settings, |rows| {
+            tiled.extend_from_slice(rows);
+            Ok(())
+        })
+        .unwrap();
+
+    // each tile's center rounds to single precision differently, which moves edge pixels
+    assert_eq!(tiled.len(), whole.as_raw().len());
+    let tiled = RgbaImage::from_raw(settings.width, settings.height, tiled).unwrap();
+    let mismatched = iteration_counts(&whole)
+        .iter()
+        .zip(iteration_counts(&tiled))
+        .filter(|(w, t)| **w != *t)
+        .count();
+    let allowed = (whole.len() as f64 / 4.0 * MAX_MISMATCHED_FRACTION) as usize;
+    assert!(
+        mismatched <= allowed,
+        "{} pixels of the tiled render differ",
+        mismatched
+    );
+}
+
+#[test]
+fn progressive_render_matches_whole() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let settings = RenderSettings {
+        width: 61,
+        height: 47,
+        center: [-0.75, 0.0],
+        zoom: 0.8,
+        iterations: 100,
+        color: ColorMode::Raw,
+        precision: Precision::Single,
+        ..Default::default()
+    };
+    let renderer = FractalRenderer::new(&context, &settings).unwrap();
+    let whole = iteration_counts(&renderer.render(&context, &settings).unwrap());
+
+    let mut render = ProgressiveRender::new(&context, &renderer, &settings).unwrap();
+    let mut active_fraction = 1.0;
+    while !render.is_finished() {
+        render.step(&context, 7);
+        assert!(render.active_fraction() <= active_fraction);
+        active_fraction = render.active_fraction();
+    }
+    assert_eq!(render.iterations_done(), 100);
+
+    // resuming from saved orbits does exactly the same math
+    assert_eq!(iteration_counts(&renderer.download(&context)), whole);
+}
+
+#[test]
+fn downsampling_keeps_constant_image_constant() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let color = Rgba([37, 180, 255, 91]);
+    let input = RgbaImage::from_pixel(64, 48, color);
+
+    for factor in [2, 4] {
+        let output = Downsampler::new(&context).apply(&context, &input, factor);
+        assert_eq!(output.dimensions(), (64 / factor, 48 / factor));
+        assert!(
+            output.pixels().all(|p| *p == color),
+            "downsampling by {} changed a constant image",
+            factor
+        );
+    }
+}
+
+#[test]
+fn samples_stay_inside_their_pixel() {
+    for pattern in ["2x2", "4x4", "rotated-grid", "jittered:3"] {
+        let supersampling: Supersampling = pattern.parse().unwrap();
+        let samples = supersampling.samples_per_side().pow(2);
+        assert_eq!(supersampling.offsets().len(), samples as usize);
+        for (x, y) in [(0, 0), (5, 17), (1023, 4)] {
+            for index in 0..samples {
+                let offset = supersampling.sample_offset(x, y, index);
+                assert!(
+                    offset.iter().all(|o| (0.0..1.0).contains(o)),
+                    "{} puts sample {} at {:?}",
+                    pattern,
+                    index,
+                    offset
+                );
+            }
+        }
+    }
+}
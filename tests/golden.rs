@@ -0,0 +1,421 @@
+//! Runs what each example that saves images does, headless, and checks the results against the
+//! reference images in tests/golden. Without a Vulkan device the tests are skipped, unless
+//! `REQUIRE_VULKAN=1` is set to make that a failure, so install a software one like lavapipe to
+//! run them on machines without a GPU. The windowed examples, graphics and mandelbrot-viewer,
+//! aren't covered.
+//!
+//! The references are made by the CPU implementations of the workloads, and
+//! `references_match_the_cpu` keeps them within the same tolerances of those, device or not. Set
+//! `UPDATE_GOLDEN=cpu` to rebuild them that way after changing a workload or its inputs, or
+//! `UPDATE_GOLDEN=1` to take them from the device instead. When a result is too far from its
+//! reference, it's saved with a heat map of the differences to target/tmp/golden.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use image::{DynamicImage, GenericImage, Rgba32FImage, RgbaImage};
+use vulkan_test::{
+    compare::{compare_cpu, heat_map_cpu, ImageComparer, Threshold},
+    context::Context,
+    convert::{convert_cpu, Conversion, Converter, Crop, ResizeFilter},
+    filters::{apply_cpu_chain, Filter, FilterPipelines},
+    format::{to_rgba8, ColorFormat},
+    fractal::{render_cpu, ColorMode, FractalRenderer, Precision, RenderSettings},
+    layered::{blur_cpu, fill_cpu, LayeredProcessor, Pattern, Shape},
+    mipmap::{downsample_cpu, read_back_levels, MipmapGenerator},
+    stats::{stats_cpu, StatsPipeline},
+    texture::Texture,
+    transfer::upload_rgba8,
+};
+use vulkano::image::ImageUsage;
+
+mod common;
+
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+
+/// How much differences are scaled up in the heat maps saved on failure, and in the compare
+/// workload's.
+const AMPLIFY: f32 = 10.0;
+
+/// A reference image, and how far results may be from it.
+#[derive(Clone, Copy)]
+struct Golden {
+    name: &'static str,
+    thresholds: &'static [Threshold],
+}
+
+/// The largest difference 8-bit results may have from the reference, in levels.
+const fn max_error(levels: u32) -> Threshold {
+    Threshold::MaxError(levels as f64 / 255.0)
+}
+
+const IMAGES: Golden = Golden {
+    name: "images",
+    thresholds: &[max_error(2)],
+};
+
+/// Single precision lands points right on the edge of escaping either side of it, so a few
+/// pixels may be far off.
+const COMPUTE_MANDELBROT: Golden = Golden {
+    name: "compute-mandelbrot",
+    thresholds: &[Threshold::Psnr(25.0), Threshold::Ssim(0.9)],
+};
+
+const IMAGE_FILTERS: Golden = Golden {
+    name: "image-filters",
+    thresholds: &[max_error(6)],
+};
+
+const IMAGE_STATS: Golden = Golden {
+    name: "image-stats",
+    thresholds: &[max_error(0)],
+};
+
+/// The levels of the chain that are checked.
+const MIPMAPS: [(usize, Golden); 2] = [
+    (
+        1,
+        Golden {
+            name: "mipmaps-1",
+            thresholds: &[max_error(2)],
+        },
+    ),
+    (
+        3,
+        Golden {
+            name: "mipmaps-3",
+            thresholds: &[max_error(2)],
+        },
+    ),
+];
+
+const VOLUMES: Golden = Golden {
+    name: "volumes",
+    thresholds: &[max_error(2)],
+};
+
+const COMPARE: Golden = Golden {
+    name: "compare",
+    thresholds: &[max_error(1)],
+};
+
+/// What `UPDATE_GOLDEN` is set to.
+fn update_golden() -> Option<String> {
+    env::var("UPDATE_GOLDEN").ok()
+}
+
+fn reference_path(golden: Golden) -> PathBuf {
+    Path::new(GOLDEN_DIR).join(format!("{}.png", golden.name))
+}
+
+fn open_reference(golden: Golden) -> DynamicImage {
+    let path = reference_path(golden);
+    image::open(&path).unwrap_or_else(|e| {
+        panic!(
+            "could not open '{}': {}, run with UPDATE_GOLDEN=cpu to create it",
+            path.display(),
+            e
+        )
+    })
+}
+
+fn save_reference(golden: Golden, image: &RgbaImage) {
+    fs::create_dir_all(GOLDEN_DIR).expect("could not create golden image directory");
+    let path = reference_path(golden);
+    image.save(&path).expect("could not save golden image");
+    eprintln!("updated '{}'", path.display());
+}
+
+/// Saves `actual` and the heat map of its differences from the reference to target/tmp/golden,
+/// and fails with `error`.
+fn fail(golden: Golden, actual: &RgbaImage, heat_map: Option<&RgbaImage>, error: &str) -> ! {
+    let failure_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    fs::create_dir_all(&failure_dir).expect("could not create failure directory");
+    actual
+        .save(failure_dir.join(format!("{}-actual.png", golden.name)))
+        .expect("could not save actual image");
+    if let Some(heat_map) = heat_map {
+        heat_map
+            .save(failure_dir.join(format!("{}-diff.png", golden.name)))
+            .expect("could not save difference image");
+    }
+    panic!(
+        "{} doesn't match its reference: {}. the result and its differences are in '{}'",
+        golden.name,
+        error,
+        failure_dir.display()
+    );
+}
+
+/// Compares what the device made with its reference, or replaces the reference with it when
+/// `UPDATE_GOLDEN` is set to anything but `cpu`.
+fn check_golden(context: &Context, golden: Golden, actual: RgbaImage) {
+    match update_golden().as_deref() {
+        // `references_match_the_cpu` is rewriting them
+        Some("cpu") => return,
+        Some(_) => return save_reference(golden, &actual),
+        None => {}
+    }
+    let reference = open_reference(golden);
+    let comparison = ImageComparer::new(context).compare_images(
+        context,
+        &DynamicImage::ImageRgba8(actual.clone()),
+        &reference,
+        AMPLIFY,
+    );
+    match &comparison {
+        Ok(comparison) => {
+            if let Err(e) = comparison.metrics.check(golden.thresholds) {
+                fail(golden, &actual, Some(&comparison.heat_map), &e);
+            }
+        }
+        Err(e) => fail(golden, &actual, None, e),
+    }
+}
+
+/// Absolute difference of every channel of every pixel, like `Comparison::difference`.
+fn difference(a: &Rgba32FImage, b: &Rgba32FImage) -> Rgba32FImage {
+    let mut difference = a.clone();
+    for (d, b) in difference.pixels_mut().zip(b.pixels()) {
+        for (d, b) in d.0.iter_mut().zip(b.0) {
+            *d = (*d - b).abs();
+        }
+    }
+    difference
+}
+
+/// Every slice of a volume side by side.
+fn side_by_side(slices: &[RgbaImage]) -> RgbaImage {
+    let (width, height) = slices[0].dimensions();
+    let mut image = RgbaImage::new(width * slices.len() as u32, height);
+    for (i, slice) in slices.iter().enumerate() {
+        image.copy_from(slice, width * i as u32, 0).unwrap();
+    }
+    image
+}
+
+fn conversion() -> Conversion {
+    Conversion {
+        crop: Some(Crop {
+            x: 6,
+            y: 4,
+            width: 60,
+            height: 44,
+        }),
+        quarter_turns: 1,
+        flip_horizontal: true,
+        size: Some([33, 50]),
+        filter: ResizeFilter::Bicubic,
+        ..Default::default()
+    }
+}
+
+fn mandelbrot_settings() -> RenderSettings {
+    RenderSettings {
+        width: 161,
+        height: 121,
+        center: [-0.75, 0.0],
+        zoom: 0.8,
+        iterations: 100,
+        color: ColorMode::Palette,
+        precision: Precision::Single,
+        ..Default::default()
+    }
+}
+
+fn filter_chain() -> Vec<Filter> {
+    ["blur:1.5", "unsharp:1,0.5", "gamma:1.2"]
+        .iter()
+        .map(|filter| filter.parse().unwrap())
+        .collect()
+}
+
+const VOLUME_SIZE: u32 = 21;
+const VOLUME_SHAPE: Shape = Shape::Volume { depth: 6 };
+
+/// An image and the reference it's compared with, which differ by a checkerboard of red.
+fn compare_inputs() -> [RgbaImage; 2] {
+    let reference = common::test_image(75, 53);
+    let mut image = reference.clone();
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        if (x / 16 + y / 16) & 1 == 0 {
+            pixel[0] = pixel[0].saturating_add(12);
+        }
+    }
+    [image, reference]
+}
+
+/// Every reference, made on the CPU.
+fn cpu_references() -> Vec<(Golden, RgbaImage)> {
+    let input = common::test_image(75, 53);
+    let converted = convert_cpu(&DynamicImage::ImageRgba8(input.clone()), &conversion()).unwrap();
+    let mut references = vec![
+        (IMAGES, to_rgba8(&converted)),
+        (COMPUTE_MANDELBROT, render_cpu(&mandelbrot_settings())),
+        (IMAGE_FILTERS, apply_cpu_chain(&input, &filter_chain())),
+        (IMAGE_STATS, stats_cpu(&input).histogram_image()),
+    ];
+
+    // each level is made from the one before as the device stores it
+    let mut level = common::test_image(64, 64);
+    for index in 1..=MIPMAPS[MIPMAPS.len() - 1].0 {
+        level = to_rgba8(&downsample_cpu(
+            &DynamicImage::ImageRgba8(level).to_rgba32f(),
+        ));
+        if let Some(&(_, golden)) = MIPMAPS.iter().find(|(checked, _)| *checked == index) {
+            references.push((golden, level.clone()));
+        }
+    }
+
+    let filled = fill_cpu(VOLUME_SIZE, VOLUME_SIZE, VOLUME_SHAPE, Pattern::Sphere);
+    let slices: Vec<RgbaImage> = blur_cpu(&filled, VOLUME_SHAPE, 1)
+        .iter()
+        .map(to_rgba8)
+        .collect();
+    references.push((VOLUMES, side_by_side(&slices)));
+
+    let [image, reference] =
+        compare_inputs().map(|image| DynamicImage::ImageRgba8(image).to_rgba32f());
+    references.push((
+        COMPARE,
+        heat_map_cpu(&difference(&image, &reference), AMPLIFY),
+    ));
+    references
+}
+
+/// Checks the references are still what the CPU implementations make, within the tolerances
+/// results from a device get, or rewrites them when `UPDATE_GOLDEN=cpu` is set.
+#[test]
+fn references_match_the_cpu() {
+    let update = update_golden();
+    // the device tests are rewriting them
+    if update.as_deref().is_some_and(|update| update != "cpu") {
+        return;
+    }
+    for (golden, expected) in cpu_references() {
+        if update.is_some() {
+            save_reference(golden, &expected);
+            continue;
+        }
+        let reference = open_reference(golden).to_rgba32f();
+        let result = compare_cpu(
+            &DynamicImage::ImageRgba8(expected.clone()).to_rgba32f(),
+            &reference,
+        )
+        .and_then(|metrics| metrics.check(golden.thresholds));
+        if let Err(e) = result {
+            fail(golden, &expected, None, &e);
+        }
+    }
+}
+
+#[test]
+fn images() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let converter = Converter::new(&context);
+    let conversion = conversion();
+    let method = converter.pick_method(&context, &conversion).unwrap();
+    let input = DynamicImage::ImageRgba8(common::test_image(75, 53));
+    let readback = converter
+        .convert(&context, &input, &conversion, method)
+        .unwrap();
+    let actual = readback.to_dynamic_image().unwrap().to_rgba8();
+    check_golden(&context, IMAGES, actual);
+}
+
+#[test]
+fn compute_mandelbrot() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let settings = mandelbrot_settings();
+    let renderer = FractalRenderer::new(&context, &settings).unwrap();
+    let actual = renderer.render(&context, &settings).unwrap();
+    check_golden(&context, COMPUTE_MANDELBROT, actual);
+}
+
+#[test]
+fn image_filters() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let input = common::test_image(75, 53);
+    let actual = FilterPipelines::new(&context).apply(&context, &input, &filter_chain());
+    check_golden(&context, IMAGE_FILTERS, actual);
+}
+
+#[test]
+fn image_stats() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let input = common::test_image(75, 53);
+    let image = upload_rgba8(&context, &input, ImageUsage::STORAGE);
+    let stats = StatsPipeline::new(&context).compute(&context, image);
+    check_golden(&context, IMAGE_STATS, stats.histogram_image());
+}
+
+#[test]
+fn mipmaps() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let generator = MipmapGenerator::new(&context);
+    let method = generator.pick_method(&context, ColorFormat::Rgba8).unwrap();
+    let input = DynamicImage::ImageRgba8(common::test_image(64, 64));
+    let image = generator
+        .generate(&context, &input, ColorFormat::Rgba8, method)
+        .unwrap();
+    let levels = read_back_levels(&context, image).unwrap();
+    for (level, golden) in MIPMAPS {
+        let actual = levels[level].to_dynamic_image().unwrap().to_rgba8();
+        check_golden(&context, golden, actual);
+    }
+}
+
+#[test]
+fn volumes() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let processor = LayeredProcessor::new(&context);
+    let image = processor
+        .create(
+            &context,
+            ColorFormat::Rgba8,
+            VOLUME_SIZE,
+            VOLUME_SIZE,
+            VOLUME_SHAPE,
+        )
+        .unwrap();
+    processor.fill(&context, image.clone(), Pattern::Sphere);
+    let image = processor.blur(&context, image, 1);
+    let slices: Vec<RgbaImage> = Texture::download(&context, image)
+        .unwrap()
+        .slices(0)
+        .into_iter()
+        .map(|slice| slice.to_dynamic_image().unwrap().to_rgba8())
+        .collect();
+    check_golden(&context, VOLUMES, side_by_side(&slices));
+}
+
+#[test]
+fn compare() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let [image, reference] = compare_inputs();
+    let comparison = ImageComparer::new(&context)
+        .compare_images(
+            &context,
+            &DynamicImage::ImageRgba8(image),
+            &DynamicImage::ImageRgba8(reference),
+            AMPLIFY,
+        )
+        .unwrap();
+    check_golden(&context, COMPARE, comparison.heat_map);
+}
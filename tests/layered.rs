@@ -0,0 +1,112 @@
+use image::{Rgba, Rgba32FImage};
+use vulkan_test::{
+    context::Context,
+    convert::max_difference,
+    format::ColorFormat,
+    layered::{blur_cpu, fill_cpu, LayeredProcessor, Pattern, Shape},
+    texture::Texture,
+};
+use vulkano::device::Features;
+
+mod common;
+
+#[test]
+fn shapes_parse() {
+    assert_eq!("array:4".parse(), Ok(Shape::Array { layers: 4 }));
+    assert_eq!("volume:64".parse(), Ok(Shape::Volume { depth: 64 }));
+    assert_eq!("cube".parse(), Ok(Shape::Cube { layers: 1 }));
+    assert_eq!("cube:2".parse(), Ok(Shape::Cube { layers: 2 }));
+    for bad in ["array", "array:0", "volume:x", "sphere:3"] {
+        assert!(bad.parse::<Shape>().is_err(), "{}", bad);
+    }
+
+    let cubes = Shape::Cube { layers: 2 };
+    assert_eq!(cubes.array_layers(), Some(12));
+    assert_eq!(cubes.extent(8, 8), [8, 8, 1]);
+    let volume = Shape::Volume { depth: 5 };
+    assert_eq!(volume.array_layers(), Some(1));
+    assert_eq!(volume.slices(), Some(5));
+    // parses, but can't be counted, let alone made
+    let too_many: Shape = "cube:1000000000".parse().unwrap();
+    assert_eq!(too_many.array_layers(), None);
+    assert_eq!(volume.extent(8, 4), [8, 4, 5]);
+}
+
+#[test]
+fn only_volumes_blur_across_slices() {
+    let slices: Vec<Rgba32FImage> = [0.0, 1.0, 0.0]
+        .into_iter()
+        .map(|c| Rgba32FImage::from_pixel(4, 4, Rgba([c, c, c, 1.0])))
+        .collect();
+    let volume = blur_cpu(&slices, Shape::Volume { depth: 3 }, 1);
+    let array = blur_cpu(&slices, Shape::Array { layers: 3 }, 1);
+    assert_eq!(volume[0].get_pixel(1, 1).0, [0.5, 0.5, 0.5, 1.0]);
+    assert!((volume[1].get_pixel(1, 1)[0] - 1.0 / 3.0).abs() < 1e-6);
+    assert_eq!(array, slices);
+}
+
+/// A device that can also load and store images without a format, when it supports that.
+fn context() -> Option<Context> {
+    common::context_with_optional(Features {
+        shader_storage_image_read_without_format: true,
+        shader_storage_image_write_without_format: true,
+        ..Features::empty()
+    })
+}
+
+#[test]
+fn fills_and_blurs_every_layer_like_the_cpu() {
+    let Some(context) = context() else {
+        return;
+    };
+    let processor = LayeredProcessor::new(&context);
+    for shape in [
+        Shape::Cube {
+            layers: 1_000_000_000,
+        },
+        Shape::Array { layers: u32::MAX },
+    ] {
+        assert!(processor
+            .create(&context, ColorFormat::Rgba8, 4, 4, shape)
+            .is_err());
+    }
+    for (format, shape) in [
+        (ColorFormat::Rgba8, Shape::Array { layers: 3 }),
+        (ColorFormat::Rgba8, Shape::Volume { depth: 9 }),
+        (ColorFormat::Rgba8, Shape::Cube { layers: 2 }),
+        (ColorFormat::Rgba16Float, Shape::Volume { depth: 4 }),
+    ] {
+        let (width, height) = if matches!(shape, Shape::Cube { .. }) {
+            (12, 12)
+        } else {
+            (13, 10)
+        };
+        let image = match processor.create(&context, format, width, height, shape) {
+            Ok(image) => image,
+            Err(e) => {
+                eprintln!("skipping {:?}: {}", format, e);
+                continue;
+            }
+        };
+        processor.fill(&context, image.clone(), Pattern::Sphere);
+        let image = processor.blur(&context, image, 2);
+        let texture = Texture::download(&context, image).unwrap();
+        assert_eq!(
+            Some(texture.layers * texture.faces * texture.depth),
+            shape.slices()
+        );
+
+        let expected = blur_cpu(&fill_cpu(width, height, shape, Pattern::Sphere), shape, 2);
+        for (slice, expected) in texture.slices(0).into_iter().zip(&expected) {
+            let actual = format.decode(width, height, &slice.into_packed());
+            let difference = max_difference(format, &actual, expected);
+            assert!(
+                difference <= 1.0 / 255.0,
+                "{:?} {:?}: {}",
+                format,
+                shape,
+                difference
+            );
+        }
+    }
+}
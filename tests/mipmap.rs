@@ -0,0 +1,80 @@
+use image::DynamicImage;
+use vulkan_test::{
+    context::Context,
+    format::ColorFormat,
+    mipmap::{
+        downsample_cpu, level_extent, mip_levels, read_back_levels, MipmapGenerator, MipmapMethod,
+    },
+};
+
+mod common;
+
+#[test]
+fn chains_go_down_to_one_pixel() {
+    assert_eq!(mip_levels(1, 1), 1);
+    assert_eq!(mip_levels(37, 23), 6);
+    assert_eq!(mip_levels(1024, 512), 11);
+    assert_eq!(level_extent(37, 23, 1), [18, 11]);
+    assert_eq!(level_extent(37, 23, 5), [1, 1]);
+}
+
+/// Largest difference between any level the GPU made and the CPU reference chain.
+fn max_error_against_cpu(
+    context: &Context,
+    generator: &MipmapGenerator,
+    input: &DynamicImage,
+    method: MipmapMethod,
+) -> Option<f32> {
+    let image = match generator.generate(context, input, ColorFormat::Rgba8, method) {
+        Ok(image) => image,
+        Err(e) => {
+            eprintln!("skipping {:?}: {}", method, e);
+            return None;
+        }
+    };
+    let levels = read_back_levels(context, image).unwrap();
+    let mut expected = input.to_rgba32f();
+    let mut max_error = 0.0f32;
+    for level in levels {
+        let actual = level.to_dynamic_image().unwrap().to_rgba32f();
+        assert_eq!(actual.dimensions(), expected.dimensions());
+        for (a, e) in actual.as_raw().iter().zip(expected.as_raw()) {
+            max_error = max_error.max((a - e).abs());
+        }
+        // carry on from what the GPU stored, so rounding doesn't build up
+        expected = downsample_cpu(&actual);
+    }
+    Some(max_error)
+}
+
+#[test]
+fn compute_matches_cpu_for_odd_sizes() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let generator = MipmapGenerator::new(&context);
+    if let Some(max_error) = max_error_against_cpu(
+        &context,
+        &generator,
+        &DynamicImage::ImageRgba8(common::test_image(37, 23)),
+        MipmapMethod::Compute,
+    ) {
+        assert!(max_error <= 1.0 / 255.0 + 1e-6, "off by {}", max_error);
+    }
+}
+
+#[test]
+fn blit_matches_cpu_for_powers_of_two() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let generator = MipmapGenerator::new(&context);
+    if let Some(max_error) = max_error_against_cpu(
+        &context,
+        &generator,
+        &DynamicImage::ImageRgba8(common::test_image(32, 16)),
+        MipmapMethod::Blit,
+    ) {
+        assert!(max_error <= 1.0 / 255.0 + 1e-6, "off by {}", max_error);
+    }
+}
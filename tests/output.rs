@@ -0,0 +1,107 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use serde_json::Value;
+use vulkan_test::output::{iso_timestamp, timestamp, Output, OutputOptions};
+
+/// An empty directory of its own for each test.
+fn out_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join("output")
+        .join(name);
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn timestamps_are_utc() {
+    assert_eq!(timestamp(UNIX_EPOCH), "19700101-000000");
+    // a leap day, and the end of a year
+    let leap_day = UNIX_EPOCH + Duration::from_secs(951_827_696);
+    assert_eq!(timestamp(leap_day), "20000229-123456");
+    let new_years_eve = UNIX_EPOCH + Duration::from_secs(1_798_761_599);
+    assert_eq!(iso_timestamp(new_years_eve), "2026-12-31T23:59:59Z");
+}
+
+#[test]
+fn names_are_safe_for_files() {
+    let output = Output::new("mandelbrot", OutputOptions::default());
+    let name = output.file_name(&["c-0.5,1".to_string(), "a/b c".to_string()], "png");
+    assert!(name.starts_with("mandelbrot-c-0.5_1-a_b_c-"), "{}", name);
+    assert!(name.ends_with(".png"), "{}", name);
+}
+
+#[test]
+fn refuses_to_overwrite() {
+    let dir = out_dir("refuses_to_overwrite");
+    let options = OutputOptions {
+        dir: dir.clone(),
+        overwrite: false,
+    };
+    let mut output = Output::new("test", options.clone());
+    let path = output.claim("nested/image.png").unwrap();
+    assert_eq!(path, dir.join("nested/image.png"));
+    fs::write(&path, b"").unwrap();
+
+    assert!(Output::new("test", options.clone())
+        .claim("nested/image.png")
+        .is_err());
+    // the sidecar is checked too
+    fs::write(dir.join("other.json"), b"").unwrap();
+    assert!(Output::new("test", options).claim("other.png").is_err());
+
+    let options = OutputOptions {
+        dir,
+        overwrite: true,
+    };
+    assert!(Output::new("test", options)
+        .claim("nested/image.png")
+        .is_ok());
+}
+
+#[test]
+fn files_are_only_claimed_once() {
+    let options = OutputOptions {
+        dir: out_dir("files_are_only_claimed_once"),
+        overwrite: true,
+    };
+    let mut output = Output::new("test", options.clone());
+    output.claim("results/a.png").unwrap();
+    assert!(output.claim("results/a.png").is_err());
+    // the sidecar is taken too
+    assert!(output.claim("results/a.json").is_err());
+    assert!(output.claim("results/b.png").is_ok());
+
+    // JSON results keep their sidecar apart
+    let mut output = Output::new("test", options);
+    assert!(output.claim("stats.json").is_ok());
+    assert!(output.claim("stats.png").is_ok());
+    assert!(output.claim("stats.sidecar.json").is_err());
+}
+
+#[test]
+fn sidecar_describes_the_run() {
+    let dir = out_dir("sidecar_describes_the_run");
+    let options = OutputOptions {
+        dir: dir.clone(),
+        overwrite: false,
+    };
+    let mut output = Output::new("test", options);
+    output.set_parameter("width", 64);
+    output.claim("frames.png").unwrap();
+    output.claim("frames-preview.png").unwrap();
+    output
+        .write_sidecar("device", Duration::from_millis(1500))
+        .unwrap();
+
+    let json: Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("frames.json")).unwrap()).unwrap();
+    assert_eq!(json["workload"], "test");
+    assert_eq!(json["parameters"]["width"], 64);
+    assert_eq!(json["device"], "device");
+    assert_eq!(json["elapsed_seconds"], 1.5);
+    assert_eq!(json["files"].as_array().unwrap().len(), 2);
+}
@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use vulkan_test::split::{split_work_groups, worker::WORK_GROUP_SIZE, Element, Worker};
+use vulkano::device::physical::PhysicalDevice;
+
+mod common;
+
+/// Checks the ranges cover `0..total` in order without any of them being empty, and returns how
+/// many groups each device got.
+fn counts(total: u32, throughputs: &[f64]) -> Vec<u32> {
+    let split = split_work_groups(total, throughputs);
+    let mut counts = vec![0; throughputs.len()];
+    let mut end = 0;
+    for (device, range) in split {
+        assert!(!range.is_empty(), "device {} got {:?}", device, range);
+        assert_eq!(
+            range.start, end,
+            "ranges of {:?} don't follow on",
+            throughputs
+        );
+        assert_eq!(counts[device], 0, "device {} got two ranges", device);
+        counts[device] = range.len() as u32;
+        end = range.end;
+    }
+    assert_eq!(end, total, "{:?} don't add up to {}", throughputs, total);
+    counts
+}
+
+#[test]
+fn splits_in_proportion() {
+    assert_eq!(counts(256, &[1.0]), [256]);
+    assert_eq!(counts(256, &[1.0, 3.0]), [64, 192]);
+    assert_eq!(counts(256, &[2e9, 2e9]), [128, 128]);
+    assert_eq!(split_work_groups(10, &[1.0, 1.0]), [(0, 0..5), (1, 5..10)]);
+}
+
+#[test]
+fn remainders_go_to_the_largest_fractions() {
+    // 10 thirds each round down to 3, and the one left over goes to the first
+    assert_eq!(counts(10, &[1.0, 1.0, 1.0]), [4, 3, 3]);
+    // 2.5, 3.75 and 3.75 round down to 8 in all, so both 0.75s get one more
+    assert_eq!(counts(10, &[2.0, 3.0, 3.0]), [2, 4, 4]);
+    for total in [1, 7, 100, 65537] {
+        counts(total, &[0.3, 0.3, 0.4, 1e-3, 7.0]);
+    }
+}
+
+#[test]
+fn slow_devices_are_left_out() {
+    assert_eq!(counts(100, &[0.0, 5.0]), [0, 100]);
+    assert_eq!(counts(100, &[f64::NAN, -1.0, 5.0]), [0, 0, 100]);
+    assert_eq!(counts(100, &[1e-6, 1.0]), [0, 100]);
+    let split = split_work_groups(100, &[0.0, 5.0]);
+    assert_eq!(split, [(1, 0..100)]);
+}
+
+#[test]
+fn nothing_measured_splits_evenly() {
+    assert_eq!(counts(9, &[0.0, 0.0, 0.0]), [3, 3, 3]);
+    assert_eq!(counts(9, &[f64::NAN, -0.0]), [5, 4]);
+}
+
+#[test]
+fn infinitely_fast_devices_take_everything() {
+    assert_eq!(counts(9, &[f64::INFINITY, f64::NAN]), [9, 0]);
+    assert_eq!(counts(9, &[1e300, f64::INFINITY]), [0, 9]);
+    assert_eq!(counts(9, &[f64::INFINITY, f64::INFINITY]), [5, 4]);
+}
+
+#[test]
+fn more_devices_than_groups() {
+    assert_eq!(counts(2, &[1.0; 5]), [1, 1, 0, 0, 0]);
+    assert_eq!(counts(0, &[1.0, 2.0]), [0, 0]);
+    assert!(split_work_groups(0, &[1.0, 2.0]).is_empty());
+    assert!(split_work_groups(5, &[]).is_empty());
+}
+
+/// Runs the kernel over a job split unevenly between two logical devices on `physical_device`,
+/// like `--devices 0,0`, and checks the merged results against the CPU.
+fn split_over_one_device<T: Element>(physical_device: &Arc<PhysicalDevice>) {
+    let workers: Vec<Worker<T>> = (0..2)
+        .map(|_| Worker::new(physical_device.clone()).unwrap())
+        .collect();
+    let throughput = workers[0].measure_throughput();
+    assert!(throughput.is_finite() && throughput > 0.0, "{}", throughput);
+
+    let data: Vec<T> = (0..16384).map(T::from_index).collect();
+    let split = split_work_groups(data.len() as u32 / WORK_GROUP_SIZE, &[1.0, 3.0]);
+    assert_eq!(split.len(), 2);
+    let chunks: Vec<_> = split
+        .into_iter()
+        .map(|(device, groups)| {
+            let elements =
+                (groups.start * WORK_GROUP_SIZE) as usize..(groups.end * WORK_GROUP_SIZE) as usize;
+            let buffer = workers[device].upload(&data[elements]);
+            (workers[device].dispatch(buffer.clone()), buffer)
+        })
+        .collect();
+    let mut merged = Vec::with_capacity(data.len());
+    for (future, buffer) in chunks {
+        future.wait(None).unwrap();
+        merged.extend_from_slice(&buffer.read().unwrap());
+    }
+
+    assert_eq!(merged.len(), data.len());
+    for (i, (gpu, value)) in merged.into_iter().zip(data).enumerate() {
+        let cpu = value.cpu_kernel();
+        assert!(
+            T::matches(gpu, cpu),
+            "{} value {}: GPU gave {:?}, CPU gave {:?}",
+            T::NAME,
+            i,
+            gpu,
+            cpu
+        );
+    }
+}
+
+#[test]
+fn two_devices_on_one_gpu_match_the_cpu() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let physical_device = context.device.physical_device();
+    split_over_one_device::<u32>(physical_device);
+    split_over_one_device::<f32>(physical_device);
+}
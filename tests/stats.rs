@@ -0,0 +1,85 @@
+use image::{Rgba, RgbaImage};
+use vulkan_test::{
+    stats::{stats_cpu, ChannelStats, StatsPipeline, CHANNEL_NAMES, DEFAULT_PERCENTILES},
+    transfer::upload_rgba8,
+};
+use vulkano::image::ImageUsage;
+
+mod common;
+
+/// Statistics of `values` worked out on the CPU.
+fn channel_stats(values: &[u8]) -> ChannelStats {
+    let image = RgbaImage::from_fn(values.len() as u32, 1, |x, _| Rgba([values[x as usize]; 4]));
+    stats_cpu(&image).channels[0].clone()
+}
+
+#[test]
+fn percentiles_pick_values_from_the_histogram() {
+    let stats = channel_stats(&[10, 10, 20, 30]);
+    assert_eq!(stats.percentile(0.0), 10);
+    assert_eq!(stats.percentile(50.0), 10);
+    assert_eq!(stats.percentile(51.0), 20);
+    assert_eq!(stats.percentile(75.0), 20);
+    assert_eq!(stats.percentile(100.0), 30);
+
+    // every percentile of a flat image is its one value
+    let flat = channel_stats(&[77; 9]);
+    for percentile in [0.0, 1.0, 50.0, 99.0, 100.0] {
+        assert_eq!(flat.percentile(percentile), 77);
+    }
+}
+
+#[test]
+fn gpu_matches_cpu() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    // a size the 16x16 work groups don't divide, with every channel spread differently
+    let image = RgbaImage::from_fn(75, 53, |x, y| {
+        Rgba([
+            (x * 3) as u8,
+            (x * y) as u8,
+            (y % 7 * 30 + 12) as u8,
+            if (x + y) % 5 == 0 { 128 } else { 255 },
+        ])
+    });
+    let upload = upload_rgba8(&context, &image, ImageUsage::STORAGE);
+    let stats = StatsPipeline::new(&context).compute(&context, upload);
+    assert_eq!((stats.width, stats.height), (75, 53));
+
+    let expected = stats_cpu(&image);
+    for (c, (actual, expected)) in stats.channels.iter().zip(&expected.channels).enumerate() {
+        let name = CHANNEL_NAMES[c];
+        assert_eq!(actual.histogram, expected.histogram, "{} histogram", name);
+        assert_eq!(actual.min, expected.min, "{} min", name);
+        assert_eq!(actual.max, expected.max, "{} max", name);
+        assert!(
+            (actual.mean - expected.mean).abs() < 1e-9,
+            "{} mean is {}, not {}",
+            name,
+            actual.mean,
+            expected.mean
+        );
+    }
+
+    let json = stats.to_json(&DEFAULT_PERCENTILES);
+    assert_eq!(json["width"], 75);
+    assert_eq!(json["height"], 53);
+    for (name, channel) in CHANNEL_NAMES.iter().zip(&stats.channels) {
+        let json = &json["channels"][name];
+        assert_eq!(json["min"], channel.min);
+        assert_eq!(json["max"], channel.max);
+        assert_eq!(json["mean"], channel.mean);
+        assert_eq!(json["histogram"].as_array().unwrap().len(), 256);
+        assert_eq!(
+            json["histogram"][channel.min as usize],
+            channel.histogram[channel.min as usize]
+        );
+        for percentile in DEFAULT_PERCENTILES {
+            assert_eq!(
+                json["percentiles"][percentile.to_string()],
+                channel.percentile(percentile)
+            );
+        }
+    }
+}
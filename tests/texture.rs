@@ -0,0 +1,199 @@
+use vulkan_test::texture::Texture;
+use vulkano::{format::Format, image::ImageUsage};
+
+mod common;
+
+/// A texture of `format` with every level filled with a different pattern.
+fn texture(format: Format, extent: [u32; 3], layers: u32, faces: u32, levels: u32) -> Texture {
+    let mut texture = Texture {
+        format,
+        width: extent[0],
+        height: extent[1],
+        depth: extent[2],
+        layers,
+        faces,
+        levels: Vec::new(),
+    };
+    texture.levels = (0..levels)
+        .map(|level| {
+            (0..texture.level_size(level))
+                .map(|i| (i * 7 + level as usize * 31) as u8)
+                .collect()
+        })
+        .collect();
+    texture
+}
+
+#[test]
+fn ktx2_levels_are_stored_smallest_first() {
+    let texture = texture(Format::R16G16B16A16_SFLOAT, [4, 2, 1], 1, 1, 3);
+    let bytes = texture.to_ktx2().unwrap();
+    let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let u64_at = |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+
+    assert_eq!(&bytes[1..4], b"KTX");
+    assert_eq!(u32_at(12), Format::R16G16B16A16_SFLOAT as i32 as u32);
+    assert_eq!(u32_at(16), 2);
+    assert_eq!([u32_at(20), u32_at(24), u32_at(28)], [4, 2, 0]);
+    assert_eq!([u32_at(32), u32_at(36), u32_at(40)], [0, 1, 3]);
+
+    let offsets: Vec<usize> = (0..3)
+        .map(|level| u64_at(80 + 24 * level) as usize)
+        .collect();
+    assert!(offsets[2] < offsets[1] && offsets[1] < offsets[0]);
+    for (level, &offset) in offsets.iter().enumerate() {
+        assert_eq!(offset % 8, 0);
+        let length = u64_at(80 + 24 * level + 8) as usize;
+        assert_eq!(&bytes[offset..offset + length], &texture.levels[level][..]);
+    }
+    assert_eq!(bytes.len(), offsets[0] + 64);
+
+    let mut short = texture;
+    short.levels[1].pop();
+    assert!(short.to_ktx2().is_err());
+}
+
+#[test]
+fn ktx2_round_trips() {
+    for texture in [
+        texture(Format::R16G16B16A16_SFLOAT, [8, 8, 1], 2, 6, 4),
+        texture(Format::R32G32B32_SFLOAT, [5, 3, 1], 3, 1, 3),
+        texture(Format::B8G8R8A8_SRGB, [4, 4, 4], 1, 1, 3),
+    ] {
+        let bytes = texture.to_ktx2().unwrap();
+        assert_eq!(Texture::from_ktx2(&bytes).unwrap(), texture);
+    }
+}
+
+#[test]
+fn dds_round_trips() {
+    for texture in [
+        texture(Format::R16G16B16A16_SFLOAT, [8, 8, 1], 2, 6, 4),
+        texture(Format::BC7_SRGB_BLOCK, [16, 8, 1], 1, 1, 5),
+        texture(Format::BC1_RGBA_UNORM_BLOCK, [6, 6, 1], 1, 6, 3),
+        texture(Format::R8_UNORM, [8, 8, 4], 1, 1, 4),
+    ] {
+        let bytes = texture.to_dds().unwrap();
+        assert_eq!(Texture::from_dds(&bytes).unwrap(), texture);
+    }
+}
+
+#[test]
+fn reads_legacy_dds_headers() {
+    let compressed = texture(Format::BC3_UNORM_BLOCK, [8, 4, 1], 1, 1, 2);
+    let mut bytes = compressed.to_dds().unwrap();
+    // swap the DX10 header for the FourCC older writers use
+    bytes[84..88].copy_from_slice(b"DXT5");
+    bytes.drain(128..148);
+    assert_eq!(Texture::from_dds(&bytes).unwrap(), compressed);
+
+    let bgra = texture(Format::B8G8R8A8_UNORM, [2, 2, 1], 1, 1, 1);
+    let mut bytes = bgra.to_dds().unwrap();
+    for (i, value) in [0x40, 0, 32, 0xff0000, 0xff00, 0xff, 0xff000000]
+        .into_iter()
+        .enumerate()
+    {
+        let offset = 80 + 4 * i;
+        bytes[offset..offset + 4].copy_from_slice(&u32::to_le_bytes(value));
+    }
+    bytes.drain(128..148);
+    assert_eq!(Texture::from_dds(&bytes).unwrap(), bgra);
+
+    // X8R8G8B8 pads where alpha would be, which comes out opaque
+    bytes[104..108].fill(0);
+    let mut opaque = bgra.clone();
+    for pixel in opaque.levels[0].chunks_exact_mut(4) {
+        pixel[3] = 255;
+    }
+    assert_ne!(opaque, bgra);
+    assert_eq!(Texture::from_dds(&bytes).unwrap(), opaque);
+}
+
+#[test]
+fn broken_files_are_errors() {
+    let rgba = texture(Format::R8G8B8A8_UNORM, [4, 4, 1], 1, 1, 3);
+    let ktx2 = rgba.to_ktx2().unwrap();
+    let dds = rgba.to_dds().unwrap();
+    for len in [0, 10, 60, 100, ktx2.len() - 1] {
+        assert!(Texture::from_ktx2(&ktx2[..len]).is_err());
+    }
+    for len in [0, 10, 100, 140, dds.len() - 1] {
+        assert!(Texture::from_dds(&dds[..len]).is_err());
+    }
+    assert!(Texture::from_ktx2(&dds).is_err());
+    assert!(Texture::from_dds(&ktx2).is_err());
+
+    // headers asking for far more than the file holds, or than can be addressed
+    let patched = |file: &[u8], patches: &[(usize, &[u8])]| {
+        let mut file = file.to_vec();
+        for (offset, bytes) in patches {
+            file[*offset..*offset + bytes.len()].copy_from_slice(bytes);
+        }
+        file
+    };
+    let max = u32::MAX.to_le_bytes();
+    let huge = 65536u32.to_le_bytes();
+    for patches in [
+        &[(28, &max[..])][..],                              // mip levels
+        &[(12, &huge[..]), (16, &huge[..])],                // height and width
+        &[(12, &max[..]), (16, &max[..])],                  // overflowing height and width
+        &[(140, &max[..])],                                 // array layers
+        &[(136, &4u32.to_le_bytes()[..]), (140, &max[..])], // cubemap layers
+    ] {
+        assert!(Texture::from_dds(&patched(&dds, patches)).is_err());
+    }
+    for patches in [
+        &[(40, &max[..])][..],                            // mip levels
+        &[(20, &max[..]), (24, &max[..])],                // overflowing width and height
+        &[(32, &max[..])],                                // array layers
+        &[(36, &6u32.to_le_bytes()[..]), (32, &max[..])], // cubemap layers
+        &[(80, &(u64::MAX - 8).to_le_bytes()[..])],       // level offset past the end
+    ] {
+        assert!(Texture::from_ktx2(&patched(&ktx2, patches)).is_err());
+    }
+
+    // ASTC can't go in a DDS file, and cubemap faces have to be square
+    let astc = texture(Format::ASTC_4x4_UNORM_BLOCK, [4, 4, 1], 1, 1, 1);
+    assert!(astc.to_dds().is_err());
+    let mut cube = texture(Format::R8G8B8A8_UNORM, [4, 2, 1], 1, 1, 1);
+    cube.faces = 6;
+    assert!(cube.to_dds().is_err());
+    assert!(cube.to_ktx2().is_err());
+}
+
+#[test]
+fn uploads_and_downloads_every_layer_and_face() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    for texture in [
+        texture(Format::R8G8B8A8_UNORM, [8, 8, 1], 2, 6, 4),
+        texture(Format::R16G16B16A16_SFLOAT, [7, 5, 1], 3, 1, 3),
+        texture(Format::R8G8B8A8_UNORM, [4, 4, 4], 1, 1, 3),
+    ] {
+        let image = match texture.upload(&context, ImageUsage::TRANSFER_SRC) {
+            Ok(image) => image,
+            Err(e) => {
+                eprintln!("skipping {:?}: {}", texture.format, e);
+                continue;
+            }
+        };
+        assert_eq!(Texture::download(&context, image).unwrap(), texture);
+    }
+}
+
+#[test]
+fn slices_cover_every_layer_face_and_depth_slice() {
+    let cubes = texture(Format::R8G8B8A8_UNORM, [4, 4, 1], 2, 6, 2);
+    let slices = cubes.slices(1);
+    assert_eq!(slices.len(), 12);
+    assert_eq!([slices[0].width, slices[0].height], [2, 2]);
+    let bytes: Vec<u8> = slices.into_iter().flat_map(|slice| slice.bytes).collect();
+    assert_eq!(bytes, cubes.levels[1]);
+
+    let volume = texture(Format::R16G16B16A16_SFLOAT, [4, 2, 3], 1, 1, 1);
+    let slices = volume.slices(0);
+    assert_eq!(slices.len(), 3);
+    assert_eq!(slices[2].row(1), &volume.levels[0][2 * 64 + 32..3 * 64]);
+    assert!(volume.slices(1).is_empty());
+}
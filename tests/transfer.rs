@@ -0,0 +1,198 @@
+use std::env;
+
+use image::{Rgba, RgbaImage};
+use vulkan_test::{
+    format::ColorFormat,
+    transfer::{create_image, download, load_image, read_back, Readback, Subresource},
+};
+use vulkano::{
+    buffer::{Buffer, BufferCreateInfo, BufferUsage},
+    command_buffer::{BufferImageCopy, CopyBufferToImageInfo},
+    format::Format,
+    image::{Image, ImageAspects, ImageCreateInfo, ImageSubresourceLayers, ImageType, ImageUsage},
+    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter},
+};
+
+mod common;
+
+#[test]
+fn loaded_images_read_back_the_same() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let dir = env::temp_dir().join(format!("vulkan-test-transfer-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    // jpeg is lossy, so compare against what the file decodes to rather than what was saved
+    for extension in ["png", "bmp", "tga", "jpg"] {
+        let path = dir.join(format!("input.{}", extension));
+        let path = path.to_str().unwrap();
+        let input = if extension == "jpg" {
+            image::DynamicImage::ImageRgb8(
+                image::DynamicImage::ImageRgba8(common::test_image(37, 23)).to_rgb8(),
+            )
+        } else {
+            image::DynamicImage::ImageRgba8(common::test_image(37, 23))
+        };
+        input.save(path).unwrap();
+        let expected = image::open(path).unwrap().to_rgba32f();
+
+        for format in [
+            ColorFormat::Rgba8,
+            ColorFormat::Rgba16,
+            ColorFormat::Rgba16Float,
+            ColorFormat::Rgba32Float,
+        ] {
+            let image = match load_image(&context, path, format, ImageUsage::TRANSFER_SRC) {
+                Ok(image) => image,
+                Err(e) => {
+                    eprintln!("skipping {:?}: {}", format, e);
+                    continue;
+                }
+            };
+            assert_eq!(image.extent(), [37, 23, 1]);
+            let actual = format.decode(37, 23, &download(&context, image));
+            let max_error = expected
+                .as_raw()
+                .iter()
+                .zip(actual.as_raw())
+                .map(|(e, a)| (e - a).abs())
+                .fold(0.0, f32::max);
+            assert!(
+                max_error <= 0.5 / 255.0 + 1e-6,
+                "{} as {:?} is off by {}",
+                extension,
+                format,
+                max_error
+            );
+        }
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn missing_files_are_errors() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let result = load_image(
+        &context,
+        "does-not-exist.png",
+        ColorFormat::Rgba8,
+        ImageUsage::TRANSFER_SRC,
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn padded_rows_convert_without_the_padding() {
+    // 3x2 BGRA with 4 bytes of padding after each row
+    let readback = Readback {
+        width: 3,
+        height: 2,
+        format: Format::B8G8R8A8_UNORM,
+        row_pitch: 16,
+        bytes: (0..28).collect(),
+    };
+    let expected: Vec<u8> = (0..28)
+        .filter(|i| i % 16 < 12)
+        .collect::<Vec<_>>()
+        .chunks_exact(4)
+        .flat_map(|p| [p[2], p[1], p[0], p[3]])
+        .collect();
+    let image = readback.to_dynamic_image().unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (3, 2));
+    assert_eq!(image.as_raw(), &expected);
+
+    let unsupported = Readback {
+        format: Format::R32_UINT,
+        ..readback
+    };
+    assert!(unsupported.to_dynamic_image().is_err());
+}
+
+#[test]
+fn reads_back_any_mip_level_and_layer() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let image = Image::new(
+        context.memory_allocator.clone(),
+        ImageCreateInfo {
+            image_type: ImageType::Dim2d,
+            format: Format::R8G8B8A8_UNORM,
+            extent: [37, 23, 1],
+            mip_levels: 3,
+            array_layers: 2,
+            usage: ImageUsage::TRANSFER_SRC | ImageUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo::default(),
+    )
+    .unwrap();
+
+    // mip level 1 is 18x11
+    let pixels = RgbaImage::from_fn(18, 11, |x, y| Rgba([x as u8, y as u8, 7, 255]));
+    let staging_buffer = Buffer::from_iter(
+        context.memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_SRC,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        },
+        pixels.as_raw().iter().copied(),
+    )
+    .unwrap();
+    let mut builder = context.command_buffer_builder();
+    builder
+        .copy_buffer_to_image(CopyBufferToImageInfo {
+            regions: [BufferImageCopy {
+                image_subresource: ImageSubresourceLayers {
+                    aspects: ImageAspects::COLOR,
+                    mip_level: 1,
+                    array_layers: 1..2,
+                },
+                image_extent: [18, 11, 1],
+                ..Default::default()
+            }]
+            .into(),
+            ..CopyBufferToImageInfo::buffer_image(staging_buffer, image.clone())
+        })
+        .unwrap();
+    context.execute(builder);
+
+    let subresource = Subresource {
+        mip_level: 1,
+        array_layer: 1,
+    };
+    let readback = read_back(&context, image.clone(), subresource).unwrap();
+    assert_eq!((readback.width, readback.height), (18, 11));
+    assert!(readback.row_pitch >= 18 * 4);
+    assert_eq!(readback.to_dynamic_image().unwrap().to_rgba8(), pixels);
+
+    let missing = Subresource {
+        mip_level: 3,
+        array_layer: 0,
+    };
+    assert!(read_back(&context, image, missing).is_err());
+}
+
+#[test]
+fn depth_images_are_errors() {
+    let Some(context) = common::context() else {
+        return;
+    };
+    let image = create_image(
+        &context,
+        Format::D32_SFLOAT,
+        16,
+        16,
+        ImageUsage::TRANSFER_SRC,
+    );
+    assert!(read_back(&context, image, Subresource::default()).is_err());
+}